@@ -1,53 +1,43 @@
-use common::url::cache::serve;
-
-use server::codes;
-use server::cache::app::App;
-
-
-use server::read::read_five_ints;
-use server::read::read_four_ints;
-use server::read::read_four_ints_and_long;
-use server::read::read_three_ints;
-use server::read::read_three_ints_and_long;
-use server::read::read_three_ints_and_two_longs;
-use server::read::read_two_ints_and_long;
-
-use server::read::wrong_request_length_12;
-use server::read::wrong_request_length_16;
-use server::read::wrong_request_length_20;
-use server::read::wrong_request_length_24;
-use server::read::wrong_request_length_28;
+use std::sync::{Arc, RwLock, RwLockReadGuard};
 
-use super::super::logic::serve::rankings::label;
-use super::super::logic::serve::rankings::location;
-use super::super::logic::serve::rankings::location_label;
+use bytes::Bytes;
 
-use super::super::logic::serve::recent::label::get_day_after_tomorrows_label_polls;
-use super::super::logic::serve::recent::label::get_next_months_label_polls;
-use super::super::logic::serve::recent::label::get_next_weeks_label_polls;
-use super::super::logic::serve::recent::label::get_tomorrows_label_polls;
-
-// use super::super::logic::serve::recent::location_and_loc_label::get_day_after_tomorrows_label_polls;
-// use super::super::logic::serve::recent::location_and_loc_label::get_next_months_label_polls;
-// use super::super::logic::serve::recent::location_and_loc_label::get_next_weeks_label_polls;
-// use super::super::logic::serve::recent::location_and_loc_label::get_tomorrows_label_polls;
+use common::model::protocol::ProtocolVersion;
+use common::url::cache::serve;
 
-use super::super::logic::serve::recent::location::get_day_after_tomorrows_location_polls;
-use super::super::logic::serve::recent::location::get_next_months_location_polls;
-use super::super::logic::serve::recent::location::get_next_weeks_location_polls;
-use super::super::logic::serve::recent::location::get_tomorrows_location_polls;
+use server::cache::app::App;
+use server::compress::{self, Encoding};
+use server::cache::events::Topic;
+use server::cache::pages::PageCache;
+use server::cache::scheduler::JobStatuses;
+use server::cache::watchers::PageKey;
+use server::cache::watchers::PageWatchers;
+use server::route::RouteKind;
 
-use super::super::logic::serve::recent::location_label::get_day_after_tomorrows_location_label_polls;
-use super::super::logic::serve::recent::location_label::get_next_months_location_label_polls;
-use super::super::logic::serve::recent::location_label::get_next_weeks_location_label_polls;
-use super::super::logic::serve::recent::location_label::get_tomorrows_location_label_polls;
+use super::super::logic::events::get_future_polls_topics;
 
+use super::super::cache::cache::Cache;
 use super::super::cache::cache_reader::CacheReader;
+use super::super::cache::cache_writer::read_cache;
+
+use super::debug;
+use super::metrics::render_metrics;
+use super::routes::get_routes;
+use super::routes::CacheRoutes;
+use super::routes::PROTOCOL_VERSIONS;
 
 
 pub struct CompleteCacheApp {
 
-    pub cache: Box<CacheReader + Send + Sync>,
+    // Shared with the update thread (the only writer), read under the read lock
+    pub cache: Arc<RwLock<Cache>>,
+    // Long-poll requests waiting on ranking pages, notified when the pages are re-ranked
+    pub page_watchers: Arc<PageWatchers>,
+    // Encoded ranking pages, invalidated on re-ranking (before the page watchers are notified)
+    pub page_cache: Arc<PageCache>,
+    // Statuses of the scheduled cache jobs, for the debug routes
+    pub job_statuses: Arc<JobStatuses>,
+    pub routes: CacheRoutes,
 
 }
 
@@ -55,13 +45,29 @@ pub struct CompleteCacheApp {
 impl CompleteCacheApp {
 
     pub fn new(
-        cache: Box<CacheReader + Send + Sync>
+        cache: Arc<RwLock<Cache>>,
+        page_watchers: Arc<PageWatchers>,
+        page_cache: Arc<PageCache>,
+        job_statuses: Arc<JobStatuses>,
     ) -> CompleteCacheApp {
         CompleteCacheApp {
-            cache
+            cache,
+            page_watchers,
+            page_cache,
+            job_statuses,
+            routes: get_routes(),
         }
     }
 
+    /**
+     * The cache as of now, it doesn't change while the guard is held.  Must not be called again
+     * while a guard is held (see read_cache).
+     */
+    #[inline]
+    pub fn read_cache(&self) -> RwLockReadGuard<Cache> {
+        read_cache(&self.cache)
+    }
+
 }
 
 
@@ -69,693 +75,98 @@ impl App for CompleteCacheApp {
 
     fn get_response(
         &self,
+        protocol_version: ProtocolVersion,
         path: &str,
         request_body: &[u8],
-    ) -> Vec<u8> {
-        match path {
-            /*
-             *
-             *  POLL RANKINGS
-             *
-             */
-
-            // Label Poll Rankings
-
-            serve::URL_THIS_MONTHS_CATEGORY_POLL_RANKINGS_BY_GLOBAL_ID => {
-                if wrong_request_length_16(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_month_id, block_index, global_label_id)
-                    = read_two_ints_and_long(request_body);
-                    label::get_this_months_label_rankings_by_global_id(
-                        vc_month_id, block_index, global_label_id, &self.cache)
-                }
-            }
-            serve::URL_THIS_MONTHS_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEX => {
-                if wrong_request_length_12(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_month_id, block_index, label_cache_index)
-                    = read_three_ints(request_body);
-                    label::get_this_months_label_rankings_by_cache_index(
-                        vc_month_id, block_index, label_cache_index, &self.cache)
-                }
-            }
-            serve::URL_THIS_WEEKS_CATEGORY_POLL_RANKINGS_BY_GLOBAL_ID => {
-                if wrong_request_length_16(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_week_id, block_index, global_label_id)
-                    = read_two_ints_and_long(request_body);
-                    label::get_this_weeks_label_rankings_by_global_id(
-                        vc_week_id, block_index, global_label_id, &self.cache)
-                }
-            }
-            serve::URL_THIS_WEEKS_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEX => {
-                if wrong_request_length_12(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_week_id, block_index, label_cache_index)
-                    = read_three_ints(request_body);
-                    label::get_this_weeks_label_rankings_by_cache_index(
-                        vc_week_id, block_index, label_cache_index, &self.cache)
-                }
-            }
-            serve::URL_TODAYS_CATEGORY_POLL_RANKINGS_BY_GLOBAL_ID => {
-                if wrong_request_length_16(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, block_index, global_label_id)
-                    = read_two_ints_and_long(request_body);
-                    label::get_todays_label_rankings_by_global_id(
-                        vc_day_id, block_index, global_label_id, &self.cache)
-                }
-            }
-            serve::URL_TODAYS_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEX => {
-                if wrong_request_length_12(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, block_index, label_cache_index)
-                    = read_three_ints(request_body);
-                    label::get_todays_label_rankings_by_cache_index(
-                        vc_day_id, block_index, label_cache_index, &self.cache)
-                }
-            }
-            serve::URL_LAST_MONTHS_CATEGORY_POLL_RANKINGS_BY_GLOBAL_ID => {
-                if wrong_request_length_16(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_month_id, block_index, global_label_id)
-                    = read_two_ints_and_long(request_body);
-                    label::get_last_months_label_rankings_by_global_id(
-                        vc_month_id, block_index, global_label_id, &self.cache)
-                }
-            }
-            serve::URL_LAST_MONTHS_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEX => {
-                if wrong_request_length_12(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_month_id, block_index, label_cache_index)
-                    = read_three_ints(request_body);
-                    label::get_last_months_label_rankings_by_cache_index(
-                        vc_month_id, block_index, label_cache_index, &self.cache)
-                }
-            }
-            serve::URL_LAST_WEEKS_CATEGORY_POLL_RANKINGS_BY_GLOBAL_ID => {
-                if wrong_request_length_16(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_week_id, block_index, global_label_id)
-                    = read_two_ints_and_long(request_body);
-                    label::get_last_weeks_label_rankings_by_global_id(
-                        vc_week_id, block_index, global_label_id, &self.cache)
-                }
-            }
-            serve::URL_LAST_WEEKS_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEX => {
-                if wrong_request_length_12(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_week_id, block_index, label_cache_index)
-                    = read_three_ints(request_body);
-                    label::get_last_weeks_label_rankings_by_cache_index(
-                        vc_week_id, block_index, label_cache_index, &self.cache)
-                }
-            }
-            serve::URL_YESTERDAYS_CATEGORY_POLL_RANKINGS_BY_GLOBAL_ID => {
-                if wrong_request_length_16(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, block_index, global_label_id)
-                    = read_two_ints_and_long(request_body);
-                    label::get_yesterdays_label_rankings_by_global_id(
-                        vc_day_id, block_index, global_label_id, &self.cache)
-                }
-            }
-            serve::URL_YESTERDAYS_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEX => {
-                if wrong_request_length_12(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, block_index, label_cache_index)
-                    = read_three_ints(request_body);
-                    label::get_yesterdays_label_rankings_by_cache_index(
-                        vc_day_id, block_index, label_cache_index, &self.cache)
-                }
-            }
-            serve::URL_DAY_B4_YESTERDAY_CATEGORY_POLL_RANKINGS_BY_GLOBAL_ID => {
-                if wrong_request_length_16(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, block_index, global_label_id)
-                    = read_two_ints_and_long(request_body);
-                    label::get_day_b4_yesterdays_label_rankings_by_global_id(
-                        vc_day_id, block_index, global_label_id, &self.cache)
-                }
-            }
-            serve::URL_DAY_B4_YESTERDAY_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEX => {
-                if wrong_request_length_12(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, block_index, label_cache_index)
-                    = read_three_ints(request_body);
-                    label::get_day_b4_yesterdays_label_rankings_by_cache_index(
-                        vc_day_id, block_index, label_cache_index, &self.cache)
-                }
-            }
-
-            // Location Poll Rankings
-
-            serve::URL_THIS_MONTHS_LOCATION_POLL_RANKINGS_BY_GLOBAL_ID => {
-                if wrong_request_length_20(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_month_id, timezone_id, block_index, global_location_id)
-                    = read_three_ints_and_long(request_body);
-                    location::get_this_months_location_rankings_by_global_id(
-                        vc_month_id, timezone_id, block_index, global_location_id, &self.cache)
-                }
-            }
-            serve::URL_THIS_MONTHS_LOCATION_POLL_RANKINGS_BY_CACHE_INDEX => {
-                if wrong_request_length_16(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_month_id, timezone_id, block_index, location_cache_index)
-                    = read_four_ints(request_body);
-                    location::get_this_months_location_rankings_by_cache_index(
-                        vc_month_id, timezone_id, block_index, location_cache_index, &self.cache)
-                }
-            }
-            serve::URL_THIS_WEEKS_LOCATION_POLL_RANKINGS_BY_GLOBAL_ID => {
-                if wrong_request_length_20(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_week_id, timezone_id, block_index, global_location_id)
-                    = read_three_ints_and_long(request_body);
-                    location::get_this_weeks_location_rankings_by_global_id(
-                        vc_week_id, timezone_id, block_index, global_location_id, &self.cache)
-                }
-            }
-            serve::URL_THIS_WEEKS_LOCATION_POLL_RANKINGS_BY_CACHE_INDEX => {
-                if wrong_request_length_16(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_week_id, timezone_id, block_index, location_cache_index)
-                    = read_four_ints(request_body);
-                    location::get_this_weeks_location_rankings_by_cache_index(
-                        vc_week_id, timezone_id, block_index, location_cache_index, &self.cache)
-                }
-            }
-            serve::URL_TODAYS_LOCATION_POLL_RANKINGS_BY_GLOBAL_ID => {
-                if wrong_request_length_20(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, timezone_id, block_index, global_location_id)
-                    = read_three_ints_and_long(request_body);
-                    location::get_todays_location_rankings_by_global_id(
-                        vc_day_id, timezone_id, block_index, global_location_id, &self.cache)
-                }
-            }
-            serve::URL_TODAYS_LOCATION_POLL_RANKINGS_BY_CACHE_INDEX => {
-                if wrong_request_length_16(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, timezone_id, block_index, location_cache_index)
-                    = read_four_ints(request_body);
-                    location::get_todays_location_rankings_by_cache_index(
-                        vc_day_id, timezone_id, block_index, location_cache_index, &self.cache)
-                }
-            }
-            serve::URL_LAST_MONTHS_LOCATION_POLL_RANKINGS_BY_GLOBAL_ID => {
-                if wrong_request_length_20(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_month_id, timezone_id, block_index, global_location_id)
-                    = read_three_ints_and_long(request_body);
-                    location::get_last_months_location_rankings_by_global_id(
-                        vc_month_id, timezone_id, block_index, global_location_id, &self.cache)
-                }
-            }
-            serve::URL_LAST_MONTHS_LOCATION_POLL_RANKINGS_BY_CACHE_INDEX => {
-                if wrong_request_length_16(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_month_id, timezone_id, block_index, location_cache_index)
-                    = read_four_ints(request_body);
-                    location::get_last_months_location_rankings_by_cache_index(
-                        vc_month_id, timezone_id, block_index, location_cache_index, &self.cache)
-                }
-            }
-            serve::URL_LAST_WEEKS_LOCATION_POLL_RANKINGS_BY_GLOBAL_ID => {
-                if wrong_request_length_20(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_week_id, timezone_id, block_index, global_location_id)
-                    = read_three_ints_and_long(request_body);
-                    location::get_last_weeks_location_rankings_by_global_id(
-                        vc_week_id, timezone_id, block_index, global_location_id, &self.cache)
-                }
-            }
-            serve::URL_LAST_WEEKS_LOCATION_POLL_RANKINGS_BY_CACHE_INDEX => {
-                if wrong_request_length_16(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_week_id, timezone_id, block_index, location_cache_index)
-                    = read_four_ints(request_body);
-                    location::get_last_weeks_location_rankings_by_cache_index(
-                        vc_week_id, timezone_id, block_index, location_cache_index, &self.cache)
-                }
-            }
-            serve::URL_YESTERDAYS_LOCATION_POLL_RANKINGS_BY_GLOBAL_ID => {
-                if wrong_request_length_20(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, timezone_id, block_index, global_location_id)
-                    = read_three_ints_and_long(request_body);
-                    location::get_yesterdays_location_rankings_by_global_id(
-                        vc_day_id, timezone_id, block_index, global_location_id, &self.cache)
-                }
-            }
-            serve::URL_YESTERDAYS_LOCATION_POLL_RANKINGS_BY_CACHE_INDEX => {
-                if wrong_request_length_16(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, timezone_id, block_index, location_cache_index)
-                    = read_four_ints(request_body);
-                    location::get_yesterdays_location_rankings_by_cache_index(
-                        vc_day_id, timezone_id, block_index, location_cache_index, &self.cache)
-                }
-            }
-            serve::URL_DAY_B4_YESTERDAY_LOCATION_POLL_RANKINGS_BY_GLOBAL_ID => {
-                if wrong_request_length_20(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, timezone_id, block_index, global_location_id)
-                    = read_three_ints_and_long(request_body);
-                    location::get_day_b4_yesterdays_location_rankings_by_global_id(
-                        vc_day_id, timezone_id, block_index, global_location_id, &self.cache)
-                }
-            }
-            serve::URL_DAY_B4_YESTERDAY_LOCATION_POLL_RANKINGS_BY_CACHE_INDEX => {
-                if wrong_request_length_16(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, timezone_id, block_index, location_cache_index)
-                    = read_four_ints(request_body);
-                    location::get_day_b4_yesterdays_location_rankings_by_cache_index(
-                        vc_day_id, timezone_id, block_index, location_cache_index, &self.cache)
-                }
-            }
-
-            // Location Label Poll Rankings
-
-            serve::URL_THIS_MONTHS_LOCATION_CATEGORY_POLL_RANKINGS_BY_GLOBAL_IDS => {
-                if wrong_request_length_28(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, timezone_id, block_index, global_location_id, global_label_id)
-                    = read_three_ints_and_two_longs(request_body);
-                    location_label::get_this_months_location_label_rankings_by_global_ids(
-                        vc_day_id, timezone_id, block_index,
-                        global_location_id, global_label_id, &self.cache)
-                }
-            }
-            serve::URL_THIS_MONTHS_LOCATION_CATEGORY_POLL_RANKINGS_BY_LOCATION_CACHE_INDEX_AND_GLOBAL_CATEGORY_ID => {
-                if wrong_request_length_24(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, timezone_id, block_index, location_cache_index, global_label_id)
-                    = read_four_ints_and_long(request_body);
-                    location_label::get_this_months_location_label_rankings_by_location_cache_index_and_global_label_ids(
-                        vc_day_id, timezone_id, block_index,
-                        location_cache_index, global_label_id, &self.cache)
-                }
-            }
-            serve::URL_THIS_MONTHS_LOCATION_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEXES => {
-                if wrong_request_length_20(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, timezone_id, block_index, location_cache_index, location_label_cache_index)
-                    = read_five_ints(request_body);
-                    location_label::get_this_months_location_label_rankings_by_cache_indexes(
-                        vc_day_id, timezone_id, block_index, location_cache_index,
-                        location_label_cache_index, &self.cache)
-                }
-            }
-            serve::URL_THIS_WEEKS_LOCATION_CATEGORY_POLL_RANKINGS_BY_GLOBAL_IDS => {
-                if wrong_request_length_28(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, timezone_id, block_index, global_location_id, global_label_id)
-                    = read_three_ints_and_two_longs(request_body);
-                    location_label::get_this_weeks_location_label_rankings_by_global_ids(
-                        vc_day_id, timezone_id, block_index, global_location_id,
-                        global_label_id, &self.cache)
-                }
-            }
-            serve::URL_THIS_WEEKS_LOCATION_CATEGORY_POLL_RANKINGS_BY_LOCATION_CACHE_INDEX_AND_GLOBAL_CATEGORY_ID => {
-                if wrong_request_length_24(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, timezone_id, block_index, location_cache_index, global_label_id)
-                    = read_four_ints_and_long(request_body);
-                    location_label::get_this_weeks_location_label_rankings_by_location_cache_index_and_global_label_ids(
-                        vc_day_id, timezone_id, block_index, location_cache_index,
-                        global_label_id, &self.cache)
-                }
-            }
-            serve::URL_THIS_WEEKS_LOCATION_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEXES => {
-                if wrong_request_length_20(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, timezone_id, block_index, location_cache_index, location_label_cache_index)
-                    = read_five_ints(request_body);
-                    location_label::get_this_weeks_location_label_rankings_by_cache_indexes(
-                        vc_day_id, timezone_id, block_index, location_cache_index,
-                        location_label_cache_index, &self.cache)
-                }
-            }
-            serve::URL_TODAYS_LOCATION_CATEGORY_POLL_RANKINGS_BY_GLOBAL_IDS => {
-                if wrong_request_length_28(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, timezone_id, block_index, global_location_id, global_label_id)
-                    = read_three_ints_and_two_longs(request_body);
-                    location_label::get_todays_location_label_rankings_by_global_ids(
-                        vc_day_id, timezone_id, block_index, global_location_id, global_label_id,
-                        &self.cache)
-                }
-            }
-            serve::URL_TODAYS_LOCATION_CATEGORY_POLL_RANKINGS_BY_LOCATION_CACHE_INDEX_AND_GLOBAL_CATEGORY_ID => {
-                if wrong_request_length_24(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, timezone_id, block_index, location_cache_index, global_label_id)
-                    = read_four_ints_and_long(request_body);
-                    location_label::get_todays_location_label_rankings_by_location_cache_index_and_global_label_ids(
-                        vc_day_id, timezone_id, block_index, location_cache_index,
-                        global_label_id, &self.cache)
-                }
-            }
-            serve::URL_TODAYS_LOCATION_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEXES => {
-                if wrong_request_length_20(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, timezone_id, block_index, location_cache_index, location_label_cache_index)
-                    = read_five_ints(request_body);
-                    location_label::get_todays_location_label_rankings_by_cache_indexes(
-                        vc_day_id, timezone_id, block_index, location_cache_index,
-                        location_label_cache_index, &self.cache)
-                }
-            }
-            serve::URL_LAST_MONTHS_LOCATION_CATEGORY_POLL_RANKINGS_BY_GLOBAL_IDS => {
-                if wrong_request_length_28(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, timezone_id, block_index, global_location_id, global_label_id)
-                    = read_three_ints_and_two_longs(request_body);
-                    location_label::get_last_months_location_label_rankings_by_global_ids(
-                        vc_day_id, timezone_id, block_index, global_location_id,
-                        global_label_id, &self.cache)
-                }
-            }
-            serve::URL_LAST_MONTHS_LOCATION_CATEGORY_POLL_RANKINGS_BY_LOCATION_CACHE_INDEX_AND_GLOBAL_CATEGORY_ID => {
-                if wrong_request_length_24(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, timezone_id, block_index, location_cache_index, global_label_id)
-                    = read_four_ints_and_long(request_body);
-                    location_label::get_last_months_location_label_rankings_by_location_cache_index_and_global_label_ids(
-                        vc_day_id, timezone_id, block_index, location_cache_index,
-                        global_label_id, &self.cache)
-                }
-            }
-            serve::URL_LAST_MONTHS_LOCATION_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEXES => {
-                if wrong_request_length_20(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, timezone_id, block_index, location_cache_index, location_label_cache_index)
-                    = read_five_ints(request_body);
-                    location_label::get_last_months_location_label_rankings_by_cache_indexes(
-                        vc_day_id, timezone_id, block_index, location_cache_index,
-                        location_label_cache_index, &self.cache)
-                }
-            }
-            serve::URL_LAST_WEEKS_LOCATION_CATEGORY_POLL_RANKINGS_BY_GLOBAL_IDS => {
-                if wrong_request_length_28(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, timezone_id, block_index, global_location_id, global_label_id)
-                    = read_three_ints_and_two_longs(request_body);
-                    location_label::get_last_weeks_location_label_rankings_by_global_ids(
-                        vc_day_id, timezone_id, block_index, global_location_id,
-                        global_label_id, &self.cache)
-                }
-            }
-            serve::URL_LAST_WEEKS_LOCATION_CATEGORY_POLL_RANKINGS_BY_LOCATION_CACHE_INDEX_AND_GLOBAL_CATEGORY_ID => {
-                if wrong_request_length_24(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, timezone_id, block_index, location_cache_index, global_label_id)
-                    = read_four_ints_and_long(request_body);
-                    location_label::get_last_weeks_location_label_rankings_by_location_cache_index_and_global_label_ids(
-                        vc_day_id, timezone_id, block_index, location_cache_index,
-                        global_label_id, &self.cache)
-                }
-            }
-            serve::URL_LAST_WEEKS_LOCATION_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEXES => {
-                if wrong_request_length_20(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, timezone_id, block_index, location_cache_index, location_label_cache_index)
-                    = read_five_ints(request_body);
-                    location_label::get_last_weeks_location_label_rankings_by_cache_indexes(
-                        vc_day_id, timezone_id, block_index, location_cache_index,
-                        location_label_cache_index, &self.cache)
-                }
-            }
-            serve::URL_YESTERDAYS_LOCATION_CATEGORY_POLL_RANKINGS_BY_GLOBAL_IDS => {
-                if wrong_request_length_28(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, timezone_id, block_index, global_location_id, global_label_id)
-                    = read_three_ints_and_two_longs(request_body);
-                    location_label::get_yesterdays_location_label_rankings_by_global_ids(
-                        vc_day_id, timezone_id, block_index, global_location_id, global_label_id,
-                        &self.cache)
-                }
-            }
-            serve::URL_YESTERDAYS_LOCATION_CATEGORY_POLL_RANKINGS_BY_LOCATION_CACHE_INDEX_AND_GLOBAL_CATEGORY_ID => {
-                if wrong_request_length_24(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, timezone_id, block_index, location_cache_index, global_label_id)
-                    = read_four_ints_and_long(request_body);
-                    location_label::get_yesterdays_location_label_rankings_by_location_cache_index_and_global_label_ids(
-                        vc_day_id, timezone_id, block_index, location_cache_index,
-                        global_label_id, &self.cache)
-                }
-            }
-            serve::URL_YESTERDAYS_LOCATION_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEXES => {
-                if wrong_request_length_20(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, timezone_id, block_index, location_cache_index, location_label_cache_index)
-                    = read_five_ints(request_body);
-                    location_label::get_yesterdays_location_label_rankings_by_cache_indexes(
-                        vc_day_id, timezone_id, block_index, location_cache_index,
-                        location_label_cache_index, &self.cache)
-                }
-            }
-            serve::URL_DAY_B4_YESTERDAY_LOCATION_YESTERDAY_CATEGORY_POLL_RANKINGS_BY_GLOBAL_IDS => {
-                if wrong_request_length_28(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, timezone_id, block_index, global_location_id, global_label_id)
-                    = read_three_ints_and_two_longs(request_body);
-                    location_label::get_day_b4_yesterdays_location_label_rankings_by_global_ids(
-                        vc_day_id, timezone_id, block_index, global_location_id, global_label_id,
-                        &self.cache)
-                }
-            }
-            serve::URL_DAY_B4_YESTERDAY_LOCATION_YESTERDAY_CATEGORY_POLL_RANKINGS_BY_LOCATION_CACHE_INDEX_AND_GLOBAL_CATEGORY_ID => {
-                if wrong_request_length_24(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, timezone_id, block_index, location_cache_index, global_label_id)
-                    = read_four_ints_and_long(request_body);
-                    location_label::get_day_b4_yesterdays_location_label_rankings_by_location_cache_index_and_global_label_ids(
-                        vc_day_id, timezone_id, block_index, location_cache_index,
-                        global_label_id, &self.cache)
-                }
-            }
-            serve::URL_DAY_B4_YESTERDAY_LOCATION_YESTERDAY_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEXES => {
-                if wrong_request_length_20(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_day_id, timezone_id, block_index, location_cache_index, location_label_cache_index)
-                    = read_five_ints(request_body);
-                    location_label::get_day_b4_yesterdays_location_label_rankings_by_cache_indexes(
-                        vc_day_id, timezone_id, block_index, location_cache_index,
-                        location_label_cache_index, &self.cache)
-                }
-            }
-
-            /*
-             *
-             *  RECENT POLLS
-             *
-             */
-
-            // Recent Polls by Location
-
-            serve::URL_NEXT_MONTHS_LOCATION_POLLS => {
-                if wrong_request_length_20(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (month_id, timezone_id, block_number, global_location_id) = read_three_ints_and_long(request_body);
-                    get_next_months_location_polls(
-                        month_id, timezone_id, block_number, global_location_id,
-                        &self.cache)
-                }
-            }
-            serve::URL_NEXT_WEEKS_LOCATION_POLLS => {
-                if wrong_request_length_20(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (week_id, timezone_id, block_number, global_location_id) = read_three_ints_and_long(request_body);
-                    get_next_weeks_location_polls(
-                        week_id, timezone_id, block_number, global_location_id,
-                        &self.cache)
-                }
-            }
-            serve::URL_TOMORROWS_LOCATION_POLLS => {
-                if wrong_request_length_20(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (day_id, timezone_id, block_number, global_location_id) = read_three_ints_and_long(request_body);
-                    get_tomorrows_location_polls(
-                        day_id, timezone_id, block_number, global_location_id,
-                        &self.cache)
-                }
-            }
-            serve::URL_DAY_AFTER_TOMORROWS_LOCATION_POLLS => {
-                if wrong_request_length_20(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (day_id, timezone_id, block_number, global_location_id) = read_three_ints_and_long(request_body);
-                    get_day_after_tomorrows_location_polls(
-                        day_id, timezone_id, block_number, global_location_id,
-                        &self.cache)
-                }
-            }
-
-            // Recent Polls by Label
-
-            serve::URL_NEXT_MONTHS_CATEGORY_POLLS => {
-                if wrong_request_length_16(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (month_id,block_number, global_label_id) = read_two_ints_and_long(request_body);
-                    get_next_months_label_polls(
-                        month_id, block_number, global_label_id, &self.cache)
-                }
-            }
-            serve::URL_NEXT_WEEKS_CATEGORY_POLLS => {
-                if wrong_request_length_16(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (week_id,block_number, global_label_id) = read_two_ints_and_long(request_body);
-                    get_next_weeks_label_polls(
-                        week_id, block_number, global_label_id, &self.cache)
-                }
-            }
-            serve::URL_TOMORROWS_CATEGORY_POLLS => {
-                if wrong_request_length_16(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (day_id,block_number, global_label_id) = read_two_ints_and_long(request_body);
-                    get_tomorrows_label_polls(
-                        day_id, block_number, global_label_id, &self.cache)
-                }
-            }
-            serve::URL_DAY_AFTER_TOMORROWS_CATEGORY_POLLS => {
-                if wrong_request_length_16(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (day_id,block_number, global_label_id) = read_two_ints_and_long(request_body);
-                    get_day_after_tomorrows_label_polls(
-                        day_id, block_number, global_label_id, &self.cache)
-                }
+    ) -> Bytes {
+        // Held until the page is kept, pages are invalidated under the write lock
+        let cache = self.read_cache();
+        // Requests for pages (the same ones long-poll requests can watch) are memoized
+        match self.routes.get_page_key(protocol_version, path, request_body, &*cache) {
+            None => {
+                Bytes::from(self.routes.get_response(protocol_version, path, request_body, &*cache))
+            }
+            Some(page_key) => {
+                self.page_cache.get_or_encode(protocol_version, path, page_key, cache.get_data_version(), || {
+                    self.routes.get_response(protocol_version, path, request_body, &*cache)
+                })
             }
+        }
+    }
 
-            // Recent Polls by Location Label
+    fn get_compressed_response(
+        &self,
+        protocol_version: ProtocolVersion,
+        path: &str,
+        request_body: &[u8],
+        encoding: Encoding,
+        min_size: usize,
+    ) -> (Bytes, Option<Bytes>) {
+        let data = {
+            let cache = self.read_cache();
+            // Memoized pages are compressed once per encoding
+            match self.routes.get_page_key(protocol_version, path, request_body, &*cache) {
+                None => {
+                    Bytes::from(self.routes.get_response(protocol_version, path, request_body, &*cache))
+                }
+                Some(page_key) => {
+                    return self.page_cache.get_or_compress(protocol_version, path, page_key, cache.get_data_version(),
+                                                           encoding, min_size, || {
+                        self.routes.get_response(protocol_version, path, request_body, &*cache)
+                    });
+                }
+            }
+        };
+        let compressed = compress::compress_body(encoding, &data, min_size);
+
+        (data, compressed)
+    }
 
-            serve::URL_NEXT_MONTHS_LOCATION_CATEGORY_POLLS => {
-                if wrong_request_length_28(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (month_id, timezone_id, block_number, global_location_id, global_label_id) = read_three_ints_and_two_longs(request_body);
-                    get_next_months_location_label_polls(
-                        month_id, timezone_id, block_number, global_location_id,
-                        global_label_id, &self.cache)
-                }
-            }
-            serve::URL_NEXT_WEEKS_LOCATION_CATEGORY_POLLS => {
-                if wrong_request_length_28(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (week_id, timezone_id, block_number, global_location_id, global_label_id) = read_three_ints_and_two_longs(request_body);
-                    get_next_weeks_location_label_polls(
-                        week_id, timezone_id, block_number, global_location_id,
-                        global_label_id, &self.cache)
-                }
-            }
-            serve::URL_TOMORROWS_LOCATION_CATEGORY_POLLS => {
-                if wrong_request_length_28(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (day_id, timezone_id, block_number, global_location_id, global_label_id) = read_three_ints_and_two_longs(request_body);
-                    get_tomorrows_location_label_polls(
-                        day_id, timezone_id, block_number, global_location_id,
-                        global_label_id, &self.cache)
-                }
-            }
-            serve::URL_DAY_AFTER_TOMORROWS_LOCATION_CATEGORY_POLLS => {
-                if wrong_request_length_28(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (day_id, timezone_id, block_number, global_location_id, global_label_id) = read_three_ints_and_two_longs(request_body);
-                    get_day_after_tomorrows_location_label_polls(
-                        day_id, timezone_id, block_number, global_location_id,
-                        global_label_id, &self.cache)
-                }
-            }
+    fn get_page_key(
+        &self,
+        protocol_version: ProtocolVersion,
+        path: &str,
+        request_body: &[u8],
+    ) -> Option<PageKey> {
+        self.routes.get_page_key(protocol_version, path, request_body, &*self.read_cache())
+    }
 
-            _ => {
-                codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-            }
-        }
+    fn get_protocol_versions(&self) -> &[ProtocolVersion] {
+        PROTOCOL_VERSIONS
     }
 
-    fn get_update_response(
+    fn get_event_topics(
         &self,
         path: &str,
         request_body: &[u8],
-    ) -> Vec<u8> {
+    ) -> Option<Vec<Topic>> {
         match path {
-            serve::URL_THIS_MONTHS_CATEGORY_POLL_RANKINGS_BY_GLOBAL_ID => {
-                if wrong_request_length_16(request_body) {
-                    codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
-                } else {
-                    let (vc_month_id, block_index, global_label_id)
-                    = read_two_ints_and_long(request_body);
-                    label::get_this_months_label_rankings_by_global_id(
-                        vc_month_id, block_index, global_label_id, &self.cache)
-                }
+            serve::URL_FUTURE_POLLS_EVENTS => {
+                get_future_polls_topics(request_body)
             }
             _ => {
-                codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
+                None
             }
         }
     }
 
+    fn get_debug_response(
+        &self,
+        path: &str,
+        query: &str,
+    ) -> Option<Result<Vec<u8>, String>> {
+        debug::get_debug_response(self, path, query)
+    }
+
+    fn get_route_ids(&self) -> Vec<&'static str> {
+        self.routes.route_ids(RouteKind::Read)
+    }
+
+    fn get_metrics(
+        &self,
+        out: &mut String,
+    ) {
+        render_metrics(self, out);
+    }
+
 }
@@ -0,0 +1,696 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::io::Cursor;
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use common::model::protocol::PROTOCOL_VERSION_1;
+use common::model::response::RESPONSE_INVALID_CATEGORY_CACHE_INDEX_FLAG;
+use common::model::response::RESPONSE_INVALID_DATA_FORMAT_FLAG;
+use common::model::response::RESPONSE_INVALID_FLAG;
+use common::model::response::RESPONSE_INVALID_GLOBAL_CATEGORY_ID_FLAG;
+use common::model::response::RESPONSE_INVALID_GLOBAL_LOCATION_ID_FLAG;
+use common::model::response::RESPONSE_INVALID_LOCATION_CACHE_INDEX_FLAG;
+use common::model::response::RESPONSE_INVALID_PERIOD_ID_FLAG;
+use common::model::response::RESPONSE_INVALID_TIMEZONE_ID_FLAG;
+use common::model::response::RESPONSE_UNSUPPORTED_PROTOCOL_VERSION_FLAG;
+use common::model::timezone::NUM_TIMEZONES;
+use common::url::cache::serve;
+
+use server::cache::app::App;
+use server::cache::scheduler::get_seconds;
+use server::cache::scheduler::JobStatus;
+
+use super::super::cache::cache_reader::CacheReader;
+use super::super::cache::model::CachePeriodIds;
+use super::super::logic::events::FUTURE_PERIOD_DAY_AFTER_TOMORROW;
+use super::super::logic::events::FUTURE_PERIOD_NEXT_MONTH;
+use super::super::logic::events::FUTURE_PERIOD_NEXT_WEEK;
+use super::super::logic::events::FUTURE_PERIOD_TOMORROW;
+use super::super::logic::serve::page_header::PAGE_HEADER_SIZE;
+use super::super::logic::serve::page_key::get_current_period_id;
+use super::super::logic::serve::page_key::RANKINGS_PERIOD_DAY_B4_YESTERDAY;
+use super::super::logic::serve::page_key::RANKINGS_PERIOD_LAST_MONTH;
+use super::super::logic::serve::page_key::RANKINGS_PERIOD_LAST_WEEK;
+use super::super::logic::serve::page_key::RANKINGS_PERIOD_THIS_MONTH;
+use super::super::logic::serve::page_key::RANKINGS_PERIOD_THIS_WEEK;
+use super::super::logic::serve::page_key::RANKINGS_PERIOD_TODAY;
+use super::super::logic::serve::page_key::RANKINGS_PERIOD_YESTERDAY;
+
+use super::app::CompleteCacheApp;
+
+const KIND_LABEL: usize = 0;
+const KIND_LOCATION: usize = 1;
+const KIND_LOCATION_LABEL: usize = 2;
+
+struct RankingsPeriod {
+    name: &'static str,
+    period: u8,
+    // By global id, by cache index
+    label_routes: [&'static str; 2],
+    // By global id, by cache index
+    location_routes: [&'static str; 2],
+    // By global ids, by location cache index and global label id, by cache indexes
+    location_label_routes: [&'static str; 3],
+}
+
+const RANKINGS_PERIODS: [RankingsPeriod; 7] = [
+    RankingsPeriod {
+        name: "this_month",
+        period: RANKINGS_PERIOD_THIS_MONTH,
+        label_routes: [
+            serve::URL_THIS_MONTHS_CATEGORY_POLL_RANKINGS_BY_GLOBAL_ID,
+            serve::URL_THIS_MONTHS_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEX],
+        location_routes: [
+            serve::URL_THIS_MONTHS_LOCATION_POLL_RANKINGS_BY_GLOBAL_ID,
+            serve::URL_THIS_MONTHS_LOCATION_POLL_RANKINGS_BY_CACHE_INDEX],
+        location_label_routes: [
+            serve::URL_THIS_MONTHS_LOCATION_CATEGORY_POLL_RANKINGS_BY_GLOBAL_IDS,
+            serve::URL_THIS_MONTHS_LOCATION_CATEGORY_POLL_RANKINGS_BY_LOCATION_CACHE_INDEX_AND_GLOBAL_CATEGORY_ID,
+            serve::URL_THIS_MONTHS_LOCATION_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEXES],
+    },
+    RankingsPeriod {
+        name: "this_week",
+        period: RANKINGS_PERIOD_THIS_WEEK,
+        label_routes: [
+            serve::URL_THIS_WEEKS_CATEGORY_POLL_RANKINGS_BY_GLOBAL_ID,
+            serve::URL_THIS_WEEKS_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEX],
+        location_routes: [
+            serve::URL_THIS_WEEKS_LOCATION_POLL_RANKINGS_BY_GLOBAL_ID,
+            serve::URL_THIS_WEEKS_LOCATION_POLL_RANKINGS_BY_CACHE_INDEX],
+        location_label_routes: [
+            serve::URL_THIS_WEEKS_LOCATION_CATEGORY_POLL_RANKINGS_BY_GLOBAL_IDS,
+            serve::URL_THIS_WEEKS_LOCATION_CATEGORY_POLL_RANKINGS_BY_LOCATION_CACHE_INDEX_AND_GLOBAL_CATEGORY_ID,
+            serve::URL_THIS_WEEKS_LOCATION_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEXES],
+    },
+    RankingsPeriod {
+        name: "today",
+        period: RANKINGS_PERIOD_TODAY,
+        label_routes: [
+            serve::URL_TODAYS_CATEGORY_POLL_RANKINGS_BY_GLOBAL_ID,
+            serve::URL_TODAYS_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEX],
+        location_routes: [
+            serve::URL_TODAYS_LOCATION_POLL_RANKINGS_BY_GLOBAL_ID,
+            serve::URL_TODAYS_LOCATION_POLL_RANKINGS_BY_CACHE_INDEX],
+        location_label_routes: [
+            serve::URL_TODAYS_LOCATION_CATEGORY_POLL_RANKINGS_BY_GLOBAL_IDS,
+            serve::URL_TODAYS_LOCATION_CATEGORY_POLL_RANKINGS_BY_LOCATION_CACHE_INDEX_AND_GLOBAL_CATEGORY_ID,
+            serve::URL_TODAYS_LOCATION_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEXES],
+    },
+    RankingsPeriod {
+        name: "last_month",
+        period: RANKINGS_PERIOD_LAST_MONTH,
+        label_routes: [
+            serve::URL_LAST_MONTHS_CATEGORY_POLL_RANKINGS_BY_GLOBAL_ID,
+            serve::URL_LAST_MONTHS_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEX],
+        location_routes: [
+            serve::URL_LAST_MONTHS_LOCATION_POLL_RANKINGS_BY_GLOBAL_ID,
+            serve::URL_LAST_MONTHS_LOCATION_POLL_RANKINGS_BY_CACHE_INDEX],
+        location_label_routes: [
+            serve::URL_LAST_MONTHS_LOCATION_CATEGORY_POLL_RANKINGS_BY_GLOBAL_IDS,
+            serve::URL_LAST_MONTHS_LOCATION_CATEGORY_POLL_RANKINGS_BY_LOCATION_CACHE_INDEX_AND_GLOBAL_CATEGORY_ID,
+            serve::URL_LAST_MONTHS_LOCATION_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEXES],
+    },
+    RankingsPeriod {
+        name: "last_week",
+        period: RANKINGS_PERIOD_LAST_WEEK,
+        label_routes: [
+            serve::URL_LAST_WEEKS_CATEGORY_POLL_RANKINGS_BY_GLOBAL_ID,
+            serve::URL_LAST_WEEKS_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEX],
+        location_routes: [
+            serve::URL_LAST_WEEKS_LOCATION_POLL_RANKINGS_BY_GLOBAL_ID,
+            serve::URL_LAST_WEEKS_LOCATION_POLL_RANKINGS_BY_CACHE_INDEX],
+        location_label_routes: [
+            serve::URL_LAST_WEEKS_LOCATION_CATEGORY_POLL_RANKINGS_BY_GLOBAL_IDS,
+            serve::URL_LAST_WEEKS_LOCATION_CATEGORY_POLL_RANKINGS_BY_LOCATION_CACHE_INDEX_AND_GLOBAL_CATEGORY_ID,
+            serve::URL_LAST_WEEKS_LOCATION_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEXES],
+    },
+    RankingsPeriod {
+        name: "yesterday",
+        period: RANKINGS_PERIOD_YESTERDAY,
+        label_routes: [
+            serve::URL_YESTERDAYS_CATEGORY_POLL_RANKINGS_BY_GLOBAL_ID,
+            serve::URL_YESTERDAYS_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEX],
+        location_routes: [
+            serve::URL_YESTERDAYS_LOCATION_POLL_RANKINGS_BY_GLOBAL_ID,
+            serve::URL_YESTERDAYS_LOCATION_POLL_RANKINGS_BY_CACHE_INDEX],
+        location_label_routes: [
+            serve::URL_YESTERDAYS_LOCATION_CATEGORY_POLL_RANKINGS_BY_GLOBAL_IDS,
+            serve::URL_YESTERDAYS_LOCATION_CATEGORY_POLL_RANKINGS_BY_LOCATION_CACHE_INDEX_AND_GLOBAL_CATEGORY_ID,
+            serve::URL_YESTERDAYS_LOCATION_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEXES],
+    },
+    RankingsPeriod {
+        name: "day_b4_yesterday",
+        period: RANKINGS_PERIOD_DAY_B4_YESTERDAY,
+        label_routes: [
+            serve::URL_DAY_B4_YESTERDAY_CATEGORY_POLL_RANKINGS_BY_GLOBAL_ID,
+            serve::URL_DAY_B4_YESTERDAY_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEX],
+        location_routes: [
+            serve::URL_DAY_B4_YESTERDAY_LOCATION_POLL_RANKINGS_BY_GLOBAL_ID,
+            serve::URL_DAY_B4_YESTERDAY_LOCATION_POLL_RANKINGS_BY_CACHE_INDEX],
+        location_label_routes: [
+            serve::URL_DAY_B4_YESTERDAY_LOCATION_YESTERDAY_CATEGORY_POLL_RANKINGS_BY_GLOBAL_IDS,
+            serve::URL_DAY_B4_YESTERDAY_LOCATION_YESTERDAY_CATEGORY_POLL_RANKINGS_BY_LOCATION_CACHE_INDEX_AND_GLOBAL_CATEGORY_ID,
+            serve::URL_DAY_B4_YESTERDAY_LOCATION_YESTERDAY_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEXES],
+    },
+];
+
+struct FuturePeriod {
+    name: &'static str,
+    period: u32,
+    // By kind (label, location, location label)
+    recent_routes: [&'static str; 3],
+    // Label polls only, locations don't record addition sequences
+    since_route: &'static str,
+}
+
+const FUTURE_PERIODS: [FuturePeriod; 4] = [
+    FuturePeriod {
+        name: "next_month",
+        period: FUTURE_PERIOD_NEXT_MONTH,
+        recent_routes: [
+            serve::URL_NEXT_MONTHS_CATEGORY_POLLS,
+            serve::URL_NEXT_MONTHS_LOCATION_POLLS,
+            serve::URL_NEXT_MONTHS_LOCATION_CATEGORY_POLLS],
+        since_route: serve::URL_NEXT_MONTHS_CATEGORY_POLLS_SINCE,
+    },
+    FuturePeriod {
+        name: "next_week",
+        period: FUTURE_PERIOD_NEXT_WEEK,
+        recent_routes: [
+            serve::URL_NEXT_WEEKS_CATEGORY_POLLS,
+            serve::URL_NEXT_WEEKS_LOCATION_POLLS,
+            serve::URL_NEXT_WEEKS_LOCATION_CATEGORY_POLLS],
+        since_route: serve::URL_NEXT_WEEKS_CATEGORY_POLLS_SINCE,
+    },
+    FuturePeriod {
+        name: "tomorrow",
+        period: FUTURE_PERIOD_TOMORROW,
+        recent_routes: [
+            serve::URL_TOMORROWS_CATEGORY_POLLS,
+            serve::URL_TOMORROWS_LOCATION_POLLS,
+            serve::URL_TOMORROWS_LOCATION_CATEGORY_POLLS],
+        since_route: serve::URL_TOMORROWS_CATEGORY_POLLS_SINCE,
+    },
+    FuturePeriod {
+        name: "day_after_tomorrow",
+        period: FUTURE_PERIOD_DAY_AFTER_TOMORROW,
+        recent_routes: [
+            serve::URL_DAY_AFTER_TOMORROWS_CATEGORY_POLLS,
+            serve::URL_DAY_AFTER_TOMORROWS_LOCATION_POLLS,
+            serve::URL_DAY_AFTER_TOMORROWS_LOCATION_CATEGORY_POLLS],
+        since_route: serve::URL_DAY_AFTER_TOMORROWS_CATEGORY_POLLS_SINCE,
+    },
+];
+
+/**
+ *  Human readable versions of the binary routes, for debugging with curl and the like.
+ *
+ *  Query parameters are packed into the same request body the binary route takes and the
+ *  request goes through the same get_response dispatch.  The binary response is then rendered
+ *  as JSON: errors and headers are decoded, poll data is returned as hex.
+ *
+ *  Parameters:
+ *      period      - this_month, this_week, today, last_month, last_week, yesterday or
+ *                    day_b4_yesterday for rankings, next_month, next_week, tomorrow or
+ *                    day_after_tomorrow for recent polls
+ *      period_id   - optional, defaults to the current id of the period
+ *      timezone    - required for location and location label routes
+ *      location, location_index, label, label_index
+ *                  - global ids or cache indexes (cache indexes are for rankings only)
+ *      block       - optional, defaults to the first block (0 for rankings, 1 for recent polls)
+ *      since       - optional sequence number, defaults to 0 (label polls only)
+ */
+pub fn get_debug_response(
+    app: &CompleteCacheApp,
+    path: &str,
+    query: &str,
+) -> Option<Result<Vec<u8>, String>> {
+    let params = parse_query(query);
+
+    match path {
+        serve::URL_DEBUG_LABEL_RANKINGS => {
+            Some(get_rankings(app, &params, KIND_LABEL))
+        }
+        serve::URL_DEBUG_LOCATION_RANKINGS => {
+            Some(get_rankings(app, &params, KIND_LOCATION))
+        }
+        serve::URL_DEBUG_LOCATION_LABEL_RANKINGS => {
+            Some(get_rankings(app, &params, KIND_LOCATION_LABEL))
+        }
+        serve::URL_DEBUG_LABEL_POLLS => {
+            Some(get_future_polls(app, &params, KIND_LABEL, false))
+        }
+        serve::URL_DEBUG_LOCATION_POLLS => {
+            Some(get_future_polls(app, &params, KIND_LOCATION, false))
+        }
+        serve::URL_DEBUG_LOCATION_LABEL_POLLS => {
+            Some(get_future_polls(app, &params, KIND_LOCATION_LABEL, false))
+        }
+        serve::URL_DEBUG_LABEL_POLLS_SINCE => {
+            Some(get_future_polls(app, &params, KIND_LABEL, true))
+        }
+        serve::URL_DEBUG_TIME_PERIOD_IDS => {
+            let response = app.get_response(PROTOCOL_VERSION_1, serve::URL_TIME_PERIOD_IDS, &[]);
+            Some(Ok(render_time_period_ids(&response)))
+        }
+        serve::URL_DEBUG_ROUTES => {
+            Some(Ok(app.routes.describe().into_bytes()))
+        }
+        serve::URL_DEBUG_JOBS => {
+            Some(Ok(render_job_statuses(&app.job_statuses.get())))
+        }
+        _ => {
+            None
+        }
+    }
+}
+
+fn get_rankings(
+    app: &CompleteCacheApp,
+    params: &HashMap<&str, &str>,
+    kind: usize,
+) -> Result<Vec<u8>, String> {
+    let period_name = get_required_param(params, "period")?;
+    let period = match RANKINGS_PERIODS.iter().find(|period| period.name == period_name) {
+        None => {
+            return Err("unknown rankings period".to_string());
+        }
+        Some(period) => {
+            period
+        }
+    };
+
+    let timezone_id = get_timezone_id(params, kind)?;
+    let period_id = match get_u32(params, "period_id")? {
+        None => get_current_period_id(get_current_period_ids(&*app.read_cache(), timezone_id), period.period),
+        Some(period_id) => period_id,
+    };
+
+    let mut body: Vec<u8> = Vec::new();
+    body.write_u32::<BigEndian>(period_id).unwrap();
+    if let Some(timezone_id) = timezone_id {
+        body.write_u32::<BigEndian>(timezone_id).unwrap();
+    }
+    body.write_u32::<BigEndian>(get_u32(params, "block")?.unwrap_or(0)).unwrap();
+
+    let route = match kind {
+        KIND_LABEL => {
+            match get_u64(params, "label")? {
+                Some(global_label_id) => {
+                    body.write_u64::<BigEndian>(global_label_id).unwrap();
+                    period.label_routes[0]
+                }
+                None => {
+                    body.write_u32::<BigEndian>(get_required_u32(params, "label_index")?).unwrap();
+                    period.label_routes[1]
+                }
+            }
+        }
+        KIND_LOCATION => {
+            match get_u64(params, "location")? {
+                Some(global_location_id) => {
+                    body.write_u64::<BigEndian>(global_location_id).unwrap();
+                    period.location_routes[0]
+                }
+                None => {
+                    body.write_u32::<BigEndian>(get_required_u32(params, "location_index")?).unwrap();
+                    period.location_routes[1]
+                }
+            }
+        }
+        _ => {
+            match get_u64(params, "location")? {
+                Some(global_location_id) => {
+                    body.write_u64::<BigEndian>(global_location_id).unwrap();
+                    body.write_u64::<BigEndian>(get_required_u64(params, "label")?).unwrap();
+                    period.location_label_routes[0]
+                }
+                None => {
+                    body.write_u32::<BigEndian>(get_required_u32(params, "location_index")?).unwrap();
+                    match get_u64(params, "label")? {
+                        Some(global_label_id) => {
+                            body.write_u64::<BigEndian>(global_label_id).unwrap();
+                            period.location_label_routes[1]
+                        }
+                        None => {
+                            body.write_u32::<BigEndian>(get_required_u32(params, "label_index")?).unwrap();
+                            period.location_label_routes[2]
+                        }
+                    }
+                }
+            }
+        }
+    };
+
+    let response = app.get_response(PROTOCOL_VERSION_1, route, &body);
+
+    Ok(render_paged_response(route, &response))
+}
+
+fn get_future_polls(
+    app: &CompleteCacheApp,
+    params: &HashMap<&str, &str>,
+    kind: usize,
+    since: bool,
+) -> Result<Vec<u8>, String> {
+    let period_name = get_required_param(params, "period")?;
+    let period = match FUTURE_PERIODS.iter().find(|period| period.name == period_name) {
+        None => {
+            return Err("unknown future period".to_string());
+        }
+        Some(period) => {
+            period
+        }
+    };
+
+    let timezone_id = get_timezone_id(params, kind)?;
+    let period_id = match get_u32(params, "period_id")? {
+        None => get_current_future_period_id(get_current_period_ids(&*app.read_cache(), timezone_id), period.period),
+        Some(period_id) => period_id,
+    };
+
+    let mut body: Vec<u8> = Vec::new();
+    body.write_u32::<BigEndian>(period_id).unwrap();
+    if let Some(timezone_id) = timezone_id {
+        body.write_u32::<BigEndian>(timezone_id).unwrap();
+    }
+    if since {
+        body.write_u64::<BigEndian>(get_u64(params, "since")?.unwrap_or(0)).unwrap();
+    } else {
+        body.write_u32::<BigEndian>(get_u32(params, "block")?.unwrap_or(1)).unwrap();
+    }
+    if kind != KIND_LABEL {
+        body.write_u64::<BigEndian>(get_required_u64(params, "location")?).unwrap();
+    }
+    if kind != KIND_LOCATION {
+        body.write_u64::<BigEndian>(get_required_u64(params, "label")?).unwrap();
+    }
+
+    if since {
+        let route = period.since_route;
+        let response = app.get_response(PROTOCOL_VERSION_1, route, &body);
+        Ok(render_since_response(route, &response))
+    } else {
+        let route = period.recent_routes[kind];
+        let response = app.get_response(PROTOCOL_VERSION_1, route, &body);
+        Ok(render_paged_response(route, &response))
+    }
+}
+
+#[inline]
+fn get_timezone_id(
+    params: &HashMap<&str, &str>,
+    kind: usize,
+) -> Result<Option<u32>, String> {
+    if kind == KIND_LABEL {
+        return Ok(None);
+    }
+
+    let timezone_id = get_required_u32(params, "timezone")?;
+    if timezone_id >= NUM_TIMEZONES {
+        return Err(format!("timezone must be less than {}", NUM_TIMEZONES));
+    }
+
+    Ok(Some(timezone_id))
+}
+
+#[inline]
+fn get_current_period_ids(
+    cache: &CacheReader,
+    timezone_id: Option<u32>,
+) -> &CachePeriodIds {
+    match timezone_id {
+        None => cache.get_label_cache_period_ids(),
+        Some(timezone_id) => &cache.get_per_timezone_cache_period_ids()[timezone_id as usize],
+    }
+}
+
+#[inline]
+fn get_current_future_period_id(
+    current_period_ids: &CachePeriodIds,
+    period: u32,
+) -> u32 {
+    match period {
+        FUTURE_PERIOD_NEXT_MONTH => current_period_ids.next_months_vc_month_id,
+        FUTURE_PERIOD_NEXT_WEEK => current_period_ids.next_weeks_vc_week_id,
+        FUTURE_PERIOD_TOMORROW => current_period_ids.tomorrows_vc_day_id,
+        _ => current_period_ids.day_after_tomorrows_vc_day_id,
+    }
+}
+
+/*
+ *  Query string parsing.  All parameters are names and numbers, so there is no percent
+ *  decoding.
+ */
+
+fn parse_query(
+    query: &str
+) -> HashMap<&str, &str> {
+    let mut params = HashMap::new();
+    for pair in query.split('&') {
+        if pair.is_empty() {
+            continue;
+        }
+        match pair.find('=') {
+            None => {
+                params.insert(pair, "");
+            }
+            Some(value_start) => {
+                params.insert(&pair[..value_start], &pair[value_start + 1..]);
+            }
+        }
+    }
+
+    params
+}
+
+#[inline]
+fn get_required_param<'a>(
+    params: &HashMap<&str, &'a str>,
+    name: &str,
+) -> Result<&'a str, String> {
+    match params.get(name) {
+        None => Err(format!("{} is required", name)),
+        Some(value) => Ok(*value),
+    }
+}
+
+#[inline]
+fn get_u32(
+    params: &HashMap<&str, &str>,
+    name: &str,
+) -> Result<Option<u32>, String> {
+    match params.get(name) {
+        None => Ok(None),
+        Some(value) => value.parse::<u32>().map(Some)
+            .map_err(|_| format!("{} must be a 32 bit unsigned number", name)),
+    }
+}
+
+#[inline]
+fn get_u64(
+    params: &HashMap<&str, &str>,
+    name: &str,
+) -> Result<Option<u64>, String> {
+    match params.get(name) {
+        None => Ok(None),
+        Some(value) => value.parse::<u64>().map(Some)
+            .map_err(|_| format!("{} must be a 64 bit unsigned number", name)),
+    }
+}
+
+#[inline]
+fn get_required_u32(
+    params: &HashMap<&str, &str>,
+    name: &str,
+) -> Result<u32, String> {
+    get_u32(params, name)?.ok_or_else(|| format!("{} is required", name))
+}
+
+#[inline]
+fn get_required_u64(
+    params: &HashMap<&str, &str>,
+    name: &str,
+) -> Result<u64, String> {
+    get_u64(params, name)?.ok_or_else(|| format!("{} is required", name))
+}
+
+/*
+ *  JSON rendering of binary responses.
+ */
+
+fn render_paged_response(
+    route: &str,
+    response: &[u8],
+) -> Vec<u8> {
+    let mut json = String::new();
+    write!(json, "{{\"route\":\"{}\"", route).unwrap();
+
+    if !append_error(&mut json, response) {
+        let header_size = PAGE_HEADER_SIZE as usize;
+        if response.len() >= header_size {
+            let mut reader = Cursor::new(response);
+            let version = reader.read_u8().unwrap();
+            let total_entries = reader.read_u32::<BigEndian>().unwrap();
+            let total_pages = reader.read_u32::<BigEndian>().unwrap();
+            let period_id = reader.read_u32::<BigEndian>().unwrap();
+            let data_version = reader.read_u32::<BigEndian>().unwrap();
+            write!(json, ",\"pageHeader\":{{\"version\":{},\"totalEntries\":{},\"totalPages\":{},\
+                          \"periodId\":{},\"dataVersion\":{}}}",
+                   version, total_entries, total_pages, period_id, data_version).unwrap();
+            append_data(&mut json, &response[header_size..]);
+        } else {
+            append_data(&mut json, response);
+        }
+    }
+    json.push('}');
+
+    json.into_bytes()
+}
+
+fn render_since_response(
+    route: &str,
+    response: &[u8],
+) -> Vec<u8> {
+    let mut json = String::new();
+    write!(json, "{{\"route\":\"{}\"", route).unwrap();
+
+    if !append_error(&mut json, response) {
+        if response.len() >= 9 {
+            let mut reader = Cursor::new(response);
+            let high_water_sequence = reader.read_u64::<BigEndian>().unwrap();
+            let more = reader.read_u8().unwrap() != 0;
+            write!(json, ",\"highWaterSequence\":{},\"more\":{}", high_water_sequence, more).unwrap();
+            append_data(&mut json, &response[9..]);
+        } else {
+            append_data(&mut json, response);
+        }
+    }
+    json.push('}');
+
+    json.into_bytes()
+}
+
+fn render_time_period_ids(
+    response: &[u8]
+) -> Vec<u8> {
+    const PERIOD_NAMES: [&str; 11] = [
+        "dayB4Yesterday", "yesterday", "today", "tomorrow", "dayAfterTomorrow",
+        "lastWeek", "thisWeek", "nextWeek", "lastMonth", "thisMonth", "nextMonth"];
+
+    let mut reader = Cursor::new(response);
+    let num_timezones = reader.read_u8().unwrap() as u32;
+    let server_time = reader.read_u64::<BigEndian>().unwrap();
+
+    let mut json = String::new();
+    write!(json, "{{\"route\":\"{}\",\"serverTime\":{},\"timezones\":[",
+           serve::URL_TIME_PERIOD_IDS, server_time).unwrap();
+    for timezone_index in 0..num_timezones {
+        if timezone_index != 0 {
+            json.push(',');
+        }
+        // Global category comes last
+        if timezone_index == NUM_TIMEZONES {
+            json.push_str("{\"timezone\":\"global\",\"periodIds\":{");
+        } else {
+            write!(json, "{{\"timezone\":{},\"periodIds\":{{", timezone_index).unwrap();
+        }
+        for (period_index, period_name) in PERIOD_NAMES.iter().enumerate() {
+            if period_index != 0 {
+                json.push(',');
+            }
+            write!(json, "\"{}\":{}", period_name, reader.read_u32::<BigEndian>().unwrap()).unwrap();
+        }
+        let rolling_over = reader.read_u8().unwrap() != 0;
+        let next_transition = reader.read_u64::<BigEndian>().unwrap();
+        write!(json, "}},\"rollingOver\":{},\"nextTransition\":{}}}",
+               rolling_over, next_transition).unwrap();
+    }
+    json.push_str("]}");
+
+    json.into_bytes()
+}
+
+/**
+ *  Times are in seconds since the Unix epoch, durations in milliseconds.
+ */
+fn render_job_statuses(
+    job_statuses: &[JobStatus]
+) -> Vec<u8> {
+    let mut json = String::new();
+    json.push_str("{\"jobs\":[");
+    for (job_index, job_status) in job_statuses.iter().enumerate() {
+        if job_index != 0 {
+            json.push(',');
+        }
+        write!(json, "{{\"name\":\"{}\",\"schedule\":\"{:?}\",\"nextRun\":{},\"running\":{}",
+               job_status.name, job_status.schedule, get_seconds(job_status.next_run),
+               job_status.running).unwrap();
+        write!(json, ",\"runs\":{},\"failures\":{},\"skipped\":{}",
+               job_status.runs, job_status.failures, job_status.skipped).unwrap();
+        if let Some(last_started) = job_status.last_started {
+            write!(json, ",\"lastStarted\":{}", get_seconds(last_started)).unwrap();
+        }
+        if let Some(last_finished) = job_status.last_finished {
+            write!(json, ",\"lastFinished\":{}", get_seconds(last_finished)).unwrap();
+        }
+        if let Some(last_duration) = job_status.last_duration {
+            write!(json, ",\"lastDurationMs\":{}",
+                   last_duration.as_secs() * 1000 + last_duration.subsec_millis() as u64).unwrap();
+        }
+        if let Some(ref last_error) = job_status.last_error {
+            write!(json, ",\"lastError\":\"{}\"",
+                   last_error.replace('\\', "\\\\").replace('"', "\\\"")).unwrap();
+        }
+        json.push('}');
+    }
+    json.push_str("]}");
+
+    json.into_bytes()
+}
+
+/**
+ *  Appends the error (if the response is one), returns true if it did.
+ */
+fn append_error(
+    json: &mut String,
+    response: &[u8],
+) -> bool {
+    if response.is_empty() || response[0] & RESPONSE_INVALID_FLAG == 0 {
+        return false;
+    }
+
+    let flag = response[0] - RESPONSE_INVALID_FLAG;
+    let error = match flag {
+        RESPONSE_INVALID_DATA_FORMAT_FLAG => "invalid data format",
+        RESPONSE_INVALID_GLOBAL_CATEGORY_ID_FLAG => "invalid global label id",
+        RESPONSE_INVALID_CATEGORY_CACHE_INDEX_FLAG => "invalid label cache index",
+        RESPONSE_INVALID_GLOBAL_LOCATION_ID_FLAG => "invalid global location id",
+        RESPONSE_INVALID_LOCATION_CACHE_INDEX_FLAG => "invalid location cache index",
+        RESPONSE_INVALID_PERIOD_ID_FLAG => "invalid period id",
+        RESPONSE_INVALID_TIMEZONE_ID_FLAG => "invalid timezone id",
+        RESPONSE_UNSUPPORTED_PROTOCOL_VERSION_FLAG => "unsupported protocol version",
+        _ => "unknown error",
+    };
+    write!(json, ",\"error\":\"{}\"", error).unwrap();
+
+    if flag == RESPONSE_INVALID_PERIOD_ID_FLAG && response.len() >= 5 {
+        let current_period_id = Cursor::new(&response[1..]).read_u32::<BigEndian>().unwrap();
+        write!(json, ",\"currentPeriodId\":{}", current_period_id).unwrap();
+    }
+
+    true
+}
+
+#[inline]
+fn append_data(
+    json: &mut String,
+    data: &[u8],
+) {
+    json.push_str(",\"data\":\"");
+    for byte in data {
+        write!(json, "{:02x}", byte).unwrap();
+    }
+    json.push('"');
+}
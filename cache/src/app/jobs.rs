@@ -0,0 +1,119 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::model::timezone::GLOBAL_ROLLOVER_LOCAL_TIME_IN_MINUTES;
+use common::model::timezone::GLOBAL_ROLLOVER_TIMEZONE;
+use common::model::timezone::NUM_TIMEZONES;
+use common::model::timezone::UTC_OFFSETS_IN_MINUTES;
+
+use server::cache::pages::PageCache;
+use server::cache::scheduler::get_seconds;
+use server::cache::scheduler::Schedule;
+use server::cache::scheduler::Scheduler;
+use server::cache::updater::UpdateWriter;
+use server::cache::watchers::PageWatchers;
+use server::config::ServerConfig;
+
+use super::super::logic::rerank::re_rank;
+use super::super::logic::rollover::get_starting_day;
+use super::super::logic::rollover::LOCK_DOWN_LOCAL_TIME_IN_MINUTES;
+
+use super::update::CompleteCacheUpdateApp;
+
+/**
+ * How often the rankings of the current periods are fully re-sorted.
+ */
+const RE_RANK_INTERVAL: Duration = Duration::from_secs(60);
+
+/**
+ * Names of the rollover jobs start with this, followed by the timezone index (or "global").
+ */
+pub const ROLLOVER_JOB_PREFIX: &str = "rollover-";
+
+pub const GLOBAL_ROLLOVER_JOB: &str = "rollover-global";
+
+/**
+ * Registers the periodic cache jobs.  All of them change the cache, so they run on the writer
+ * thread (in between updates), the scheduler only decides when.
+ *
+ *  lock-down-<timezone index>  - counts of the timezone are locked down, ahead of its rollover
+ *  rollover-<timezone index>   - the timezone moves on to the next day, at its local midnight
+ *  rollover-global             - the global category (and future polls) move on to the next
+ *                                day, at the global rollover time
+ *  re-rank                     - full re-sort of the current period rankings
+ *  snapshot                    - saves a snapshot (if there is a snapshot file)
+ */
+pub fn schedule_jobs(
+    scheduler: &mut Scheduler,
+    update_writer: &UpdateWriter<CompleteCacheUpdateApp>,
+    config: &ServerConfig,
+    page_watchers: Arc<PageWatchers>,
+    page_cache: Arc<PageCache>,
+) {
+    for timezone_index in 0..NUM_TIMEZONES as usize {
+        let utc_offset_minutes = UTC_OFFSETS_IN_MINUTES[timezone_index];
+
+        let writer = update_writer.clone();
+        scheduler.add_job(&format!("lock-down-{}", timezone_index), Schedule::DailyAt {
+            utc_offset_minutes,
+            local_time_minutes: LOCK_DOWN_LOCAL_TIME_IN_MINUTES,
+        }, move |_| {
+            writer.run(move |app| {
+                app.lock_down_timezone(timezone_index);
+                Ok(())
+            })
+        });
+
+        let writer = update_writer.clone();
+        let rollover_page_watchers = page_watchers.clone();
+        let rollover_page_cache = page_cache.clone();
+        scheduler.add_job(&format!("{}{}", ROLLOVER_JOB_PREFIX, timezone_index), Schedule::DailyAt {
+            utc_offset_minutes,
+            local_time_minutes: 0,
+        }, move |scheduled_time| {
+            let starting_day = get_starting_day(get_seconds(scheduled_time), utc_offset_minutes, 0);
+            let page_watchers = rollover_page_watchers.clone();
+            let page_cache = rollover_page_cache.clone();
+            writer.run(move |app| {
+                app.roll_over_timezone(timezone_index, &starting_day, &page_watchers, &page_cache);
+                Ok(())
+            })
+        });
+    }
+
+    let utc_offset_minutes = UTC_OFFSETS_IN_MINUTES[GLOBAL_ROLLOVER_TIMEZONE];
+    let writer = update_writer.clone();
+    let rollover_page_watchers = page_watchers.clone();
+    let rollover_page_cache = page_cache.clone();
+    scheduler.add_job(GLOBAL_ROLLOVER_JOB, Schedule::DailyAt {
+        utc_offset_minutes,
+        local_time_minutes: GLOBAL_ROLLOVER_LOCAL_TIME_IN_MINUTES,
+    }, move |scheduled_time| {
+        let starting_day = get_starting_day(get_seconds(scheduled_time), utc_offset_minutes,
+                                            GLOBAL_ROLLOVER_LOCAL_TIME_IN_MINUTES);
+        let page_watchers = rollover_page_watchers.clone();
+        let page_cache = rollover_page_cache.clone();
+        writer.run(move |app| {
+            app.roll_over_global(&starting_day, &page_watchers, &page_cache);
+            Ok(())
+        })
+    });
+
+    let writer = update_writer.clone();
+    scheduler.add_job("re-rank", Schedule::Every(RE_RANK_INTERVAL), move |_| {
+        let page_watchers = page_watchers.clone();
+        let page_cache = page_cache.clone();
+        writer.run(move |app| {
+            re_rank(&mut app.cache_writer.cache(), &mut app.page_digests, &page_watchers, &page_cache);
+            Ok(())
+        })
+    });
+
+    if config.snapshot_file.is_some() {
+        let writer = update_writer.clone();
+        let snapshot_interval = Duration::from_secs(config.snapshot_interval_seconds);
+        scheduler.add_job("snapshot", Schedule::Every(snapshot_interval), move |_| {
+            writer.run(|app| app.save_snapshot())
+        });
+    }
+}
@@ -0,0 +1,205 @@
+use std::fmt::Write;
+
+use int_hash::IntHashMap;
+
+use common::model::timezone::NUM_TIMEZONES;
+use common::model::types::PollId;
+
+use server::cache::scheduler::get_seconds;
+use server::cache::scheduler::JobStatus;
+
+use super::super::cache::cache_reader::CacheReader;
+use super::super::cache::polls_by_label::PollsByLabel;
+
+use super::app::CompleteCacheApp;
+use super::jobs::ROLLOVER_JOB_PREFIX;
+
+/**
+ *  Metrics of the cache (in the Prometheus text format), appended to the server's request
+ *  metrics:
+ *
+ *  vc_cache_labels                 - labels with rankings, by period (across timezones)
+ *  vc_cache_locations              - locations with rankings, by period and timezone
+ *  vc_cache_polls                  - polls (of all dimensions) with counts, by period and
+ *                                    timezone
+ *  vc_cache_future_labels          - labels with future polls, by future period
+ *  vc_cache_future_label_polls     - poll ids of future polls (once per label), by future period
+ *  vc_cache_future_locations       - locations with future polls, by future period and timezone
+ *  vc_cache_last_rollover_timestamp_seconds
+ *                                  - when each timezone (and the global category) last rolled
+ *                                    over, since the process started
+ *  vc_job_runs_total, vc_job_failures_total, vc_job_skipped_total
+ *                                  - by scheduled job
+ *
+ *  Cache sizes are all read under the same read lock, as of the same update.
+ */
+pub fn render_metrics(
+    app: &CompleteCacheApp,
+    out: &mut String,
+) {
+    render_cache_metrics(&*app.read_cache(), out);
+    render_job_metrics(&app.job_statuses.get(), out);
+}
+
+fn render_cache_metrics(
+    cache: &CacheReader,
+    out: &mut String,
+) {
+    let label_index_map = cache.get_label_index_map();
+    render_header(out, "vc_cache_labels", "Labels with rankings, by period (across timezones)");
+    for &(period, labels) in [
+        ("last_month", label_index_map.last_month.len()),
+        ("this_month", label_index_map.this_month.len()),
+        ("last_week", label_index_map.last_week.len()),
+        ("this_week", label_index_map.this_week.len()),
+        ("day_b4_yesterday", label_index_map.day_b4_yesterday.len()),
+        ("yesterday", label_index_map.yesterday.len()),
+        ("today", label_index_map.today.len()),
+    ].iter() {
+        writeln!(out, "vc_cache_labels{{period=\"{}\"}} {}", period, labels).unwrap();
+    }
+
+    let rankings = cache.get_location_poll_rankings();
+    render_header(out, "vc_cache_locations", "Locations with rankings, by period and timezone");
+    for &(period, locations) in [
+        ("last_month", &rankings.last_month),
+        ("this_month", &rankings.this_month),
+        ("last_week", &rankings.last_week),
+        ("this_week", &rankings.this_week),
+        ("day_b4_yesterday", &rankings.day_b4_yesterday),
+        ("yesterday", &rankings.yesterday),
+        ("today", &rankings.today),
+    ].iter() {
+        render_per_timezone(out, "vc_cache_locations", period, |timezone_index| {
+            locations.get(timezone_index).map_or(0, |locations| locations.len())
+        });
+    }
+
+    let (polls_1_d, polls_2_d, polls_3_d) = (cache.get_polls_1_d(), cache.get_polls_2_d(), cache.get_polls_3_d());
+    render_header(out, "vc_cache_polls", "Polls with counts, by period and timezone");
+    for &(period, one_d, two_d, three_d) in [
+        ("last_month", &polls_1_d.last_month, &polls_2_d.last_month, &polls_3_d.last_month),
+        ("this_month", &polls_1_d.this_month, &polls_2_d.this_month, &polls_3_d.this_month),
+        ("last_week", &polls_1_d.last_week, &polls_2_d.last_week, &polls_3_d.last_week),
+        ("this_week", &polls_1_d.this_week, &polls_2_d.this_week, &polls_3_d.this_week),
+        ("day_b4_yesterday", &polls_1_d.day_b4_yesterday, &polls_2_d.day_b4_yesterday, &polls_3_d.day_b4_yesterday),
+        ("yesterday", &polls_1_d.yesterday, &polls_2_d.yesterday, &polls_3_d.yesterday),
+        ("today", &polls_1_d.today, &polls_2_d.today, &polls_3_d.today),
+    ].iter() {
+        render_per_timezone(out, "vc_cache_polls", period, |timezone_index| {
+            get_num_polls(one_d, timezone_index)
+                + get_num_polls(two_d, timezone_index)
+                + get_num_polls(three_d, timezone_index)
+        });
+    }
+
+    render_future_label_metrics(cache.get_future_polls_by_label(), out);
+
+    let future_polls_by_location = cache.get_future_polls_by_location();
+    render_header(out, "vc_cache_future_locations", "Locations with future polls, by future period and timezone");
+    for &(period, locations) in [
+        ("next_month", &future_polls_by_location.next_month),
+        ("next_week", &future_polls_by_location.next_week),
+        ("tomorrow", &future_polls_by_location.tomorrow),
+        ("day_after_tomorrow", &future_polls_by_location.day_after_tomorrow),
+    ].iter() {
+        render_per_timezone(out, "vc_cache_future_locations", period, |timezone_index| {
+            locations.get(timezone_index).map_or(0, |locations| locations.len())
+        });
+    }
+}
+
+fn render_future_label_metrics(
+    future_polls_by_label: &PollsByLabel,
+    out: &mut String,
+) {
+    let periods = [
+        ("next_month", &future_polls_by_label.next_month),
+        ("next_week", &future_polls_by_label.next_week),
+        ("tomorrow", &future_polls_by_label.tomorrow),
+        ("day_after_tomorrow", &future_polls_by_label.day_after_tomorrow),
+    ];
+
+    render_header(out, "vc_cache_future_labels", "Labels with future polls, by future period");
+    for &(period, polls_by_label) in periods.iter() {
+        writeln!(out, "vc_cache_future_labels{{period=\"{}\"}} {}", period, polls_by_label.len()).unwrap();
+    }
+
+    render_header(out, "vc_cache_future_label_polls", "Poll ids of future polls (once per label), by future period");
+    for &(period, polls_by_label) in periods.iter() {
+        let num_poll_ids: u64 = polls_by_label.values()
+            .map(|poll_id_frames| poll_id_frames.num_poll_ids as u64).sum();
+        writeln!(out, "vc_cache_future_label_polls{{period=\"{}\"}} {}", period, num_poll_ids).unwrap();
+    }
+}
+
+fn render_job_metrics(
+    job_statuses: &[JobStatus],
+    out: &mut String,
+) {
+    // Rollovers that haven't run yet (or whose last run failed) are left out
+    render_header(out, "vc_cache_last_rollover_timestamp_seconds",
+                  "When a timezone (or the global category) last rolled over, since the process started");
+    for job_status in job_statuses.iter() {
+        if !job_status.name.starts_with(ROLLOVER_JOB_PREFIX) {
+            continue;
+        }
+        if let (Some(last_finished), None) = (job_status.last_finished, job_status.last_error.as_ref()) {
+            writeln!(out, "vc_cache_last_rollover_timestamp_seconds{{timezone=\"{}\"}} {}",
+                     &job_status.name[ROLLOVER_JOB_PREFIX.len()..], get_seconds(last_finished)).unwrap();
+        }
+    }
+
+    render_counter_header(out, "vc_job_runs_total", "Finished runs, by scheduled job");
+    for job_status in job_statuses.iter() {
+        writeln!(out, "vc_job_runs_total{{job=\"{}\"}} {}", job_status.name, job_status.runs).unwrap();
+    }
+    render_counter_header(out, "vc_job_failures_total", "Failed runs, by scheduled job");
+    for job_status in job_statuses.iter() {
+        writeln!(out, "vc_job_failures_total{{job=\"{}\"}} {}", job_status.name, job_status.failures).unwrap();
+    }
+    render_counter_header(out, "vc_job_skipped_total", "Runs skipped (the previous run was still going), by scheduled job");
+    for job_status in job_statuses.iter() {
+        writeln!(out, "vc_job_skipped_total{{job=\"{}\"}} {}", job_status.name, job_status.skipped).unwrap();
+    }
+}
+
+#[inline]
+fn get_num_polls<T>(
+    polls: &[IntHashMap<PollId, T>],
+    timezone_index: usize,
+) -> usize {
+    // Timezones without polls (yet) have no maps
+    polls.get(timezone_index).map_or(0, |polls| polls.len())
+}
+
+#[inline]
+fn render_per_timezone<F>(
+    out: &mut String,
+    name: &str,
+    period: &str,
+    get_count: F,
+) where F: Fn(usize) -> usize {
+    for timezone_index in 0..NUM_TIMEZONES as usize {
+        writeln!(out, "{}{{period=\"{}\",timezone=\"{}\"}} {}",
+                 name, period, timezone_index, get_count(timezone_index)).unwrap();
+    }
+}
+
+#[inline]
+fn render_header(
+    out: &mut String,
+    name: &str,
+    help: &str,
+) {
+    writeln!(out, "# HELP {} {}\n# TYPE {} gauge", name, help, name).unwrap();
+}
+
+#[inline]
+fn render_counter_header(
+    out: &mut String,
+    name: &str,
+    help: &str,
+) {
+    writeln!(out, "# HELP {} {}\n# TYPE {} counter", name, help, name).unwrap();
+}
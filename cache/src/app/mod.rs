@@ -1 +1,6 @@
 pub mod app;
+pub mod debug;
+pub mod jobs;
+pub mod metrics;
+pub mod routes;
+pub mod update;
\ No newline at end of file
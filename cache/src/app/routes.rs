@@ -0,0 +1,691 @@
+use common::model::protocol::ProtocolVersion;
+use common::model::protocol::PROTOCOL_VERSION_1;
+use common::url::cache::add;
+use common::url::cache::serve;
+
+use server::route::Field;
+use server::route::FieldType;
+use server::route::Route;
+use server::route::RouteRegistry;
+
+use super::super::logic::serve::page_key;
+use super::super::logic::serve::page_key::RANKINGS_PERIOD_DAY_B4_YESTERDAY;
+use super::super::logic::serve::page_key::RANKINGS_PERIOD_LAST_MONTH;
+use super::super::logic::serve::page_key::RANKINGS_PERIOD_LAST_WEEK;
+use super::super::logic::serve::page_key::RANKINGS_PERIOD_THIS_MONTH;
+use super::super::logic::serve::page_key::RANKINGS_PERIOD_THIS_WEEK;
+use super::super::logic::serve::page_key::RANKINGS_PERIOD_TODAY;
+use super::super::logic::serve::page_key::RANKINGS_PERIOD_YESTERDAY;
+use super::super::logic::serve::period_ids::get_time_period_ids;
+use super::super::logic::serve::rankings::label;
+use super::super::logic::serve::rankings::location;
+use super::super::logic::serve::rankings::location_label;
+
+use super::super::logic::serve::recent::label::get_day_after_tomorrows_label_polls;
+use super::super::logic::serve::recent::label::get_next_months_label_polls;
+use super::super::logic::serve::recent::label::get_next_weeks_label_polls;
+use super::super::logic::serve::recent::label::get_tomorrows_label_polls;
+use super::super::logic::serve::recent::label::get_day_after_tomorrows_label_polls_since;
+use super::super::logic::serve::recent::label::get_next_months_label_polls_since;
+use super::super::logic::serve::recent::label::get_next_weeks_label_polls_since;
+use super::super::logic::serve::recent::label::get_tomorrows_label_polls_since;
+
+use super::super::logic::serve::recent::location::get_day_after_tomorrows_location_polls;
+use super::super::logic::serve::recent::location::get_next_months_location_polls;
+use super::super::logic::serve::recent::location::get_next_weeks_location_polls;
+use super::super::logic::serve::recent::location::get_tomorrows_location_polls;
+
+use super::super::logic::serve::recent::location_label::get_day_after_tomorrows_location_label_polls;
+use super::super::logic::serve::recent::location_label::get_next_months_location_label_polls;
+use super::super::logic::serve::recent::location_label::get_next_weeks_location_label_polls;
+use super::super::logic::serve::recent::location_label::get_tomorrows_location_label_polls;
+
+use super::super::cache::cache::Cache;
+use super::super::cache::cache_reader::CacheReader;
+use super::update::get_add_label_polls;
+use super::update::CacheUpdate;
+
+pub type CacheRoutes = RouteRegistry<CacheReader, CacheUpdate>;
+
+/**
+ *  Protocol versions served.  A new version is added here, with registrations (limited to the
+ *  new version) for the routes whose formats it changes, the older registrations are limited
+ *  to the older versions and keep serving them.
+ */
+pub const PROTOCOL_VERSIONS: &[ProtocolVersion] = &[PROTOCOL_VERSION_1];
+
+/*
+ *  Request body fields
+ */
+
+const PERIOD_ID: Field = Field { name: "period_id", field_type: FieldType::Int };
+const TIMEZONE_ID: Field = Field { name: "timezone_id", field_type: FieldType::Int };
+const BLOCK_INDEX: Field = Field { name: "block_index", field_type: FieldType::Int };
+const BLOCK_NUMBER: Field = Field { name: "block_number", field_type: FieldType::Int };
+const SINCE_SEQUENCE: Field = Field { name: "since_sequence", field_type: FieldType::Long };
+const GLOBAL_LABEL_ID: Field = Field { name: "global_label_id", field_type: FieldType::Long };
+const LABEL_CACHE_INDEX: Field = Field { name: "label_cache_index", field_type: FieldType::Int };
+const GLOBAL_LOCATION_ID: Field = Field { name: "global_location_id", field_type: FieldType::Long };
+const LOCATION_CACHE_INDEX: Field = Field { name: "location_cache_index", field_type: FieldType::Int };
+const LOCATION_LABEL_CACHE_INDEX: Field = Field { name: "location_label_cache_index", field_type: FieldType::Int };
+
+/*
+ *  Request body schemas, shared by all periods of a route
+ */
+
+const LABEL_RANKINGS_BY_GLOBAL_ID: &[Field] = &[PERIOD_ID, BLOCK_INDEX, GLOBAL_LABEL_ID];
+const LABEL_RANKINGS_BY_CACHE_INDEX: &[Field] = &[PERIOD_ID, BLOCK_INDEX, LABEL_CACHE_INDEX];
+const LOCATION_RANKINGS_BY_GLOBAL_ID: &[Field] = &[PERIOD_ID, TIMEZONE_ID, BLOCK_INDEX, GLOBAL_LOCATION_ID];
+const LOCATION_RANKINGS_BY_CACHE_INDEX: &[Field] = &[PERIOD_ID, TIMEZONE_ID, BLOCK_INDEX, LOCATION_CACHE_INDEX];
+const LOCATION_LABEL_RANKINGS_BY_GLOBAL_IDS: &[Field] = &[PERIOD_ID, TIMEZONE_ID, BLOCK_INDEX, GLOBAL_LOCATION_ID, GLOBAL_LABEL_ID];
+const LOCATION_LABEL_RANKINGS_BY_LOCATION_CACHE_INDEX_AND_GLOBAL_LABEL_ID: &[Field] = &[PERIOD_ID, TIMEZONE_ID, BLOCK_INDEX, LOCATION_CACHE_INDEX, GLOBAL_LABEL_ID];
+const LOCATION_LABEL_RANKINGS_BY_CACHE_INDEXES: &[Field] = &[PERIOD_ID, TIMEZONE_ID, BLOCK_INDEX, LOCATION_CACHE_INDEX, LOCATION_LABEL_CACHE_INDEX];
+const LOCATION_POLLS: &[Field] = &[PERIOD_ID, TIMEZONE_ID, BLOCK_NUMBER, GLOBAL_LOCATION_ID];
+const LABEL_POLLS: &[Field] = &[PERIOD_ID, BLOCK_NUMBER, GLOBAL_LABEL_ID];
+const LOCATION_LABEL_POLLS: &[Field] = &[PERIOD_ID, TIMEZONE_ID, BLOCK_NUMBER, GLOBAL_LOCATION_ID, GLOBAL_LABEL_ID];
+const LABEL_POLLS_SINCE: &[Field] = &[PERIOD_ID, SINCE_SEQUENCE, GLOBAL_LABEL_ID];
+// Followed by the label poll items, see update::get_add_label_polls
+const ADD_LABEL_POLLS: &[Field] = &[PERIOD_ID];
+
+/**
+ *  All routes served by the cache, read and update.  Adding a route (or a period variant of
+ *  one) is a single registration here, request validation and decoding is done by the registry.
+ */
+pub fn get_routes() -> CacheRoutes {
+    let mut routes: CacheRoutes = RouteRegistry::new();
+
+    /*
+     *  POLL RANKINGS
+     */
+
+    // Label Poll Rankings
+    routes.register(Route::read(
+        serve::URL_THIS_MONTHS_CATEGORY_POLL_RANKINGS_BY_GLOBAL_ID,
+        "this_months_label_rankings_by_global_id",
+        LABEL_RANKINGS_BY_GLOBAL_ID,
+        |args, cache| label::get_this_months_label_rankings_by_global_id(
+            args.int(0), args.int(1), args.long(2), cache))
+        .with_page_key(|args, cache| page_key::get_label_rankings_page_key_by_global_id(
+            RANKINGS_PERIOD_THIS_MONTH,
+            args.int(0), args.int(1), args.long(2), cache)));
+    routes.register(Route::read(
+        serve::URL_THIS_MONTHS_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEX,
+        "this_months_label_rankings_by_cache_index",
+        LABEL_RANKINGS_BY_CACHE_INDEX,
+        |args, cache| label::get_this_months_label_rankings_by_cache_index(
+            args.int(0), args.int(1), args.int(2), cache))
+        .with_page_key(|args, cache| page_key::get_label_rankings_page_key_by_cache_index(
+            RANKINGS_PERIOD_THIS_MONTH,
+            args.int(0), args.int(1), args.int(2), cache)));
+    routes.register(Route::read(
+        serve::URL_THIS_WEEKS_CATEGORY_POLL_RANKINGS_BY_GLOBAL_ID,
+        "this_weeks_label_rankings_by_global_id",
+        LABEL_RANKINGS_BY_GLOBAL_ID,
+        |args, cache| label::get_this_weeks_label_rankings_by_global_id(
+            args.int(0), args.int(1), args.long(2), cache))
+        .with_page_key(|args, cache| page_key::get_label_rankings_page_key_by_global_id(
+            RANKINGS_PERIOD_THIS_WEEK,
+            args.int(0), args.int(1), args.long(2), cache)));
+    routes.register(Route::read(
+        serve::URL_THIS_WEEKS_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEX,
+        "this_weeks_label_rankings_by_cache_index",
+        LABEL_RANKINGS_BY_CACHE_INDEX,
+        |args, cache| label::get_this_weeks_label_rankings_by_cache_index(
+            args.int(0), args.int(1), args.int(2), cache))
+        .with_page_key(|args, cache| page_key::get_label_rankings_page_key_by_cache_index(
+            RANKINGS_PERIOD_THIS_WEEK,
+            args.int(0), args.int(1), args.int(2), cache)));
+    routes.register(Route::read(
+        serve::URL_TODAYS_CATEGORY_POLL_RANKINGS_BY_GLOBAL_ID,
+        "todays_label_rankings_by_global_id",
+        LABEL_RANKINGS_BY_GLOBAL_ID,
+        |args, cache| label::get_todays_label_rankings_by_global_id(
+            args.int(0), args.int(1), args.long(2), cache))
+        .with_page_key(|args, cache| page_key::get_label_rankings_page_key_by_global_id(
+            RANKINGS_PERIOD_TODAY,
+            args.int(0), args.int(1), args.long(2), cache)));
+    routes.register(Route::read(
+        serve::URL_TODAYS_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEX,
+        "todays_label_rankings_by_cache_index",
+        LABEL_RANKINGS_BY_CACHE_INDEX,
+        |args, cache| label::get_todays_label_rankings_by_cache_index(
+            args.int(0), args.int(1), args.int(2), cache))
+        .with_page_key(|args, cache| page_key::get_label_rankings_page_key_by_cache_index(
+            RANKINGS_PERIOD_TODAY,
+            args.int(0), args.int(1), args.int(2), cache)));
+    routes.register(Route::read(
+        serve::URL_LAST_MONTHS_CATEGORY_POLL_RANKINGS_BY_GLOBAL_ID,
+        "last_months_label_rankings_by_global_id",
+        LABEL_RANKINGS_BY_GLOBAL_ID,
+        |args, cache| label::get_last_months_label_rankings_by_global_id(
+            args.int(0), args.int(1), args.long(2), cache))
+        .with_page_key(|args, cache| page_key::get_label_rankings_page_key_by_global_id(
+            RANKINGS_PERIOD_LAST_MONTH,
+            args.int(0), args.int(1), args.long(2), cache)));
+    routes.register(Route::read(
+        serve::URL_LAST_MONTHS_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEX,
+        "last_months_label_rankings_by_cache_index",
+        LABEL_RANKINGS_BY_CACHE_INDEX,
+        |args, cache| label::get_last_months_label_rankings_by_cache_index(
+            args.int(0), args.int(1), args.int(2), cache))
+        .with_page_key(|args, cache| page_key::get_label_rankings_page_key_by_cache_index(
+            RANKINGS_PERIOD_LAST_MONTH,
+            args.int(0), args.int(1), args.int(2), cache)));
+    routes.register(Route::read(
+        serve::URL_LAST_WEEKS_CATEGORY_POLL_RANKINGS_BY_GLOBAL_ID,
+        "last_weeks_label_rankings_by_global_id",
+        LABEL_RANKINGS_BY_GLOBAL_ID,
+        |args, cache| label::get_last_weeks_label_rankings_by_global_id(
+            args.int(0), args.int(1), args.long(2), cache))
+        .with_page_key(|args, cache| page_key::get_label_rankings_page_key_by_global_id(
+            RANKINGS_PERIOD_LAST_WEEK,
+            args.int(0), args.int(1), args.long(2), cache)));
+    routes.register(Route::read(
+        serve::URL_LAST_WEEKS_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEX,
+        "last_weeks_label_rankings_by_cache_index",
+        LABEL_RANKINGS_BY_CACHE_INDEX,
+        |args, cache| label::get_last_weeks_label_rankings_by_cache_index(
+            args.int(0), args.int(1), args.int(2), cache))
+        .with_page_key(|args, cache| page_key::get_label_rankings_page_key_by_cache_index(
+            RANKINGS_PERIOD_LAST_WEEK,
+            args.int(0), args.int(1), args.int(2), cache)));
+    routes.register(Route::read(
+        serve::URL_YESTERDAYS_CATEGORY_POLL_RANKINGS_BY_GLOBAL_ID,
+        "yesterdays_label_rankings_by_global_id",
+        LABEL_RANKINGS_BY_GLOBAL_ID,
+        |args, cache| label::get_yesterdays_label_rankings_by_global_id(
+            args.int(0), args.int(1), args.long(2), cache))
+        .with_page_key(|args, cache| page_key::get_label_rankings_page_key_by_global_id(
+            RANKINGS_PERIOD_YESTERDAY,
+            args.int(0), args.int(1), args.long(2), cache)));
+    routes.register(Route::read(
+        serve::URL_YESTERDAYS_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEX,
+        "yesterdays_label_rankings_by_cache_index",
+        LABEL_RANKINGS_BY_CACHE_INDEX,
+        |args, cache| label::get_yesterdays_label_rankings_by_cache_index(
+            args.int(0), args.int(1), args.int(2), cache))
+        .with_page_key(|args, cache| page_key::get_label_rankings_page_key_by_cache_index(
+            RANKINGS_PERIOD_YESTERDAY,
+            args.int(0), args.int(1), args.int(2), cache)));
+    routes.register(Route::read(
+        serve::URL_DAY_B4_YESTERDAY_CATEGORY_POLL_RANKINGS_BY_GLOBAL_ID,
+        "day_b4_yesterdays_label_rankings_by_global_id",
+        LABEL_RANKINGS_BY_GLOBAL_ID,
+        |args, cache| label::get_day_b4_yesterdays_label_rankings_by_global_id(
+            args.int(0), args.int(1), args.long(2), cache))
+        .with_page_key(|args, cache| page_key::get_label_rankings_page_key_by_global_id(
+            RANKINGS_PERIOD_DAY_B4_YESTERDAY,
+            args.int(0), args.int(1), args.long(2), cache)));
+    routes.register(Route::read(
+        serve::URL_DAY_B4_YESTERDAY_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEX,
+        "day_b4_yesterdays_label_rankings_by_cache_index",
+        LABEL_RANKINGS_BY_CACHE_INDEX,
+        |args, cache| label::get_day_b4_yesterdays_label_rankings_by_cache_index(
+            args.int(0), args.int(1), args.int(2), cache))
+        .with_page_key(|args, cache| page_key::get_label_rankings_page_key_by_cache_index(
+            RANKINGS_PERIOD_DAY_B4_YESTERDAY,
+            args.int(0), args.int(1), args.int(2), cache)));
+
+    // Location Poll Rankings
+    routes.register(Route::read(
+        serve::URL_THIS_MONTHS_LOCATION_POLL_RANKINGS_BY_GLOBAL_ID,
+        "this_months_location_rankings_by_global_id",
+        LOCATION_RANKINGS_BY_GLOBAL_ID,
+        |args, cache| location::get_this_months_location_rankings_by_global_id(
+            args.int(0), args.int(1), args.int(2), args.long(3), cache))
+        .with_page_key(|args, cache| page_key::get_location_rankings_page_key_by_global_id(
+            RANKINGS_PERIOD_THIS_MONTH,
+            args.int(0), args.int(1), args.int(2), args.long(3), cache)));
+    routes.register(Route::read(
+        serve::URL_THIS_MONTHS_LOCATION_POLL_RANKINGS_BY_CACHE_INDEX,
+        "this_months_location_rankings_by_cache_index",
+        LOCATION_RANKINGS_BY_CACHE_INDEX,
+        |args, cache| location::get_this_months_location_rankings_by_cache_index(
+            args.int(0), args.int(1), args.int(2), args.int(3), cache))
+        .with_page_key(|args, cache| page_key::get_location_rankings_page_key_by_cache_index(
+            RANKINGS_PERIOD_THIS_MONTH,
+            args.int(0), args.int(1), args.int(2), args.int(3), cache)));
+    routes.register(Route::read(
+        serve::URL_THIS_WEEKS_LOCATION_POLL_RANKINGS_BY_GLOBAL_ID,
+        "this_weeks_location_rankings_by_global_id",
+        LOCATION_RANKINGS_BY_GLOBAL_ID,
+        |args, cache| location::get_this_weeks_location_rankings_by_global_id(
+            args.int(0), args.int(1), args.int(2), args.long(3), cache))
+        .with_page_key(|args, cache| page_key::get_location_rankings_page_key_by_global_id(
+            RANKINGS_PERIOD_THIS_WEEK,
+            args.int(0), args.int(1), args.int(2), args.long(3), cache)));
+    routes.register(Route::read(
+        serve::URL_THIS_WEEKS_LOCATION_POLL_RANKINGS_BY_CACHE_INDEX,
+        "this_weeks_location_rankings_by_cache_index",
+        LOCATION_RANKINGS_BY_CACHE_INDEX,
+        |args, cache| location::get_this_weeks_location_rankings_by_cache_index(
+            args.int(0), args.int(1), args.int(2), args.int(3), cache))
+        .with_page_key(|args, cache| page_key::get_location_rankings_page_key_by_cache_index(
+            RANKINGS_PERIOD_THIS_WEEK,
+            args.int(0), args.int(1), args.int(2), args.int(3), cache)));
+    routes.register(Route::read(
+        serve::URL_TODAYS_LOCATION_POLL_RANKINGS_BY_GLOBAL_ID,
+        "todays_location_rankings_by_global_id",
+        LOCATION_RANKINGS_BY_GLOBAL_ID,
+        |args, cache| location::get_todays_location_rankings_by_global_id(
+            args.int(0), args.int(1), args.int(2), args.long(3), cache))
+        .with_page_key(|args, cache| page_key::get_location_rankings_page_key_by_global_id(
+            RANKINGS_PERIOD_TODAY,
+            args.int(0), args.int(1), args.int(2), args.long(3), cache)));
+    routes.register(Route::read(
+        serve::URL_TODAYS_LOCATION_POLL_RANKINGS_BY_CACHE_INDEX,
+        "todays_location_rankings_by_cache_index",
+        LOCATION_RANKINGS_BY_CACHE_INDEX,
+        |args, cache| location::get_todays_location_rankings_by_cache_index(
+            args.int(0), args.int(1), args.int(2), args.int(3), cache))
+        .with_page_key(|args, cache| page_key::get_location_rankings_page_key_by_cache_index(
+            RANKINGS_PERIOD_TODAY,
+            args.int(0), args.int(1), args.int(2), args.int(3), cache)));
+    routes.register(Route::read(
+        serve::URL_LAST_MONTHS_LOCATION_POLL_RANKINGS_BY_GLOBAL_ID,
+        "last_months_location_rankings_by_global_id",
+        LOCATION_RANKINGS_BY_GLOBAL_ID,
+        |args, cache| location::get_last_months_location_rankings_by_global_id(
+            args.int(0), args.int(1), args.int(2), args.long(3), cache))
+        .with_page_key(|args, cache| page_key::get_location_rankings_page_key_by_global_id(
+            RANKINGS_PERIOD_LAST_MONTH,
+            args.int(0), args.int(1), args.int(2), args.long(3), cache)));
+    routes.register(Route::read(
+        serve::URL_LAST_MONTHS_LOCATION_POLL_RANKINGS_BY_CACHE_INDEX,
+        "last_months_location_rankings_by_cache_index",
+        LOCATION_RANKINGS_BY_CACHE_INDEX,
+        |args, cache| location::get_last_months_location_rankings_by_cache_index(
+            args.int(0), args.int(1), args.int(2), args.int(3), cache))
+        .with_page_key(|args, cache| page_key::get_location_rankings_page_key_by_cache_index(
+            RANKINGS_PERIOD_LAST_MONTH,
+            args.int(0), args.int(1), args.int(2), args.int(3), cache)));
+    routes.register(Route::read(
+        serve::URL_LAST_WEEKS_LOCATION_POLL_RANKINGS_BY_GLOBAL_ID,
+        "last_weeks_location_rankings_by_global_id",
+        LOCATION_RANKINGS_BY_GLOBAL_ID,
+        |args, cache| location::get_last_weeks_location_rankings_by_global_id(
+            args.int(0), args.int(1), args.int(2), args.long(3), cache))
+        .with_page_key(|args, cache| page_key::get_location_rankings_page_key_by_global_id(
+            RANKINGS_PERIOD_LAST_WEEK,
+            args.int(0), args.int(1), args.int(2), args.long(3), cache)));
+    routes.register(Route::read(
+        serve::URL_LAST_WEEKS_LOCATION_POLL_RANKINGS_BY_CACHE_INDEX,
+        "last_weeks_location_rankings_by_cache_index",
+        LOCATION_RANKINGS_BY_CACHE_INDEX,
+        |args, cache| location::get_last_weeks_location_rankings_by_cache_index(
+            args.int(0), args.int(1), args.int(2), args.int(3), cache))
+        .with_page_key(|args, cache| page_key::get_location_rankings_page_key_by_cache_index(
+            RANKINGS_PERIOD_LAST_WEEK,
+            args.int(0), args.int(1), args.int(2), args.int(3), cache)));
+    routes.register(Route::read(
+        serve::URL_YESTERDAYS_LOCATION_POLL_RANKINGS_BY_GLOBAL_ID,
+        "yesterdays_location_rankings_by_global_id",
+        LOCATION_RANKINGS_BY_GLOBAL_ID,
+        |args, cache| location::get_yesterdays_location_rankings_by_global_id(
+            args.int(0), args.int(1), args.int(2), args.long(3), cache))
+        .with_page_key(|args, cache| page_key::get_location_rankings_page_key_by_global_id(
+            RANKINGS_PERIOD_YESTERDAY,
+            args.int(0), args.int(1), args.int(2), args.long(3), cache)));
+    routes.register(Route::read(
+        serve::URL_YESTERDAYS_LOCATION_POLL_RANKINGS_BY_CACHE_INDEX,
+        "yesterdays_location_rankings_by_cache_index",
+        LOCATION_RANKINGS_BY_CACHE_INDEX,
+        |args, cache| location::get_yesterdays_location_rankings_by_cache_index(
+            args.int(0), args.int(1), args.int(2), args.int(3), cache))
+        .with_page_key(|args, cache| page_key::get_location_rankings_page_key_by_cache_index(
+            RANKINGS_PERIOD_YESTERDAY,
+            args.int(0), args.int(1), args.int(2), args.int(3), cache)));
+    routes.register(Route::read(
+        serve::URL_DAY_B4_YESTERDAY_LOCATION_POLL_RANKINGS_BY_GLOBAL_ID,
+        "day_b4_yesterdays_location_rankings_by_global_id",
+        LOCATION_RANKINGS_BY_GLOBAL_ID,
+        |args, cache| location::get_day_b4_yesterdays_location_rankings_by_global_id(
+            args.int(0), args.int(1), args.int(2), args.long(3), cache))
+        .with_page_key(|args, cache| page_key::get_location_rankings_page_key_by_global_id(
+            RANKINGS_PERIOD_DAY_B4_YESTERDAY,
+            args.int(0), args.int(1), args.int(2), args.long(3), cache)));
+    routes.register(Route::read(
+        serve::URL_DAY_B4_YESTERDAY_LOCATION_POLL_RANKINGS_BY_CACHE_INDEX,
+        "day_b4_yesterdays_location_rankings_by_cache_index",
+        LOCATION_RANKINGS_BY_CACHE_INDEX,
+        |args, cache| location::get_day_b4_yesterdays_location_rankings_by_cache_index(
+            args.int(0), args.int(1), args.int(2), args.int(3), cache))
+        .with_page_key(|args, cache| page_key::get_location_rankings_page_key_by_cache_index(
+            RANKINGS_PERIOD_DAY_B4_YESTERDAY,
+            args.int(0), args.int(1), args.int(2), args.int(3), cache)));
+
+    // Location Label Poll Rankings
+    routes.register(Route::read(
+        serve::URL_THIS_MONTHS_LOCATION_CATEGORY_POLL_RANKINGS_BY_GLOBAL_IDS,
+        "this_months_location_label_rankings_by_global_ids",
+        LOCATION_LABEL_RANKINGS_BY_GLOBAL_IDS,
+        |args, cache| location_label::get_this_months_location_label_rankings_by_global_ids(
+            args.int(0), args.int(1), args.int(2), args.long(3), args.long(4), cache))
+        .with_page_key(|args, cache| page_key::get_location_label_rankings_page_key_by_global_ids(
+            RANKINGS_PERIOD_THIS_MONTH,
+            args.int(0), args.int(1), args.int(2), args.long(3), args.long(4), cache)));
+    routes.register(Route::read(
+        serve::URL_THIS_MONTHS_LOCATION_CATEGORY_POLL_RANKINGS_BY_LOCATION_CACHE_INDEX_AND_GLOBAL_CATEGORY_ID,
+        "this_months_location_label_rankings_by_location_cache_index_and_global_label_ids",
+        LOCATION_LABEL_RANKINGS_BY_LOCATION_CACHE_INDEX_AND_GLOBAL_LABEL_ID,
+        |args, cache| location_label::get_this_months_location_label_rankings_by_location_cache_index_and_global_label_ids(
+            args.int(0), args.int(1), args.int(2), args.int(3), args.long(4), cache))
+        .with_page_key(|args, cache| page_key::get_location_label_rankings_page_key_by_location_cache_index_and_global_label_id(
+            RANKINGS_PERIOD_THIS_MONTH,
+            args.int(0), args.int(1), args.int(2), args.int(3), args.long(4), cache)));
+    routes.register(Route::read(
+        serve::URL_THIS_MONTHS_LOCATION_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEXES,
+        "this_months_location_label_rankings_by_cache_indexes",
+        LOCATION_LABEL_RANKINGS_BY_CACHE_INDEXES,
+        |args, cache| location_label::get_this_months_location_label_rankings_by_cache_indexes(
+            args.int(0), args.int(1), args.int(2), args.int(3), args.int(4), cache))
+        .with_page_key(|args, cache| page_key::get_location_label_rankings_page_key_by_cache_indexes(
+            RANKINGS_PERIOD_THIS_MONTH,
+            args.int(0), args.int(1), args.int(2), args.int(3), args.int(4), cache)));
+    routes.register(Route::read(
+        serve::URL_THIS_WEEKS_LOCATION_CATEGORY_POLL_RANKINGS_BY_GLOBAL_IDS,
+        "this_weeks_location_label_rankings_by_global_ids",
+        LOCATION_LABEL_RANKINGS_BY_GLOBAL_IDS,
+        |args, cache| location_label::get_this_weeks_location_label_rankings_by_global_ids(
+            args.int(0), args.int(1), args.int(2), args.long(3), args.long(4), cache))
+        .with_page_key(|args, cache| page_key::get_location_label_rankings_page_key_by_global_ids(
+            RANKINGS_PERIOD_THIS_WEEK,
+            args.int(0), args.int(1), args.int(2), args.long(3), args.long(4), cache)));
+    routes.register(Route::read(
+        serve::URL_THIS_WEEKS_LOCATION_CATEGORY_POLL_RANKINGS_BY_LOCATION_CACHE_INDEX_AND_GLOBAL_CATEGORY_ID,
+        "this_weeks_location_label_rankings_by_location_cache_index_and_global_label_ids",
+        LOCATION_LABEL_RANKINGS_BY_LOCATION_CACHE_INDEX_AND_GLOBAL_LABEL_ID,
+        |args, cache| location_label::get_this_weeks_location_label_rankings_by_location_cache_index_and_global_label_ids(
+            args.int(0), args.int(1), args.int(2), args.int(3), args.long(4), cache))
+        .with_page_key(|args, cache| page_key::get_location_label_rankings_page_key_by_location_cache_index_and_global_label_id(
+            RANKINGS_PERIOD_THIS_WEEK,
+            args.int(0), args.int(1), args.int(2), args.int(3), args.long(4), cache)));
+    routes.register(Route::read(
+        serve::URL_THIS_WEEKS_LOCATION_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEXES,
+        "this_weeks_location_label_rankings_by_cache_indexes",
+        LOCATION_LABEL_RANKINGS_BY_CACHE_INDEXES,
+        |args, cache| location_label::get_this_weeks_location_label_rankings_by_cache_indexes(
+            args.int(0), args.int(1), args.int(2), args.int(3), args.int(4), cache))
+        .with_page_key(|args, cache| page_key::get_location_label_rankings_page_key_by_cache_indexes(
+            RANKINGS_PERIOD_THIS_WEEK,
+            args.int(0), args.int(1), args.int(2), args.int(3), args.int(4), cache)));
+    routes.register(Route::read(
+        serve::URL_TODAYS_LOCATION_CATEGORY_POLL_RANKINGS_BY_GLOBAL_IDS,
+        "todays_location_label_rankings_by_global_ids",
+        LOCATION_LABEL_RANKINGS_BY_GLOBAL_IDS,
+        |args, cache| location_label::get_todays_location_label_rankings_by_global_ids(
+            args.int(0), args.int(1), args.int(2), args.long(3), args.long(4), cache))
+        .with_page_key(|args, cache| page_key::get_location_label_rankings_page_key_by_global_ids(
+            RANKINGS_PERIOD_TODAY,
+            args.int(0), args.int(1), args.int(2), args.long(3), args.long(4), cache)));
+    routes.register(Route::read(
+        serve::URL_TODAYS_LOCATION_CATEGORY_POLL_RANKINGS_BY_LOCATION_CACHE_INDEX_AND_GLOBAL_CATEGORY_ID,
+        "todays_location_label_rankings_by_location_cache_index_and_global_label_ids",
+        LOCATION_LABEL_RANKINGS_BY_LOCATION_CACHE_INDEX_AND_GLOBAL_LABEL_ID,
+        |args, cache| location_label::get_todays_location_label_rankings_by_location_cache_index_and_global_label_ids(
+            args.int(0), args.int(1), args.int(2), args.int(3), args.long(4), cache))
+        .with_page_key(|args, cache| page_key::get_location_label_rankings_page_key_by_location_cache_index_and_global_label_id(
+            RANKINGS_PERIOD_TODAY,
+            args.int(0), args.int(1), args.int(2), args.int(3), args.long(4), cache)));
+    routes.register(Route::read(
+        serve::URL_TODAYS_LOCATION_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEXES,
+        "todays_location_label_rankings_by_cache_indexes",
+        LOCATION_LABEL_RANKINGS_BY_CACHE_INDEXES,
+        |args, cache| location_label::get_todays_location_label_rankings_by_cache_indexes(
+            args.int(0), args.int(1), args.int(2), args.int(3), args.int(4), cache))
+        .with_page_key(|args, cache| page_key::get_location_label_rankings_page_key_by_cache_indexes(
+            RANKINGS_PERIOD_TODAY,
+            args.int(0), args.int(1), args.int(2), args.int(3), args.int(4), cache)));
+    routes.register(Route::read(
+        serve::URL_LAST_MONTHS_LOCATION_CATEGORY_POLL_RANKINGS_BY_GLOBAL_IDS,
+        "last_months_location_label_rankings_by_global_ids",
+        LOCATION_LABEL_RANKINGS_BY_GLOBAL_IDS,
+        |args, cache| location_label::get_last_months_location_label_rankings_by_global_ids(
+            args.int(0), args.int(1), args.int(2), args.long(3), args.long(4), cache))
+        .with_page_key(|args, cache| page_key::get_location_label_rankings_page_key_by_global_ids(
+            RANKINGS_PERIOD_LAST_MONTH,
+            args.int(0), args.int(1), args.int(2), args.long(3), args.long(4), cache)));
+    routes.register(Route::read(
+        serve::URL_LAST_MONTHS_LOCATION_CATEGORY_POLL_RANKINGS_BY_LOCATION_CACHE_INDEX_AND_GLOBAL_CATEGORY_ID,
+        "last_months_location_label_rankings_by_location_cache_index_and_global_label_ids",
+        LOCATION_LABEL_RANKINGS_BY_LOCATION_CACHE_INDEX_AND_GLOBAL_LABEL_ID,
+        |args, cache| location_label::get_last_months_location_label_rankings_by_location_cache_index_and_global_label_ids(
+            args.int(0), args.int(1), args.int(2), args.int(3), args.long(4), cache))
+        .with_page_key(|args, cache| page_key::get_location_label_rankings_page_key_by_location_cache_index_and_global_label_id(
+            RANKINGS_PERIOD_LAST_MONTH,
+            args.int(0), args.int(1), args.int(2), args.int(3), args.long(4), cache)));
+    routes.register(Route::read(
+        serve::URL_LAST_MONTHS_LOCATION_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEXES,
+        "last_months_location_label_rankings_by_cache_indexes",
+        LOCATION_LABEL_RANKINGS_BY_CACHE_INDEXES,
+        |args, cache| location_label::get_last_months_location_label_rankings_by_cache_indexes(
+            args.int(0), args.int(1), args.int(2), args.int(3), args.int(4), cache))
+        .with_page_key(|args, cache| page_key::get_location_label_rankings_page_key_by_cache_indexes(
+            RANKINGS_PERIOD_LAST_MONTH,
+            args.int(0), args.int(1), args.int(2), args.int(3), args.int(4), cache)));
+    routes.register(Route::read(
+        serve::URL_LAST_WEEKS_LOCATION_CATEGORY_POLL_RANKINGS_BY_GLOBAL_IDS,
+        "last_weeks_location_label_rankings_by_global_ids",
+        LOCATION_LABEL_RANKINGS_BY_GLOBAL_IDS,
+        |args, cache| location_label::get_last_weeks_location_label_rankings_by_global_ids(
+            args.int(0), args.int(1), args.int(2), args.long(3), args.long(4), cache))
+        .with_page_key(|args, cache| page_key::get_location_label_rankings_page_key_by_global_ids(
+            RANKINGS_PERIOD_LAST_WEEK,
+            args.int(0), args.int(1), args.int(2), args.long(3), args.long(4), cache)));
+    routes.register(Route::read(
+        serve::URL_LAST_WEEKS_LOCATION_CATEGORY_POLL_RANKINGS_BY_LOCATION_CACHE_INDEX_AND_GLOBAL_CATEGORY_ID,
+        "last_weeks_location_label_rankings_by_location_cache_index_and_global_label_ids",
+        LOCATION_LABEL_RANKINGS_BY_LOCATION_CACHE_INDEX_AND_GLOBAL_LABEL_ID,
+        |args, cache| location_label::get_last_weeks_location_label_rankings_by_location_cache_index_and_global_label_ids(
+            args.int(0), args.int(1), args.int(2), args.int(3), args.long(4), cache))
+        .with_page_key(|args, cache| page_key::get_location_label_rankings_page_key_by_location_cache_index_and_global_label_id(
+            RANKINGS_PERIOD_LAST_WEEK,
+            args.int(0), args.int(1), args.int(2), args.int(3), args.long(4), cache)));
+    routes.register(Route::read(
+        serve::URL_LAST_WEEKS_LOCATION_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEXES,
+        "last_weeks_location_label_rankings_by_cache_indexes",
+        LOCATION_LABEL_RANKINGS_BY_CACHE_INDEXES,
+        |args, cache| location_label::get_last_weeks_location_label_rankings_by_cache_indexes(
+            args.int(0), args.int(1), args.int(2), args.int(3), args.int(4), cache))
+        .with_page_key(|args, cache| page_key::get_location_label_rankings_page_key_by_cache_indexes(
+            RANKINGS_PERIOD_LAST_WEEK,
+            args.int(0), args.int(1), args.int(2), args.int(3), args.int(4), cache)));
+    routes.register(Route::read(
+        serve::URL_YESTERDAYS_LOCATION_CATEGORY_POLL_RANKINGS_BY_GLOBAL_IDS,
+        "yesterdays_location_label_rankings_by_global_ids",
+        LOCATION_LABEL_RANKINGS_BY_GLOBAL_IDS,
+        |args, cache| location_label::get_yesterdays_location_label_rankings_by_global_ids(
+            args.int(0), args.int(1), args.int(2), args.long(3), args.long(4), cache))
+        .with_page_key(|args, cache| page_key::get_location_label_rankings_page_key_by_global_ids(
+            RANKINGS_PERIOD_YESTERDAY,
+            args.int(0), args.int(1), args.int(2), args.long(3), args.long(4), cache)));
+    routes.register(Route::read(
+        serve::URL_YESTERDAYS_LOCATION_CATEGORY_POLL_RANKINGS_BY_LOCATION_CACHE_INDEX_AND_GLOBAL_CATEGORY_ID,
+        "yesterdays_location_label_rankings_by_location_cache_index_and_global_label_ids",
+        LOCATION_LABEL_RANKINGS_BY_LOCATION_CACHE_INDEX_AND_GLOBAL_LABEL_ID,
+        |args, cache| location_label::get_yesterdays_location_label_rankings_by_location_cache_index_and_global_label_ids(
+            args.int(0), args.int(1), args.int(2), args.int(3), args.long(4), cache))
+        .with_page_key(|args, cache| page_key::get_location_label_rankings_page_key_by_location_cache_index_and_global_label_id(
+            RANKINGS_PERIOD_YESTERDAY,
+            args.int(0), args.int(1), args.int(2), args.int(3), args.long(4), cache)));
+    routes.register(Route::read(
+        serve::URL_YESTERDAYS_LOCATION_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEXES,
+        "yesterdays_location_label_rankings_by_cache_indexes",
+        LOCATION_LABEL_RANKINGS_BY_CACHE_INDEXES,
+        |args, cache| location_label::get_yesterdays_location_label_rankings_by_cache_indexes(
+            args.int(0), args.int(1), args.int(2), args.int(3), args.int(4), cache))
+        .with_page_key(|args, cache| page_key::get_location_label_rankings_page_key_by_cache_indexes(
+            RANKINGS_PERIOD_YESTERDAY,
+            args.int(0), args.int(1), args.int(2), args.int(3), args.int(4), cache)));
+    routes.register(Route::read(
+        serve::URL_DAY_B4_YESTERDAY_LOCATION_YESTERDAY_CATEGORY_POLL_RANKINGS_BY_GLOBAL_IDS,
+        "day_b4_yesterdays_location_label_rankings_by_global_ids",
+        LOCATION_LABEL_RANKINGS_BY_GLOBAL_IDS,
+        |args, cache| location_label::get_day_b4_yesterdays_location_label_rankings_by_global_ids(
+            args.int(0), args.int(1), args.int(2), args.long(3), args.long(4), cache))
+        .with_page_key(|args, cache| page_key::get_location_label_rankings_page_key_by_global_ids(
+            RANKINGS_PERIOD_DAY_B4_YESTERDAY,
+            args.int(0), args.int(1), args.int(2), args.long(3), args.long(4), cache)));
+    routes.register(Route::read(
+        serve::URL_DAY_B4_YESTERDAY_LOCATION_YESTERDAY_CATEGORY_POLL_RANKINGS_BY_LOCATION_CACHE_INDEX_AND_GLOBAL_CATEGORY_ID,
+        "day_b4_yesterdays_location_label_rankings_by_location_cache_index_and_global_label_ids",
+        LOCATION_LABEL_RANKINGS_BY_LOCATION_CACHE_INDEX_AND_GLOBAL_LABEL_ID,
+        |args, cache| location_label::get_day_b4_yesterdays_location_label_rankings_by_location_cache_index_and_global_label_ids(
+            args.int(0), args.int(1), args.int(2), args.int(3), args.long(4), cache))
+        .with_page_key(|args, cache| page_key::get_location_label_rankings_page_key_by_location_cache_index_and_global_label_id(
+            RANKINGS_PERIOD_DAY_B4_YESTERDAY,
+            args.int(0), args.int(1), args.int(2), args.int(3), args.long(4), cache)));
+    routes.register(Route::read(
+        serve::URL_DAY_B4_YESTERDAY_LOCATION_YESTERDAY_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEXES,
+        "day_b4_yesterdays_location_label_rankings_by_cache_indexes",
+        LOCATION_LABEL_RANKINGS_BY_CACHE_INDEXES,
+        |args, cache| location_label::get_day_b4_yesterdays_location_label_rankings_by_cache_indexes(
+            args.int(0), args.int(1), args.int(2), args.int(3), args.int(4), cache))
+        .with_page_key(|args, cache| page_key::get_location_label_rankings_page_key_by_cache_indexes(
+            RANKINGS_PERIOD_DAY_B4_YESTERDAY,
+            args.int(0), args.int(1), args.int(2), args.int(3), args.int(4), cache)));
+
+    /*
+     *  RECENT POLLS
+     */
+
+    // Recent Polls by Location
+    routes.register(Route::read(
+        serve::URL_NEXT_MONTHS_LOCATION_POLLS,
+        "next_months_location_polls",
+        LOCATION_POLLS,
+        |args, cache| get_next_months_location_polls(
+            args.int(0), args.int(1), args.int(2), args.long(3), cache)));
+    routes.register(Route::read(
+        serve::URL_NEXT_WEEKS_LOCATION_POLLS,
+        "next_weeks_location_polls",
+        LOCATION_POLLS,
+        |args, cache| get_next_weeks_location_polls(
+            args.int(0), args.int(1), args.int(2), args.long(3), cache)));
+    routes.register(Route::read(
+        serve::URL_TOMORROWS_LOCATION_POLLS,
+        "tomorrows_location_polls",
+        LOCATION_POLLS,
+        |args, cache| get_tomorrows_location_polls(
+            args.int(0), args.int(1), args.int(2), args.long(3), cache)));
+    routes.register(Route::read(
+        serve::URL_DAY_AFTER_TOMORROWS_LOCATION_POLLS,
+        "day_after_tomorrows_location_polls",
+        LOCATION_POLLS,
+        |args, cache| get_day_after_tomorrows_location_polls(
+            args.int(0), args.int(1), args.int(2), args.long(3), cache)));
+
+    // Recent Polls by Label
+    routes.register(Route::read(
+        serve::URL_NEXT_MONTHS_CATEGORY_POLLS,
+        "next_months_label_polls",
+        LABEL_POLLS,
+        |args, cache| get_next_months_label_polls(
+            args.int(0), args.int(1), args.long(2), cache)));
+    routes.register(Route::read(
+        serve::URL_NEXT_WEEKS_CATEGORY_POLLS,
+        "next_weeks_label_polls",
+        LABEL_POLLS,
+        |args, cache| get_next_weeks_label_polls(
+            args.int(0), args.int(1), args.long(2), cache)));
+    routes.register(Route::read(
+        serve::URL_TOMORROWS_CATEGORY_POLLS,
+        "tomorrows_label_polls",
+        LABEL_POLLS,
+        |args, cache| get_tomorrows_label_polls(
+            args.int(0), args.int(1), args.long(2), cache)));
+    routes.register(Route::read(
+        serve::URL_DAY_AFTER_TOMORROWS_CATEGORY_POLLS,
+        "day_after_tomorrows_label_polls",
+        LABEL_POLLS,
+        |args, cache| get_day_after_tomorrows_label_polls(
+            args.int(0), args.int(1), args.long(2), cache)));
+
+    // Recent Polls by Location Label
+    routes.register(Route::read(
+        serve::URL_NEXT_MONTHS_LOCATION_CATEGORY_POLLS,
+        "next_months_location_label_polls",
+        LOCATION_LABEL_POLLS,
+        |args, cache| get_next_months_location_label_polls(
+            args.int(0), args.int(1), args.int(2), args.long(3), args.long(4), cache)));
+    routes.register(Route::read(
+        serve::URL_NEXT_WEEKS_LOCATION_CATEGORY_POLLS,
+        "next_weeks_location_label_polls",
+        LOCATION_LABEL_POLLS,
+        |args, cache| get_next_weeks_location_label_polls(
+            args.int(0), args.int(1), args.int(2), args.long(3), args.long(4), cache)));
+    routes.register(Route::read(
+        serve::URL_TOMORROWS_LOCATION_CATEGORY_POLLS,
+        "tomorrows_location_label_polls",
+        LOCATION_LABEL_POLLS,
+        |args, cache| get_tomorrows_location_label_polls(
+            args.int(0), args.int(1), args.int(2), args.long(3), args.long(4), cache)));
+    routes.register(Route::read(
+        serve::URL_DAY_AFTER_TOMORROWS_LOCATION_CATEGORY_POLLS,
+        "day_after_tomorrows_location_label_polls",
+        LOCATION_LABEL_POLLS,
+        |args, cache| get_day_after_tomorrows_location_label_polls(
+            args.int(0), args.int(1), args.int(2), args.long(3), args.long(4), cache)));
+
+    /*
+     *  POLLS ADDED SINCE
+     */
+
+    // Polls added since, by Label
+    routes.register(Route::read(
+        serve::URL_NEXT_MONTHS_CATEGORY_POLLS_SINCE,
+        "next_months_label_polls_since",
+        LABEL_POLLS_SINCE,
+        |args, cache| get_next_months_label_polls_since(
+            args.int(0), args.long(1), args.long(2), cache)));
+    routes.register(Route::read(
+        serve::URL_NEXT_WEEKS_CATEGORY_POLLS_SINCE,
+        "next_weeks_label_polls_since",
+        LABEL_POLLS_SINCE,
+        |args, cache| get_next_weeks_label_polls_since(
+            args.int(0), args.long(1), args.long(2), cache)));
+    routes.register(Route::read(
+        serve::URL_TOMORROWS_CATEGORY_POLLS_SINCE,
+        "tomorrows_label_polls_since",
+        LABEL_POLLS_SINCE,
+        |args, cache| get_tomorrows_label_polls_since(
+            args.int(0), args.long(1), args.long(2), cache)));
+    routes.register(Route::read(
+        serve::URL_DAY_AFTER_TOMORROWS_CATEGORY_POLLS_SINCE,
+        "day_after_tomorrows_label_polls_since",
+        LABEL_POLLS_SINCE,
+        |args, cache| get_day_after_tomorrows_label_polls_since(
+            args.int(0), args.long(1), args.long(2), cache)));
+
+    /*
+     *  PERIOD IDS
+     */
+
+    routes.register(Route::read(
+        serve::URL_TIME_PERIOD_IDS,
+        "time_period_ids",
+        &[],
+        |_, cache| get_time_period_ids(cache)));
+
+    /*
+     *  ADD POLLS (updates)
+     */
+
+    routes.register(Route::update(
+        add::URL_NEXT_MONTHS_LABEL_POLLS,
+        "add_next_months_label_polls",
+        ADD_LABEL_POLLS,
+        |args, items| get_add_label_polls(Cache::add_future_month_polls, args.int(0), items)));
+    routes.register(Route::update(
+        add::URL_NEXT_WEEKS_LABEL_POLLS,
+        "add_next_weeks_label_polls",
+        ADD_LABEL_POLLS,
+        |args, items| get_add_label_polls(Cache::add_future_week_polls, args.int(0), items)));
+    routes.register(Route::update(
+        add::URL_FUTURE_DAYS_LABEL_POLLS,
+        "add_future_days_label_polls",
+        ADD_LABEL_POLLS,
+        |args, items| get_add_label_polls(Cache::add_future_day_polls, args.int(0), items)));
+
+    routes
+}
@@ -0,0 +1,230 @@
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use byteorder::{BigEndian, ReadBytesExt};
+
+use common::model::types::LabelId;
+use common::model::types::PollId;
+
+use server::cache::pages::PageCache;
+use server::cache::producers::ProducerSequences;
+use server::cache::update_app::RejectionReason;
+use server::cache::update_app::UpdateApp;
+use server::cache::update_app::UpdateResult;
+use server::cache::watchers::PageWatchers;
+
+use super::super::cache::cache::Cache;
+use super::super::cache::cache_reader::CacheReader;
+use super::super::cache::cache_writer::CacheWriter;
+use super::super::logic::notify::PageDigests;
+use super::super::logic::rollover::lock_down_timezone;
+use super::super::logic::rollover::roll_over_global;
+use super::super::logic::rollover::roll_over_timezone;
+use super::super::logic::rollover::StartingDay;
+use super::super::logic::snapshot::save_snapshot;
+use super::routes::get_routes;
+use super::routes::CacheRoutes;
+
+/**
+ * Adds polls to a future period, rejecting them if it isn't the one the period id is for.
+ */
+pub type AddLabelPolls = fn(&mut Cache, u32, Vec<LabelId>, Vec<Vec<PollId>>) -> Result<(), RejectionReason>;
+
+/**
+ * Update requests, as decoded on the updater thread.
+ */
+pub enum CacheUpdate {
+    // Polls for a future period, by label (one item per label)
+    AddLabelPolls {
+        add_polls: AddLabelPolls,
+        period_id: u32,
+        label_polls: Vec<(LabelId, Vec<PollId>)>,
+    },
+}
+
+/**
+ * Applies update requests to the cache, on the writer thread.
+ */
+pub struct CompleteCacheUpdateApp {
+    pub cache_writer: CacheWriter,
+    // Loaded with the cache from the last snapshot (if any)
+    pub producer_sequences: ProducerSequences,
+    pub snapshot_file: Option<PathBuf>,
+    // Updates (or rollovers) have been applied since the last snapshot
+    pub unsaved_changes: bool,
+    // Pages of the current rankings as of the last re-rank
+    pub page_digests: PageDigests,
+}
+
+impl CompleteCacheUpdateApp {
+    pub fn new(
+        cache_writer: CacheWriter,
+        producer_sequences: ProducerSequences,
+        snapshot_file: Option<PathBuf>,
+    ) -> CompleteCacheUpdateApp {
+        CompleteCacheUpdateApp {
+            cache_writer,
+            producer_sequences,
+            snapshot_file,
+            unsaved_changes: false,
+            page_digests: PageDigests::new(),
+        }
+    }
+
+    /**
+     * Saves a snapshot (if there is a snapshot file), unless nothing changed since the last one.
+     */
+    pub fn save_snapshot(&mut self) -> Result<(), String> {
+        if !self.unsaved_changes {
+            return Ok(());
+        }
+        if let Some(ref snapshot_file) = self.snapshot_file {
+            save_snapshot(&self.cache_writer.cache_ref(), &self.producer_sequences, snapshot_file)?;
+        }
+        self.unsaved_changes = false;
+
+        Ok(())
+    }
+
+    pub fn lock_down_timezone(
+        &mut self,
+        timezone_index: usize,
+    ) {
+        lock_down_timezone(&mut self.cache_writer.cache(), timezone_index);
+    }
+
+    pub fn roll_over_timezone(
+        &mut self,
+        timezone_index: usize,
+        starting_day: &StartingDay,
+        page_watchers: &PageWatchers,
+        page_cache: &PageCache,
+    ) {
+        roll_over_timezone(&mut self.cache_writer.cache(), timezone_index, starting_day, page_watchers, page_cache);
+        self.unsaved_changes = true;
+    }
+
+    pub fn roll_over_global(
+        &mut self,
+        starting_day: &StartingDay,
+        page_watchers: &PageWatchers,
+        page_cache: &PageCache,
+    ) {
+        roll_over_global(&mut self.cache_writer.cache(), starting_day, page_watchers, page_cache);
+        self.unsaved_changes = true;
+    }
+
+    fn add_label_polls(
+        &mut self,
+        add_polls: AddLabelPolls,
+        period_id: u32,
+        label_polls: Vec<(LabelId, Vec<PollId>)>,
+    ) -> UpdateResult {
+        let mut cache = self.cache_writer.cache();
+        let mut rejected = Vec::new();
+        let mut indexes = Vec::with_capacity(label_polls.len());
+        let mut label_ids = Vec::with_capacity(label_polls.len());
+        let mut poll_ids = Vec::with_capacity(label_polls.len());
+        for (index, (label_id, label_poll_ids)) in label_polls.into_iter().enumerate() {
+            if label_poll_ids.is_empty() {
+                rejected.push((index as u32, RejectionReason::Invalid));
+                continue;
+            }
+            indexes.push(index as u32);
+            label_ids.push(label_id);
+            poll_ids.push(label_poll_ids);
+        }
+
+        let mut accepted = 0;
+        if !label_ids.is_empty() {
+            match add_polls(&mut cache, period_id, label_ids, poll_ids) {
+                Ok(()) => {
+                    accepted = indexes.len() as u32;
+                }
+                Err(reason) => {
+                    for index in indexes {
+                        rejected.push((index, reason));
+                    }
+                }
+            }
+        }
+
+        let mut result = UpdateResult::new(cache.get_data_version());
+        result.accepted = accepted;
+        rejected.sort_by_key(|&(index, _)| index);
+        for (index, reason) in rejected {
+            result.reject(index, reason);
+        }
+
+        result
+    }
+}
+
+impl UpdateApp for CompleteCacheUpdateApp {
+    type Update = CacheUpdate;
+    type Context = CacheReader;
+
+    fn get_routes() -> CacheRoutes {
+        get_routes()
+    }
+
+    fn apply_update(
+        &mut self,
+        update: CacheUpdate,
+    ) -> UpdateResult {
+        self.unsaved_changes = true;
+        match update {
+            CacheUpdate::AddLabelPolls { add_polls, period_id, label_polls } => {
+                self.add_label_polls(add_polls, period_id, label_polls)
+            }
+        }
+    }
+
+    fn get_data_version(&self) -> u32 {
+        self.cache_writer.cache_ref().get_data_version()
+    }
+
+    fn get_producer_sequences(&mut self) -> &mut ProducerSequences {
+        &mut self.producer_sequences
+    }
+}
+
+/**
+ * Body of the add polls routes (all numbers are Big Endian):
+ *
+ *  4 bytes - id of the (future) period
+ *  Followed by any number of items, each one:
+ *      8 bytes - global label id
+ *      4 bytes - number of polls, followed by that many:
+ *          8 bytes - global poll id
+ *
+ * The period id is decoded by the route, the items here.  None if the items are cut short.
+ */
+pub fn get_add_label_polls(
+    add_polls: AddLabelPolls,
+    period_id: u32,
+    request_items: &[u8],
+) -> Option<CacheUpdate> {
+    let mut cursor = Cursor::new(request_items);
+
+    let mut label_polls = Vec::new();
+    while (cursor.position() as usize) < request_items.len() {
+        let label_id = cursor.read_u64::<BigEndian>().ok()?;
+        let num_polls = cursor.read_u32::<BigEndian>().ok()? as usize;
+        // Don't reserve more than the rest of the body can hold
+        if num_polls > (request_items.len() - cursor.position() as usize) / 8 {
+            return None;
+        }
+        let mut poll_ids = Vec::with_capacity(num_polls);
+        for _ in 0..num_polls {
+            poll_ids.push(cursor.read_u64::<BigEndian>().ok()?);
+        }
+        label_polls.push((label_id, poll_ids));
+    }
+
+    Some(CacheUpdate::AddLabelPolls {
+        add_polls,
+        period_id,
+        label_polls,
+    })
+}
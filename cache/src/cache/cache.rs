@@ -1,4 +1,5 @@
 //use std::collections::HashMap;
+use std::sync::Arc;
 
 //use int_hash::IntBuildHasher;
 //use int_hash::IntHashMap;
@@ -13,6 +14,16 @@ use common::model::types::MonthId;
 use common::model::types::PollId;
 use common::model::types::WeekId;
 
+use server::cache::events::EventHub;
+use server::cache::update_app::RejectionReason;
+
+use super::super::logic::events::FUTURE_PERIOD_DAY_AFTER_TOMORROW;
+use super::super::logic::events::FUTURE_PERIOD_NEXT_MONTH;
+use super::super::logic::events::FUTURE_PERIOD_NEXT_WEEK;
+use super::super::logic::events::FUTURE_PERIOD_TOMORROW;
+use super::super::logic::events::get_label_polls_events;
+use super::super::logic::events::publish_events;
+
 use super::cache_reader::CacheReader;
 use super::label_index_map::LabelIndexMap;
 use super::label_poll_rankings::LabelPollRankings;
@@ -89,6 +100,16 @@ pub struct Cache {
     pub polls_2_d: Polls<TwoDPoll>,
     pub polls_3_d: Polls<ThreeDPoll>,
 
+    /**
+     *  Incremented every time cached poll data is modified.
+     */
+    pub data_version: u32,
+
+    /**
+     *  Subscribers to future poll additions, shared with the server.
+     */
+    pub event_hub: Arc<EventHub>,
+
 }
 
 impl CacheReader for Cache {
@@ -203,10 +224,20 @@ impl CacheReader for Cache {
         &self.polls_3_d
     }
 
+    /**
+     *  Incremented every time cached poll data is modified.
+     */
+    #[inline]
+    fn get_data_version(&self) -> u32 {
+        self.data_version
+    }
+
 }
 
 impl Cache {
-    pub fn new() -> Cache {
+    pub fn new(
+        event_hub: Arc<EventHub>
+    ) -> Cache {
         Cache {
             label_cache_period_ids: CachePeriodIds::new(),
             per_timezone_cache_period_ids: [
@@ -305,60 +336,102 @@ impl Cache {
             polls_1_d: Polls::new(),
             polls_2_d: Polls::new(),
             polls_3_d: Polls::new(),
+
+            data_version: 0,
+
+            event_hub,
         }
     }
 
+    /**
+     * Adds polls to tomorrow or the day after tomorrow, rejecting all of them if the day is
+     * already current (too late) or further out (too early).
+     */
     pub fn add_future_day_polls(
         &mut self,
         day_id: DayId,
         label_ids: Vec<LabelId>,
         poll_ids: Vec<Vec<PollId>>,
-    ) {
+    ) -> Result<(), RejectionReason> {
         let tomorrows_day_id = self.time_period_ids.tomorrow[ALL_TIME_ZONES];
         if day_id == tomorrows_day_id {
-            &self.future_polls_by_label.add_tomorrows_polls(label_ids, poll_ids);
+            self.add_future_label_polls(FUTURE_PERIOD_TOMORROW, label_ids, poll_ids,
+                                        PollsByLabel::add_tomorrows_polls);
         } else if day_id == tomorrows_day_id + 1 {
-            &self.future_polls_by_label.add_day_after_tomorrows_polls(label_ids, poll_ids);
+            self.add_future_label_polls(FUTURE_PERIOD_DAY_AFTER_TOMORROW, label_ids, poll_ids,
+                                        PollsByLabel::add_day_after_tomorrows_polls);
         } else if day_id < tomorrows_day_id {
             // TOO LATE TO ADD
-            return;
+            return Err(RejectionReason::TooLate);
         } else {
             // TOO EARLY TO ADD
+            return Err(RejectionReason::TooEarly);
         }
+
+        Ok(())
     }
 
+    /**
+     * Adds polls to next week, rejecting all of them for any other week.
+     */
     pub fn add_future_week_polls(
         &mut self,
         week_id: WeekId,
         label_ids: Vec<LabelId>,
         poll_ids: Vec<Vec<PollId>>,
-    ) {
+    ) -> Result<(), RejectionReason> {
         let next_week_id = self.time_period_ids.next_week[ALL_TIME_ZONES];
         if week_id == next_week_id {
-            &self.future_polls_by_label.add_next_weeks_polls(label_ids, poll_ids);
+            self.add_future_label_polls(FUTURE_PERIOD_NEXT_WEEK, label_ids, poll_ids,
+                                        PollsByLabel::add_next_weeks_polls);
         } else if week_id < next_week_id {
             // TOO LATE TO ADD
-            return;
+            return Err(RejectionReason::TooLate);
         } else {
             // TOO EARLY TO ADD
+            return Err(RejectionReason::TooEarly);
         }
+
+        Ok(())
     }
 
+    /**
+     * Adds polls to next month, rejecting all of them for any other month.
+     */
     pub fn add_future_month_polls(
         &mut self,
         month_id: MonthId,
         label_ids: Vec<LabelId>,
         poll_ids: Vec<Vec<PollId>>,
-    ) {
+    ) -> Result<(), RejectionReason> {
         let next_month_id = self.time_period_ids.next_month[ALL_TIME_ZONES];
         if month_id == next_month_id {
-            &self.future_polls_by_label.add_next_months_polls(label_ids, poll_ids);
+            self.add_future_label_polls(FUTURE_PERIOD_NEXT_MONTH, label_ids, poll_ids,
+                                        PollsByLabel::add_next_months_polls);
         } else if month_id < next_month_id {
             // TOO LATE TO ADD
-            return;
+            return Err(RejectionReason::TooLate);
         } else {
             // TOO EARLY TO ADD
+            return Err(RejectionReason::TooEarly);
         }
+
+        Ok(())
+    }
+
+    fn add_future_label_polls(
+        &mut self,
+        period: u32,
+        label_ids: Vec<LabelId>,
+        poll_ids: Vec<Vec<PollId>>,
+        add_polls: fn(&mut PollsByLabel, Vec<LabelId>, Vec<Vec<PollId>>, u64),
+    ) {
+        let sequence = self.future_polls_by_label.next_sequence();
+        let events = get_label_polls_events(
+            &self.event_hub, period, &label_ids, &poll_ids, sequence);
+        add_polls(&mut self.future_polls_by_label, label_ids, poll_ids, sequence);
+        self.data_version = self.data_version.wrapping_add(1);
+        publish_events(&self.event_hub, events);
     }
 }
 
@@ -77,4 +77,9 @@ pub trait CacheReader {
     fn get_polls_2_d(&self) -> &Polls<TwoDPoll>;
     fn get_polls_3_d(&self) -> &Polls<ThreeDPoll>;
 
+    /**
+     *  Incremented every time cached poll data is modified.
+     */
+    fn get_data_version(&self) -> u32;
+
 }
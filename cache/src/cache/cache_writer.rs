@@ -0,0 +1,46 @@
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use super::cache::Cache;
+
+/**
+ *  Write access to a Cache that is being read at the same time (by the read app).
+ *
+ *  Readers take the read lock for the duration of a request, the update thread (which owns the
+ *  only CacheWriter) takes the write lock for the duration of an update or a job.  A panic while
+ *  writing poisons the lock, the cache is still served (as the panicking write left it) rather
+ *  than failing every request after it.
+ */
+pub struct CacheWriter {
+    cache: Arc<RwLock<Cache>>,
+}
+
+impl CacheWriter {
+    pub fn new(
+        cache: Arc<RwLock<Cache>>
+    ) -> CacheWriter {
+        CacheWriter {
+            cache,
+        }
+    }
+
+    #[inline]
+    pub fn cache_ref(&self) -> RwLockReadGuard<Cache> {
+        read_cache(&self.cache)
+    }
+
+    #[inline]
+    pub fn cache(&mut self) -> RwLockWriteGuard<Cache> {
+        self.cache.write().unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+/**
+ *  Read access to the shared cache, held for as long as the returned guard lives.  Must not be
+ *  taken again while a guard is held (a waiting writer would block the second read).
+ */
+#[inline]
+pub fn read_cache(
+    cache: &RwLock<Cache>
+) -> RwLockReadGuard<Cache> {
+    cache.read().unwrap_or_else(|error| error.into_inner())
+}
@@ -1,5 +1,6 @@
 pub mod cache;
 pub mod cache_reader;
+pub mod cache_writer;
 pub mod label_index_map;
 pub mod label_poll_rankings;
 pub mod location_label_index_map;
@@ -8,6 +9,7 @@ pub mod locations_poll_rankings;
 pub mod manager;
 pub mod model;
 pub mod poll_id_byte_counts;
+pub mod poll_id_frames;
 pub mod polls;
 pub mod polls_by_label;
 pub mod polls_by_location;
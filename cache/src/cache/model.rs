@@ -14,6 +14,8 @@ use common::model::types::WeekId;
 
 use super::super::logic::add::polls::add_polls_to_per_label_map;
 
+use super::poll_id_frames::PollIdFrames;
+
 pub struct CachePeriodIds {
     pub day_after_tomorrows_vc_day_id: DayId,
     pub day_b4_yesterdays_vc_day_id: DayId,
@@ -114,15 +116,15 @@ pub struct LocationPollRankings {
  */
 pub struct LocationPollPrependLists {
 
-    // Inner vector is a page/frame (Ex: capped @ 1024) and outer vector grows
-    pub location: Vec<Vec<PollId>>,
+    // Frames of 1024 poll ids, only the last frame is not full
+    pub location: PollIdFrames,
 
     // PollId in frames of 1024 by LabelId
     /*
     When the update thread is running, it is possible for the poll id page request to be
     failed because the the hashmap must be re-hashed.
     */
-    pub label_locations: IntHashMap<LabelId, Vec<Vec<PollId>>>,
+    pub label_locations: IntHashMap<LabelId, PollIdFrames>,
 
     // True if the label_locations map is currently being rehashed
     pub label_locations_rehashing: bool,
@@ -130,45 +132,34 @@ pub struct LocationPollPrependLists {
 }
 
 impl LocationPollPrependLists {
-
-
-
-    pub fn add_tomorrows_polls(
-        &mut self,
-        label_ids: Vec<LabelId>,
-        poll_ids: Vec<Vec<PollId>>,
-    ) {
-        add_polls_to_per_label_map(&mut self.tomorrow, &mut self.tomorrow_rehash,
-                                   label_ids, poll_ids);
-    }
-
-    pub fn add_day_after_tomorrows_polls(
-        &mut self,
-        label_ids: Vec<LabelId>,
-        poll_ids: Vec<Vec<PollId>>,
-    ) {
-        add_polls_to_per_label_map(&mut self.day_after_tomorrow, &mut self.day_after_tomorrow_rehash,
-                                   label_ids, poll_ids);
+    pub fn new(
+        num_labels: usize,
+    ) -> LocationPollPrependLists {
+        LocationPollPrependLists {
+            location: PollIdFrames::new(),
+            label_locations: HashMap::with_capacity_and_hasher(
+                num_labels, IntBuildHasher::default()),
+            label_locations_rehashing: false,
+        }
     }
 
-    pub fn add_next_weeks_polls(
+    pub fn add_location_polls(
         &mut self,
-        label_ids: Vec<LabelId>,
-        poll_ids: Vec<Vec<PollId>>,
+        poll_ids: Vec<PollId>,
+        sequence: u64,
     ) {
-        add_polls_to_per_label_map(&mut self.next_week, &mut self.next_week_rehash,
-                                   label_ids, poll_ids);
+        self.location.add_poll_ids(poll_ids, sequence);
     }
 
-    pub fn add_next_months_polls(
+    pub fn add_label_location_polls(
         &mut self,
         label_ids: Vec<LabelId>,
         poll_ids: Vec<Vec<PollId>>,
+        sequence: u64,
     ) {
-        add_polls_to_per_label_map(&mut self.next_month, &mut self.next_month_rehash,
-                                   label_ids, poll_ids);
+        add_polls_to_per_label_map(&mut self.label_locations, &mut self.label_locations_rehashing,
+                                   label_ids, poll_ids, sequence);
     }
-
 }
 
 
@@ -0,0 +1,274 @@
+use common::model::types::PollId;
+
+/**
+ * Number of poll ids in every frame, except (possibly) the last one.
+ */
+pub const POLL_ID_FRAME_SIZE: usize = 1024;
+
+/**
+ *  A single append of poll ids.
+ */
+pub struct PollIdBatch {
+    // Sequence number the batch was added with
+    pub sequence: u64,
+    // Index (across all frames) one past the last poll id of the batch
+    pub end_poll_index: u32,
+}
+
+/**
+ *  Poll ids added after a given sequence number.
+ */
+pub struct PollIdsSince {
+    pub poll_ids: Vec<PollId>,
+    // Sequence number of the last batch included in poll_ids
+    pub high_water_sequence: u64,
+    // True if there are more batches after high_water_sequence
+    pub more: bool,
+}
+
+/**
+ *  Time ordered (in order of addition) poll ids, split into frames of POLL_ID_FRAME_SIZE.
+ *
+ *  The last frame is always filled up before a new one is started, so all frames but the
+ *  last one are always full.  Blocks are served from the oldest frame forward, so block numbers
+ *  stay stable as more polls are added: only the last block grows (or new ones are started).
+ */
+pub struct PollIdFrames {
+    pub frames: Vec<Vec<PollId>>,
+    // Total number of poll ids across all frames
+    pub num_poll_ids: u32,
+    // Appends in order of addition (and hence of sequence)
+    pub batches: Vec<PollIdBatch>,
+}
+
+impl PollIdFrames {
+    pub fn new() -> PollIdFrames {
+        PollIdFrames {
+            frames: Vec::new(),
+            num_poll_ids: 0,
+            batches: Vec::new(),
+        }
+    }
+
+    pub fn add_poll_ids(
+        &mut self,
+        poll_ids_to_add: Vec<PollId>,
+        sequence: u64,
+    ) {
+        let num_polls_to_add = poll_ids_to_add.len();
+        let mut poll_ids_added = 0;
+
+        // Top off the last frame first
+        if let Some(last_frame) = self.frames.last_mut() {
+            let spare_frame_capacity = POLL_ID_FRAME_SIZE - last_frame.len();
+            let num_polls_to_top_off = if spare_frame_capacity < num_polls_to_add {
+                spare_frame_capacity
+            } else {
+                num_polls_to_add
+            };
+            last_frame.extend_from_slice(&poll_ids_to_add[0..num_polls_to_top_off]);
+            poll_ids_added = num_polls_to_top_off;
+        }
+
+        // Then add full frames (and a partial one for any remainder)
+        while poll_ids_added < num_polls_to_add {
+            let mut frame_end = poll_ids_added + POLL_ID_FRAME_SIZE;
+            if frame_end > num_polls_to_add {
+                frame_end = num_polls_to_add;
+            }
+            let mut frame: Vec<PollId> = Vec::with_capacity(POLL_ID_FRAME_SIZE);
+            frame.extend_from_slice(&poll_ids_to_add[poll_ids_added..frame_end]);
+            self.frames.push(frame);
+            poll_ids_added = frame_end;
+        }
+
+        self.num_poll_ids += num_polls_to_add as u32;
+        self.batches.push(PollIdBatch {
+            sequence,
+            end_poll_index: self.num_poll_ids,
+        });
+    }
+
+    /**
+     *  Poll ids of batches added after since_sequence, up to (about) max_poll_ids.
+     *
+     *  Only whole batches are returned, so that the high-water sequence is exact.  The first
+     *  batch is always returned in full, even if it is bigger than max_poll_ids.
+     */
+    pub fn get_poll_ids_since(
+        &self,
+        since_sequence: u64,
+        max_poll_ids: usize,
+    ) -> PollIdsSince {
+        let num_batches = self.batches.len();
+        let first_batch_index = match self.batches
+            .binary_search_by(|batch| batch.sequence.cmp(&since_sequence)) {
+            Ok(batch_index) => batch_index + 1,
+            Err(batch_index) => batch_index,
+        };
+
+        if first_batch_index == num_batches {
+            return PollIdsSince {
+                poll_ids: Vec::new(),
+                high_water_sequence: since_sequence,
+                more: false,
+            };
+        }
+
+        let start_poll_index = if first_batch_index == 0 {
+            0
+        } else {
+            self.batches[first_batch_index - 1].end_poll_index as usize
+        };
+
+        let mut last_batch_index = first_batch_index;
+        while last_batch_index + 1 < num_batches
+            && self.batches[last_batch_index + 1].end_poll_index as usize - start_poll_index
+            <= max_poll_ids {
+            last_batch_index += 1;
+        }
+        let end_poll_index = self.batches[last_batch_index].end_poll_index as usize;
+
+        let mut poll_ids: Vec<PollId> = Vec::with_capacity(end_poll_index - start_poll_index);
+        for poll_index in start_poll_index..end_poll_index {
+            poll_ids.push(self.frames[poll_index / POLL_ID_FRAME_SIZE][poll_index % POLL_ID_FRAME_SIZE]);
+        }
+
+        PollIdsSince {
+            poll_ids,
+            high_water_sequence: self.batches[last_batch_index].sequence,
+            more: last_batch_index + 1 < num_batches,
+        }
+    }
+
+    #[inline]
+    pub fn num_frames(&self) -> usize {
+        self.frames.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::model::types::PollId;
+
+    use super::PollIdFrames;
+    use super::POLL_ID_FRAME_SIZE;
+
+    fn get_poll_ids(
+        first_poll_id: PollId,
+        num_poll_ids: usize,
+    ) -> Vec<PollId> {
+        (first_poll_id..first_poll_id + num_poll_ids as PollId).collect()
+    }
+
+    fn get_frame_sizes(
+        poll_id_frames: &PollIdFrames
+    ) -> Vec<usize> {
+        poll_id_frames.frames.iter().map(|frame| frame.len()).collect()
+    }
+
+    // Three batches of 10, 20 and 30 poll ids, with sequences 2, 4 and 6
+    fn get_batched_frames() -> PollIdFrames {
+        let mut poll_id_frames = PollIdFrames::new();
+        poll_id_frames.add_poll_ids(get_poll_ids(0, 10), 2);
+        poll_id_frames.add_poll_ids(get_poll_ids(10, 20), 4);
+        poll_id_frames.add_poll_ids(get_poll_ids(30, 30), 6);
+
+        poll_id_frames
+    }
+
+    #[test]
+    fn partial_last_frame() {
+        let mut poll_id_frames = PollIdFrames::new();
+        poll_id_frames.add_poll_ids(get_poll_ids(0, 10), 1);
+        assert_eq!(get_frame_sizes(&poll_id_frames), vec![10]);
+
+        // Tops off the last frame without starting a new one
+        poll_id_frames.add_poll_ids(get_poll_ids(10, POLL_ID_FRAME_SIZE - 10), 2);
+        assert_eq!(get_frame_sizes(&poll_id_frames), vec![POLL_ID_FRAME_SIZE]);
+        assert_eq!(poll_id_frames.frames[0], get_poll_ids(0, POLL_ID_FRAME_SIZE));
+
+        // A full last frame is left alone
+        poll_id_frames.add_poll_ids(get_poll_ids(POLL_ID_FRAME_SIZE as PollId, 5), 3);
+        assert_eq!(get_frame_sizes(&poll_id_frames), vec![POLL_ID_FRAME_SIZE, 5]);
+        assert_eq!(poll_id_frames.num_poll_ids, POLL_ID_FRAME_SIZE as u32 + 5);
+
+        let end_poll_indexes: Vec<u32> = poll_id_frames.batches.iter()
+            .map(|batch| batch.end_poll_index).collect();
+        assert_eq!(end_poll_indexes, vec![10, POLL_ID_FRAME_SIZE as u32, POLL_ID_FRAME_SIZE as u32 + 5]);
+    }
+
+    #[test]
+    fn multiple_frames() {
+        let mut poll_id_frames = PollIdFrames::new();
+        poll_id_frames.add_poll_ids(get_poll_ids(0, 100), 1);
+
+        // Tops off the first frame, adds a full one and a partial one for the remainder
+        let num_poll_ids = 2 * POLL_ID_FRAME_SIZE + 50;
+        poll_id_frames.add_poll_ids(get_poll_ids(100, num_poll_ids), 2);
+        assert_eq!(get_frame_sizes(&poll_id_frames), vec![POLL_ID_FRAME_SIZE, POLL_ID_FRAME_SIZE, 150]);
+        assert_eq!(poll_id_frames.num_frames(), 3);
+        assert_eq!(poll_id_frames.num_poll_ids, (100 + num_poll_ids) as u32);
+
+        let all_poll_ids: Vec<PollId> = poll_id_frames.frames.concat();
+        assert_eq!(all_poll_ids, get_poll_ids(0, 100 + num_poll_ids));
+
+        // Batches spanning frames are returned across frame boundaries
+        let poll_ids_since = poll_id_frames.get_poll_ids_since(1, usize::max_value());
+        assert_eq!(poll_ids_since.poll_ids, get_poll_ids(100, num_poll_ids));
+        assert_eq!(poll_ids_since.high_water_sequence, 2);
+        assert!(!poll_ids_since.more);
+    }
+
+    #[test]
+    fn since_bounds() {
+        let poll_id_frames = get_batched_frames();
+
+        // Before the first batch
+        let poll_ids_since = poll_id_frames.get_poll_ids_since(0, usize::max_value());
+        assert_eq!(poll_ids_since.poll_ids, get_poll_ids(0, 60));
+        assert_eq!(poll_ids_since.high_water_sequence, 6);
+        assert!(!poll_ids_since.more);
+
+        // Exactly on a batch, that batch is excluded
+        let poll_ids_since = poll_id_frames.get_poll_ids_since(2, usize::max_value());
+        assert_eq!(poll_ids_since.poll_ids, get_poll_ids(10, 50));
+        assert_eq!(poll_ids_since.high_water_sequence, 6);
+
+        // Between batches
+        let poll_ids_since = poll_id_frames.get_poll_ids_since(5, usize::max_value());
+        assert_eq!(poll_ids_since.poll_ids, get_poll_ids(30, 30));
+        assert_eq!(poll_ids_since.high_water_sequence, 6);
+
+        // On and after the last batch
+        for since_sequence in 6..8 {
+            let poll_ids_since = poll_id_frames.get_poll_ids_since(since_sequence, usize::max_value());
+            assert!(poll_ids_since.poll_ids.is_empty());
+            assert_eq!(poll_ids_since.high_water_sequence, since_sequence);
+            assert!(!poll_ids_since.more);
+        }
+
+        // Empty list
+        let poll_ids_since = PollIdFrames::new().get_poll_ids_since(3, usize::max_value());
+        assert!(poll_ids_since.poll_ids.is_empty());
+        assert_eq!(poll_ids_since.high_water_sequence, 3);
+        assert!(!poll_ids_since.more);
+    }
+
+    #[test]
+    fn since_limits() {
+        let poll_id_frames = get_batched_frames();
+
+        // Only whole batches that fit are returned
+        let poll_ids_since = poll_id_frames.get_poll_ids_since(0, 35);
+        assert_eq!(poll_ids_since.poll_ids, get_poll_ids(0, 30));
+        assert_eq!(poll_ids_since.high_water_sequence, 4);
+        assert!(poll_ids_since.more);
+
+        // The first batch is returned in full, even if it is over the limit
+        let poll_ids_since = poll_id_frames.get_poll_ids_since(4, 5);
+        assert_eq!(poll_ids_since.poll_ids, get_poll_ids(30, 30));
+        assert_eq!(poll_ids_since.high_water_sequence, 6);
+        assert!(!poll_ids_since.more);
+    }
+}
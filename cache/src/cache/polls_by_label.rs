@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::mem;
 //use std::collections::hash_map::Entry;
 //use std::collections::hash_map::RandomState;
 
@@ -17,6 +18,8 @@ use common::model::types::PollId;
 
 use super::super::logic::add::polls::add_polls_to_per_label_map;
 
+use super::poll_id_frames::PollIdFrames;
+
 pub struct DayPollAddition {
     pub vc_day_id: DayId,
     pub global_poll_id: PollId,
@@ -32,7 +35,7 @@ pub struct LabelPollAddition {
  *  Future period prepend data structures for per label access.
  *      By:     labelId
  *  Contain only the prepended Poll Ids in an Vector of equal size blocks (1024 each), hence
- *  a Vec<Vec<PollId>>
+ *  PollIdFrames
  *
  *  Polls can be added to per-label lists on a given day until 8PM PST/PDT.  At that point
  *  "day after tomorrow" polls roll into "tomorrow"s.  "tomorrow"'s polls are dropped, since it's the
@@ -125,15 +128,17 @@ impl PollsByLabel {
     WriteHandle<K, V, (), RandomState>,
  */
 pub struct PollsByLabel {
-    // Map by label id of a Vec of Frames (each also a vec of ?1024? poll ids)
-    pub next_month: IntHashMap<LabelId, Vec<Vec<PollId>>>,
+    // Map by label id of poll id Frames (each a vec of 1024 poll ids)
+    pub next_month: IntHashMap<LabelId, PollIdFrames>,
     pub next_month_rehash: bool,
-    pub next_week: IntHashMap<LabelId, Vec<Vec<PollId>>>,
+    pub next_week: IntHashMap<LabelId, PollIdFrames>,
     pub next_week_rehash: bool,
-    pub tomorrow: IntHashMap<LabelId, Vec<Vec<PollId>>>,
+    pub tomorrow: IntHashMap<LabelId, PollIdFrames>,
     pub tomorrow_rehash: bool,
-    pub day_after_tomorrow: IntHashMap<LabelId, Vec<Vec<PollId>>>,
+    pub day_after_tomorrow: IntHashMap<LabelId, PollIdFrames>,
     pub day_after_tomorrow_rehash: bool,
+    // Sequence number of the last addition, across all periods
+    pub last_sequence: u64,
 }
 
 impl PollsByLabel {
@@ -148,42 +153,88 @@ impl PollsByLabel {
             tomorrow_rehash: false,
             day_after_tomorrow: HashMap::with_capacity_and_hasher(1000000, IntBuildHasher::default()),
             day_after_tomorrow_rehash: false,
+            last_sequence: 0,
         }
     }
 
+    pub fn next_sequence(&mut self) -> u64 {
+        self.last_sequence += 1;
+
+        self.last_sequence
+    }
+
     pub fn add_tomorrows_polls(
         &mut self,
         label_ids: Vec<LabelId>,
         poll_ids: Vec<Vec<PollId>>,
+        sequence: u64,
     ) {
         add_polls_to_per_label_map(&mut self.tomorrow, &mut self.tomorrow_rehash,
-                                      label_ids, poll_ids);
+                                      label_ids, poll_ids, sequence);
     }
 
     pub fn add_day_after_tomorrows_polls(
         &mut self,
         label_ids: Vec<LabelId>,
         poll_ids: Vec<Vec<PollId>>,
+        sequence: u64,
     ) {
         add_polls_to_per_label_map(&mut self.day_after_tomorrow, &mut self.day_after_tomorrow_rehash,
-                                      label_ids, poll_ids);
+                                      label_ids, poll_ids, sequence);
     }
 
     pub fn add_next_weeks_polls(
         &mut self,
         label_ids: Vec<LabelId>,
         poll_ids: Vec<Vec<PollId>>,
+        sequence: u64,
     ) {
         add_polls_to_per_label_map(&mut self.next_week, &mut self.next_week_rehash,
-                                      label_ids, poll_ids);
+                                      label_ids, poll_ids, sequence);
     }
 
     pub fn add_next_months_polls(
         &mut self,
         label_ids: Vec<LabelId>,
         poll_ids: Vec<Vec<PollId>>,
+        sequence: u64,
     ) {
         add_polls_to_per_label_map(&mut self.next_month, &mut self.next_month_rehash,
-                                      label_ids, poll_ids);
+                                      label_ids, poll_ids, sequence);
+    }
+
+    /**
+     * At the global rollover the day after tomorrow becomes tomorrow, tomorrow's polls are
+     * returned (tomorrow is now today, see promote_label_polls).  New maps start with the
+     * capacity of the ones they follow.
+     */
+    pub fn roll_over_days(&mut self) -> IntHashMap<LabelId, PollIdFrames> {
+        let day_after_tomorrow = get_next_period_map(&self.day_after_tomorrow);
+        let tomorrow = mem::replace(&mut self.day_after_tomorrow, day_after_tomorrow);
+        self.tomorrow_rehash = self.day_after_tomorrow_rehash;
+        self.day_after_tomorrow_rehash = false;
+
+        mem::replace(&mut self.tomorrow, tomorrow)
+    }
+
+    pub fn roll_over_week(&mut self) -> IntHashMap<LabelId, PollIdFrames> {
+        let next_week = get_next_period_map(&self.next_week);
+        self.next_week_rehash = false;
+
+        mem::replace(&mut self.next_week, next_week)
     }
+
+    pub fn roll_over_month(&mut self) -> IntHashMap<LabelId, PollIdFrames> {
+        let next_month = get_next_period_map(&self.next_month);
+        self.next_month_rehash = false;
+
+        mem::replace(&mut self.next_month, next_month)
+    }
+}
+
+#[inline]
+fn get_next_period_map(
+    poll_map: &IntHashMap<LabelId, PollIdFrames>
+) -> IntHashMap<LabelId, PollIdFrames> {
+    HashMap::with_capacity_and_hasher(poll_map.capacity(), IntBuildHasher::default())
 }
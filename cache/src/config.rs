@@ -0,0 +1,440 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use clap::App as CommandLine;
+use clap::Arg;
+use clap::ArgMatches;
+use toml;
+
+use server::config::ListenAddress;
+use server::config::RateLimitConfig;
+use server::config::ServerConfig;
+use server::config::TlsConfig;
+use server::limit::check_rate_limit_config;
+
+/**
+ *  Config file contents, all settings are optional (defaults are in ServerConfig).
+ *
+ *      server_addresses = ["0.0.0.0:4321", "[::]:4321", "unix:/run/vc/cache.sock"]
+ *      raw_addresses = ["0.0.0.0:4322"]
+ *      updater_addresses = ["127.0.0.1:5432"]
+ *      threads = 8
+ *      backlog = 10240
+ *      max_body_size = 65536
+ *      debug_routes = false
+ *      metrics = false
+ *      compression = true
+ *      compression_min_size = 1024
+ *      update_keys_file = "/etc/vc/update-keys"
+ *      allow_unsigned_updates = false
+ *      update_queue_size = 1024
+ *      max_connections = 20000
+ *      snapshot_file = "/var/lib/vc/cache.snapshot"
+ *      snapshot_interval_seconds = 60
+ *
+ *      [rate_limit]
+ *      requests_per_second = 50
+ *      burst = 200
+ *
+ *      [rate_limit.route_costs]
+ *      "~0" = 10
+ *
+ *      [server_tls]
+ *      cert_file = "/etc/vc/cache.crt"
+ *      key_file = "/etc/vc/cache.key"
+ *
+ *      [updater_tls]
+ *      cert_file = "/etc/vc/updater.crt"
+ *      key_file = "/etc/vc/updater.key"
+ *      client_ca_file = "/etc/vc/updater-clients-ca.crt"
+ */
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    server_addresses: Option<Vec<String>>,
+    raw_addresses: Option<Vec<String>>,
+    updater_addresses: Option<Vec<String>>,
+    threads: Option<usize>,
+    backlog: Option<i32>,
+    max_body_size: Option<usize>,
+    debug_routes: Option<bool>,
+    metrics: Option<bool>,
+    compression: Option<bool>,
+    compression_min_size: Option<usize>,
+    update_keys_file: Option<String>,
+    allow_unsigned_updates: Option<bool>,
+    update_queue_size: Option<usize>,
+    max_connections: Option<usize>,
+    snapshot_file: Option<String>,
+    snapshot_interval_seconds: Option<u64>,
+    rate_limit: Option<RateLimitSection>,
+    server_tls: Option<TlsSection>,
+    updater_tls: Option<TlsSection>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RateLimitSection {
+    requests_per_second: f64,
+    burst: Option<f64>,
+    route_costs: Option<HashMap<String, u32>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TlsSection {
+    cert_file: String,
+    key_file: String,
+    client_ca_file: Option<String>,
+}
+
+impl TlsSection {
+    fn into_config(self) -> TlsConfig {
+        TlsConfig {
+            cert_file: PathBuf::from(self.cert_file),
+            key_file: PathBuf::from(self.key_file),
+            client_ca_file: self.client_ca_file.map(PathBuf::from),
+        }
+    }
+}
+
+/**
+ *  Server configuration: the defaults, overridden by the config file (if one is given) and
+ *  then by the command line flags.
+ */
+pub fn get_config() -> Result<ServerConfig, String> {
+    let matches = CommandLine::new("vc-cache-all")
+        .version("0.1.0")
+        .about("VC Complete (not-split up) Cache")
+        .arg(Arg::with_name("config")
+            .short("c")
+            .long("config")
+            .value_name("FILE")
+            .takes_value(true)
+            .help("TOML config file"))
+        .arg(Arg::with_name("listen")
+            .long("listen")
+            .value_name("ADDRESS")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("Address for read requests: host:port, [ipv6]:port or unix:/path (repeatable)"))
+        .arg(Arg::with_name("raw-listen")
+            .long("raw-listen")
+            .value_name("ADDRESS")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("Address for read requests over the raw (non-HTTP) protocol (repeatable)"))
+        .arg(Arg::with_name("updater-listen")
+            .long("updater-listen")
+            .value_name("ADDRESS")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("Address for update requests: host:port, [ipv6]:port or unix:/path (repeatable)"))
+        .arg(Arg::with_name("threads")
+            .long("threads")
+            .value_name("COUNT")
+            .takes_value(true)
+            .help("Number of reactor threads serving read requests (default: number of CPUs)"))
+        .arg(Arg::with_name("backlog")
+            .long("backlog")
+            .value_name("COUNT")
+            .takes_value(true)
+            .help("Listen backlog of TCP listeners"))
+        .arg(Arg::with_name("max-body-size")
+            .long("max-body-size")
+            .value_name("BYTES")
+            .takes_value(true)
+            .help("Largest request body accepted"))
+        .arg(Arg::with_name("debug-routes")
+            .long("debug-routes")
+            .help("Serve the human readable GET routes"))
+        .arg(Arg::with_name("metrics")
+            .long("metrics")
+            .help("Record requests and serve them (with the sizes of the cache) on GET /metrics"))
+        .arg(Arg::with_name("no-compression")
+            .long("no-compression")
+            .help("Never compress responses"))
+        .arg(Arg::with_name("compression-min-size")
+            .long("compression-min-size")
+            .value_name("BYTES")
+            .takes_value(true)
+            .help("Smallest response body that gets compressed"))
+        .arg(Arg::with_name("update-keys-file")
+            .long("update-keys-file")
+            .value_name("FILE")
+            .takes_value(true)
+            .help("Keys update requests are signed with, one \"<key id> <hex key>\" per line"))
+        .arg(Arg::with_name("allow-unsigned-updates")
+            .long("allow-unsigned-updates")
+            .help("Accept unsigned update requests when there is no keys file"))
+        .arg(Arg::with_name("update-queue-size")
+            .long("update-queue-size")
+            .value_name("COUNT")
+            .takes_value(true)
+            .help("Most updates waiting to be applied, further update requests get a 503"))
+        .arg(Arg::with_name("max-connections")
+            .long("max-connections")
+            .value_name("COUNT")
+            .takes_value(true)
+            .help("Most concurrent connections to the read listeners (default: no limit)"))
+        .arg(Arg::with_name("snapshot-file")
+            .long("snapshot-file")
+            .value_name("FILE")
+            .takes_value(true)
+            .help("File updates are saved to (periodically) and loaded from on start"))
+        .arg(Arg::with_name("snapshot-interval")
+            .long("snapshot-interval")
+            .value_name("SECONDS")
+            .takes_value(true)
+            .help("How often updates are saved to the snapshot file"))
+        .arg(Arg::with_name("rate-limit")
+            .long("rate-limit")
+            .value_name("REQUESTS_PER_SECOND")
+            .takes_value(true)
+            .help("Read requests per second allowed per client IP address (default: no limit)"))
+        .arg(Arg::with_name("rate-limit-burst")
+            .long("rate-limit-burst")
+            .value_name("REQUESTS")
+            .takes_value(true)
+            .requires("rate-limit")
+            .help("Most read requests a client can make at once (default: one second's worth)"))
+        .arg(Arg::with_name("tls-cert")
+            .long("tls-cert")
+            .value_name("FILE")
+            .takes_value(true)
+            .requires("tls-key")
+            .help("Certificate chain (PEM) for TLS on the read listeners"))
+        .arg(Arg::with_name("tls-key")
+            .long("tls-key")
+            .value_name("FILE")
+            .takes_value(true)
+            .requires("tls-cert")
+            .help("Private key (PEM) for TLS on the read listeners"))
+        .arg(Arg::with_name("updater-tls-cert")
+            .long("updater-tls-cert")
+            .value_name("FILE")
+            .takes_value(true)
+            .requires("updater-tls-key")
+            .help("Certificate chain (PEM) for TLS on the update listeners"))
+        .arg(Arg::with_name("updater-tls-key")
+            .long("updater-tls-key")
+            .value_name("FILE")
+            .takes_value(true)
+            .requires("updater-tls-cert")
+            .help("Private key (PEM) for TLS on the update listeners"))
+        .arg(Arg::with_name("updater-client-ca")
+            .long("updater-client-ca")
+            .value_name("FILE")
+            .takes_value(true)
+            .requires("updater-tls-cert")
+            .help("Require update clients to have a certificate signed by this CA (PEM), \
+                   instead of signing requests"))
+        .get_matches();
+
+    let mut config = ServerConfig::default();
+
+    if let Some(path) = matches.value_of("config") {
+        apply_config_file(&mut config, path)?;
+    }
+    apply_command_line(&mut config, &matches)?;
+
+    if config.server_addresses.is_empty() {
+        return Err("At least one server address is required".to_string());
+    }
+    if config.num_threads == 0 {
+        return Err("At least one thread is required".to_string());
+    }
+    if config.update_queue_size == 0 {
+        return Err("The update queue size must be at least 1".to_string());
+    }
+    if config.snapshot_interval_seconds == 0 {
+        return Err("The snapshot interval must be at least 1 second".to_string());
+    }
+    if let Some(ref rate_limit) = config.rate_limit {
+        check_rate_limit_config(rate_limit)?;
+    }
+
+    Ok(config)
+}
+
+fn apply_config_file(
+    config: &mut ServerConfig,
+    path: &str,
+) -> Result<(), String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|err| format!("Could not read config file {}: {}", path, err))?;
+    let config_file: ConfigFile = toml::from_str(&contents)
+        .map_err(|err| format!("Invalid config file {}: {}", path, err))?;
+
+    if let Some(server_addresses) = config_file.server_addresses {
+        config.server_addresses = parse_addresses(server_addresses.iter().map(|address| address.as_str()))?;
+    }
+    if let Some(raw_addresses) = config_file.raw_addresses {
+        config.raw_addresses = parse_addresses(raw_addresses.iter().map(|address| address.as_str()))?;
+    }
+    if let Some(updater_addresses) = config_file.updater_addresses {
+        config.updater_addresses = parse_addresses(updater_addresses.iter().map(|address| address.as_str()))?;
+    }
+    if let Some(threads) = config_file.threads {
+        config.num_threads = threads;
+    }
+    if let Some(backlog) = config_file.backlog {
+        config.backlog = backlog;
+    }
+    if let Some(max_body_size) = config_file.max_body_size {
+        config.max_body_size = max_body_size;
+    }
+    if let Some(debug_routes) = config_file.debug_routes {
+        config.debug_routes = debug_routes;
+    }
+    if let Some(metrics) = config_file.metrics {
+        config.metrics = metrics;
+    }
+    if let Some(compression) = config_file.compression {
+        config.compression = compression;
+    }
+    if let Some(compression_min_size) = config_file.compression_min_size {
+        config.compression_min_size = compression_min_size;
+    }
+    if let Some(update_keys_file) = config_file.update_keys_file {
+        config.update_keys_file = Some(PathBuf::from(update_keys_file));
+    }
+    if let Some(allow_unsigned_updates) = config_file.allow_unsigned_updates {
+        config.allow_unsigned_updates = allow_unsigned_updates;
+    }
+    if let Some(update_queue_size) = config_file.update_queue_size {
+        config.update_queue_size = update_queue_size;
+    }
+    if let Some(max_connections) = config_file.max_connections {
+        config.max_connections = max_connections;
+    }
+    if let Some(snapshot_file) = config_file.snapshot_file {
+        config.snapshot_file = Some(PathBuf::from(snapshot_file));
+    }
+    if let Some(snapshot_interval_seconds) = config_file.snapshot_interval_seconds {
+        config.snapshot_interval_seconds = snapshot_interval_seconds;
+    }
+    if let Some(rate_limit) = config_file.rate_limit {
+        config.rate_limit = Some(RateLimitConfig {
+            requests_per_second: rate_limit.requests_per_second,
+            burst: rate_limit.burst.unwrap_or(rate_limit.requests_per_second),
+            route_costs: rate_limit.route_costs.unwrap_or_default(),
+        });
+    }
+    if let Some(server_tls) = config_file.server_tls {
+        config.server_tls = Some(server_tls.into_config());
+    }
+    if let Some(updater_tls) = config_file.updater_tls {
+        config.updater_tls = Some(updater_tls.into_config());
+    }
+
+    Ok(())
+}
+
+fn apply_command_line(
+    config: &mut ServerConfig,
+    matches: &ArgMatches,
+) -> Result<(), String> {
+    if let Some(server_addresses) = matches.values_of("listen") {
+        config.server_addresses = parse_addresses(server_addresses)?;
+    }
+    if let Some(raw_addresses) = matches.values_of("raw-listen") {
+        config.raw_addresses = parse_addresses(raw_addresses)?;
+    }
+    if let Some(updater_addresses) = matches.values_of("updater-listen") {
+        config.updater_addresses = parse_addresses(updater_addresses)?;
+    }
+    if let Some(threads) = matches.value_of("threads") {
+        config.num_threads = parse_number("threads", threads)?;
+    }
+    if let Some(backlog) = matches.value_of("backlog") {
+        config.backlog = parse_number("backlog", backlog)?;
+    }
+    if let Some(max_body_size) = matches.value_of("max-body-size") {
+        config.max_body_size = parse_number("max-body-size", max_body_size)?;
+    }
+    if matches.is_present("debug-routes") {
+        config.debug_routes = true;
+    }
+    if matches.is_present("metrics") {
+        config.metrics = true;
+    }
+    if matches.is_present("no-compression") {
+        config.compression = false;
+    }
+    if let Some(compression_min_size) = matches.value_of("compression-min-size") {
+        config.compression_min_size = parse_number("compression-min-size", compression_min_size)?;
+    }
+    if let Some(update_keys_file) = matches.value_of("update-keys-file") {
+        config.update_keys_file = Some(PathBuf::from(update_keys_file));
+    }
+    if matches.is_present("allow-unsigned-updates") {
+        config.allow_unsigned_updates = true;
+    }
+    if let Some(update_queue_size) = matches.value_of("update-queue-size") {
+        config.update_queue_size = parse_number("update-queue-size", update_queue_size)?;
+    }
+    if let Some(max_connections) = matches.value_of("max-connections") {
+        config.max_connections = parse_number("max-connections", max_connections)?;
+    }
+    if let Some(snapshot_file) = matches.value_of("snapshot-file") {
+        config.snapshot_file = Some(PathBuf::from(snapshot_file));
+    }
+    if let Some(snapshot_interval_seconds) = matches.value_of("snapshot-interval") {
+        config.snapshot_interval_seconds = parse_number("snapshot-interval", snapshot_interval_seconds)?;
+    }
+    if let Some(requests_per_second) = matches.value_of("rate-limit") {
+        let requests_per_second: f64 = parse_number("rate-limit", requests_per_second)?;
+        let burst = match matches.value_of("rate-limit-burst") {
+            None => requests_per_second,
+            Some(burst) => parse_number("rate-limit-burst", burst)?,
+        };
+        // Route costs only come from the config file
+        let route_costs = match config.rate_limit {
+            None => HashMap::new(),
+            Some(ref rate_limit) => rate_limit.route_costs.clone(),
+        };
+        config.rate_limit = Some(RateLimitConfig {
+            requests_per_second,
+            burst,
+            route_costs,
+        });
+    }
+    if let (Some(cert_file), Some(key_file)) = (matches.value_of("tls-cert"), matches.value_of("tls-key")) {
+        config.server_tls = Some(TlsConfig {
+            cert_file: PathBuf::from(cert_file),
+            key_file: PathBuf::from(key_file),
+            client_ca_file: None,
+        });
+    }
+    if let (Some(cert_file), Some(key_file)) = (matches.value_of("updater-tls-cert"), matches.value_of("updater-tls-key")) {
+        config.updater_tls = Some(TlsConfig {
+            cert_file: PathBuf::from(cert_file),
+            key_file: PathBuf::from(key_file),
+            client_ca_file: matches.value_of("updater-client-ca").map(PathBuf::from),
+        });
+    }
+
+    Ok(())
+}
+
+#[inline]
+fn parse_addresses<'a, I>(
+    addresses: I
+) -> Result<Vec<ListenAddress>, String>
+    where I: Iterator<Item=&'a str> {
+    addresses.map(|address| address.parse::<ListenAddress>()).collect()
+}
+
+#[inline]
+fn parse_number<T: FromStr>(
+    name: &str,
+    value: &str,
+) -> Result<T, String> {
+    value.parse::<T>().map_err(|_| format!("Invalid {}: {}", name, value))
+}
@@ -1,17 +1 @@
-//use std::thread;
-//use std::time::Duration;
-//
 pub mod byte_counts;
-//
-//
-//static CACHE_TICK: u64 = 1 * 60 * 1000;
-//static mut CHECK_CACHE_TICK: u32 = 0;
-//
-//fn cache_cicker() {
-//    loop {
-//        thread::sleep(Duration::from_millis(CACHE_TICK));
-//        unsafe {
-//            CHECK_CACHE_TICK += 1;
-//        }
-//    }
-//}
@@ -10,6 +10,8 @@ use common::model::types::LabelId;
 use common::model::types::PollId;
 //use common::model::types::WeekId;
 
+use super::super::super::cache::poll_id_frames::PollIdFrames;
+
 /**
     The timeout needed for any existing requests to finish
     before rehashing starts.  Assuming very fast requests.
@@ -119,13 +121,15 @@ const NUM_MILLIS_TO_SLEEP_BEFORE_HASHMAP_REHASH: u64 = 10;
 
 
 pub fn add_polls_to_per_label_map(
-    poll_map: &mut IntHashMap<LabelId, Vec<Vec<PollId>>>,
+    poll_map: &mut IntHashMap<LabelId, PollIdFrames>,
     rehash: &mut bool,
     // Vec of label ids with PollIds to be added
     label_ids: Vec<LabelId>,
     // Vec of Vec<PollId>s, in the same order as the Vec<LabelId> above.  Each nested Vec
     // contains the PollIds for that given LabelId
     poll_ids: Vec<Vec<PollId>>,
+    // Sequence number of this addition
+    sequence: u64,
 ) {
     let mut missing_label_ids: Vec<LabelId> = Vec::new();
     let mut missing_poll_ids: Vec<Vec<PollId>> = Vec::new();
@@ -134,7 +138,7 @@ pub fn add_polls_to_per_label_map(
     for poll_ids_to_add in poll_ids {
         let label_id = label_ids[i];
         if poll_map.contains_key(&label_id) {
-            insert_polls_ids_to_per_label_map(poll_map, poll_ids_to_add, label_id, false);
+            insert_polls_ids_to_per_label_map(poll_map, poll_ids_to_add, label_id, false, sequence);
         } else {
             missing_label_ids.push(label_id);
             missing_poll_ids.push(poll_ids_to_add);
@@ -159,7 +163,7 @@ pub fn add_polls_to_per_label_map(
     for poll_ids_to_add in missing_poll_ids {
         let label_id = missing_label_ids[i];
 
-        insert_polls_ids_to_per_label_map(poll_map, poll_ids_to_add, label_id, true);
+        insert_polls_ids_to_per_label_map(poll_map, poll_ids_to_add, label_id, true, sequence);
 
         i += 1;
     }
@@ -167,34 +171,16 @@ pub fn add_polls_to_per_label_map(
 }
 
 fn insert_polls_ids_to_per_label_map(
-    poll_map: &mut IntHashMap<LabelId, Vec<Vec<PollId>>>,
+    poll_map: &mut IntHashMap<LabelId, PollIdFrames>,
     poll_ids_to_add: Vec<PollId>,
     label_id: LabelId,
     new_vec: bool,
+    sequence: u64,
 ) {
     if new_vec {
-        poll_map.insert(label_id, Vec::new());
+        poll_map.insert(label_id, PollIdFrames::new());
     }
 
     let poll_id_frames = poll_map.get_mut(&label_id).unwrap();
-
-    let num_polls = poll_ids_to_add.len() * 1;
-    if num_polls <= 1024 {
-        poll_id_frames.push(poll_ids_to_add);
-        return;
-    }
-
-    let mut num_frames = num_polls / 1024;
-    if num_polls % 1024 != 0 {
-        num_frames += 1;
-    }
-    let last_frame_index = num_frames - 1;
-    for frame_index in 0..last_frame_index {
-        let frame = poll_ids_to_add[frame_index * 1024..frame_index + 1 * 1024]
-            .iter().cloned().collect();
-        poll_id_frames.push(frame);
-    }
-    let last_frame = poll_ids_to_add[last_frame_index * 1024..poll_ids_to_add.len()]
-        .iter().cloned().collect();
-    poll_id_frames.push(last_frame);
+    poll_id_frames.add_poll_ids(poll_ids_to_add, sequence);
 }
@@ -0,0 +1,173 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fmt::Write;
+use std::hash::Hasher;
+use std::io::Cursor;
+
+use byteorder::{BigEndian, ReadBytesExt};
+
+use common::model::types::LabelId;
+use common::model::types::LocationId;
+use common::model::types::PollId;
+use common::model::types::TimezoneId;
+
+use server::cache::events::EventHub;
+use server::cache::events::Topic;
+
+/*
+ *  Future periods that can be subscribed to, same order as the future poll routes.
+ */
+pub const FUTURE_PERIOD_NEXT_MONTH: u32 = 0;
+pub const FUTURE_PERIOD_NEXT_WEEK: u32 = 1;
+pub const FUTURE_PERIOD_TOMORROW: u32 = 2;
+pub const FUTURE_PERIOD_DAY_AFTER_TOMORROW: u32 = 3;
+
+/**
+ *  Size of a single subscription in the request body:
+ *      period (u32), timezone id (u32), global location id (u64), global label id (u64)
+ *  Only label subscriptions (location id of 0, timezone id ignored) are served for now.
+ *  Location and location label subscriptions (non-zero location id) are rejected until
+ *  PollsByLocation additions publish events.
+ */
+const SUBSCRIPTION_SIZE: usize = 24;
+
+/**
+ *  Maximum number of subscriptions in a single event stream request.
+ */
+const MAX_SUBSCRIPTIONS: usize = 256;
+
+const TOPIC_KIND_LABEL: u8 = 0;
+
+pub fn get_label_polls_topic(
+    period: u32,
+    global_label_id: LabelId,
+) -> Topic {
+    get_topic(TOPIC_KIND_LABEL, period, 0, 0, global_label_id)
+}
+
+/**
+ *  Topics of a future polls event stream request, None if the request is malformed (or
+ *  subscribes to anything other than labels).
+ */
+pub fn get_future_polls_topics(
+    request_body: &[u8]
+) -> Option<Vec<Topic>> {
+    let num_subscriptions = request_body.len() / SUBSCRIPTION_SIZE;
+    if request_body.len() % SUBSCRIPTION_SIZE != 0
+        || num_subscriptions == 0
+        || num_subscriptions > MAX_SUBSCRIPTIONS {
+        return None;
+    }
+
+    let mut request_data_reader = Cursor::new(request_body);
+    let mut topics: Vec<Topic> = Vec::with_capacity(num_subscriptions);
+
+    for _ in 0..num_subscriptions {
+        let period = request_data_reader.read_u32::<BigEndian>().unwrap();
+        // Timezone id, only used by location (and location label) subscriptions
+        request_data_reader.read_u32::<BigEndian>().unwrap();
+        let global_location_id = request_data_reader.read_u64::<BigEndian>().unwrap();
+        let global_label_id = request_data_reader.read_u64::<BigEndian>().unwrap();
+
+        if period > FUTURE_PERIOD_DAY_AFTER_TOMORROW {
+            return None;
+        }
+
+        // Location and location label polls aren't published (yet)
+        if global_location_id != 0 || global_label_id == 0 {
+            return None;
+        }
+        topics.push(get_label_polls_topic(period, global_label_id));
+    }
+
+    Some(topics)
+}
+
+/**
+ *  Encodes "polls" events for labels that have subscribers.  Encoded ahead of the addition
+ *  (which consumes the poll ids) and published after it.
+ *
+ *  TODO: location and location label events, once PollsByLocation additions are implemented
+ */
+pub fn get_label_polls_events(
+    event_hub: &EventHub,
+    period: u32,
+    label_ids: &[LabelId],
+    poll_ids: &[Vec<PollId>],
+    sequence: u64,
+) -> Vec<(Topic, Vec<u8>)> {
+    let mut events: Vec<(Topic, Vec<u8>)> = Vec::new();
+
+    for (label_id, label_poll_ids) in label_ids.iter().zip(poll_ids.iter()) {
+        let topic = get_label_polls_topic(period, *label_id);
+        if !event_hub.has_subscribers(topic) {
+            continue;
+        }
+
+        let mut data = String::with_capacity(64 + label_poll_ids.len() * 12);
+        write!(data, "{{\"period\":{},\"labelId\":{},\"sequence\":{},\"pollIds\":[",
+               period, label_id, sequence).unwrap();
+        append_poll_ids(&mut data, label_poll_ids);
+        data.push_str("]}");
+
+        events.push((topic, get_event("polls", &data)));
+    }
+
+    events
+}
+
+pub fn publish_events(
+    event_hub: &EventHub,
+    events: Vec<(Topic, Vec<u8>)>,
+) {
+    for (topic, event) in events {
+        event_hub.publish(topic, event);
+    }
+}
+
+#[inline]
+fn get_topic(
+    kind: u8,
+    period: u32,
+    timezone_id: TimezoneId,
+    global_location_id: LocationId,
+    global_label_id: LabelId,
+) -> Topic {
+    let mut hasher = DefaultHasher::new();
+    hasher.write_u8(kind);
+    hasher.write_u32(period);
+    hasher.write_u32(timezone_id);
+    hasher.write_u64(global_location_id);
+    hasher.write_u64(global_label_id);
+
+    hasher.finish()
+}
+
+#[inline]
+fn append_poll_ids(
+    data: &mut String,
+    poll_ids: &[PollId],
+) {
+    let mut first = true;
+    for poll_id in poll_ids {
+        if first {
+            first = false;
+        } else {
+            data.push(',');
+        }
+        write!(data, "{}", poll_id).unwrap();
+    }
+}
+
+/**
+ *  A single text/event-stream event, data must not contain newlines.
+ */
+#[inline]
+fn get_event(
+    name: &str,
+    data: &str,
+) -> Vec<u8> {
+    let mut event = String::with_capacity(name.len() + data.len() + 16);
+    write!(event, "event: {}\ndata: {}\n\n", name, data).unwrap();
+
+    event.into_bytes()
+}
@@ -1,2 +1,7 @@
 pub mod add;
-pub mod serve;
\ No newline at end of file
+pub mod events;
+pub mod notify;
+pub mod rerank;
+pub mod rollover;
+pub mod serve;
+pub mod snapshot;
\ No newline at end of file
@@ -0,0 +1,228 @@
+use std::cmp;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::Hasher;
+use std::mem;
+
+use common::model::types::LabelCacheIndex;
+use common::model::types::LocationCacheIndex;
+use common::model::types::LocationLabelCacheIndex;
+use common::model::types::TimezoneId;
+
+use server::cache::pages::PageCache;
+use server::cache::watchers::PageKey;
+use server::cache::watchers::PageWatchers;
+
+use super::super::cache::model::VoteCount;
+
+use super::serve::page_header::PAGE_SIZE;
+use super::serve::page_key::get_label_rankings_page_key;
+use super::serve::page_key::get_location_label_rankings_page_key;
+use super::serve::page_key::get_location_rankings_page_key;
+
+/**
+ *  Digests of the pages (blocks of PAGE_SIZE vote counts) of the current rankings, as of the
+ *  last time they were checked.  A page whose digest differs has changed since: polls moved in
+ *  or out of it, any of its counts changed or the rankings grew (or shrank), which changes the
+ *  totals in the header of every page.
+ *
+ *  Digests are 64 bit hashes of the poll ids and counts of a page (and the number of vote
+ *  counts in its rankings), a page only goes unnoticed if its new digest happens to be equal
+ *  to its old one.
+ */
+pub struct PageDigests {
+    by_page_key: HashMap<PageKey, u64>,
+    // Digests of the pages checked by the current pass, pages that are no longer ranked (or
+    // no longer current) are dropped at the end of it
+    checked: HashMap<PageKey, u64>,
+}
+
+impl PageDigests {
+    pub fn new() -> PageDigests {
+        PageDigests {
+            by_page_key: HashMap::new(),
+            checked: HashMap::new(),
+        }
+    }
+
+    /**
+     * To be called after all current rankings have been checked.
+     */
+    pub fn end_pass(&mut self) {
+        mem::swap(&mut self.by_page_key, &mut self.checked);
+        self.checked.clear();
+    }
+
+    #[inline]
+    fn check(
+        &mut self,
+        page_key: PageKey,
+        vote_counts: &[VoteCount],
+        total_vote_counts: usize,
+    ) -> bool {
+        let digest = get_digest(vote_counts, total_vote_counts);
+        self.checked.insert(page_key, digest);
+
+        self.by_page_key.get(&page_key) != Some(&digest)
+    }
+}
+
+/*
+ *  To be called by the re-ranking logic, after the vote counts of a label/location/location
+ *  label (for a given period) have been re-sorted.  Pages of the rankings that have changed
+ *  since they were last checked (any of their counts or the polls in them) are invalidated and
+ *  notified, as changed at the data version the re-ranking is published at.  Returns the
+ *  number of changed pages.
+ */
+
+pub fn notify_label_rankings_changed(
+    page_watchers: &PageWatchers,
+    page_cache: &PageCache,
+    page_digests: &mut PageDigests,
+    data_version: u32,
+    period: u8,
+    period_id: u32,
+    label_cache_index: LabelCacheIndex,
+    vote_counts: &[VoteCount],
+) -> usize {
+    notify_changed_pages(page_watchers, page_cache, page_digests, data_version, vote_counts, |block_index| {
+        get_label_rankings_page_key(period, period_id, label_cache_index, block_index)
+    })
+}
+
+pub fn notify_location_rankings_changed(
+    page_watchers: &PageWatchers,
+    page_cache: &PageCache,
+    page_digests: &mut PageDigests,
+    data_version: u32,
+    period: u8,
+    period_id: u32,
+    timezone_id: TimezoneId,
+    location_cache_index: LocationCacheIndex,
+    vote_counts: &[VoteCount],
+) -> usize {
+    notify_changed_pages(page_watchers, page_cache, page_digests, data_version, vote_counts, |block_index| {
+        get_location_rankings_page_key(period, period_id, timezone_id, location_cache_index, block_index)
+    })
+}
+
+pub fn notify_location_label_rankings_changed(
+    page_watchers: &PageWatchers,
+    page_cache: &PageCache,
+    page_digests: &mut PageDigests,
+    data_version: u32,
+    period: u8,
+    period_id: u32,
+    timezone_id: TimezoneId,
+    location_cache_index: LocationCacheIndex,
+    location_label_cache_index: LocationLabelCacheIndex,
+    vote_counts: &[VoteCount],
+) -> usize {
+    notify_changed_pages(page_watchers, page_cache, page_digests, data_version, vote_counts, |block_index| {
+        get_location_label_rankings_page_key(period, period_id, timezone_id, location_cache_index,
+                                             location_label_cache_index, block_index)
+    })
+}
+
+#[inline]
+fn notify_changed_pages<F>(
+    page_watchers: &PageWatchers,
+    page_cache: &PageCache,
+    page_digests: &mut PageDigests,
+    data_version: u32,
+    vote_counts: &[VoteCount],
+    get_page_key: F,
+) -> usize
+    where F: Fn(u32) -> PageKey {
+    let mut num_changed = 0;
+    let page_size = PAGE_SIZE as usize;
+    // Always check the first page, a request for it may be waiting on the first polls
+    let num_pages = cmp::max(1, (vote_counts.len() + page_size - 1) / page_size);
+    for block_index in 0..num_pages {
+        let block_start = block_index * page_size;
+        let block_end = cmp::min(block_start + page_size, vote_counts.len());
+        let page_key = get_page_key(block_index as u32);
+        if page_digests.check(page_key, &vote_counts[block_start..block_end], vote_counts.len()) {
+            page_changed(page_watchers, page_cache, page_key, data_version);
+            num_changed += 1;
+        }
+    }
+
+    num_changed
+}
+
+#[inline]
+fn page_changed(
+    page_watchers: &PageWatchers,
+    page_cache: &PageCache,
+    page_key: PageKey,
+    data_version: u32,
+) {
+    // Invalidated first, so that the notified requests get the new page
+    page_cache.invalidate(page_key);
+    page_watchers.notify(page_key, data_version);
+}
+
+#[inline]
+fn get_digest(
+    vote_counts: &[VoteCount],
+    total_vote_counts: usize,
+) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hasher.write_usize(total_vote_counts);
+    for vote_count in vote_counts {
+        hasher.write_u8(vote_count.poll_type_and_tz);
+        hasher.write_u64(vote_count.poll_id);
+        hasher.write_u32(vote_count.count);
+    }
+
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use server::cache::pages::PageCache;
+    use server::cache::watchers::PageWatchers;
+
+    use super::super::super::cache::model::VoteCount;
+    use super::notify_label_rankings_changed;
+    use super::PageDigests;
+
+    fn get_vote_counts(
+        counts: &[u32]
+    ) -> Vec<VoteCount> {
+        counts.iter().enumerate().map(|(index, &count)| VoteCount {
+            poll_type_and_tz: 0,
+            poll_id: index as u64 + 1,
+            count,
+        }).collect()
+    }
+
+    #[test]
+    fn changed_pages() {
+        let page_watchers = PageWatchers::new();
+        let page_cache = PageCache::new();
+        let mut page_digests = PageDigests::new();
+        let mut check = |data_version: u32, vote_counts: &[VoteCount]| {
+            let num_changed = notify_label_rankings_changed(
+                &page_watchers, &page_cache, &mut page_digests, data_version, 6, 7, 0, vote_counts);
+            page_digests.end_pass();
+            num_changed
+        };
+
+        assert_eq!(check(1, &get_vote_counts(&[3, 2])), 1);
+        assert_eq!(check(2, &get_vote_counts(&[3, 2])), 0);
+        assert_eq!(check(2, &get_vote_counts(&[3, 1])), 1);
+
+        // A second page changes the totals in the header of the first one as well
+        let mut vote_counts = get_vote_counts(&[1; 1024]);
+        assert_eq!(check(3, &vote_counts), 1);
+        vote_counts.push(VoteCount {
+            poll_type_and_tz: 0,
+            poll_id: 2000,
+            count: 1,
+        });
+        assert_eq!(check(4, &vote_counts), 2);
+        assert_eq!(check(5, &vote_counts), 0);
+    }
+}
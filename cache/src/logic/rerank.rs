@@ -0,0 +1,135 @@
+use common::model::timezone::NUM_TIMEZONES;
+use common::model::types::TimezoneId;
+
+use server::cache::pages::PageCache;
+use server::cache::watchers::PageWatchers;
+
+use super::super::cache::cache::Cache;
+use super::super::cache::label_poll_rankings::LabelPollRankings;
+use super::super::cache::locations_poll_rankings::LocationsPollRankings;
+use super::super::cache::model::LocationPollRankings;
+use super::super::cache::model::VoteCount;
+
+use super::notify::notify_label_rankings_changed;
+use super::notify::notify_location_label_rankings_changed;
+use super::notify::notify_location_rankings_changed;
+use super::notify::PageDigests;
+use super::serve::page_key::get_current_period_id;
+use super::serve::page_key::RANKINGS_PERIOD_THIS_MONTH;
+use super::serve::page_key::RANKINGS_PERIOD_THIS_WEEK;
+use super::serve::page_key::RANKINGS_PERIOD_TODAY;
+
+/**
+ *  Periods that are still being voted on, past periods are ranked once and for all.
+ */
+const CURRENT_RANKINGS_PERIODS: [u8; 3] = [
+    RANKINGS_PERIOD_TODAY,
+    RANKINGS_PERIOD_THIS_WEEK,
+    RANKINGS_PERIOD_THIS_MONTH,
+];
+
+/**
+ *  Full re-sort of the rankings of the current periods (by vote count, highest first): label
+ *  rankings and the location (and location label) rankings of every timezone.  Pages of the
+ *  rankings that changed since the last re-rank (re-sorted, or with any of their counts
+ *  changed) are invalidated and their watchers notified, and the data version is bumped if any
+ *  were.
+ *
+ *  Returns the number of pages that changed.
+ */
+pub fn re_rank(
+    cache: &mut Cache,
+    page_digests: &mut PageDigests,
+    page_watchers: &PageWatchers,
+    page_cache: &PageCache,
+) -> usize {
+    let mut num_changed = 0;
+    // Changed pages are notified as of the data version bumped at the end
+    let data_version = cache.data_version.wrapping_add(1);
+
+    for &period in CURRENT_RANKINGS_PERIODS.iter() {
+        let period_id = get_current_period_id(&cache.label_cache_period_ids, period);
+        let label_rankings = get_label_rankings(&mut cache.label_poll_rankings, period);
+        for (label_cache_index, vote_counts) in label_rankings.iter_mut().enumerate() {
+            sort_vote_counts(vote_counts);
+            num_changed += notify_label_rankings_changed(
+                page_watchers, page_cache, page_digests, data_version, period, period_id,
+                label_cache_index as u32, vote_counts);
+        }
+
+        let location_rankings = get_location_rankings(&mut cache.location_poll_rankings, period);
+        for timezone_index in 0..NUM_TIMEZONES as usize {
+            // Timezones without rankings (yet) have nothing to sort
+            if timezone_index >= location_rankings.len() {
+                break;
+            }
+            let period_id = get_current_period_id(
+                &cache.per_timezone_cache_period_ids[timezone_index], period);
+            let timezone_id = timezone_index as TimezoneId;
+            for (location_cache_index, location) in location_rankings[timezone_index].iter_mut().enumerate() {
+                let location_cache_index = location_cache_index as u32;
+                sort_vote_counts(&mut location.location);
+                num_changed += notify_location_rankings_changed(
+                    page_watchers, page_cache, page_digests, data_version, period, period_id,
+                    timezone_id, location_cache_index, &location.location);
+                for (location_label_cache_index, vote_counts) in location.label_locations.iter_mut().enumerate() {
+                    sort_vote_counts(vote_counts);
+                    num_changed += notify_location_label_rankings_changed(
+                        page_watchers, page_cache, page_digests, data_version, period, period_id, timezone_id,
+                        location_cache_index, location_label_cache_index as u32, vote_counts);
+                }
+            }
+        }
+    }
+    page_digests.end_pass();
+
+    if num_changed > 0 {
+        cache.data_version = data_version;
+    }
+
+    num_changed
+}
+
+/**
+ *  Sorts vote counts highest first (keeping the order of equal counts), unless they are
+ *  already in order.
+ */
+#[inline]
+fn sort_vote_counts(
+    vote_counts: &mut Vec<VoteCount>
+) {
+    if vote_counts.windows(2).all(|pair| pair[0].count >= pair[1].count) {
+        return;
+    }
+    vote_counts.sort_by(|first, second| second.count.cmp(&first.count));
+}
+
+#[inline]
+fn get_label_rankings(
+    rankings: &mut LabelPollRankings,
+    period: u8,
+) -> &mut Vec<Vec<VoteCount>> {
+    match period {
+        RANKINGS_PERIOD_THIS_MONTH => &mut rankings.this_month,
+        RANKINGS_PERIOD_THIS_WEEK => &mut rankings.this_week,
+        RANKINGS_PERIOD_TODAY => &mut rankings.today,
+        _ => {
+            panic!("Unexpected current rankings period {}", period)
+        }
+    }
+}
+
+#[inline]
+fn get_location_rankings(
+    rankings: &mut LocationsPollRankings,
+    period: u8,
+) -> &mut Vec<Vec<LocationPollRankings>> {
+    match period {
+        RANKINGS_PERIOD_THIS_MONTH => &mut rankings.this_month,
+        RANKINGS_PERIOD_THIS_WEEK => &mut rankings.this_week,
+        RANKINGS_PERIOD_TODAY => &mut rankings.today,
+        _ => {
+            panic!("Unexpected current rankings period {}", period)
+        }
+    }
+}
@@ -0,0 +1,387 @@
+use std::mem;
+
+use int_hash::IntHashMap;
+
+use common::model::timezone::ALL_TIME_ZONES;
+use common::model::types::LabelCacheIndex;
+use common::model::types::LabelId;
+
+use server::cache::pages::PageCache;
+use server::cache::watchers::PageWatchers;
+
+use super::super::cache::cache::Cache;
+use super::super::cache::label_poll_rankings::LabelPollRankings;
+use super::super::cache::label_index_map::LabelIndexMap;
+use super::super::cache::locations_poll_rankings::LocationsPollRankings;
+use super::super::cache::model::CachePeriodIds;
+use super::super::cache::model::VoteCount;
+use super::super::cache::poll_id_frames::PollIdFrames;
+use super::super::cache::time_period_ids::TimePeriodIds;
+
+const SECONDS_IN_A_DAY: i64 = 24 * 60 * 60;
+
+/**
+ *  Local time (in minutes past midnight) at which the counts of a timezone are locked down,
+ *  ahead of its rollover at midnight.
+ */
+pub const LOCK_DOWN_LOCAL_TIME_IN_MINUTES: i32 = 23 * 60 + 55;
+
+/**
+ *  The (local) day a rollover starts.
+ */
+pub struct StartingDay {
+    // Weeks start on Monday
+    pub starts_week: bool,
+    pub starts_month: bool,
+}
+
+/**
+ *  The day a rollover scheduled at a given time starts.  Rollovers at midnight start the day
+ *  they are on, rollovers at any other (local) time start the next day.
+ */
+pub fn get_starting_day(
+    scheduled_seconds: u64,
+    utc_offset_minutes: i32,
+    local_time_minutes: i32,
+) -> StartingDay {
+    let mut days = (scheduled_seconds as i64 + utc_offset_minutes as i64 * 60
+        - local_time_minutes as i64 * 60) / SECONDS_IN_A_DAY;
+    if local_time_minutes != 0 {
+        days += 1;
+    }
+
+    StartingDay {
+        // 1970-01-01 was a Thursday
+        starts_week: (days + 3) % 7 == 0,
+        starts_month: get_day_of_month(days) == 1,
+    }
+}
+
+/**
+ *  Locks down the counts of a timezone, ahead of its rollover.  The timezone is reported as
+ *  rolling over (see the period ids route) until the rollover, so that clients stop working
+ *  with the day that is about to end.
+ */
+pub fn lock_down_timezone(
+    cache: &mut Cache,
+    timezone_index: usize,
+) {
+    cache.time_zone_modification_flags[timezone_index] = true;
+}
+
+/**
+ *  Moves a timezone on to the next day (and week and month, when those start as well): its
+ *  period ids and the location rankings of its current periods move back one period.  The
+ *  rankings of the new current periods start out empty, vote counts are not yet initialized
+ *  from the polls added for them.
+ *
+ *  Location index maps are across timezones, they move on with the global rollover.
+ */
+pub fn roll_over_timezone(
+    cache: &mut Cache,
+    timezone_index: usize,
+    starting_day: &StartingDay,
+    page_watchers: &PageWatchers,
+    page_cache: &PageCache,
+) {
+    roll_over_time_period_ids(&mut cache.time_period_ids, timezone_index, starting_day);
+    roll_over_cache_period_ids(&mut cache.per_timezone_cache_period_ids[timezone_index], starting_day);
+    roll_over_location_rankings(&mut cache.location_poll_rankings, timezone_index, starting_day);
+
+    cache.time_zone_modification_flags[timezone_index] = false;
+    periods_changed(cache, page_watchers, page_cache);
+}
+
+/**
+ *  Moves the global category (across timezones) on to the next day, at the global rollover:
+ *  its period ids, label rankings and label index maps move back one period, and so do the
+ *  future polls.  Polls of the future periods that become current are added to the label
+ *  rankings of the new current periods (see promote_label_polls), vote counts for them are
+ *  not yet initialized (see roll_over_timezone).
+ */
+pub fn roll_over_global(
+    cache: &mut Cache,
+    starting_day: &StartingDay,
+    page_watchers: &PageWatchers,
+    page_cache: &PageCache,
+) {
+    roll_over_time_period_ids(&mut cache.time_period_ids, ALL_TIME_ZONES, starting_day);
+    roll_over_cache_period_ids(&mut cache.label_cache_period_ids, starting_day);
+    roll_over_label_rankings(&mut cache.label_poll_rankings, starting_day);
+    roll_over_label_index_map(&mut cache.label_index_map, starting_day);
+
+    let todays_polls = cache.future_polls_by_label.roll_over_days();
+    promote_label_polls(&mut cache.label_index_map.today,
+                        &mut cache.label_poll_rankings.today, todays_polls);
+    if starting_day.starts_week {
+        let this_weeks_polls = cache.future_polls_by_label.roll_over_week();
+        promote_label_polls(&mut cache.label_index_map.this_week,
+                            &mut cache.label_poll_rankings.this_week, this_weeks_polls);
+    }
+    if starting_day.starts_month {
+        let this_months_polls = cache.future_polls_by_label.roll_over_month();
+        promote_label_polls(&mut cache.label_index_map.this_month,
+                            &mut cache.label_poll_rankings.this_month, this_months_polls);
+    }
+
+    periods_changed(cache, page_watchers, page_cache);
+}
+
+/**
+ *  Every page may now be for a different period: memoized pages are dropped and all
+ *  long-polls are woken up (to request the page again, under the new period ids), all pages
+ *  changed as of the new data version.
+ */
+fn periods_changed(
+    cache: &mut Cache,
+    page_watchers: &PageWatchers,
+    page_cache: &PageCache,
+) {
+    cache.data_version = cache.data_version.wrapping_add(1);
+    page_cache.clear();
+    page_watchers.notify_all(cache.data_version);
+}
+
+fn roll_over_time_period_ids(
+    time_period_ids: &mut TimePeriodIds,
+    index: usize,
+    starting_day: &StartingDay,
+) {
+    time_period_ids.day_b4_yesterday[index] = time_period_ids.yesterday[index];
+    time_period_ids.yesterday[index] = time_period_ids.today[index];
+    time_period_ids.today[index] = time_period_ids.tomorrow[index];
+    time_period_ids.tomorrow[index] = time_period_ids.day_after_tomorrow[index];
+    time_period_ids.day_after_tomorrow[index] = time_period_ids.tomorrow[index] + 1;
+
+    if starting_day.starts_week {
+        time_period_ids.last_week[index] = time_period_ids.this_week[index];
+        time_period_ids.this_week[index] = time_period_ids.next_week[index];
+        time_period_ids.next_week[index] = time_period_ids.this_week[index] + 1;
+    }
+
+    if starting_day.starts_month {
+        time_period_ids.last_month[index] = time_period_ids.this_month[index];
+        time_period_ids.this_month[index] = time_period_ids.next_month[index];
+        time_period_ids.next_month[index] = time_period_ids.this_month[index] + 1;
+    }
+}
+
+fn roll_over_cache_period_ids(
+    cache_period_ids: &mut CachePeriodIds,
+    starting_day: &StartingDay,
+) {
+    cache_period_ids.day_b4_yesterdays_vc_day_id = cache_period_ids.yesterdays_vc_day_id;
+    cache_period_ids.yesterdays_vc_day_id = cache_period_ids.todays_vc_day_id;
+    cache_period_ids.todays_vc_day_id = cache_period_ids.tomorrows_vc_day_id;
+    cache_period_ids.tomorrows_vc_day_id = cache_period_ids.day_after_tomorrows_vc_day_id;
+    cache_period_ids.day_after_tomorrows_vc_day_id = cache_period_ids.tomorrows_vc_day_id + 1;
+
+    if starting_day.starts_week {
+        cache_period_ids.last_weeks_vc_week_id = cache_period_ids.this_weeks_vc_week_id;
+        cache_period_ids.this_weeks_vc_week_id = cache_period_ids.next_weeks_vc_week_id;
+        cache_period_ids.next_weeks_vc_week_id = cache_period_ids.this_weeks_vc_week_id + 1;
+    }
+
+    if starting_day.starts_month {
+        cache_period_ids.last_months_vc_month_id = cache_period_ids.this_months_vc_month_id;
+        cache_period_ids.this_months_vc_month_id = cache_period_ids.next_months_vc_month_id;
+        cache_period_ids.next_months_vc_month_id = cache_period_ids.this_months_vc_month_id + 1;
+    }
+}
+
+fn roll_over_location_rankings(
+    rankings: &mut LocationsPollRankings,
+    timezone_index: usize,
+    starting_day: &StartingDay,
+) {
+    // Timezones without rankings (yet) have nothing to move
+    if timezone_index < rankings.today.len()
+        && timezone_index < rankings.yesterday.len()
+        && timezone_index < rankings.day_b4_yesterday.len() {
+        let today = mem::replace(&mut rankings.today[timezone_index], Vec::new());
+        let yesterday = mem::replace(&mut rankings.yesterday[timezone_index], today);
+        rankings.day_b4_yesterday[timezone_index] = yesterday;
+    }
+
+    if starting_day.starts_week
+        && timezone_index < rankings.this_week.len()
+        && timezone_index < rankings.last_week.len() {
+        let this_week = mem::replace(&mut rankings.this_week[timezone_index], Vec::new());
+        rankings.last_week[timezone_index] = this_week;
+    }
+
+    if starting_day.starts_month
+        && timezone_index < rankings.this_month.len()
+        && timezone_index < rankings.last_month.len() {
+        let this_month = mem::replace(&mut rankings.this_month[timezone_index], Vec::new());
+        rankings.last_month[timezone_index] = this_month;
+    }
+}
+
+fn roll_over_label_rankings(
+    rankings: &mut LabelPollRankings,
+    starting_day: &StartingDay,
+) {
+    let today = mem::replace(&mut rankings.today, Vec::new());
+    rankings.day_b4_yesterday = mem::replace(&mut rankings.yesterday, today);
+
+    if starting_day.starts_week {
+        rankings.last_week = mem::replace(&mut rankings.this_week, Vec::new());
+    }
+
+    if starting_day.starts_month {
+        rankings.last_month = mem::replace(&mut rankings.this_month, Vec::new());
+    }
+}
+
+fn roll_over_label_index_map(
+    label_index_map: &mut LabelIndexMap,
+    starting_day: &StartingDay,
+) {
+    let today = mem::replace(&mut label_index_map.today, Default::default());
+    label_index_map.day_b4_yesterday = mem::replace(&mut label_index_map.yesterday, today);
+
+    if starting_day.starts_week {
+        label_index_map.last_week = mem::replace(&mut label_index_map.this_week, Default::default());
+    }
+
+    if starting_day.starts_month {
+        label_index_map.last_month = mem::replace(&mut label_index_map.this_month, Default::default());
+    }
+}
+
+/**
+ *  Adds the polls of a future period that became current to the label rankings of that period,
+ *  with no votes yet (the type and timezone of a poll are not known until it has counts, label
+ *  rankings don't use them).  Labels new to the period get the next label cache index.
+ */
+pub fn promote_label_polls(
+    label_index_map: &mut IntHashMap<LabelId, LabelCacheIndex>,
+    label_rankings: &mut Vec<Vec<VoteCount>>,
+    polls_by_label: IntHashMap<LabelId, PollIdFrames>,
+) {
+    for (label_id, poll_id_frames) in polls_by_label {
+        let label_cache_index = *label_index_map.entry(label_id)
+            .or_insert(label_rankings.len() as LabelCacheIndex) as usize;
+        if label_cache_index == label_rankings.len() {
+            label_rankings.push(Vec::with_capacity(poll_id_frames.num_poll_ids as usize));
+        }
+        let vote_counts = &mut label_rankings[label_cache_index];
+        for frame in poll_id_frames.frames.iter() {
+            for poll_id in frame.iter() {
+                vote_counts.push(VoteCount {
+                    poll_type_and_tz: 0,
+                    poll_id: *poll_id,
+                    count: 0,
+                });
+            }
+        }
+    }
+}
+
+/**
+ *  Day of the month (1 based) of a day since the Unix epoch, in the proleptic Gregorian
+ *  calendar.
+ */
+fn get_day_of_month(
+    days: i64
+) -> i64 {
+    // Days since 0000-03-01, in 400 year eras (March based years put leap days last)
+    let days = days + 719468;
+    let day_of_era = days - days / 146097 * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+
+    day_of_year - (153 * month + 2) / 5 + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::sync::Arc;
+
+    use common::model::protocol::DEFAULT_PROTOCOL_VERSION;
+    use common::model::timezone::ALL_TIME_ZONES;
+
+    use server::cache::events::EventHub;
+    use server::cache::pages::PageCache;
+    use server::cache::watchers::PageKey;
+    use server::cache::watchers::PageWatchers;
+
+    use super::super::super::cache::cache::Cache;
+    use super::super::super::cache::model::VoteCount;
+    use super::roll_over_global;
+    use super::StartingDay;
+
+    const PAGE_KEY: PageKey = PageKey {
+        kind: 1,
+        period: 0,
+        period_id: 10,
+        timezone_id: 0,
+        indexes: [0, 0],
+        block_index: 0,
+    };
+
+    fn get_poll_ids(
+        vote_counts: &[VoteCount]
+    ) -> Vec<u64> {
+        vote_counts.iter().map(|vote_count| vote_count.poll_id).collect()
+    }
+
+    #[test]
+    fn promoted_polls() {
+        let mut cache = Cache::new(Arc::new(EventHub::new()));
+        {
+            let time_period_ids = &mut cache.time_period_ids;
+            time_period_ids.today[ALL_TIME_ZONES] = 10;
+            time_period_ids.tomorrow[ALL_TIME_ZONES] = 11;
+            time_period_ids.day_after_tomorrow[ALL_TIME_ZONES] = 12;
+            time_period_ids.this_week[ALL_TIME_ZONES] = 3;
+            time_period_ids.next_week[ALL_TIME_ZONES] = 4;
+            time_period_ids.this_month[ALL_TIME_ZONES] = 1;
+            time_period_ids.next_month[ALL_TIME_ZONES] = 2;
+        }
+        cache.add_future_day_polls(11, vec![1], vec![vec![100, 101]]).unwrap();
+        cache.add_future_day_polls(12, vec![2], vec![vec![120]]).unwrap();
+        cache.add_future_week_polls(4, vec![1], vec![vec![400]]).unwrap();
+        cache.add_future_month_polls(2, vec![1], vec![vec![200]]).unwrap();
+        let data_version = cache.data_version;
+
+        let page_watchers = PageWatchers::new();
+        let page_cache = PageCache::new();
+        let encodings = Cell::new(0);
+        let encode = || {
+            encodings.set(encodings.get() + 1);
+            vec![1u8]
+        };
+        page_cache.get_or_encode(DEFAULT_PROTOCOL_VERSION, "0", PAGE_KEY, data_version, &encode);
+        let mut page_changed = page_watchers.watch(PAGE_KEY, data_version).unwrap();
+
+        // A Monday, but not the first of the month
+        roll_over_global(&mut cache, &StartingDay {
+            starts_week: true,
+            starts_month: false,
+        }, &page_watchers, &page_cache);
+
+        // Tomorrow's and next week's polls are now current, with no votes yet
+        let label_cache_index = cache.label_index_map.today[&1] as usize;
+        assert_eq!(get_poll_ids(&cache.label_poll_rankings.today[label_cache_index]), vec![100, 101]);
+        let label_cache_index = cache.label_index_map.this_week[&1] as usize;
+        assert_eq!(get_poll_ids(&cache.label_poll_rankings.this_week[label_cache_index]), vec![400]);
+        assert!(cache.label_index_map.this_month.is_empty());
+
+        // The other future polls move back one period
+        assert_eq!(cache.future_polls_by_label.tomorrow[&2].frames, vec![vec![120]]);
+        assert!(cache.future_polls_by_label.day_after_tomorrow.is_empty());
+        assert!(cache.future_polls_by_label.next_week.is_empty());
+        assert_eq!(cache.future_polls_by_label.next_month[&1].frames, vec![vec![200]]);
+
+        // Pages are served again under the new periods
+        assert_eq!(cache.data_version, data_version.wrapping_add(1));
+        assert_eq!(page_changed.try_recv(), Ok(Some(())));
+        assert!(page_watchers.watch(PAGE_KEY, data_version).is_none());
+        page_cache.get_or_encode(DEFAULT_PROTOCOL_VERSION, "0", PAGE_KEY, data_version, &encode);
+        assert_eq!(encodings.get(), 2);
+    }
+}
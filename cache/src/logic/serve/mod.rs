@@ -1,3 +1,6 @@
 pub mod details;
+pub mod page_header;
+pub mod page_key;
+pub mod period_ids;
 pub mod rankings;
 pub mod recent;
\ No newline at end of file
@@ -0,0 +1,78 @@
+use std::mem::transmute;
+
+/**
+ * Number of vote counts in a page (block) of rankings.  Must fit into a u16.
+ */
+pub const PAGE_SIZE: u32 = 1024;
+
+pub const PAGE_HEADER_VERSION_1: u8 = 1;
+
+pub const PAGE_HEADER_SIZE: u32 =
+// space for the header version
+    1 +
+        // space for the total number of entries
+        4 +
+        // space for the total number of pages
+        4 +
+        // space for the period id
+        4 +
+        // space for the data version
+        4;
+
+/**
+ * Leading header of ranking and recent poll responses (all numbers are Big Endian):
+ *
+ *  1 byte  - header version (PAGE_HEADER_VERSION_*)
+ *  4 bytes - total number of entries across all pages
+ *  4 bytes - total number of pages
+ *  4 bytes - period id the page belongs to
+ *  4 bytes - data version of the cache at the time the page was generated
+ *
+ * Lets clients know how many pages there are (and hence when they've reached the end) without
+ * probing with additional requests.
+ */
+pub fn get_page_header(
+    total_entries: u32,
+    total_pages: u32,
+    period_id: u32,
+    data_version: u32,
+) -> [u8; PAGE_HEADER_SIZE as usize] {
+    let total_entries_bytes: [u8; 4] = unsafe {
+        transmute(total_entries.to_be())
+    };
+    let total_pages_bytes: [u8; 4] = unsafe {
+        transmute(total_pages.to_be())
+    };
+    let period_id_bytes: [u8; 4] = unsafe {
+        transmute(period_id.to_be())
+    };
+    let data_version_bytes: [u8; 4] = unsafe {
+        transmute(data_version.to_be())
+    };
+
+    let mut page_header = [0; PAGE_HEADER_SIZE as usize];
+    page_header[0] = PAGE_HEADER_VERSION_1;
+    page_header[1..5].copy_from_slice(&total_entries_bytes);
+    page_header[5..9].copy_from_slice(&total_pages_bytes);
+    page_header[9..13].copy_from_slice(&period_id_bytes);
+    page_header[13..17].copy_from_slice(&data_version_bytes);
+
+    page_header
+}
+
+/**
+ * Page header for a ranked list of vote counts, paged by PAGE_SIZE.
+ */
+#[inline]
+pub fn get_rankings_page_header(
+    total_entries: usize,
+    period_id: u32,
+    data_version: u32,
+) -> [u8; PAGE_HEADER_SIZE as usize] {
+    let mut total_pages = total_entries as u32 / PAGE_SIZE;
+    if total_entries as u32 % PAGE_SIZE != 0 {
+        total_pages += 1;
+    }
+
+    get_page_header(total_entries as u32, total_pages, period_id, data_version)
+}
@@ -0,0 +1,333 @@
+use int_hash::IntHashMap;
+
+use common::model::timezone::NUM_TIMEZONES;
+use common::model::types::LabelCacheIndex;
+use common::model::types::LabelId;
+use common::model::types::LocationCacheIndex;
+use common::model::types::LocationId;
+use common::model::types::LocationLabelCacheIndex;
+use common::model::types::TimezoneId;
+
+use server::cache::watchers::PageKey;
+
+use super::super::super::cache::cache_reader::CacheReader;
+use super::super::super::cache::model::CachePeriodIds;
+use super::super::super::cache::model::LocationPeriodIds;
+
+pub const RANKINGS_PERIOD_LAST_MONTH: u8 = 0;
+pub const RANKINGS_PERIOD_THIS_MONTH: u8 = 1;
+pub const RANKINGS_PERIOD_LAST_WEEK: u8 = 2;
+pub const RANKINGS_PERIOD_THIS_WEEK: u8 = 3;
+pub const RANKINGS_PERIOD_DAY_B4_YESTERDAY: u8 = 4;
+pub const RANKINGS_PERIOD_YESTERDAY: u8 = 5;
+pub const RANKINGS_PERIOD_TODAY: u8 = 6;
+
+const RANKINGS_KIND_LABEL: u8 = 0;
+const RANKINGS_KIND_LOCATION: u8 = 1;
+const RANKINGS_KIND_LOCATION_LABEL: u8 = 2;
+
+/**
+ *  Page keys identify a block of poll rankings (by cache indexes), for long-poll requests.
+ *
+ *  The re-ranking path computes the same keys (from the cache indexes it re-ranks) to notify
+ *  the waiting requests and to invalidate the memoized pages.
+ */
+pub fn get_label_rankings_page_key(
+    period: u8,
+    period_id: u32,
+    label_cache_index: LabelCacheIndex,
+    block_index: u32,
+) -> PageKey {
+    get_page_key(RANKINGS_KIND_LABEL, period, period_id,
+                 0, 0, label_cache_index, block_index)
+}
+
+pub fn get_location_rankings_page_key(
+    period: u8,
+    period_id: u32,
+    timezone_id: TimezoneId,
+    location_cache_index: LocationCacheIndex,
+    block_index: u32,
+) -> PageKey {
+    get_page_key(RANKINGS_KIND_LOCATION, period, period_id,
+                 timezone_id, location_cache_index, 0, block_index)
+}
+
+pub fn get_location_label_rankings_page_key(
+    period: u8,
+    period_id: u32,
+    timezone_id: TimezoneId,
+    location_cache_index: LocationCacheIndex,
+    location_label_cache_index: LocationLabelCacheIndex,
+    block_index: u32,
+) -> PageKey {
+    get_page_key(RANKINGS_KIND_LOCATION_LABEL, period, period_id,
+                 timezone_id, location_cache_index, location_label_cache_index, block_index)
+}
+
+/*
+ *  Page keys of long-poll requests, in the same order of arguments as the poll rankings
+ *  requests.  None if the request does not address a current page (the request is then
+ *  responded to right away, with the usual error).
+ */
+
+pub fn get_label_rankings_page_key_by_global_id(
+    period: u8,
+    period_id: u32,
+    block_index: u32,
+    global_label_id: LabelId,
+    cache: &CacheReader,
+) -> Option<PageKey> {
+    if get_current_period_id(cache.get_label_cache_period_ids(), period) != period_id {
+        return None;
+    }
+
+    match get_label_index_map(cache, period).get(&global_label_id) {
+        None => {
+            return None;
+        }
+        Some(label_cache_index) => {
+            return Some(get_label_rankings_page_key(
+                period, period_id, *label_cache_index, block_index));
+        }
+    }
+}
+
+pub fn get_label_rankings_page_key_by_cache_index(
+    period: u8,
+    period_id: u32,
+    block_index: u32,
+    label_cache_index: LabelCacheIndex,
+    cache: &CacheReader,
+) -> Option<PageKey> {
+    if get_current_period_id(cache.get_label_cache_period_ids(), period) != period_id {
+        return None;
+    }
+
+    return Some(get_label_rankings_page_key(
+        period, period_id, label_cache_index, block_index));
+}
+
+pub fn get_location_rankings_page_key_by_global_id(
+    period: u8,
+    period_id: u32,
+    timezone_id: TimezoneId,
+    block_index: u32,
+    global_location_id: LocationId,
+    cache: &CacheReader,
+) -> Option<PageKey> {
+    if !is_current_location_period(cache, period, period_id, timezone_id) {
+        return None;
+    }
+
+    match get_location_index_map(cache, period).get(&global_location_id) {
+        None => {
+            return None;
+        }
+        Some(location_period_ids) => {
+            return Some(get_location_rankings_page_key(
+                period, period_id, timezone_id,
+                location_period_ids.location_cache_index, block_index));
+        }
+    }
+}
+
+pub fn get_location_rankings_page_key_by_cache_index(
+    period: u8,
+    period_id: u32,
+    timezone_id: TimezoneId,
+    block_index: u32,
+    location_cache_index: LocationCacheIndex,
+    cache: &CacheReader,
+) -> Option<PageKey> {
+    if !is_current_location_period(cache, period, period_id, timezone_id) {
+        return None;
+    }
+
+    return Some(get_location_rankings_page_key(
+        period, period_id, timezone_id, location_cache_index, block_index));
+}
+
+pub fn get_location_label_rankings_page_key_by_global_ids(
+    period: u8,
+    period_id: u32,
+    timezone_id: TimezoneId,
+    block_index: u32,
+    global_location_id: LocationId,
+    global_label_id: LabelId,
+    cache: &CacheReader,
+) -> Option<PageKey> {
+    if !is_current_location_period(cache, period, period_id, timezone_id) {
+        return None;
+    }
+
+    let location_period_ids: &LocationPeriodIds = match get_location_index_map(cache, period)
+        .get(&global_location_id) {
+        None => {
+            return None;
+        }
+        Some(location_period_ids) => {
+            location_period_ids
+        }
+    };
+
+    match location_period_ids.location_label_cache_index_map.get(&global_label_id) {
+        None => {
+            return None;
+        }
+        Some(location_label_cache_index) => {
+            return Some(get_location_label_rankings_page_key(
+                period, period_id, timezone_id, location_period_ids.location_cache_index,
+                *location_label_cache_index, block_index));
+        }
+    }
+}
+
+pub fn get_location_label_rankings_page_key_by_location_cache_index_and_global_label_id(
+    period: u8,
+    period_id: u32,
+    timezone_id: TimezoneId,
+    block_index: u32,
+    location_cache_index: LocationCacheIndex,
+    global_label_id: LabelId,
+    cache: &CacheReader,
+) -> Option<PageKey> {
+    if !is_current_location_period(cache, period, period_id, timezone_id) {
+        return None;
+    }
+
+    // Same lookup as location_label::get_location_label_rankings_by_location_cache_index_and_global_label_id
+    let location_period_ids: &LocationPeriodIds = match get_location_index_map(cache, period)
+        .get(&(location_cache_index as u64)) {
+        None => {
+            return None;
+        }
+        Some(location_period_ids) => {
+            location_period_ids
+        }
+    };
+
+    match location_period_ids.location_label_cache_index_map.get(&global_label_id) {
+        None => {
+            return None;
+        }
+        Some(location_label_cache_index) => {
+            return Some(get_location_label_rankings_page_key(
+                period, period_id, timezone_id, location_cache_index,
+                *location_label_cache_index, block_index));
+        }
+    }
+}
+
+pub fn get_location_label_rankings_page_key_by_cache_indexes(
+    period: u8,
+    period_id: u32,
+    timezone_id: TimezoneId,
+    block_index: u32,
+    location_cache_index: LocationCacheIndex,
+    location_label_cache_index: LocationLabelCacheIndex,
+    cache: &CacheReader,
+) -> Option<PageKey> {
+    if !is_current_location_period(cache, period, period_id, timezone_id) {
+        return None;
+    }
+
+    return Some(get_location_label_rankings_page_key(
+        period, period_id, timezone_id, location_cache_index,
+        location_label_cache_index, block_index));
+}
+
+#[inline]
+fn get_page_key(
+    kind: u8,
+    period: u8,
+    period_id: u32,
+    timezone_id: TimezoneId,
+    location_cache_index: LocationCacheIndex,
+    label_cache_index: u32,
+    block_index: u32,
+) -> PageKey {
+    PageKey {
+        kind,
+        period,
+        period_id,
+        timezone_id,
+        indexes: [location_cache_index, label_cache_index],
+        block_index,
+    }
+}
+
+#[inline]
+fn is_current_location_period(
+    cache: &CacheReader,
+    period: u8,
+    period_id: u32,
+    timezone_id: TimezoneId,
+) -> bool {
+    if timezone_id >= NUM_TIMEZONES {
+        return false;
+    }
+    let current_period_ids: &CachePeriodIds
+    = &cache.get_per_timezone_cache_period_ids()[timezone_id as usize];
+
+    get_current_period_id(current_period_ids, period) == period_id
+}
+
+#[inline]
+pub fn get_current_period_id(
+    current_period_ids: &CachePeriodIds,
+    period: u8,
+) -> u32 {
+    match period {
+        RANKINGS_PERIOD_LAST_MONTH => current_period_ids.last_months_vc_month_id,
+        RANKINGS_PERIOD_THIS_MONTH => current_period_ids.this_months_vc_month_id,
+        RANKINGS_PERIOD_LAST_WEEK => current_period_ids.last_weeks_vc_week_id,
+        RANKINGS_PERIOD_THIS_WEEK => current_period_ids.this_weeks_vc_week_id,
+        RANKINGS_PERIOD_DAY_B4_YESTERDAY => current_period_ids.day_b4_yesterdays_vc_day_id,
+        RANKINGS_PERIOD_YESTERDAY => current_period_ids.yesterdays_vc_day_id,
+        RANKINGS_PERIOD_TODAY => current_period_ids.todays_vc_day_id,
+        _ => {
+            panic!("Unexpected rankings period {}", period)
+        }
+    }
+}
+
+#[inline]
+fn get_label_index_map(
+    cache: &CacheReader,
+    period: u8,
+) -> &IntHashMap<LabelId, LabelCacheIndex> {
+    let label_index_map = cache.get_label_index_map();
+    match period {
+        RANKINGS_PERIOD_LAST_MONTH => &label_index_map.last_month,
+        RANKINGS_PERIOD_THIS_MONTH => &label_index_map.this_month,
+        RANKINGS_PERIOD_LAST_WEEK => &label_index_map.last_week,
+        RANKINGS_PERIOD_THIS_WEEK => &label_index_map.this_week,
+        RANKINGS_PERIOD_DAY_B4_YESTERDAY => &label_index_map.day_b4_yesterday,
+        RANKINGS_PERIOD_YESTERDAY => &label_index_map.yesterday,
+        RANKINGS_PERIOD_TODAY => &label_index_map.today,
+        _ => {
+            panic!("Unexpected rankings period {}", period)
+        }
+    }
+}
+
+#[inline]
+fn get_location_index_map(
+    cache: &CacheReader,
+    period: u8,
+) -> &IntHashMap<LocationId, LocationPeriodIds> {
+    let location_index_map = cache.get_location_index_map();
+    match period {
+        RANKINGS_PERIOD_LAST_MONTH => &location_index_map.last_month,
+        RANKINGS_PERIOD_THIS_MONTH => &location_index_map.this_month,
+        RANKINGS_PERIOD_LAST_WEEK => &location_index_map.last_week,
+        RANKINGS_PERIOD_THIS_WEEK => &location_index_map.this_week,
+        RANKINGS_PERIOD_DAY_B4_YESTERDAY => &location_index_map.day_b4_yesterday,
+        RANKINGS_PERIOD_YESTERDAY => &location_index_map.yesterday,
+        RANKINGS_PERIOD_TODAY => &location_index_map.today,
+        _ => {
+            panic!("Unexpected rankings period {}", period)
+        }
+    }
+}
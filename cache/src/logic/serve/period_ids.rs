@@ -0,0 +1,173 @@
+use std::mem::transmute;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use common::model::timezone::GLOBAL_ROLLOVER_LOCAL_TIME_IN_MINUTES;
+use common::model::timezone::GLOBAL_ROLLOVER_TIMEZONE;
+use common::model::timezone::NUM_TIMEZONES;
+use common::model::timezone::NUM_TIMEZONES_WITH_GLOBAL_CATEGORY;
+use common::model::timezone::UTC_OFFSETS_IN_MINUTES;
+
+use super::super::super::cache::cache_reader::CacheReader;
+use super::super::super::cache::time_period_ids::TimePeriodIds;
+
+const SECONDS_IN_A_DAY: i64 = 24 * 60 * 60;
+
+pub const PERIOD_STATUS_STABLE: u8 = 0;
+pub const PERIOD_STATUS_ROLLING_OVER: u8 = 1;
+
+const NUM_PERIOD_IDS_PER_TIMEZONE: u32 = 11;
+
+const PERIOD_IDS_RESPONSE_SIZE: u32 =
+// space for the number of timezones (including global)
+    1 +
+        // space for the current server time
+        8 +
+        NUM_TIMEZONES_WITH_GLOBAL_CATEGORY * (
+            // space for the period ids
+            NUM_PERIOD_IDS_PER_TIMEZONE * 4 +
+                // space for the rollover status
+                1 +
+                // space for the next transition time
+                8
+        );
+
+/**
+ * Returns the full TimePeriodIds table, so that clients can build requests with valid period
+ * ids and know when to refresh them.
+ *
+ * Response layout (all numbers are Big Endian):
+ *
+ *  1 byte  - number of timezones in the response (38 + global)
+ *  8 bytes - current server time, in seconds since the Unix epoch
+ *
+ *  Per timezone (by timezone index, with the global category last):
+ *      11 * 4 bytes - period ids, in order: day before yesterday, yesterday, today, tomorrow,
+ *                     day after tomorrow, last week, this week, next week, last month,
+ *                     this month, next month
+ *      1 byte       - rollover status (PERIOD_STATUS_*)
+ *      8 bytes      - time of the next transition, in seconds since the Unix epoch
+ */
+pub fn get_time_period_ids(
+    cache: &CacheReader,
+) -> Vec<u8> {
+    let now_seconds = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let time_period_ids: &TimePeriodIds = cache.get_time_period_ids();
+    let modification_flags: &[bool; NUM_TIMEZONES as usize] = cache.get_time_zone_modification_flags();
+
+    let mut response: Vec<u8> = Vec::with_capacity(PERIOD_IDS_RESPONSE_SIZE as usize);
+    response.push(NUM_TIMEZONES_WITH_GLOBAL_CATEGORY as u8);
+    append_u64(&mut response, now_seconds);
+
+    let mut any_timezone_rolling_over = false;
+    for timezone_index in 0..NUM_TIMEZONES as usize {
+        let rolling_over = modification_flags[timezone_index];
+        any_timezone_rolling_over |= rolling_over;
+
+        append_period_ids(&mut response, time_period_ids, timezone_index);
+        append_status(&mut response, rolling_over);
+        append_u64(&mut response, get_next_transition_seconds(timezone_index, now_seconds));
+    }
+
+    // Global category is rolling over while any of its timezones are
+    let global_index = NUM_TIMEZONES as usize;
+    append_period_ids(&mut response, time_period_ids, global_index);
+    append_status(&mut response, any_timezone_rolling_over);
+    append_u64(&mut response, get_next_global_transition_seconds(now_seconds));
+
+    return response;
+}
+
+/**
+ * Time (in seconds since the Unix epoch) of the next local midnight in a given timezone.
+ */
+pub fn get_next_transition_seconds(
+    timezone_index: usize,
+    now_seconds: u64,
+) -> u64 {
+    let offset_seconds = UTC_OFFSETS_IN_MINUTES[timezone_index] as i64 * 60;
+
+    return get_next_local_time_seconds(offset_seconds, 0, now_seconds);
+}
+
+/**
+ * Time (in seconds since the Unix epoch) of the next global (across all timezones) transition.
+ */
+pub fn get_next_global_transition_seconds(
+    now_seconds: u64,
+) -> u64 {
+    let offset_seconds = UTC_OFFSETS_IN_MINUTES[GLOBAL_ROLLOVER_TIMEZONE] as i64 * 60;
+    let local_time_seconds = GLOBAL_ROLLOVER_LOCAL_TIME_IN_MINUTES as i64 * 60;
+
+    return get_next_local_time_seconds(offset_seconds, local_time_seconds, now_seconds);
+}
+
+#[inline]
+fn get_next_local_time_seconds(
+    offset_seconds: i64,
+    local_time_seconds: i64,
+    now_seconds: u64,
+) -> u64 {
+    let local_now_seconds = now_seconds as i64 + offset_seconds;
+    let local_day_start_seconds = local_now_seconds - local_now_seconds % SECONDS_IN_A_DAY;
+
+    let mut next_local_seconds = local_day_start_seconds + local_time_seconds;
+    if next_local_seconds <= local_now_seconds {
+        next_local_seconds += SECONDS_IN_A_DAY;
+    }
+
+    return (next_local_seconds - offset_seconds) as u64;
+}
+
+#[inline]
+fn append_period_ids(
+    response: &mut Vec<u8>,
+    time_period_ids: &TimePeriodIds,
+    timezone_index: usize,
+) {
+    append_u32(response, time_period_ids.day_b4_yesterday[timezone_index]);
+    append_u32(response, time_period_ids.yesterday[timezone_index]);
+    append_u32(response, time_period_ids.today[timezone_index]);
+    append_u32(response, time_period_ids.tomorrow[timezone_index]);
+    append_u32(response, time_period_ids.day_after_tomorrow[timezone_index]);
+    append_u32(response, time_period_ids.last_week[timezone_index]);
+    append_u32(response, time_period_ids.this_week[timezone_index]);
+    append_u32(response, time_period_ids.next_week[timezone_index]);
+    append_u32(response, time_period_ids.last_month[timezone_index]);
+    append_u32(response, time_period_ids.this_month[timezone_index]);
+    append_u32(response, time_period_ids.next_month[timezone_index]);
+}
+
+#[inline]
+fn append_status(
+    response: &mut Vec<u8>,
+    rolling_over: bool,
+) {
+    if rolling_over {
+        response.push(PERIOD_STATUS_ROLLING_OVER);
+    } else {
+        response.push(PERIOD_STATUS_STABLE);
+    }
+}
+
+#[inline]
+fn append_u32(
+    response: &mut Vec<u8>,
+    value: u32,
+) {
+    let value_bytes: [u8; 4] = unsafe {
+        transmute(value.to_be())
+    };
+    response.extend_from_slice(&value_bytes);
+}
+
+#[inline]
+fn append_u64(
+    response: &mut Vec<u8>,
+    value: u64,
+) {
+    let value_bytes: [u8; 8] = unsafe {
+        transmute(value.to_be())
+    };
+    response.extend_from_slice(&value_bytes);
+}
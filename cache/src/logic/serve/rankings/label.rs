@@ -12,103 +12,118 @@ use super::super::super::super::cache::model::VoteCount;
 use super::super::super::super::data::byte_counts::ByteCounts;
 use super::super::super::super::server::codes;
 
-// NOTE: max page size must fin into u16
-const PAGE_SIZE: u32 = 1024;
+use super::super::page_header::PAGE_HEADER_SIZE;
+use super::super::page_header::get_rankings_page_header;
+use super::super::page_header::PAGE_SIZE;
 
 const INITIAL_RESPONSE_VECTOR_SIZE_8_POLL_BYTES: u32 =
-// space for the leading header byte
+    // space for the page header
+    PAGE_HEADER_SIZE +
+    // space for the leading header byte
     1 +
-        // space for label cache index (if any
-        4 +
-        PAGE_SIZE +
-        // space for poll ids & vote counts
-        PAGE_SIZE * (8 + 3) +
-        // space for the byte counts
-        PAGE_SIZE / 4 +
-        // space for trailing size bytes
-        2;
+    // space for label cache index (if any
+    4 +
+    PAGE_SIZE +
+    // space for poll ids & vote counts
+    PAGE_SIZE * (8 + 3) +
+    // space for the byte counts
+    PAGE_SIZE / 4 +
+    // space for trailing size bytes
+    2;
 
 const INITIAL_RESPONSE_VECTOR_SIZE_7_POLL_BYTES: u32 =
-// space for the leading header byte
+    // space for the page header
+    PAGE_HEADER_SIZE +
+    // space for the leading header byte
     1 +
-        // space for label cache index (if any
-        4 +
-        PAGE_SIZE +
-        // space for poll ids & vote counts
-        PAGE_SIZE * (7 + 3) +
-        // space for the byte counts
-        PAGE_SIZE / 4 +
-        // space for trailing size bytes
-        2;
+    // space for label cache index (if any
+    4 +
+    PAGE_SIZE +
+    // space for poll ids & vote counts
+    PAGE_SIZE * (7 + 3) +
+    // space for the byte counts
+    PAGE_SIZE / 4 +
+    // space for trailing size bytes
+    2;
 
 const INITIAL_RESPONSE_VECTOR_SIZE_6_POLL_BYTES: u32 =
-// space for the leading header byte
+    // space for the page header
+    PAGE_HEADER_SIZE +
+    // space for the leading header byte
     1 +
-        // space for label cache index (if any
-        4 +
-        PAGE_SIZE +
-        // space for poll ids & vote counts
-        PAGE_SIZE * (6 + 3) +
-        // space for the byte counts
-        PAGE_SIZE / 4 +
-        // space for trailing size bytes
-        2;
+    // space for label cache index (if any
+    4 +
+    PAGE_SIZE +
+    // space for poll ids & vote counts
+    PAGE_SIZE * (6 + 3) +
+    // space for the byte counts
+    PAGE_SIZE / 4 +
+    // space for trailing size bytes
+    2;
 
 const INITIAL_RESPONSE_VECTOR_SIZE_5_POLL_BYTES: u32 =
-// space for the leading header byte
+    // space for the page header
+    PAGE_HEADER_SIZE +
+    // space for the leading header byte
     1 +
-        // space for label cache index (if any
-        4 +
-        PAGE_SIZE +
-        // space for poll ids & vote counts
-        PAGE_SIZE * (5 + 3) +
-        // space for the byte counts
-        PAGE_SIZE / 4 +
-        // space for trailing size bytes
-        2;
+    // space for label cache index (if any
+    4 +
+    PAGE_SIZE +
+    // space for poll ids & vote counts
+    PAGE_SIZE * (5 + 3) +
+    // space for the byte counts
+    PAGE_SIZE / 4 +
+    // space for trailing size bytes
+    2;
 
 const INITIAL_RESPONSE_VECTOR_SIZE_4_POLL_BYTES: u32 =
-// space for the leading header byte
+    // space for the page header
+    PAGE_HEADER_SIZE +
+    // space for the leading header byte
     1 +
-        // space for label cache index (if any
-        4 +
-        PAGE_SIZE +
-        // space for poll ids & vote counts
-        PAGE_SIZE * (4 + 3) +
-        // space for the byte counts
-        PAGE_SIZE / 4 +
-        // space for trailing size bytes
-        2;
+    // space for label cache index (if any
+    4 +
+    PAGE_SIZE +
+    // space for poll ids & vote counts
+    PAGE_SIZE * (4 + 3) +
+    // space for the byte counts
+    PAGE_SIZE / 4 +
+    // space for trailing size bytes
+    2;
 
 const INITIAL_RESPONSE_VECTOR_SIZE_3_POLL_BYTES: u32 =
-// space for the leading header byte
+    // space for the page header
+    PAGE_HEADER_SIZE +
+    // space for the leading header byte
     1 +
-        // space for label cache index (if any
-        4 +
-        // space for po ids & vote counts
-        PAGE_SIZE * (3 + 3)
-        // space for the byte counts
-        + PAGE_SIZE / 4 +
-        // space for trailing size bytes
-        2;
+    // space for label cache index (if any
+    4 +
+    // space for po ids & vote counts
+    PAGE_SIZE * (3 + 3)
+    // space for the byte counts
+    + PAGE_SIZE / 4 +
+    // space for trailing size bytes
+    2;
 
 const INITIAL_RESPONSE_VECTOR_SIZE_2_POLL_BYTES: u32 =
-// space for the leading header byte
+    // space for the page header
+    PAGE_HEADER_SIZE +
+    // space for the leading header byte
     1 +
-        // space for label cache index (if any
-        4 +
-        // space for label ids & vote counts
-        PAGE_SIZE * (2 + 3)
-        // space for the byte counts
-        + PAGE_SIZE / 4 +
-        // space for trailing size bytes
-        2;
+    // space for label cache index (if any
+    4 +
+    // space for label ids & vote counts
+    PAGE_SIZE * (2 + 3)
+    // space for the byte counts
+    + PAGE_SIZE / 4 +
+    // space for trailing size bytes
+    2;
 
 pub fn get_todays_label_rankings_by_global_id(
     vc_day_id: DayId,
     block_index: u32,
     global_label_id: LabelId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     return get_label_rankings_by_global_id(
         cache.get_label_cache_period_ids().todays_vc_day_id,
@@ -118,6 +133,7 @@ pub fn get_todays_label_rankings_by_global_id(
         global_label_id,
         block_index,
         cache.get_poll_id_byte_counts().today[38],
+        cache.get_data_version(),
     );
 }
 
@@ -125,7 +141,7 @@ pub fn get_todays_label_rankings_by_cache_index(
     vc_day_id: DayId,
     block_index: u32,
     label_cache_index: LabelCacheIndex,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     return get_label_rankings_by_cache_index(
         cache.get_label_cache_period_ids().todays_vc_day_id,
@@ -134,6 +150,7 @@ pub fn get_todays_label_rankings_by_cache_index(
         label_cache_index,
         block_index,
         cache.get_poll_id_byte_counts().today[38],
+        cache.get_data_version(),
     );
 }
 
@@ -141,7 +158,7 @@ pub fn get_yesterdays_label_rankings_by_global_id(
     vc_day_id: DayId,
     block_index: u32,
     global_label_id: LabelId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     return get_label_rankings_by_global_id(
         cache.get_label_cache_period_ids().yesterdays_vc_day_id,
@@ -151,6 +168,7 @@ pub fn get_yesterdays_label_rankings_by_global_id(
         global_label_id,
         block_index,
         cache.get_poll_id_byte_counts().yesterday[38],
+        cache.get_data_version(),
     );
 }
 
@@ -158,7 +176,7 @@ pub fn get_yesterdays_label_rankings_by_cache_index(
     vc_day_id: DayId,
     block_index: u32,
     label_cache_index: LabelCacheIndex,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     return get_label_rankings_by_cache_index(
         cache.get_label_cache_period_ids().yesterdays_vc_day_id,
@@ -167,6 +185,7 @@ pub fn get_yesterdays_label_rankings_by_cache_index(
         label_cache_index,
         block_index,
         cache.get_poll_id_byte_counts().yesterday[38],
+        cache.get_data_version(),
     );
 }
 
@@ -174,7 +193,7 @@ pub fn get_day_b4_yesterdays_label_rankings_by_global_id(
     vc_day_id: DayId,
     block_index: u32,
     global_label_id: LabelId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     return get_label_rankings_by_global_id(
         cache.get_label_cache_period_ids().day_b4_yesterdays_vc_day_id,
@@ -184,6 +203,7 @@ pub fn get_day_b4_yesterdays_label_rankings_by_global_id(
         global_label_id,
         block_index,
         cache.get_poll_id_byte_counts().day_b4_yesterday[38],
+        cache.get_data_version(),
     );
 }
 
@@ -191,7 +211,7 @@ pub fn get_day_b4_yesterdays_label_rankings_by_cache_index(
     vc_day_id: DayId,
     block_index: u32,
     label_cache_index: LabelCacheIndex,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     return get_label_rankings_by_cache_index(
         cache.get_label_cache_period_ids().day_b4_yesterdays_vc_day_id,
@@ -200,6 +220,7 @@ pub fn get_day_b4_yesterdays_label_rankings_by_cache_index(
         label_cache_index,
         block_index,
         cache.get_poll_id_byte_counts().day_b4_yesterday[38],
+        cache.get_data_version(),
     );
 }
 
@@ -207,7 +228,7 @@ pub fn get_this_weeks_label_rankings_by_global_id(
     vc_week_id: WeekId,
     block_index: u32,
     global_label_id: LabelId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     return get_label_rankings_by_global_id(
         cache.get_label_cache_period_ids().this_weeks_vc_week_id,
@@ -217,6 +238,7 @@ pub fn get_this_weeks_label_rankings_by_global_id(
         global_label_id,
         block_index,
         cache.get_poll_id_byte_counts().this_week[38],
+        cache.get_data_version(),
     );
 }
 
@@ -224,7 +246,7 @@ pub fn get_this_weeks_label_rankings_by_cache_index(
     vc_week_id: WeekId,
     block_index: u32,
     label_cache_index: LabelCacheIndex,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     return get_label_rankings_by_cache_index(
         cache.get_label_cache_period_ids().this_weeks_vc_week_id,
@@ -233,6 +255,7 @@ pub fn get_this_weeks_label_rankings_by_cache_index(
         label_cache_index,
         block_index,
         cache.get_poll_id_byte_counts().this_week[38],
+        cache.get_data_version(),
     );
 }
 
@@ -240,7 +263,7 @@ pub fn get_last_weeks_label_rankings_by_global_id(
     vc_week_id: WeekId,
     block_index: u32,
     global_label_id: LabelId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     return get_label_rankings_by_global_id(
         cache.get_label_cache_period_ids().last_weeks_vc_week_id,
@@ -250,6 +273,7 @@ pub fn get_last_weeks_label_rankings_by_global_id(
         global_label_id,
         block_index,
         cache.get_poll_id_byte_counts().last_week[38],
+        cache.get_data_version(),
     );
 }
 
@@ -257,7 +281,7 @@ pub fn get_last_weeks_label_rankings_by_cache_index(
     vc_week_id: WeekId,
     block_index: u32,
     label_cache_index: LabelCacheIndex,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     return get_label_rankings_by_cache_index(
         cache.get_label_cache_period_ids().last_weeks_vc_week_id,
@@ -266,6 +290,7 @@ pub fn get_last_weeks_label_rankings_by_cache_index(
         label_cache_index,
         block_index,
         cache.get_poll_id_byte_counts().last_week[38],
+        cache.get_data_version(),
     );
 }
 
@@ -273,7 +298,7 @@ pub fn get_this_months_label_rankings_by_global_id(
     vc_month_id: MonthId,
     block_index: u32,
     global_label_id: LabelId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     return get_label_rankings_by_global_id(
         cache.get_label_cache_period_ids().this_months_vc_month_id,
@@ -283,6 +308,7 @@ pub fn get_this_months_label_rankings_by_global_id(
         global_label_id,
         block_index,
         cache.get_poll_id_byte_counts().this_month[38],
+        cache.get_data_version(),
     );
 }
 
@@ -290,7 +316,7 @@ pub fn get_this_months_label_rankings_by_cache_index(
     vc_month_id: MonthId,
     block_index: u32,
     label_cache_index: LabelCacheIndex,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     return get_label_rankings_by_cache_index(
         cache.get_label_cache_period_ids().this_months_vc_month_id,
@@ -299,6 +325,7 @@ pub fn get_this_months_label_rankings_by_cache_index(
         label_cache_index,
         block_index,
         cache.get_poll_id_byte_counts().this_month[38],
+        cache.get_data_version(),
     );
 }
 
@@ -306,7 +333,7 @@ pub fn get_last_months_label_rankings_by_global_id(
     vc_month_id: MonthId,
     block_index: u32,
     global_label_id: LabelId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     return get_label_rankings_by_global_id(
         cache.get_label_cache_period_ids().last_months_vc_month_id,
@@ -316,6 +343,7 @@ pub fn get_last_months_label_rankings_by_global_id(
         global_label_id,
         block_index,
         cache.get_poll_id_byte_counts().last_month[38],
+        cache.get_data_version(),
     );
 }
 
@@ -323,7 +351,7 @@ pub fn get_last_months_label_rankings_by_cache_index(
     vc_month_id: MonthId,
     block_index: u32,
     label_cache_index: LabelCacheIndex,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     return get_label_rankings_by_cache_index(
         cache.get_label_cache_period_ids().last_months_vc_month_id,
@@ -332,6 +360,7 @@ pub fn get_last_months_label_rankings_by_cache_index(
         label_cache_index,
         block_index,
         cache.get_poll_id_byte_counts().last_month[38],
+        cache.get_data_version(),
     );
 }
 
@@ -343,9 +372,10 @@ fn get_label_rankings_by_global_id(
     global_label_id: LabelId,
     block_index: u32,
     max_poll_number_bytes: u8,
+    data_version: u32,
 ) -> Vec<u8> {
     if current_period_id != expected_period_id {
-        return codes::INVALID_PERIOD_ID_RESPONSE.to_vec();
+        return codes::get_invalid_period_id_response(current_period_id);
     }
 
     let first_record_index = PAGE_SIZE * block_index;
@@ -357,7 +387,8 @@ fn get_label_rankings_by_global_id(
         Some(label_cache_index) => {
             return get_label_rankings_with_label_cache_index(
                 first_record_index, *label_cache_index,
-                given_period_label_poll_rankings, max_poll_number_bytes);
+                given_period_label_poll_rankings, max_poll_number_bytes,
+                current_period_id, data_version);
         }
     }
 }
@@ -369,9 +400,10 @@ fn get_label_rankings_by_cache_index(
     label_cache_index: LabelCacheIndex,
     block_index: u32,
     max_poll_number_bytes: u8,
+    data_version: u32,
 ) -> Vec<u8> {
     if current_period_id != expected_period_id {
-        return codes::INVALID_PERIOD_ID_RESPONSE.to_vec();
+        return codes::get_invalid_period_id_response(current_period_id);
     }
 
     let first_record_index = PAGE_SIZE * block_index;
@@ -383,7 +415,8 @@ fn get_label_rankings_by_cache_index(
         Some(_) => {
             return get_label_rankings(
                 first_record_index, label_cache_index,
-                vote_counts_by_label_index, max_poll_number_bytes);
+                vote_counts_by_label_index, max_poll_number_bytes,
+                current_period_id, data_version);
         }
     }
 }
@@ -394,6 +427,8 @@ fn get_label_rankings_with_label_cache_index(
     label_cache_index: LabelCacheIndex,
     vote_counts_by_label_index: &Vec<Vec<VoteCount>>,
     max_poll_number_bytes: u8,
+    period_id: u32,
+    data_version: u32,
 ) -> Vec<u8> {
     let vote_counts_for_label: &Vec<VoteCount> = vote_counts_by_label_index.get(label_cache_index as usize).unwrap();
     let label_cache_index_bytes: [u8; 4] = unsafe {
@@ -401,9 +436,12 @@ fn get_label_rankings_with_label_cache_index(
     };
 
 
+    let page_header = get_rankings_page_header(vote_counts_for_label.len(), period_id, data_version);
+
     match max_poll_number_bytes {
         3 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_3_POLL_BYTES as usize);
+            response.extend_from_slice(&page_header);
             response.push(0b00000011);
             response.extend_from_slice(&label_cache_index_bytes);
 
@@ -411,6 +449,7 @@ fn get_label_rankings_with_label_cache_index(
         }
         4 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_4_POLL_BYTES as usize);
+            response.extend_from_slice(&page_header);
             response.push(0b00000100);
             response.extend_from_slice(&label_cache_index_bytes);
 
@@ -418,6 +457,7 @@ fn get_label_rankings_with_label_cache_index(
         }
         5 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_5_POLL_BYTES as usize);
+            response.extend_from_slice(&page_header);
             response.push(0b00000101);
             response.extend_from_slice(&label_cache_index_bytes);
 
@@ -425,6 +465,7 @@ fn get_label_rankings_with_label_cache_index(
         }
         6 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_6_POLL_BYTES as usize);
+            response.extend_from_slice(&page_header);
             response.push(0b00000110);
             response.extend_from_slice(&label_cache_index_bytes);
 
@@ -432,6 +473,7 @@ fn get_label_rankings_with_label_cache_index(
         }
         7 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_7_POLL_BYTES as usize);
+            response.extend_from_slice(&page_header);
             response.push(0b00000111);
             response.extend_from_slice(&label_cache_index_bytes);
 
@@ -439,6 +481,7 @@ fn get_label_rankings_with_label_cache_index(
         }
         8 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_8_POLL_BYTES as usize);
+            response.extend_from_slice(&page_header);
             response.push(0b00000000);
             response.extend_from_slice(&label_cache_index_bytes);
 
@@ -446,6 +489,7 @@ fn get_label_rankings_with_label_cache_index(
         }
         2 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_2_POLL_BYTES as usize);
+            response.extend_from_slice(&page_header);
             response.push(0b00000010);
             response.extend_from_slice(&label_cache_index_bytes);
 
@@ -464,42 +508,53 @@ fn get_label_rankings(
     label_cache_index: LabelCacheIndex,
     given_period_label_poll_rankings: &Vec<Vec<VoteCount>>,
     max_poll_number_bytes: u8,
+    period_id: u32,
+    data_version: u32,
 ) -> Vec<u8> {
     let vote_counts_for_label: &Vec<VoteCount> = given_period_label_poll_rankings.get(label_cache_index as usize).unwrap();
 
+    let page_header = get_rankings_page_header(vote_counts_for_label.len(), period_id, data_version);
+
     match max_poll_number_bytes {
         3 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_3_POLL_BYTES as usize);
+            response.extend_from_slice(&page_header);
             response.push(0b00000011);
             return get_3_byte_recent_polls(vote_counts_for_label, first_record_index as usize, response);
         }
         4 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_4_POLL_BYTES as usize);
+            response.extend_from_slice(&page_header);
             response.push(0b00000100);
             return get_4_byte_recent_polls(vote_counts_for_label, first_record_index as usize, response);
         }
         5 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_5_POLL_BYTES as usize);
+            response.extend_from_slice(&page_header);
             response.push(0b00000101);
             return get_5_byte_recent_polls(vote_counts_for_label, first_record_index as usize, response);
         }
         6 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_6_POLL_BYTES as usize);
+            response.extend_from_slice(&page_header);
             response.push(0b00000110);
             return get_6_byte_recent_polls(vote_counts_for_label, first_record_index as usize, response);
         }
         7 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_7_POLL_BYTES as usize);
+            response.extend_from_slice(&page_header);
             response.push(0b00000111);
             return get_7_byte_recent_polls(vote_counts_for_label, first_record_index as usize, response);
         }
         8 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_8_POLL_BYTES as usize);
+            response.extend_from_slice(&page_header);
             response.push(0b00000000);
             return get_8_byte_recent_polls(vote_counts_for_label, first_record_index as usize, response);
         }
         2 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_2_POLL_BYTES as usize);
+            response.extend_from_slice(&page_header);
             response.push(0b00000010);
             return get_2_byte_recent_polls(vote_counts_for_label, first_record_index as usize, response);
         }
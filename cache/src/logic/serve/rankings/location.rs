@@ -15,6 +15,9 @@ use super::super::super::super::cache::model::LocationPeriodIds;
 use super::super::super::super::cache::model::LocationPollRankings;
 use super::super::super::super::server::codes;
 
+use super::super::page_header::get_rankings_page_header;
+use super::super::page_header::PAGE_SIZE;
+
 use super::location_and_loc_label::INITIAL_RESPONSE_VECTOR_SIZE_2_POLL_BYTES;
 use super::location_and_loc_label::INITIAL_RESPONSE_VECTOR_SIZE_3_POLL_BYTES;
 use super::location_and_loc_label::INITIAL_RESPONSE_VECTOR_SIZE_4_POLL_BYTES;
@@ -30,15 +33,12 @@ use super::location_and_loc_label::get_6_byte_recent_polls;
 use super::location_and_loc_label::get_7_byte_recent_polls;
 use super::location_and_loc_label::get_8_byte_recent_polls;
 
-// NOTE: max page size must fin into u16
-const PAGE_SIZE: u32 = 1024;
-
 pub fn get_todays_location_rankings_by_global_id(
     vc_day_id: DayId,
     timezone_id: TimezoneId,
     block_index: u32,
     global_location_id: LocationId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -56,6 +56,7 @@ pub fn get_todays_location_rankings_by_global_id(
         global_location_id,
         block_index,
         cache.get_poll_id_byte_counts().today[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -64,7 +65,7 @@ pub fn get_todays_location_rankings_by_cache_index(
     timezone_id: TimezoneId,
     block_index: u32,
     location_cache_index: LocationCacheIndex,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -81,6 +82,7 @@ pub fn get_todays_location_rankings_by_cache_index(
         location_cache_index,
         block_index,
         cache.get_poll_id_byte_counts().today[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -89,7 +91,7 @@ pub fn get_yesterdays_location_rankings_by_global_id(
     timezone_id: TimezoneId,
     block_index: u32,
     global_location_id: LocationId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -107,6 +109,7 @@ pub fn get_yesterdays_location_rankings_by_global_id(
         global_location_id,
         block_index,
         cache.get_poll_id_byte_counts().yesterday[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -115,7 +118,7 @@ pub fn get_yesterdays_location_rankings_by_cache_index(
     timezone_id: TimezoneId,
     block_index: u32,
     location_cache_index: LocationCacheIndex,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -132,6 +135,7 @@ pub fn get_yesterdays_location_rankings_by_cache_index(
         location_cache_index,
         block_index,
         cache.get_poll_id_byte_counts().yesterday[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -140,7 +144,7 @@ pub fn get_day_b4_yesterdays_location_rankings_by_global_id(
     timezone_id: TimezoneId,
     block_index: u32,
     global_location_id: LocationId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -158,6 +162,7 @@ pub fn get_day_b4_yesterdays_location_rankings_by_global_id(
         global_location_id,
         block_index,
         cache.get_poll_id_byte_counts().day_b4_yesterday[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -166,7 +171,7 @@ pub fn get_day_b4_yesterdays_location_rankings_by_cache_index(
     timezone_id: TimezoneId,
     block_index: u32,
     location_cache_index: LocationCacheIndex,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -183,6 +188,7 @@ pub fn get_day_b4_yesterdays_location_rankings_by_cache_index(
         location_cache_index,
         block_index,
         cache.get_poll_id_byte_counts().day_b4_yesterday[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -191,7 +197,7 @@ pub fn get_this_weeks_location_rankings_by_global_id(
     timezone_id: TimezoneId,
     block_index: u32,
     global_location_id: LocationId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -209,6 +215,7 @@ pub fn get_this_weeks_location_rankings_by_global_id(
         global_location_id,
         block_index,
         cache.get_poll_id_byte_counts().this_week[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -217,7 +224,7 @@ pub fn get_this_weeks_location_rankings_by_cache_index(
     timezone_id: TimezoneId,
     block_index: u32,
     location_cache_index: LocationCacheIndex,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -234,6 +241,7 @@ pub fn get_this_weeks_location_rankings_by_cache_index(
         location_cache_index,
         block_index,
         cache.get_poll_id_byte_counts().this_week[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -242,7 +250,7 @@ pub fn get_last_weeks_location_rankings_by_global_id(
     timezone_id: TimezoneId,
     block_index: u32,
     global_location_id: LocationId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -260,6 +268,7 @@ pub fn get_last_weeks_location_rankings_by_global_id(
         global_location_id,
         block_index,
         cache.get_poll_id_byte_counts().last_week[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -268,7 +277,7 @@ pub fn get_last_weeks_location_rankings_by_cache_index(
     timezone_id: TimezoneId,
     block_index: u32,
     location_cache_index: LocationCacheIndex,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -285,6 +294,7 @@ pub fn get_last_weeks_location_rankings_by_cache_index(
         location_cache_index,
         block_index,
         cache.get_poll_id_byte_counts().last_week[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -293,7 +303,7 @@ pub fn get_this_months_location_rankings_by_global_id(
     timezone_id: TimezoneId,
     block_index: u32,
     global_location_id: LocationId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -311,6 +321,7 @@ pub fn get_this_months_location_rankings_by_global_id(
         global_location_id,
         block_index,
         cache.get_poll_id_byte_counts().this_month[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -319,7 +330,7 @@ pub fn get_this_months_location_rankings_by_cache_index(
     timezone_id: TimezoneId,
     block_index: u32,
     location_cache_index: LocationCacheIndex,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -336,6 +347,7 @@ pub fn get_this_months_location_rankings_by_cache_index(
         location_cache_index,
         block_index,
         cache.get_poll_id_byte_counts().this_month[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -344,7 +356,7 @@ pub fn get_last_months_location_rankings_by_global_id(
     timezone_id: TimezoneId,
     block_index: u32,
     global_location_id: LocationId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -362,6 +374,7 @@ pub fn get_last_months_location_rankings_by_global_id(
         global_location_id,
         block_index,
         cache.get_poll_id_byte_counts().last_month[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -370,7 +383,7 @@ pub fn get_last_months_location_rankings_by_cache_index(
     timezone_id: TimezoneId,
     block_index: u32,
     location_cache_index: LocationCacheIndex,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -387,6 +400,7 @@ pub fn get_last_months_location_rankings_by_cache_index(
         location_cache_index,
         block_index,
         cache.get_poll_id_byte_counts().last_month[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -399,9 +413,10 @@ fn get_location_rankings_by_global_id(
     global_location_id: LocationId,
     block_index: u32,
     max_poll_number_bytes: u8,
+    data_version: u32,
 ) -> Vec<u8> {
     if current_period_id != expected_period_id {
-        return codes::INVALID_PERIOD_ID_RESPONSE.to_vec();
+        return codes::get_invalid_period_id_response(current_period_id);
     }
 
     let location_period_ids: &LocationPeriodIds = match location_index_map.get(&global_location_id) {
@@ -420,7 +435,8 @@ fn get_location_rankings_by_global_id(
 
     return get_location_rankings_with_location_cache_index(
         first_record_index as usize, location_cache_index,
-        &location_poll_rankings, max_poll_number_bytes);
+        &location_poll_rankings, max_poll_number_bytes,
+        current_period_id, data_version);
 }
 
 fn get_location_rankings_by_cache_index(
@@ -431,9 +447,10 @@ fn get_location_rankings_by_cache_index(
     location_cache_index: LocationCacheIndex,
     block_index: u32,
     max_poll_number_bytes: u8,
+    data_version: u32,
 ) -> Vec<u8> {
     if current_period_id != expected_period_id {
-        return codes::INVALID_PERIOD_ID_RESPONSE.to_vec();
+        return codes::get_invalid_period_id_response(current_period_id);
     }
 
     let location_poll_rankings: &LocationPollRankings
@@ -449,7 +466,8 @@ fn get_location_rankings_by_cache_index(
     let first_record_index = PAGE_SIZE * block_index;
 
     return get_location_rankings(
-        first_record_index as usize, &location_poll_rankings, max_poll_number_bytes);
+        first_record_index as usize, &location_poll_rankings, max_poll_number_bytes,
+        current_period_id, data_version);
 }
 
 #[inline]
@@ -458,15 +476,20 @@ fn get_location_rankings_with_location_cache_index(
     location_cache_index: LocationCacheIndex,
     location_poll_rankings: &LocationPollRankings,
     max_poll_number_bytes: u8,
+    period_id: u32,
+    data_version: u32,
 ) -> Vec<u8> {
     let vote_counts_for_location = &location_poll_rankings.location;
     let location_cache_index_bytes: [u8; 4] = unsafe {
         transmute(location_cache_index)
     };
 
+    let page_header = get_rankings_page_header(vote_counts_for_location.len(), period_id, data_version);
+
     match max_poll_number_bytes {
         3 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_3_POLL_BYTES as usize);
+            response.extend_from_slice(&page_header);
             response.push(0b00000011);
             response.extend_from_slice(&location_cache_index_bytes);
 
@@ -474,6 +497,7 @@ fn get_location_rankings_with_location_cache_index(
         }
         4 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_4_POLL_BYTES as usize);
+            response.extend_from_slice(&page_header);
             response.push(0b00000100);
             response.extend_from_slice(&location_cache_index_bytes);
 
@@ -481,6 +505,7 @@ fn get_location_rankings_with_location_cache_index(
         }
         5 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_5_POLL_BYTES as usize);
+            response.extend_from_slice(&page_header);
             response.push(0b00000101);
             response.extend_from_slice(&location_cache_index_bytes);
 
@@ -488,6 +513,7 @@ fn get_location_rankings_with_location_cache_index(
         }
         6 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_6_POLL_BYTES as usize);
+            response.extend_from_slice(&page_header);
             response.push(0b00000110);
             response.extend_from_slice(&location_cache_index_bytes);
 
@@ -495,6 +521,7 @@ fn get_location_rankings_with_location_cache_index(
         }
         7 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_7_POLL_BYTES as usize);
+            response.extend_from_slice(&page_header);
             response.push(0b00000111);
             response.extend_from_slice(&location_cache_index_bytes);
 
@@ -502,6 +529,7 @@ fn get_location_rankings_with_location_cache_index(
         }
         8 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_8_POLL_BYTES as usize);
+            response.extend_from_slice(&page_header);
             response.push(0b00000000);
             response.extend_from_slice(&location_cache_index_bytes);
 
@@ -509,6 +537,7 @@ fn get_location_rankings_with_location_cache_index(
         }
         2 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_2_POLL_BYTES as usize);
+            response.extend_from_slice(&page_header);
             response.push(0b00000010);
             response.extend_from_slice(&location_cache_index_bytes);
 
@@ -526,48 +555,59 @@ fn get_location_rankings(
     first_record_index: usize,
     location_poll_rankings: &LocationPollRankings,
     max_poll_number_bytes: u8,
+    period_id: u32,
+    data_version: u32,
 ) -> Vec<u8> {
     let vote_counts_for_location = &location_poll_rankings.location;
 
+    let page_header = get_rankings_page_header(vote_counts_for_location.len(), period_id, data_version);
+
     match max_poll_number_bytes {
         3 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_3_POLL_BYTES as usize);
+            response.extend_from_slice(&page_header);
             response.push(0b00000011);
 
             return get_3_byte_recent_polls(vote_counts_for_location, first_record_index as usize, response);
         }
         4 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_4_POLL_BYTES as usize);
+            response.extend_from_slice(&page_header);
             response.push(0b00000100);
 
             return get_4_byte_recent_polls(vote_counts_for_location, first_record_index as usize, response);
         }
         5 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_5_POLL_BYTES as usize);
+            response.extend_from_slice(&page_header);
             response.push(0b00000101);
 
             return get_5_byte_recent_polls(vote_counts_for_location, first_record_index as usize, response);
         }
         6 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_6_POLL_BYTES as usize);
+            response.extend_from_slice(&page_header);
             response.push(0b00000110);
 
             return get_6_byte_recent_polls(vote_counts_for_location, first_record_index as usize, response);
         }
         7 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_7_POLL_BYTES as usize);
+            response.extend_from_slice(&page_header);
             response.push(0b00000111);
 
             return get_7_byte_recent_polls(vote_counts_for_location, first_record_index as usize, response);
         }
         8 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_8_POLL_BYTES as usize);
+            response.extend_from_slice(&page_header);
             response.push(0b00000000);
 
             return get_8_byte_recent_polls(vote_counts_for_location, first_record_index as usize, response);
         }
         2 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_2_POLL_BYTES as usize);
+            response.extend_from_slice(&page_header);
             response.push(0b00000010);
 
             return get_2_byte_recent_polls(vote_counts_for_location, first_record_index as usize, response);
@@ -5,120 +5,134 @@ use common::model::consts;
 use super::super::super::super::cache::model::VoteCount;
 use super::super::super::super::data::byte_counts::ByteCounts;
 
-// NOTE: max page size must fin into u16
-const PAGE_SIZE: u32 = 1024;
+use super::super::page_header::PAGE_HEADER_SIZE;
+use super::super::page_header::PAGE_SIZE;
 
 pub const INITIAL_RESPONSE_VECTOR_SIZE_8_POLL_BYTES: u32 =
-// space for the leading header byte
+    // space for the page header
+    PAGE_HEADER_SIZE +
+    // space for the leading header byte
     1 +
-        // space for location cache index (if any
-        4 +
-        // space for label cache index (if any
-        4 +
-        // space for location ids & vote counts
-        PAGE_SIZE * (8 + 3) +
-        // space for poll type
-        PAGE_SIZE / 4 +
-        // space for the byte counts
-        PAGE_SIZE / 4 +
-        // space for trailing size bytes
-        2;
+    // space for location cache index (if any
+    4 +
+    // space for label cache index (if any
+    4 +
+    // space for location ids & vote counts
+    PAGE_SIZE * (8 + 3) +
+    // space for poll type
+    PAGE_SIZE / 4 +
+    // space for the byte counts
+    PAGE_SIZE / 4 +
+    // space for trailing size bytes
+    2;
 
 pub const INITIAL_RESPONSE_VECTOR_SIZE_7_POLL_BYTES: u32 =
-// space for the leading header byte
+    // space for the page header
+    PAGE_HEADER_SIZE +
+    // space for the leading header byte
     1 +
-        // space for location cache index (if any
-        4 +
-        // space for label cache index (if any
-        4 +
-        // space for location ids & vote counts
-        PAGE_SIZE * (7 + 3) +
-        // space for poll type
-        PAGE_SIZE / 4 +
-        // space for the byte counts
-        PAGE_SIZE / 4 +
-        // space for trailing size bytes
-        2;
+    // space for location cache index (if any
+    4 +
+    // space for label cache index (if any
+    4 +
+    // space for location ids & vote counts
+    PAGE_SIZE * (7 + 3) +
+    // space for poll type
+    PAGE_SIZE / 4 +
+    // space for the byte counts
+    PAGE_SIZE / 4 +
+    // space for trailing size bytes
+    2;
 
 pub const INITIAL_RESPONSE_VECTOR_SIZE_6_POLL_BYTES: u32 =
-// space for the leading header byte
+    // space for the page header
+    PAGE_HEADER_SIZE +
+    // space for the leading header byte
     1 +
-        // space for location cache index (if any
-        4 +
-        // space for label cache index (if any
-        4 +
-        // space for location ids & vote counts
-        PAGE_SIZE * (6 + 3) +
-        // space for poll type
-        PAGE_SIZE / 4 +
-        // space for the byte counts
-        PAGE_SIZE / 4 +
-        // space for trailing size bytes
-        2;
+    // space for location cache index (if any
+    4 +
+    // space for label cache index (if any
+    4 +
+    // space for location ids & vote counts
+    PAGE_SIZE * (6 + 3) +
+    // space for poll type
+    PAGE_SIZE / 4 +
+    // space for the byte counts
+    PAGE_SIZE / 4 +
+    // space for trailing size bytes
+    2;
 
 pub const INITIAL_RESPONSE_VECTOR_SIZE_5_POLL_BYTES: u32 =
-// space for the leading header byte
+    // space for the page header
+    PAGE_HEADER_SIZE +
+    // space for the leading header byte
     1 +
-        // space for location cache index (if any
-        4 +
-        // space for label cache index (if any
-        4 +
-        // space for location ids & vote counts
-        PAGE_SIZE * (5 + 3) +
-        // space for poll type
-        PAGE_SIZE / 4 +
-        // space for the byte counts
-        PAGE_SIZE / 4 +
-        // space for trailing size bytes
-        2;
+    // space for location cache index (if any
+    4 +
+    // space for label cache index (if any
+    4 +
+    // space for location ids & vote counts
+    PAGE_SIZE * (5 + 3) +
+    // space for poll type
+    PAGE_SIZE / 4 +
+    // space for the byte counts
+    PAGE_SIZE / 4 +
+    // space for trailing size bytes
+    2;
 
 pub const INITIAL_RESPONSE_VECTOR_SIZE_4_POLL_BYTES: u32 =
-// space for the leading header byte
+    // space for the page header
+    PAGE_HEADER_SIZE +
+    // space for the leading header byte
     1 +
-        // space for location cache index (if any
-        4 +
-        // space for label cache index (if any
-        4 +
-        // space for location ids & vote counts
-        PAGE_SIZE * (4 + 3) +
-        // space for poll type
-        PAGE_SIZE / 4 +
-        // space for the byte counts
-        PAGE_SIZE / 4 +
-        // space for trailing size bytes
-        2;
+    // space for location cache index (if any
+    4 +
+    // space for label cache index (if any
+    4 +
+    // space for location ids & vote counts
+    PAGE_SIZE * (4 + 3) +
+    // space for poll type
+    PAGE_SIZE / 4 +
+    // space for the byte counts
+    PAGE_SIZE / 4 +
+    // space for trailing size bytes
+    2;
 
 pub const INITIAL_RESPONSE_VECTOR_SIZE_3_POLL_BYTES: u32 =
-// space for the leading header byte
+    // space for the page header
+    PAGE_HEADER_SIZE +
+    // space for the leading header byte
     1 +
-        // space for location cache index (if any
-        4 +
-        // space for label cache index (if any
-        4 +
-        // space for location ids & vote counts
-        PAGE_SIZE * (3 + 3) +
-        // space for poll type
-        PAGE_SIZE / 4 +
-        // space for the byte counts
-        PAGE_SIZE / 4 +
-        // space for trailing size bytes
-        2;
+    // space for location cache index (if any
+    4 +
+    // space for label cache index (if any
+    4 +
+    // space for location ids & vote counts
+    PAGE_SIZE * (3 + 3) +
+    // space for poll type
+    PAGE_SIZE / 4 +
+    // space for the byte counts
+    PAGE_SIZE / 4 +
+    // space for trailing size bytes
+    2;
 
 pub const INITIAL_RESPONSE_VECTOR_SIZE_2_POLL_BYTES: u32 =
-// space for the leading header byte
+    // space for the page header
+    PAGE_HEADER_SIZE +
+    // space for the leading header byte
     1 +
-        // space for location cache index (if any
-        4 +
-        // space for label cache index (if any
-        4 +
-        // space for location ids & vote counts
-        PAGE_SIZE * (2 + 3) +
-        // space for poll type
-        PAGE_SIZE / 4 +
-        // space for the byte counts
-        PAGE_SIZE / 4 +
-        // space for trailing size bytes
-        2;
+    // space for location cache index (if any
+    4 +
+    // space for label cache index (if any
+    4 +
+    // space for location ids & vote counts
+    PAGE_SIZE * (2 + 3) +
+    // space for poll type
+    PAGE_SIZE / 4 +
+    // space for the byte counts
+    PAGE_SIZE / 4 +
+    // space for trailing size bytes
+    2;
 
 #[inline]
 pub fn get_8_byte_recent_polls(
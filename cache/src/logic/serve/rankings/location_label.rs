@@ -19,6 +19,9 @@ use super::super::super::super::cache::model::LocationPeriodIds;
 use super::super::super::super::cache::model::LocationPollRankings;
 use super::super::super::super::cache::model::VoteCount;
 
+use super::super::page_header::get_rankings_page_header;
+use super::super::page_header::PAGE_SIZE;
+
 use super::location_and_loc_label::INITIAL_RESPONSE_VECTOR_SIZE_2_POLL_BYTES;
 use super::location_and_loc_label::INITIAL_RESPONSE_VECTOR_SIZE_3_POLL_BYTES;
 use super::location_and_loc_label::INITIAL_RESPONSE_VECTOR_SIZE_4_POLL_BYTES;
@@ -34,16 +37,13 @@ use super::location_and_loc_label::get_6_byte_recent_polls;
 use super::location_and_loc_label::get_7_byte_recent_polls;
 use super::location_and_loc_label::get_8_byte_recent_polls;
 
-// NOTE: max page size must fin into u16
-const PAGE_SIZE: u32 = 1024;
-
 pub fn get_todays_location_label_rankings_by_global_ids(
     vc_day_id: DayId,
     timezone_id: TimezoneId,
     block_index: u32,
     global_location_id: LocationId,
     global_label_id: LabelId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -62,6 +62,7 @@ pub fn get_todays_location_label_rankings_by_global_ids(
         global_label_id,
         block_index,
         cache.get_poll_id_byte_counts().today[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -71,7 +72,7 @@ pub fn get_todays_location_label_rankings_by_location_cache_index_and_global_lab
     block_index: u32,
     location_cache_index: LocationCacheIndex,
     global_label_id: LabelId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -90,6 +91,7 @@ pub fn get_todays_location_label_rankings_by_location_cache_index_and_global_lab
         global_label_id,
         block_index,
         cache.get_poll_id_byte_counts().today[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -99,7 +101,7 @@ pub fn get_todays_location_label_rankings_by_cache_indexes(
     block_index: u32,
     location_cache_index: LocationCacheIndex,
     location_label_cache_index: LocationLabelCacheIndex,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -117,6 +119,7 @@ pub fn get_todays_location_label_rankings_by_cache_indexes(
         location_label_cache_index,
         block_index,
         cache.get_poll_id_byte_counts().today[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -126,7 +129,7 @@ pub fn get_yesterdays_location_label_rankings_by_global_ids(
     block_index: u32,
     global_location_id: LocationId,
     global_label_id: LabelId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -145,6 +148,7 @@ pub fn get_yesterdays_location_label_rankings_by_global_ids(
         global_label_id,
         block_index,
         cache.get_poll_id_byte_counts().yesterday[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -154,7 +158,7 @@ pub fn get_yesterdays_location_label_rankings_by_location_cache_index_and_global
     block_index: u32,
     location_cache_index: LocationCacheIndex,
     global_label_id: LabelId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -173,6 +177,7 @@ pub fn get_yesterdays_location_label_rankings_by_location_cache_index_and_global
         global_label_id,
         block_index,
         cache.get_poll_id_byte_counts().yesterday[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -182,7 +187,7 @@ pub fn get_yesterdays_location_label_rankings_by_cache_indexes(
     block_index: u32,
     location_cache_index: LocationCacheIndex,
     location_label_cache_index: LocationLabelCacheIndex,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -200,6 +205,7 @@ pub fn get_yesterdays_location_label_rankings_by_cache_indexes(
         location_label_cache_index,
         block_index,
         cache.get_poll_id_byte_counts().yesterday[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -209,7 +215,7 @@ pub fn get_day_b4_yesterdays_location_label_rankings_by_global_ids(
     block_index: u32,
     global_location_id: LocationId,
     global_label_id: LabelId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -228,6 +234,7 @@ pub fn get_day_b4_yesterdays_location_label_rankings_by_global_ids(
         global_label_id,
         block_index,
         cache.get_poll_id_byte_counts().day_b4_yesterday[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -237,7 +244,7 @@ pub fn get_day_b4_yesterdays_location_label_rankings_by_location_cache_index_and
     block_index: u32,
     location_cache_index: LocationCacheIndex,
     global_label_id: LabelId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -256,6 +263,7 @@ pub fn get_day_b4_yesterdays_location_label_rankings_by_location_cache_index_and
         global_label_id,
         block_index,
         cache.get_poll_id_byte_counts().day_b4_yesterday[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -265,7 +273,7 @@ pub fn get_day_b4_yesterdays_location_label_rankings_by_cache_indexes(
     block_index: u32,
     location_cache_index: LocationCacheIndex,
     location_label_cache_index: LocationLabelCacheIndex,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -283,6 +291,7 @@ pub fn get_day_b4_yesterdays_location_label_rankings_by_cache_indexes(
         location_label_cache_index,
         block_index,
         cache.get_poll_id_byte_counts().day_b4_yesterday[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -292,7 +301,7 @@ pub fn get_this_weeks_location_label_rankings_by_global_ids(
     block_index: u32,
     global_location_id: LocationId,
     global_label_id: LabelId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -311,6 +320,7 @@ pub fn get_this_weeks_location_label_rankings_by_global_ids(
         global_label_id,
         block_index,
         cache.get_poll_id_byte_counts().this_week[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -320,7 +330,7 @@ pub fn get_this_weeks_location_label_rankings_by_location_cache_index_and_global
     block_index: u32,
     location_cache_index: LocationCacheIndex,
     global_label_id: LabelId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -339,6 +349,7 @@ pub fn get_this_weeks_location_label_rankings_by_location_cache_index_and_global
         global_label_id,
         block_index,
         cache.get_poll_id_byte_counts().this_week[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -348,7 +359,7 @@ pub fn get_this_weeks_location_label_rankings_by_cache_indexes(
     block_index: u32,
     location_cache_index: LocationCacheIndex,
     location_label_cache_index: LocationLabelCacheIndex,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -366,6 +377,7 @@ pub fn get_this_weeks_location_label_rankings_by_cache_indexes(
         location_label_cache_index,
         block_index,
         cache.get_poll_id_byte_counts().this_week[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -375,7 +387,7 @@ pub fn get_last_weeks_location_label_rankings_by_global_ids(
     block_index: u32,
     global_location_id: LocationId,
     global_label_id: LabelId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -394,6 +406,7 @@ pub fn get_last_weeks_location_label_rankings_by_global_ids(
         global_label_id,
         block_index,
         cache.get_poll_id_byte_counts().last_week[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -403,7 +416,7 @@ pub fn get_last_weeks_location_label_rankings_by_location_cache_index_and_global
     block_index: u32,
     location_cache_index: LocationCacheIndex,
     global_label_id: LabelId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -422,6 +435,7 @@ pub fn get_last_weeks_location_label_rankings_by_location_cache_index_and_global
         global_label_id,
         block_index,
         cache.get_poll_id_byte_counts().last_week[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -431,7 +445,7 @@ pub fn get_last_weeks_location_label_rankings_by_cache_indexes(
     block_index: u32,
     location_cache_index: LocationCacheIndex,
     location_label_cache_index: LocationLabelCacheIndex,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -449,6 +463,7 @@ pub fn get_last_weeks_location_label_rankings_by_cache_indexes(
         location_label_cache_index,
         block_index,
         cache.get_poll_id_byte_counts().last_week[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -458,7 +473,7 @@ pub fn get_this_months_location_label_rankings_by_global_ids(
     block_index: u32,
     global_location_id: LocationId,
     global_label_id: LabelId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -477,6 +492,7 @@ pub fn get_this_months_location_label_rankings_by_global_ids(
         global_label_id,
         block_index,
         cache.get_poll_id_byte_counts().this_month[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -486,7 +502,7 @@ pub fn get_this_months_location_label_rankings_by_location_cache_index_and_globa
     block_index: u32,
     location_cache_index: LocationCacheIndex,
     global_label_id: LabelId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -505,6 +521,7 @@ pub fn get_this_months_location_label_rankings_by_location_cache_index_and_globa
         global_label_id,
         block_index,
         cache.get_poll_id_byte_counts().this_month[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -514,7 +531,7 @@ pub fn get_this_months_location_label_rankings_by_cache_indexes(
     block_index: u32,
     location_cache_index: LocationCacheIndex,
     location_label_cache_index: u32,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -532,6 +549,7 @@ pub fn get_this_months_location_label_rankings_by_cache_indexes(
         location_label_cache_index,
         block_index,
         cache.get_poll_id_byte_counts().this_month[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -541,7 +559,7 @@ pub fn get_last_months_location_label_rankings_by_global_ids(
     block_index: u32,
     global_location_id: LocationId,
     global_label_id: LabelId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -560,6 +578,7 @@ pub fn get_last_months_location_label_rankings_by_global_ids(
         global_label_id,
         block_index,
         cache.get_poll_id_byte_counts().last_month[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -569,7 +588,7 @@ pub fn get_last_months_location_label_rankings_by_location_cache_index_and_globa
     block_index: u32,
     location_cache_index: LocationCacheIndex,
     global_label_id: LabelId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -588,6 +607,7 @@ pub fn get_last_months_location_label_rankings_by_location_cache_index_and_globa
         global_label_id,
         block_index,
         cache.get_poll_id_byte_counts().last_month[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -597,7 +617,7 @@ pub fn get_last_months_location_label_rankings_by_cache_indexes(
     block_index: u32,
     location_cache_index: LocationCacheIndex,
     location_label_cache_index: LocationLabelCacheIndex,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     if timezone_id >= NUM_TIMEZONES {
         return codes::INVALID_TIMEZONE_ID_RESPONSE.to_vec();
@@ -615,6 +635,7 @@ pub fn get_last_months_location_label_rankings_by_cache_indexes(
         location_label_cache_index,
         block_index,
         cache.get_poll_id_byte_counts().last_month[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -629,9 +650,10 @@ fn get_location_label_rankings_by_global_ids(
     global_label_id: LabelId,
     block_index: u32,
     max_poll_number_bytes: u8,
+    data_version: u32,
 ) -> Vec<u8> {
     if current_period_id != expected_period_id {
-        return codes::INVALID_PERIOD_ID_RESPONSE.to_vec();
+        return codes::get_invalid_period_id_response(current_period_id);
     }
 
     let location_period_ids: &LocationPeriodIds = match location_label_index_map.get(&global_location_id) {
@@ -662,7 +684,8 @@ fn get_location_label_rankings_by_global_ids(
     return get_location_label_rankings_with_cache_indexes(
          first_record_index as usize, location_cache_index,
          location_label_cache_index,
-        &location_poll_rankings, max_poll_number_bytes);
+        &location_poll_rankings, max_poll_number_bytes,
+        current_period_id, data_version);
 }
 
 #[inline]
@@ -676,9 +699,10 @@ fn get_location_label_rankings_by_location_cache_index_and_global_label_id(
     global_label_id: LabelId,
     block_index: u32,
     max_poll_number_bytes: u8,
+    data_version: u32,
 ) -> Vec<u8> {
     if current_period_id != expected_period_id {
-        return codes::INVALID_PERIOD_ID_RESPONSE.to_vec();
+        return codes::get_invalid_period_id_response(current_period_id);
     }
 
     let location_poll_rankings: &LocationPollRankings
@@ -711,7 +735,8 @@ fn get_location_label_rankings_by_location_cache_index_and_global_label_id(
 
     return get_location_label_rankings_with_label_cache_index(
         first_record_index as usize, location_label_cache_index,
-        &location_poll_rankings, max_poll_number_bytes);
+        &location_poll_rankings, max_poll_number_bytes,
+        current_period_id, data_version);
 }
 
 #[inline]
@@ -724,9 +749,10 @@ fn get_location_label_rankings_by_cache_indexes(
     location_label_cache_index: LocationLabelCacheIndex,
     block_index: u32,
     max_poll_number_bytes: u8,
+    data_version: u32,
 ) -> Vec<u8> {
     if current_period_id != expected_period_id {
-        return codes::INVALID_PERIOD_ID_RESPONSE.to_vec();
+        return codes::get_invalid_period_id_response(current_period_id);
     }
 
     let location_poll_rankings: &LocationPollRankings
@@ -754,7 +780,8 @@ fn get_location_label_rankings_by_cache_indexes(
 
     return get_location_label_rankings(
         first_record_index as usize, location_label_vote_counts,
-        max_poll_number_bytes);
+        max_poll_number_bytes,
+        current_period_id, data_version);
 }
 
 #[inline]
@@ -764,6 +791,8 @@ fn get_location_label_rankings_with_cache_indexes(
     label_cache_index: LabelCacheIndex,
     location_poll_rankings: &LocationPollRankings,
     max_poll_number_bytes: u8,
+    period_id: u32,
+    data_version: u32,
 ) -> Vec<u8> {
     let vote_counts_for_location
     = location_poll_rankings.label_locations.get(label_cache_index as usize).unwrap();
@@ -774,9 +803,12 @@ fn get_location_label_rankings_with_cache_indexes(
         transmute(label_cache_index)
     };
 
+    let page_header = get_rankings_page_header(vote_counts_for_location.len(), period_id, data_version);
+
     match max_poll_number_bytes {
         3 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_3_POLL_BYTES as usize);;
+            response.extend_from_slice(&page_header);
             response.push(0b00000011);
             response.extend_from_slice(&location_cache_index_bytes);
             response.extend_from_slice(&label_cache_index_bytes);
@@ -785,6 +817,7 @@ fn get_location_label_rankings_with_cache_indexes(
         }
         4 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_4_POLL_BYTES as usize);;
+            response.extend_from_slice(&page_header);
             response.push(0b00000100);
             response.extend_from_slice(&location_cache_index_bytes);
             response.extend_from_slice(&label_cache_index_bytes);
@@ -793,6 +826,7 @@ fn get_location_label_rankings_with_cache_indexes(
         }
         5 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_5_POLL_BYTES as usize);;
+            response.extend_from_slice(&page_header);
             response.push(0b00000101);
             response.extend_from_slice(&location_cache_index_bytes);
             response.extend_from_slice(&label_cache_index_bytes);
@@ -801,6 +835,7 @@ fn get_location_label_rankings_with_cache_indexes(
         }
         6 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_6_POLL_BYTES as usize);;
+            response.extend_from_slice(&page_header);
             response.push(0b00000110);
             response.extend_from_slice(&location_cache_index_bytes);
             response.extend_from_slice(&label_cache_index_bytes);
@@ -809,6 +844,7 @@ fn get_location_label_rankings_with_cache_indexes(
         }
         7 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_7_POLL_BYTES as usize);;
+            response.extend_from_slice(&page_header);
             response.push(0b00000111);
             response.extend_from_slice(&location_cache_index_bytes);
             response.extend_from_slice(&label_cache_index_bytes);
@@ -817,6 +853,7 @@ fn get_location_label_rankings_with_cache_indexes(
         }
         8 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_8_POLL_BYTES as usize);;
+            response.extend_from_slice(&page_header);
             response.push(0b00000000);
             response.extend_from_slice(&location_cache_index_bytes);
             response.extend_from_slice(&label_cache_index_bytes);
@@ -825,6 +862,7 @@ fn get_location_label_rankings_with_cache_indexes(
         }
         2 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_2_POLL_BYTES as usize);;
+            response.extend_from_slice(&page_header);
             response.push(0b00000010);
             response.extend_from_slice(&location_cache_index_bytes);
             response.extend_from_slice(&label_cache_index_bytes);
@@ -844,6 +882,8 @@ fn get_location_label_rankings_with_label_cache_index(
     label_cache_index: LabelCacheIndex,
     location_poll_rankings: &LocationPollRankings,
     max_poll_number_bytes: u8,
+    period_id: u32,
+    data_version: u32,
 ) -> Vec<u8> {
     let vote_counts_for_location
     = location_poll_rankings.label_locations.get(label_cache_index as usize).unwrap();
@@ -851,9 +891,12 @@ fn get_location_label_rankings_with_label_cache_index(
         transmute(label_cache_index)
     };
 
+    let page_header = get_rankings_page_header(vote_counts_for_location.len(), period_id, data_version);
+
     match max_poll_number_bytes {
         3 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_3_POLL_BYTES as usize);;
+            response.extend_from_slice(&page_header);
             response.push(0b00000011);
             response.extend_from_slice(&label_cache_index_bytes);
 
@@ -861,6 +904,7 @@ fn get_location_label_rankings_with_label_cache_index(
         }
         4 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_4_POLL_BYTES as usize);;
+            response.extend_from_slice(&page_header);
             response.push(0b00000100);
             response.extend_from_slice(&label_cache_index_bytes);
 
@@ -868,6 +912,7 @@ fn get_location_label_rankings_with_label_cache_index(
         }
         5 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_5_POLL_BYTES as usize);;
+            response.extend_from_slice(&page_header);
             response.push(0b00000101);
             response.extend_from_slice(&label_cache_index_bytes);
 
@@ -875,6 +920,7 @@ fn get_location_label_rankings_with_label_cache_index(
         }
         6 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_6_POLL_BYTES as usize);;
+            response.extend_from_slice(&page_header);
             response.push(0b00000110);
             response.extend_from_slice(&label_cache_index_bytes);
 
@@ -882,6 +928,7 @@ fn get_location_label_rankings_with_label_cache_index(
         }
         7 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_7_POLL_BYTES as usize);;
+            response.extend_from_slice(&page_header);
             response.push(0b00000111);
             response.extend_from_slice(&label_cache_index_bytes);
 
@@ -889,6 +936,7 @@ fn get_location_label_rankings_with_label_cache_index(
         }
         8 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_8_POLL_BYTES as usize);;
+            response.extend_from_slice(&page_header);
             response.push(0b00000000);
             response.extend_from_slice(&label_cache_index_bytes);
 
@@ -896,6 +944,7 @@ fn get_location_label_rankings_with_label_cache_index(
         }
         2 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_2_POLL_BYTES as usize);;
+            response.extend_from_slice(&page_header);
             response.push(0b00000010);
             response.extend_from_slice(&label_cache_index_bytes);
 
@@ -913,46 +962,57 @@ fn get_location_label_rankings(
     first_record_index: usize,
     location_label_vote_counts: &Vec<VoteCount>,
     max_poll_number_bytes: u8,
+    period_id: u32,
+    data_version: u32,
 ) -> Vec<u8> {
+    let page_header = get_rankings_page_header(location_label_vote_counts.len(), period_id, data_version);
+
     match max_poll_number_bytes {
         3 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_3_POLL_BYTES as usize);;
+            response.extend_from_slice(&page_header);
             response.push(0b00000011);
 
             return get_3_byte_recent_polls(location_label_vote_counts, first_record_index as usize, response);
         }
         4 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_4_POLL_BYTES as usize);;
+            response.extend_from_slice(&page_header);
             response.push(0b00000100);
 
             return get_4_byte_recent_polls(location_label_vote_counts, first_record_index as usize, response);
         }
         5 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_5_POLL_BYTES as usize);;
+            response.extend_from_slice(&page_header);
             response.push(0b00000101);
 
             return get_5_byte_recent_polls(location_label_vote_counts, first_record_index as usize, response);
         }
         6 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_6_POLL_BYTES as usize);;
+            response.extend_from_slice(&page_header);
             response.push(0b00000110);
 
             return get_6_byte_recent_polls(location_label_vote_counts, first_record_index as usize, response);
         }
         7 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_7_POLL_BYTES as usize);;
+            response.extend_from_slice(&page_header);
             response.push(0b00000111);
 
             return get_7_byte_recent_polls(location_label_vote_counts, first_record_index as usize, response);
         }
         8 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_8_POLL_BYTES as usize);;
+            response.extend_from_slice(&page_header);
             response.push(0b00000000);
 
             return get_8_byte_recent_polls(location_label_vote_counts, first_record_index as usize, response);
         }
         2 => {
             let mut response: Vec<u8> = Vec::with_capacity(INITIAL_RESPONSE_VECTOR_SIZE_2_POLL_BYTES as usize);;
+            response.extend_from_slice(&page_header);
             response.push(0b00000010);
 
             return get_2_byte_recent_polls(location_label_vote_counts, first_record_index as usize, response);
@@ -9,8 +9,11 @@ use common::model::types::PollId;
 use common::model::types::WeekId;
 
 use super::super::super::super::cache::cache_reader::CacheReader;
+use super::super::super::super::cache::poll_id_frames::PollIdFrames;
 use super::super::super::super::server::codes;
 
+use super::super::page_header::PAGE_HEADER_SIZE;
+
 use super::utils::get_2_byte_recent_poll_ids;
 use super::utils::get_3_byte_recent_poll_ids;
 use super::utils::get_4_byte_recent_poll_ids;
@@ -18,20 +21,27 @@ use super::utils::get_5_byte_recent_poll_ids;
 use super::utils::get_6_byte_recent_poll_ids;
 use super::utils::get_7_byte_recent_poll_ids;
 use super::utils::get_8_byte_recent_poll_ids;
+use super::utils::MAX_POLL_IDS_SINCE;
+use super::utils::get_empty_recent_polls_response;
+use super::utils::get_no_poll_ids_since_response;
+use super::utils::get_poll_ids_since_response;
+use super::utils::get_recent_polls_block;
+use super::utils::get_recent_polls_page_header;
 
 pub fn get_tomorrows_label_polls(
     vc_day_id: DayId,
     // 1 based index
     block_number: u32,
     global_label_id: LabelId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     return get_global_label_polls(
         cache.get_label_cache_period_ids().tomorrows_vc_day_id,
         vc_day_id,
         &cache.get_future_polls_by_label().tomorrow,
         block_number, global_label_id,
-        cache.get_poll_id_byte_counts().tomorrow[GLOBAL_TZ_INDEX as usize]);
+        cache.get_poll_id_byte_counts().tomorrow[GLOBAL_TZ_INDEX as usize],
+        cache.get_data_version());
 }
 
 pub fn get_day_after_tomorrows_label_polls(
@@ -39,14 +49,15 @@ pub fn get_day_after_tomorrows_label_polls(
     // 1 based index
     block_number: u32,
     global_label_id: LabelId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     return get_global_label_polls(
         cache.get_label_cache_period_ids().day_after_tomorrows_vc_day_id,
         vc_day_id,
         &cache.get_future_polls_by_label().day_after_tomorrow,
         block_number, global_label_id,
-        cache.get_poll_id_byte_counts().day_after_tomorrow[GLOBAL_TZ_INDEX as usize]);
+        cache.get_poll_id_byte_counts().day_after_tomorrow[GLOBAL_TZ_INDEX as usize],
+        cache.get_data_version());
 }
 
 pub fn get_next_weeks_label_polls(
@@ -54,14 +65,15 @@ pub fn get_next_weeks_label_polls(
     // 1 based index
     block_number: u32,
     global_label_id: LabelId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     return get_global_label_polls(
         cache.get_label_cache_period_ids().next_weeks_vc_week_id,
         vc_week_id,
         &cache.get_future_polls_by_label().next_week,
         block_number, global_label_id,
-        cache.get_poll_id_byte_counts().next_week[GLOBAL_TZ_INDEX as usize]);
+        cache.get_poll_id_byte_counts().next_week[GLOBAL_TZ_INDEX as usize],
+        cache.get_data_version());
 }
 
 pub fn get_next_months_label_polls(
@@ -69,47 +81,53 @@ pub fn get_next_months_label_polls(
     // 1 based index
     block_number: u32,
     global_label_id: LabelId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     return get_global_label_polls(
         cache.get_label_cache_period_ids().next_months_vc_month_id,
         vc_month_id,
         &cache.get_future_polls_by_label().next_month,
         block_number, global_label_id,
-        cache.get_poll_id_byte_counts().next_month[GLOBAL_TZ_INDEX as usize]);
+        cache.get_poll_id_byte_counts().next_month[GLOBAL_TZ_INDEX as usize],
+        cache.get_data_version());
 }
 
 
 fn get_global_label_polls(
     current_period_id: u32,
     expected_period_id: u32,
-    global_label_polls: &IntHashMap<LabelId, Vec<Vec<PollId>>>,
+    global_label_polls: &IntHashMap<LabelId, PollIdFrames>,
     // 1 based index
     block_number: u32,
     global_label_id: LabelId,
     max_poll_number_bytes: u8,
+    data_version: u32,
 ) -> Vec<u8> {
     if current_period_id != expected_period_id {
-        return codes::INVALID_PERIOD_ID_RESPONSE.to_vec();
+        return codes::get_invalid_period_id_response(current_period_id);
     }
 
-    let label_polls: &Vec<Vec<PollId>> = match global_label_polls.get(&global_label_id) {
+    let label_polls: &PollIdFrames = match global_label_polls.get(&global_label_id) {
         None => {
-            return Vec::new();
+            return get_empty_recent_polls_response(current_period_id, data_version);
         }
         Some(polls) => {
             polls
         }
     };
-    let polls_block: &Vec<PollId> = match label_polls.get(label_polls.len() - (block_number as usize)) {
+    let page_header = get_recent_polls_page_header(label_polls, current_period_id, data_version);
+    let polls_block: &Vec<PollId> = match get_recent_polls_block(label_polls, block_number) {
         None => {
-            return Vec::new();
+            // No more blocks, just the header
+            return page_header.to_vec();
         }
         Some(block) => {
             block
         }
     };
-    let mut response: Vec<u8> = Vec::with_capacity(max_poll_number_bytes as usize * polls_block.len()  + 1);
+    let mut response: Vec<u8> = Vec::with_capacity(
+        PAGE_HEADER_SIZE as usize + max_poll_number_bytes as usize * polls_block.len() + 1);
+    response.extend_from_slice(&page_header);
 
     match max_poll_number_bytes {
         3 => {
@@ -144,4 +162,86 @@ fn get_global_label_polls(
             panic!("Unexpected number of bytes {}", max_poll_number_bytes)
         }
     }
-}
\ No newline at end of file
+}
+
+pub fn get_tomorrows_label_polls_since(
+    vc_day_id: DayId,
+    since_sequence: u64,
+    global_label_id: LabelId,
+    cache: &CacheReader,
+) -> Vec<u8> {
+    return get_global_label_polls_since(
+        cache.get_label_cache_period_ids().tomorrows_vc_day_id,
+        vc_day_id,
+        &cache.get_future_polls_by_label().tomorrow,
+        since_sequence, global_label_id,
+        cache.get_poll_id_byte_counts().tomorrow[GLOBAL_TZ_INDEX as usize]);
+}
+
+pub fn get_day_after_tomorrows_label_polls_since(
+    vc_day_id: DayId,
+    since_sequence: u64,
+    global_label_id: LabelId,
+    cache: &CacheReader,
+) -> Vec<u8> {
+    return get_global_label_polls_since(
+        cache.get_label_cache_period_ids().day_after_tomorrows_vc_day_id,
+        vc_day_id,
+        &cache.get_future_polls_by_label().day_after_tomorrow,
+        since_sequence, global_label_id,
+        cache.get_poll_id_byte_counts().day_after_tomorrow[GLOBAL_TZ_INDEX as usize]);
+}
+
+pub fn get_next_weeks_label_polls_since(
+    vc_week_id: WeekId,
+    since_sequence: u64,
+    global_label_id: LabelId,
+    cache: &CacheReader,
+) -> Vec<u8> {
+    return get_global_label_polls_since(
+        cache.get_label_cache_period_ids().next_weeks_vc_week_id,
+        vc_week_id,
+        &cache.get_future_polls_by_label().next_week,
+        since_sequence, global_label_id,
+        cache.get_poll_id_byte_counts().next_week[GLOBAL_TZ_INDEX as usize]);
+}
+
+pub fn get_next_months_label_polls_since(
+    vc_month_id: MonthId,
+    since_sequence: u64,
+    global_label_id: LabelId,
+    cache: &CacheReader,
+) -> Vec<u8> {
+    return get_global_label_polls_since(
+        cache.get_label_cache_period_ids().next_months_vc_month_id,
+        vc_month_id,
+        &cache.get_future_polls_by_label().next_month,
+        since_sequence, global_label_id,
+        cache.get_poll_id_byte_counts().next_month[GLOBAL_TZ_INDEX as usize]);
+}
+
+fn get_global_label_polls_since(
+    current_period_id: u32,
+    expected_period_id: u32,
+    global_label_polls: &IntHashMap<LabelId, PollIdFrames>,
+    since_sequence: u64,
+    global_label_id: LabelId,
+    max_poll_number_bytes: u8,
+) -> Vec<u8> {
+    if current_period_id != expected_period_id {
+        return codes::get_invalid_period_id_response(current_period_id);
+    }
+
+    let label_polls: &PollIdFrames = match global_label_polls.get(&global_label_id) {
+        None => {
+            return get_no_poll_ids_since_response(since_sequence);
+        }
+        Some(polls) => {
+            polls
+        }
+    };
+
+    let poll_ids_since = label_polls.get_poll_ids_since(since_sequence, MAX_POLL_IDS_SINCE);
+
+    return get_poll_ids_since_response(&poll_ids_since, max_poll_number_bytes);
+}
@@ -12,6 +12,8 @@ use super::super::super::super::cache::model::LocationPollPrependLists;
 
 use super::super::super::super::server::codes;
 
+use super::super::page_header::PAGE_HEADER_SIZE;
+
 use super::utils::get_2_byte_recent_poll_ids;
 use super::utils::get_3_byte_recent_poll_ids;
 use super::utils::get_4_byte_recent_poll_ids;
@@ -19,13 +21,16 @@ use super::utils::get_5_byte_recent_poll_ids;
 use super::utils::get_6_byte_recent_poll_ids;
 use super::utils::get_7_byte_recent_poll_ids;
 use super::utils::get_8_byte_recent_poll_ids;
+use super::utils::get_empty_recent_polls_response;
+use super::utils::get_recent_polls_block;
+use super::utils::get_recent_polls_page_header;
 
 pub fn get_tomorrows_location_polls(
     vc_day_id: DayId,
     timezone_id: TimezoneId,
     block_index: u32,
     global_location_id: LocationId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     return get_global_location_polls(
         cache.get_label_cache_period_ids().tomorrows_vc_day_id,
@@ -35,6 +40,7 @@ pub fn get_tomorrows_location_polls(
         block_index,
         global_location_id,
         cache.get_poll_id_byte_counts().tomorrow[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -43,7 +49,7 @@ pub fn get_day_after_tomorrows_location_polls(
     timezone_id: TimezoneId,
     block_index: u32,
     global_location_id: LocationId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     return get_global_location_polls(
         cache.get_label_cache_period_ids().day_after_tomorrows_vc_day_id,
@@ -53,6 +59,7 @@ pub fn get_day_after_tomorrows_location_polls(
         block_index,
         global_location_id,
         cache.get_poll_id_byte_counts().day_after_tomorrow[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -61,7 +68,7 @@ pub fn get_next_weeks_location_polls(
     timezone_id: TimezoneId,
     block_index: u32,
     global_location_id: LocationId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     return get_global_location_polls(
         cache.get_label_cache_period_ids().next_weeks_vc_week_id,
@@ -71,6 +78,7 @@ pub fn get_next_weeks_location_polls(
         block_index,
         global_location_id,
         cache.get_poll_id_byte_counts().next_week[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -79,7 +87,7 @@ pub fn get_next_months_location_polls(
     timezone_id: TimezoneId,
     block_index: u32,
     global_location_id: LocationId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     return get_global_location_polls(
         cache.get_label_cache_period_ids().next_months_vc_month_id,
@@ -89,6 +97,7 @@ pub fn get_next_months_location_polls(
         block_index,
         global_location_id,
         cache.get_poll_id_byte_counts().next_month[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -102,9 +111,10 @@ fn get_global_location_polls(
     block_number: u32,
     global_location_id: LocationId,
     max_poll_number_bytes: u8,
+    data_version: u32,
 ) -> Vec<u8> {
     if current_period_id != expected_period_id {
-        return codes::INVALID_PERIOD_ID_RESPONSE.to_vec();
+        return codes::get_invalid_period_id_response(current_period_id);
     }
 
     let location_polls_for_timezone: &IntHashMap<LocationId, LocationPollPrependLists> =
@@ -119,22 +129,26 @@ fn get_global_location_polls(
 
     let location_polls: &LocationPollPrependLists = match location_polls_for_timezone.get(&global_location_id) {
         None => {
-            return Vec::new();
+            return get_empty_recent_polls_response(current_period_id, data_version);
         }
         Some(location_polls) => {
             location_polls
         }
     };
 
-    let polls_block: &Vec<PollId> = match location_polls.location.get(location_polls.location.len() - block_number as usize) {
+    let page_header = get_recent_polls_page_header(&location_polls.location, current_period_id, data_version);
+    let polls_block: &Vec<PollId> = match get_recent_polls_block(&location_polls.location, block_number) {
         None => {
-            return Vec::new();
+            // No more blocks, just the header
+            return page_header.to_vec();
         }
         Some(block) => {
             block
         }
     };
-    let mut response: Vec<u8> = Vec::with_capacity(max_poll_number_bytes as usize * polls_block.len() + 1);
+    let mut response: Vec<u8> = Vec::with_capacity(
+        PAGE_HEADER_SIZE as usize + max_poll_number_bytes as usize * polls_block.len() + 1);
+    response.extend_from_slice(&page_header);
 
     match max_poll_number_bytes {
         3 => {
@@ -9,10 +9,13 @@ use common::model::types::TimezoneId;
 use common::model::types::WeekId;
 
 use super::super::super::super::cache::cache_reader::CacheReader;
+use super::super::super::super::cache::poll_id_frames::PollIdFrames;
 use super::super::super::super::cache::model::LocationPollPrependLists;
 
 use super::super::super::super::server::codes;
 
+use super::super::page_header::PAGE_HEADER_SIZE;
+
 use super::utils::get_2_byte_recent_poll_ids;
 use super::utils::get_3_byte_recent_poll_ids;
 use super::utils::get_4_byte_recent_poll_ids;
@@ -20,6 +23,9 @@ use super::utils::get_5_byte_recent_poll_ids;
 use super::utils::get_6_byte_recent_poll_ids;
 use super::utils::get_7_byte_recent_poll_ids;
 use super::utils::get_8_byte_recent_poll_ids;
+use super::utils::get_empty_recent_polls_response;
+use super::utils::get_recent_polls_block;
+use super::utils::get_recent_polls_page_header;
 
 pub fn get_tomorrows_location_label_polls(
     vc_day_id: DayId,
@@ -27,7 +33,7 @@ pub fn get_tomorrows_location_label_polls(
     block_index: u32,
     global_location_id: LocationId,
     global_label_id: LabelId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     return get_global_location_label_polls(
         cache.get_label_cache_period_ids().tomorrows_vc_day_id,
@@ -38,6 +44,7 @@ pub fn get_tomorrows_location_label_polls(
         global_location_id,
         global_label_id,
         cache.get_poll_id_byte_counts().tomorrow[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -47,7 +54,7 @@ pub fn get_day_after_tomorrows_location_label_polls(
     block_index: u32,
     global_location_id: LocationId,
     global_label_id: LabelId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     return get_global_location_label_polls(
         cache.get_label_cache_period_ids().day_after_tomorrows_vc_day_id,
@@ -58,6 +65,7 @@ pub fn get_day_after_tomorrows_location_label_polls(
         global_location_id,
         global_label_id,
         cache.get_poll_id_byte_counts().day_after_tomorrow[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -67,7 +75,7 @@ pub fn get_next_weeks_location_label_polls(
     block_index: u32,
     global_location_id: LocationId,
     global_label_id: LabelId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     return get_global_location_label_polls(
         cache.get_label_cache_period_ids().next_weeks_vc_week_id,
@@ -78,6 +86,7 @@ pub fn get_next_weeks_location_label_polls(
         global_location_id,
         global_label_id,
         cache.get_poll_id_byte_counts().next_week[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -87,7 +96,7 @@ pub fn get_next_months_location_label_polls(
     block_index: u32,
     global_location_id: LocationId,
     global_label_id: LabelId,
-    cache: &Box<CacheReader + Send + Sync>,
+    cache: &CacheReader,
 ) -> Vec<u8> {
     return get_global_location_label_polls(
         cache.get_label_cache_period_ids().next_months_vc_month_id,
@@ -98,6 +107,7 @@ pub fn get_next_months_location_label_polls(
         global_location_id,
         global_label_id,
         cache.get_poll_id_byte_counts().next_month[timezone_id as usize],
+        cache.get_data_version(),
     );
 }
 
@@ -112,9 +122,10 @@ fn get_global_location_label_polls(
     global_location_id: LocationId,
     global_label_id: LabelId,
     max_poll_number_bytes: u8,
+    data_version: u32,
 ) -> Vec<u8> {
     if current_period_id != expected_period_id {
-        return codes::INVALID_PERIOD_ID_RESPONSE.to_vec();
+        return codes::get_invalid_period_id_response(current_period_id);
     }
 
     let location_polls_for_timezone: &IntHashMap<LocationId, LocationPollPrependLists> =
@@ -129,31 +140,35 @@ fn get_global_location_label_polls(
 
     let location_polls: &LocationPollPrependLists = match location_polls_for_timezone.get(&global_location_id) {
         None => {
-            return Vec::new();
+            return get_empty_recent_polls_response(current_period_id, data_version);
         }
         Some(location_polls) => {
             location_polls
         }
     };
 
-    let location_label_polls: &Vec<Vec<PollId>> = match location_polls.label_locations.get(&global_label_id) {
+    let location_label_polls: &PollIdFrames = match location_polls.label_locations.get(&global_label_id) {
         None => {
-            return Vec::new();
+            return get_empty_recent_polls_response(current_period_id, data_version);
         }
         Some(location_label_polls) => {
             location_label_polls
         }
     };
 
-    let polls_block: &Vec<PollId> = match location_label_polls.get(location_label_polls.len() - block_number as usize) {
+    let page_header = get_recent_polls_page_header(location_label_polls, current_period_id, data_version);
+    let polls_block: &Vec<PollId> = match get_recent_polls_block(location_label_polls, block_number) {
         None => {
-            return Vec::new();
+            // No more blocks, just the header
+            return page_header.to_vec();
         }
         Some(block) => {
             block
         }
     };
-    let mut response: Vec<u8> = Vec::with_capacity(max_poll_number_bytes as usize * polls_block.len() + 1);
+    let mut response: Vec<u8> = Vec::with_capacity(
+        PAGE_HEADER_SIZE as usize + max_poll_number_bytes as usize * polls_block.len() + 1);
+    response.extend_from_slice(&page_header);
 
     match max_poll_number_bytes {
         3 => {
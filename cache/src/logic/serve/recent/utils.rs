@@ -1,6 +1,139 @@
 use std::mem::transmute;
 use common::model::types::PollId;
 
+use super::super::super::super::cache::poll_id_frames::PollIdFrames;
+use super::super::super::super::cache::poll_id_frames::PollIdsSince;
+
+use super::super::page_header::PAGE_HEADER_SIZE;
+use super::super::page_header::get_page_header;
+
+/**
+ * Maximum number of poll ids returned by a single "polls added since" request.  Only whole
+ * additions are returned, so a single large addition can go over it.
+ */
+pub const MAX_POLL_IDS_SINCE: usize = 1024;
+
+/**
+ * Page header for recent polls, every frame of poll ids is returned as a page.
+ */
+#[inline]
+pub fn get_recent_polls_page_header(
+    poll_id_frames: &PollIdFrames,
+    period_id: u32,
+    data_version: u32,
+) -> [u8; PAGE_HEADER_SIZE as usize] {
+    get_page_header(poll_id_frames.num_poll_ids, poll_id_frames.num_frames() as u32,
+                    period_id, data_version)
+}
+
+/**
+ * Page header for when there are no recent polls to return.
+ */
+#[inline]
+pub fn get_empty_recent_polls_response(
+    period_id: u32,
+    data_version: u32,
+) -> Vec<u8> {
+    get_page_header(0, 0, period_id, data_version).to_vec()
+}
+
+/**
+ * Frame of poll ids by 1 based block number, counting from the oldest frame.  The most recent
+ * polls are in the last block (the total number of blocks is in the page header).
+ */
+#[inline]
+pub fn get_recent_polls_block(
+    poll_id_frames: &PollIdFrames,
+    // 1 based index
+    block_number: u32,
+) -> Option<&Vec<PollId>> {
+    let num_frames = poll_id_frames.num_frames();
+    if block_number == 0 || block_number as usize > num_frames {
+        return None;
+    }
+
+    poll_id_frames.frames.get(block_number as usize - 1)
+}
+
+/**
+ * Response for poll ids added after a given sequence number (all numbers are Big Endian):
+ *
+ *  8 bytes - high-water sequence number, to be sent as the "since" sequence of the next request
+ *  1 byte  - 1 if more poll ids were added past the high-water sequence, 0 otherwise
+ *  1 byte  - poll id width header (only if any poll ids follow)
+ *  N bytes - poll ids, in order of addition
+ */
+pub fn get_poll_ids_since_response(
+    poll_ids_since: &PollIdsSince,
+    max_poll_number_bytes: u8,
+) -> Vec<u8> {
+    let high_water_sequence_bytes: [u8; 8] = unsafe {
+        transmute(poll_ids_since.high_water_sequence.to_be())
+    };
+    let poll_ids = &poll_ids_since.poll_ids;
+
+    let mut response: Vec<u8> = Vec::with_capacity(
+        8 + 1 + 1 + max_poll_number_bytes as usize * poll_ids.len());
+    response.extend_from_slice(&high_water_sequence_bytes);
+    if poll_ids_since.more {
+        response.push(1);
+    } else {
+        response.push(0);
+    }
+
+    if poll_ids.is_empty() {
+        return response;
+    }
+
+    match max_poll_number_bytes {
+        3 => {
+            response.push(0b00000011);
+            return get_3_byte_recent_poll_ids(poll_ids, response);
+        }
+        4 => {
+            response.push(0b00000100);
+            return get_4_byte_recent_poll_ids(poll_ids, response);
+        }
+        5 => {
+            response.push(0b00000101);
+            return get_5_byte_recent_poll_ids(poll_ids, response);
+        }
+        6 => {
+            response.push(0b00000110);
+            return get_6_byte_recent_poll_ids(poll_ids, response);
+        }
+        7 => {
+            response.push(0b00000111);
+            return get_7_byte_recent_poll_ids(poll_ids, response);
+        }
+        8 => {
+            response.push(0b00000000);
+            return get_8_byte_recent_poll_ids(poll_ids, response);
+        }
+        2 => {
+            response.push(0b00000010);
+            return get_2_byte_recent_poll_ids(poll_ids, response);
+        }
+        _ => {
+            panic!("Unexpected number of bytes {}", max_poll_number_bytes)
+        }
+    }
+}
+
+/**
+ * Response for when nothing has been added after a given sequence number.
+ */
+#[inline]
+pub fn get_no_poll_ids_since_response(
+    since_sequence: u64,
+) -> Vec<u8> {
+    get_poll_ids_since_response(&PollIdsSince {
+        poll_ids: Vec::new(),
+        high_water_sequence: since_sequence,
+        more: false,
+    }, 0)
+}
+
 #[inline]
 pub fn get_2_byte_recent_poll_ids(
     poll_ids: &Vec<PollId>,
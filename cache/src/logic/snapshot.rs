@@ -0,0 +1,402 @@
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Cursor, Read, Write};
+use std::path::Path;
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use int_hash::IntBuildHasher;
+use int_hash::IntHashMap;
+
+use common::model::timezone::ALL_TIME_ZONES;
+use common::model::types::LabelId;
+use common::model::types::PollId;
+
+use server::cache::producers::ProducerSequences;
+use server::cache::producers::MAX_PRODUCER_ID_LENGTH;
+
+use super::super::cache::cache::Cache;
+use super::super::cache::poll_id_frames::PollIdFrames;
+use super::super::cache::poll_id_frames::POLL_ID_FRAME_SIZE;
+
+use super::rollover::promote_label_polls;
+
+pub const SNAPSHOT_VERSION_1: u8 = 1;
+
+/**
+ *  Snapshots of the data updates are applied to (the future polls by label), along with the
+ *  last applied sequence of every producer.  Both are saved (and loaded) together, so that
+ *  batches in the snapshot are never applied again and batches that are not in it are.
+ *
+ *  Snapshot file (all numbers are Big Endian):
+ *
+ *  1 byte  - snapshot version (SNAPSHOT_VERSION_*)
+ *  4 bytes - data version
+ *  8 bytes - sequence number of the last poll addition
+ *  4 bytes - number of producers, followed by that many:
+ *      1 byte   - length of the producer id
+ *      N bytes  - producer id (UTF-8)
+ *      8 bytes  - last applied sequence
+ *  Followed by the future periods (next month, next week, tomorrow, day after tomorrow):
+ *      4 bytes - period id
+ *      4 bytes - number of labels, followed by that many:
+ *          8 bytes - label id
+ *          4 bytes - number of batches, followed by that many:
+ *              8 bytes - sequence number of the batch
+ *              4 bytes - number of polls, followed by that many:
+ *                  8 bytes - poll id
+ *
+ *  Snapshots are written to a temporary file which then replaces the previous snapshot, so a
+ *  snapshot is never read half written.
+ */
+pub fn save_snapshot(
+    cache: &Cache,
+    producer_sequences: &ProducerSequences,
+    path: &Path,
+) -> Result<(), String> {
+    let temp_path = path.with_extension("tmp");
+    write_snapshot(cache, producer_sequences, &temp_path)
+        .and_then(|_| fs::rename(&temp_path, path))
+        .map_err(|err| format!("Could not write snapshot {}: {}", path.display(), err))
+}
+
+/**
+ *  Loads a snapshot into a new cache, false if there is no snapshot file.  The periods moved on
+ *  if the snapshot was saved before a rollover: stored periods are matched to the cache's
+ *  periods by period id, polls of periods that became current are added to the current label
+ *  rankings (see promote_label_polls) and those of past periods are dropped.
+ */
+pub fn load_snapshot(
+    cache: &mut Cache,
+    producer_sequences: &mut ProducerSequences,
+    path: &Path,
+) -> Result<bool, String> {
+    let mut data = Vec::new();
+    match File::open(path) {
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+            return Ok(false);
+        }
+        Err(err) => {
+            return Err(format!("Could not read snapshot {}: {}", path.display(), err));
+        }
+        Ok(mut file) => {
+            file.read_to_end(&mut data)
+                .map_err(|err| format!("Could not read snapshot {}: {}", path.display(), err))?;
+        }
+    }
+
+    read_snapshot(cache, producer_sequences, &data)
+        .map_err(|err| format!("Invalid snapshot {}: {}", path.display(), err))?;
+
+    Ok(true)
+}
+
+fn write_snapshot(
+    cache: &Cache,
+    producer_sequences: &ProducerSequences,
+    path: &Path,
+) -> io::Result<()> {
+    let file = File::create(path)?;
+    let mut out = BufWriter::new(file);
+
+    out.write_u8(SNAPSHOT_VERSION_1)?;
+    out.write_u32::<BigEndian>(cache.data_version)?;
+    out.write_u64::<BigEndian>(cache.future_polls_by_label.last_sequence)?;
+
+    out.write_u32::<BigEndian>(producer_sequences.len() as u32)?;
+    for (producer_id, sequence) in producer_sequences.iter() {
+        out.write_u8(producer_id.len() as u8)?;
+        out.write_all(producer_id.as_bytes())?;
+        out.write_u64::<BigEndian>(*sequence)?;
+    }
+
+    let period_ids = get_future_period_ids(cache);
+    let polls_by_label = &cache.future_polls_by_label;
+    let poll_maps = [
+        &polls_by_label.next_month,
+        &polls_by_label.next_week,
+        &polls_by_label.tomorrow,
+        &polls_by_label.day_after_tomorrow,
+    ];
+    for (period_id, poll_map) in period_ids.iter().zip(poll_maps.iter()) {
+        out.write_u32::<BigEndian>(*period_id)?;
+        write_poll_map(&mut out, poll_map)?;
+    }
+
+    let file = out.into_inner().map_err(|err| err.into_error())?;
+    file.sync_all()
+}
+
+fn write_poll_map<W: Write>(
+    out: &mut W,
+    poll_map: &IntHashMap<LabelId, PollIdFrames>,
+) -> io::Result<()> {
+    out.write_u32::<BigEndian>(poll_map.len() as u32)?;
+    for (label_id, poll_id_frames) in poll_map.iter() {
+        out.write_u64::<BigEndian>(*label_id)?;
+        out.write_u32::<BigEndian>(poll_id_frames.batches.len() as u32)?;
+        let mut start_poll_index = 0;
+        for batch in poll_id_frames.batches.iter() {
+            let end_poll_index = batch.end_poll_index as usize;
+            out.write_u64::<BigEndian>(batch.sequence)?;
+            out.write_u32::<BigEndian>((end_poll_index - start_poll_index) as u32)?;
+            for poll_index in start_poll_index..end_poll_index {
+                out.write_u64::<BigEndian>(poll_id_frames.frames
+                    [poll_index / POLL_ID_FRAME_SIZE][poll_index % POLL_ID_FRAME_SIZE])?;
+            }
+            start_poll_index = end_poll_index;
+        }
+    }
+
+    Ok(())
+}
+
+fn read_snapshot(
+    cache: &mut Cache,
+    producer_sequences: &mut ProducerSequences,
+    data: &[u8],
+) -> io::Result<()> {
+    let mut cursor = Cursor::new(data);
+
+    let version = cursor.read_u8()?;
+    if version != SNAPSHOT_VERSION_1 {
+        return Err(invalid_snapshot("unsupported snapshot version"));
+    }
+    cache.data_version = cursor.read_u32::<BigEndian>()?;
+    cache.future_polls_by_label.last_sequence = cursor.read_u64::<BigEndian>()?;
+
+    let num_producers = cursor.read_u32::<BigEndian>()?;
+    for _ in 0..num_producers {
+        let producer_id_length = cursor.read_u8()? as usize;
+        if producer_id_length > MAX_PRODUCER_ID_LENGTH {
+            return Err(invalid_snapshot("producer id too long"));
+        }
+        let mut producer_id = vec![0; producer_id_length];
+        cursor.read_exact(&mut producer_id)?;
+        let producer_id = String::from_utf8(producer_id)
+            .map_err(|_| invalid_snapshot("producer id is not UTF-8"))?;
+        producer_sequences.set_applied(&producer_id, cursor.read_u64::<BigEndian>()?);
+    }
+
+    let (month_id, months_polls) = read_poll_map(&mut cursor)?;
+    let (week_id, weeks_polls) = read_poll_map(&mut cursor)?;
+    let (first_day_id, first_days_polls) = read_poll_map(&mut cursor)?;
+    let (second_day_id, second_days_polls) = read_poll_map(&mut cursor)?;
+
+    if (cursor.position() as usize) < data.len() {
+        return Err(invalid_snapshot("trailing data"));
+    }
+
+    load_month_polls(cache, month_id, months_polls);
+    load_week_polls(cache, week_id, weeks_polls);
+    load_day_polls(cache, first_day_id, first_days_polls);
+    load_day_polls(cache, second_day_id, second_days_polls);
+
+    Ok(())
+}
+
+fn load_month_polls(
+    cache: &mut Cache,
+    month_id: u32,
+    polls_by_label: IntHashMap<LabelId, PollIdFrames>,
+) {
+    let time_period_ids = &cache.time_period_ids;
+    if month_id == time_period_ids.next_month[ALL_TIME_ZONES] {
+        cache.future_polls_by_label.next_month.extend(polls_by_label);
+    } else if month_id == time_period_ids.this_month[ALL_TIME_ZONES] {
+        promote_label_polls(&mut cache.label_index_map.this_month,
+                            &mut cache.label_poll_rankings.this_month, polls_by_label);
+    }
+}
+
+fn load_week_polls(
+    cache: &mut Cache,
+    week_id: u32,
+    polls_by_label: IntHashMap<LabelId, PollIdFrames>,
+) {
+    let time_period_ids = &cache.time_period_ids;
+    if week_id == time_period_ids.next_week[ALL_TIME_ZONES] {
+        cache.future_polls_by_label.next_week.extend(polls_by_label);
+    } else if week_id == time_period_ids.this_week[ALL_TIME_ZONES] {
+        promote_label_polls(&mut cache.label_index_map.this_week,
+                            &mut cache.label_poll_rankings.this_week, polls_by_label);
+    }
+}
+
+fn load_day_polls(
+    cache: &mut Cache,
+    day_id: u32,
+    polls_by_label: IntHashMap<LabelId, PollIdFrames>,
+) {
+    // The day after tomorrow may have become tomorrow, and tomorrow today
+    let time_period_ids = &cache.time_period_ids;
+    let tomorrows_day_id = time_period_ids.tomorrow[ALL_TIME_ZONES];
+    if day_id == tomorrows_day_id {
+        cache.future_polls_by_label.tomorrow.extend(polls_by_label);
+    } else if day_id == tomorrows_day_id + 1 {
+        cache.future_polls_by_label.day_after_tomorrow.extend(polls_by_label);
+    } else if day_id == time_period_ids.today[ALL_TIME_ZONES] {
+        promote_label_polls(&mut cache.label_index_map.today,
+                            &mut cache.label_poll_rankings.today, polls_by_label);
+    }
+}
+
+/**
+ *  Id of a stored period and its polls by label.
+ */
+fn read_poll_map(
+    cursor: &mut Cursor<&[u8]>
+) -> io::Result<(u32, IntHashMap<LabelId, PollIdFrames>)> {
+    let period_id = cursor.read_u32::<BigEndian>()?;
+    let num_labels = cursor.read_u32::<BigEndian>()?;
+    let mut poll_map: IntHashMap<LabelId, PollIdFrames> = HashMap::with_hasher(IntBuildHasher::default());
+    for _ in 0..num_labels {
+        let label_id = cursor.read_u64::<BigEndian>()?;
+        let num_batches = cursor.read_u32::<BigEndian>()?;
+        let mut poll_id_frames = PollIdFrames::new();
+        for _ in 0..num_batches {
+            let sequence = cursor.read_u64::<BigEndian>()?;
+            let num_polls = cursor.read_u32::<BigEndian>()? as usize;
+            if num_polls > (cursor.get_ref().len() - cursor.position() as usize) / 8 {
+                return Err(invalid_snapshot("more polls than the rest of the snapshot holds"));
+            }
+            let mut poll_ids: Vec<PollId> = Vec::with_capacity(num_polls);
+            for _ in 0..num_polls {
+                poll_ids.push(cursor.read_u64::<BigEndian>()?);
+            }
+            poll_id_frames.add_poll_ids(poll_ids, sequence);
+        }
+        poll_map.insert(label_id, poll_id_frames);
+    }
+
+    Ok((period_id, poll_map))
+}
+
+/**
+ *  Ids of the future periods, in the order they are in the snapshot.
+ */
+#[inline]
+fn get_future_period_ids(
+    cache: &Cache
+) -> [u32; 4] {
+    let time_period_ids = &cache.time_period_ids;
+    let tomorrows_day_id = time_period_ids.tomorrow[ALL_TIME_ZONES];
+
+    [
+        time_period_ids.next_month[ALL_TIME_ZONES],
+        time_period_ids.next_week[ALL_TIME_ZONES],
+        tomorrows_day_id,
+        tomorrows_day_id + 1,
+    ]
+}
+
+#[inline]
+fn invalid_snapshot(
+    message: &str
+) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+    use std::sync::Arc;
+
+    use common::model::timezone::ALL_TIME_ZONES;
+
+    use server::cache::events::EventHub;
+    use server::cache::producers::Batch;
+    use server::cache::producers::ProducerSequences;
+
+    use super::super::super::cache::cache::Cache;
+    use super::load_snapshot;
+    use super::save_snapshot;
+
+    fn new_cache(
+        todays_day_id: u32
+    ) -> Cache {
+        let mut cache = Cache::new(Arc::new(EventHub::new()));
+        let time_period_ids = &mut cache.time_period_ids;
+        time_period_ids.today[ALL_TIME_ZONES] = todays_day_id;
+        time_period_ids.tomorrow[ALL_TIME_ZONES] = todays_day_id + 1;
+        time_period_ids.day_after_tomorrow[ALL_TIME_ZONES] = todays_day_id + 2;
+        time_period_ids.this_week[ALL_TIME_ZONES] = 3;
+        time_period_ids.next_week[ALL_TIME_ZONES] = 4;
+        time_period_ids.this_month[ALL_TIME_ZONES] = 1;
+        time_period_ids.next_month[ALL_TIME_ZONES] = 2;
+
+        cache
+    }
+
+    fn batch(
+        producer_id: &str,
+        sequence: u64,
+    ) -> Batch {
+        Batch {
+            producer_id: producer_id.to_string(),
+            sequence,
+        }
+    }
+
+    fn snapshot_path(
+        name: &str
+    ) -> PathBuf {
+        env::temp_dir().join(format!("vc-cache-snapshot-{}-{}", name, process::id()))
+    }
+
+    #[test]
+    fn producer_sequences() {
+        let path = snapshot_path("sequences");
+        let mut producer_sequences = ProducerSequences::new();
+        producer_sequences.set_applied("ingest-1", 5);
+        producer_sequences.set_applied("ingest-2", 12);
+        save_snapshot(&new_cache(10), &producer_sequences, &path).unwrap();
+
+        let mut loaded_sequences = ProducerSequences::new();
+        assert!(load_snapshot(&mut new_cache(10), &mut loaded_sequences, &path).unwrap());
+        fs::remove_file(&path).unwrap();
+
+        // Batches applied before the snapshot are not applied again after it is loaded
+        assert_eq!(loaded_sequences.len(), 2);
+        assert_eq!(loaded_sequences.get_applied(&batch("ingest-1", 5)), Some(5));
+        assert_eq!(loaded_sequences.get_applied(&batch("ingest-1", 6)), None);
+        assert_eq!(loaded_sequences.get_applied(&batch("ingest-2", 12)), Some(12));
+        assert_eq!(loaded_sequences.get_applied(&batch("ingest-3", 1)), None);
+    }
+
+    #[test]
+    fn missing_snapshot() {
+        let mut producer_sequences = ProducerSequences::new();
+        assert!(!load_snapshot(&mut new_cache(10), &mut producer_sequences,
+                               &snapshot_path("missing")).unwrap());
+    }
+
+    #[test]
+    fn periods_moved_on() {
+        let path = snapshot_path("periods");
+        let mut cache = new_cache(10);
+        cache.add_future_day_polls(11, vec![1], vec![vec![100, 101]]).unwrap();
+        cache.add_future_day_polls(12, vec![1, 2], vec![vec![120], vec![121]]).unwrap();
+        cache.add_future_week_polls(4, vec![1], vec![vec![400]]).unwrap();
+        save_snapshot(&cache, &ProducerSequences::new(), &path).unwrap();
+
+        // Loaded a day later: tomorrow became today and the day after tomorrow became tomorrow
+        let mut cache = new_cache(11);
+        assert!(load_snapshot(&mut cache, &mut ProducerSequences::new(), &path).unwrap());
+        fs::remove_file(&path).unwrap();
+
+        let label_cache_index = cache.label_index_map.today[&1] as usize;
+        let poll_ids: Vec<u64> = cache.label_poll_rankings.today[label_cache_index].iter()
+            .map(|vote_count| vote_count.poll_id).collect();
+        assert_eq!(poll_ids, vec![100, 101]);
+        let tomorrow = &cache.future_polls_by_label.tomorrow;
+        assert_eq!(tomorrow.len(), 2);
+        assert_eq!(tomorrow[&1].frames, vec![vec![120]]);
+        assert!(cache.future_polls_by_label.day_after_tomorrow.is_empty());
+        assert_eq!(cache.future_polls_by_label.next_week[&1].frames, vec![vec![400]]);
+    }
+}
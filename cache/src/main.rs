@@ -1,32 +1,157 @@
 extern crate byteorder;
 extern crate bytes;
+extern crate clap;
 extern crate core;
 extern crate evmap;
 extern crate int_hash;
 extern crate lazy_static;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate toml;
 
 extern crate common;
 extern crate server;
 
+use std::process;
+use std::sync::{Arc, RwLock};
 
 pub mod cache;
+pub mod config;
 pub mod data;
 pub mod logic;
 pub mod app;
 
+use server::auth::UpdateAuthenticator;
 use server::cache::app::App;
+use server::cache::events::EventHub;
+use server::cache::pages::PageCache;
+use server::cache::producers::ProducerSequences;
+use server::cache::scheduler::Scheduler;
+use server::cache::scheduler::SystemClock;
 use server::cache::server::Server;
+use server::cache::update_app::UpdateApp;
+use server::cache::updater::Updater;
+use server::cache::watchers::PageWatchers;
+use server::config::TlsConfig;
+use server::limit::RateLimiter;
+use server::metrics::Metrics;
+use server::tls::TlsAcceptor;
 
 use app::app::CompleteCacheApp;
+use app::jobs::schedule_jobs;
+use app::update::CompleteCacheUpdateApp;
 use cache::cache::Cache;
-use cache::cache_reader::CacheReader;
+use cache::cache_writer::CacheWriter;
+use logic::snapshot::load_snapshot;
 
 fn main() {
     println!("VC Complete (not-split up) Cache v0.1.0");
 
-    let cache: Box<CacheReader + Send + Sync> = Box::new(Cache::new());
-    let cache_server: Box<App + Sync + Send> = Box::new(CompleteCacheApp::new(cache));
-    let server: Server = Server::new(cache_server);
+    let config = match config::get_config() {
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(1);
+        }
+        Ok(config) => {
+            config
+        }
+    };
+
+    // Shared between the cache (which publishes future poll additions) and the server (event
+    // stream requests)
+    let event_hub: Arc<EventHub> = Arc::new(EventHub::new());
+    let mut cache = Box::new(Cache::new(event_hub.clone()));
+    // Updates (and the producer sequences they were applied with) from the last run
+    let mut producer_sequences = ProducerSequences::new();
+    if let Some(ref snapshot_file) = config.snapshot_file {
+        match load_snapshot(&mut cache, &mut producer_sequences, snapshot_file) {
+            Err(message) => {
+                eprintln!("{}", message);
+                process::exit(1);
+            }
+            Ok(true) => {
+                println!("Loaded snapshot {} (data version {})", snapshot_file.display(), cache.data_version);
+            }
+            Ok(false) => {
+                println!("No snapshot at {}, starting empty", snapshot_file.display());
+            }
+        }
+    }
+    // Shared between the read app and the update thread (the only writer)
+    let cache: Arc<RwLock<Cache>> = Arc::new(RwLock::new(*cache));
+    let cache_writer = CacheWriter::new(cache.clone());
+    // Shared between the app (which notifies on re-ranking) and the server (long-poll requests)
+    let page_watchers: Arc<PageWatchers> = Arc::new(PageWatchers::new());
+    let page_cache: Arc<PageCache> = Arc::new(PageCache::new());
+    // Periodic cache jobs (rollovers, re-ranking, snapshots), their statuses are served by the
+    // debug routes
+    let mut scheduler = Scheduler::new(Arc::new(SystemClock));
+    let cache_server: Box<App + Sync + Send> = Box::new(CompleteCacheApp::new(
+        cache, page_watchers.clone(), page_cache.clone(), scheduler.get_job_statuses()));
+    // Shared between the server (read requests) and the updater (update requests and queue),
+    // served on GET /metrics
+    let metrics = if config.metrics {
+        Some(Arc::new(Metrics::new(&cache_server.get_route_ids(), &CompleteCacheUpdateApp::get_route_ids())))
+    } else {
+        None
+    };
+    let mut server: Server = Server::new_shared(cache_server, page_watchers.clone(), event_hub);
+    server.set_max_body_size(config.max_body_size);
+    // Human readable GET routes, for debugging only
+    server.set_debug_routes_enabled(config.debug_routes);
+    if config.compression {
+        server.set_compression_min_size(config.compression_min_size);
+    }
+    if config.max_connections != 0 {
+        server.set_max_connections(config.max_connections);
+    }
+    if let Some(ref rate_limit) = config.rate_limit {
+        server.set_rate_limiter(RateLimiter::new(rate_limit));
+    }
+    if let Some(ref server_tls) = config.server_tls {
+        server.set_server_tls(get_tls_acceptor(server_tls));
+    }
+    if let Some(ref metrics) = metrics {
+        server.set_metrics(metrics.clone());
+    }
+
+    let mut updater = Updater::new(CompleteCacheUpdateApp::new(
+        cache_writer, producer_sequences, config.snapshot_file.clone()));
+    updater.set_max_body_size(config.max_body_size);
+    updater.set_queue_size(config.update_queue_size);
+    if let Some(metrics) = metrics {
+        updater.set_metrics(metrics);
+    }
+    let mut updater_verifies_clients = false;
+    if let Some(ref updater_tls) = config.updater_tls {
+        let updater_tls = get_tls_acceptor(updater_tls);
+        updater_verifies_clients = updater_tls.verifies_clients();
+        updater.set_tls(updater_tls);
+    }
+    // Update requests must be signed (or come from a client with a verified certificate),
+    // unless explicitly allowed (local development)
+    match config.update_keys_file {
+        None => {
+            if config.allow_unsigned_updates {
+                println!("WARNING: accepting unsigned update requests");
+            } else if !updater_verifies_clients {
+                println!("No update keys file, all update requests will be rejected");
+            }
+        }
+        Some(ref update_keys_file) => {
+            match UpdateAuthenticator::new(update_keys_file) {
+                Err(message) => {
+                    eprintln!("{}", message);
+                    process::exit(1);
+                }
+                Ok(update_authenticator) => {
+                    updater.set_update_authenticator(update_authenticator);
+                }
+            }
+        }
+    }
+    updater.set_unsigned_updates_allowed(config.allow_unsigned_updates);
 
     /**
     Cache moving logic
@@ -40,7 +165,37 @@ fn main() {
         Probably periodically throughout the 24 hour period, as the counts are locked down for
             each timezone.
 
+    Lock-down and rollover of each timezone are scheduled jobs (see app::jobs), initializing the
+    vote counts of the new current periods is still to be done.
     */
 
-    Server::start_small_load_optimized(server, "0.0.0.0", 4321, 5432);
+    let update_writer = match Updater::start(updater, &config) {
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(1);
+        }
+        Ok(update_writer) => {
+            update_writer
+        }
+    };
+    schedule_jobs(&mut scheduler, &update_writer, &config, page_watchers, page_cache);
+    Scheduler::start(scheduler);
+    if let Err(message) = Server::start_small_load_optimized(server, &config) {
+        eprintln!("{}", message);
+        process::exit(1);
+    }
+}
+
+fn get_tls_acceptor(
+    tls_config: &TlsConfig
+) -> TlsAcceptor {
+    match TlsAcceptor::new(tls_config) {
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(1);
+        }
+        Ok(tls_acceptor) => {
+            tls_acceptor
+        }
+    }
 }
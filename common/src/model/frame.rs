@@ -0,0 +1,171 @@
+//! Framing of the raw (non-HTTP) TCP protocol.  All numbers are Big Endian.
+//!
+//! Request frame:
+//!
+//! ```text
+//! length              u32     Size of the rest of the frame
+//! request id          u32     Chosen by the client, echoed in the response
+//! protocol version    u16     FRAME_DEFAULT_PROTOCOL_VERSION for the server's default
+//! route length        u8
+//! route               [u8]    Route id, same as the HTTP path ("0", "+1", "~2", "@" ...)
+//! body                [u8]    Same as the HTTP request body
+//! ```
+//!
+//! Response frame:
+//!
+//! ```text
+//! length              u32     Size of the rest of the frame
+//! request id          u32
+//! body                [u8]    Same as the HTTP response body
+//! ```
+//!
+//! Requests on a connection are served concurrently and responses are sent as they complete,
+//! so (long-poll) responses can come back in a different order than the requests were sent.
+//! Event streams are only served over HTTP.  A malformed frame closes the connection.
+//!
+//! Clients encode requests with encode_request_frame and decode responses with
+//! decode_response_frame, matching them up by request id.  Connecting, pipelining and retrying
+//! are up to the client.
+
+/**
+ *  Size of the length field that every frame starts with.
+ */
+pub const FRAME_LENGTH_SIZE: usize = 4;
+
+/**
+ *  Size of the request id, protocol version and route length fields of a request frame.
+ */
+pub const REQUEST_FRAME_HEADER_SIZE: usize = 4 + 2 + 1;
+
+/**
+ *  Size of the request id field of a response frame.
+ */
+pub const RESPONSE_FRAME_HEADER_SIZE: usize = 4;
+
+pub const MAX_FRAME_ROUTE_LENGTH: usize = 255;
+
+pub const FRAME_DEFAULT_PROTOCOL_VERSION: u16 = 0;
+
+/**
+ *  A request frame, ready to be written out.  None if the route is too long to frame.
+ */
+pub fn encode_request_frame(
+    request_id: u32,
+    protocol_version: u16,
+    route: &str,
+    body: &[u8],
+) -> Option<Vec<u8>> {
+    if route.len() > MAX_FRAME_ROUTE_LENGTH {
+        return None;
+    }
+    let length = REQUEST_FRAME_HEADER_SIZE + route.len() + body.len();
+    if length > u32::MAX as usize {
+        return None;
+    }
+
+    let mut frame: Vec<u8> = Vec::with_capacity(FRAME_LENGTH_SIZE + length);
+    frame.extend_from_slice(&(length as u32).to_be_bytes());
+    frame.extend_from_slice(&request_id.to_be_bytes());
+    frame.extend_from_slice(&protocol_version.to_be_bytes());
+    frame.push(route.len() as u8);
+    frame.extend_from_slice(route.as_bytes());
+    frame.extend_from_slice(body);
+
+    Some(frame)
+}
+
+/**
+ *  A response frame, decoded from the start of what has been read so far.
+ */
+pub struct ResponseFrame<'a> {
+    pub request_id: u32,
+    pub body: &'a [u8],
+    // Bytes taken by the frame, the next frame starts after them
+    pub frame_size: usize,
+}
+
+/**
+ *  Decodes the response frame at the start of the buffer.  Ok(None) until the whole frame has
+ *  been read, Err if the frame is malformed (the connection can't be read any further then).
+ */
+pub fn decode_response_frame<'a>(
+    buf: &'a [u8]
+) -> Result<Option<ResponseFrame<'a>>, String> {
+    if buf.len() < FRAME_LENGTH_SIZE {
+        return Ok(None);
+    }
+    let length = read_u32(&buf[..FRAME_LENGTH_SIZE]) as usize;
+    if length < RESPONSE_FRAME_HEADER_SIZE {
+        return Err(format!("Response frame of {} bytes is shorter than its header", length));
+    }
+    if buf.len() < FRAME_LENGTH_SIZE + length {
+        return Ok(None);
+    }
+
+    let body_start = FRAME_LENGTH_SIZE + RESPONSE_FRAME_HEADER_SIZE;
+    Ok(Some(ResponseFrame {
+        request_id: read_u32(&buf[FRAME_LENGTH_SIZE..body_start]),
+        body: &buf[body_start..FRAME_LENGTH_SIZE + length],
+        frame_size: FRAME_LENGTH_SIZE + length,
+    }))
+}
+
+#[inline]
+fn read_u32(
+    bytes: &[u8]
+) -> u32 {
+    let mut be_bytes = [0u8; 4];
+    be_bytes.copy_from_slice(bytes);
+
+    u32::from_be_bytes(be_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_response_frame;
+    use super::encode_request_frame;
+    use super::FRAME_DEFAULT_PROTOCOL_VERSION;
+    use super::MAX_FRAME_ROUTE_LENGTH;
+
+    #[test]
+    fn request_frame() {
+        let frame = encode_request_frame(7, FRAME_DEFAULT_PROTOCOL_VERSION, "~2", &[1, 2, 3]).unwrap();
+        assert_eq!(frame, vec![
+            0, 0, 0, 12,
+            0, 0, 0, 7,
+            0, 0,
+            2, b'~', b'2',
+            1, 2, 3,
+        ]);
+
+        assert!(encode_request_frame(1, 1, &"r".repeat(MAX_FRAME_ROUTE_LENGTH), &[]).is_some());
+        assert!(encode_request_frame(1, 1, &"r".repeat(MAX_FRAME_ROUTE_LENGTH + 1), &[]).is_none());
+    }
+
+    #[test]
+    fn response_frames() {
+        let buf = [
+            0, 0, 0, 6, 0, 0, 0, 9, 0xAB, 0xCD,
+            0, 0, 0, 4, 0, 0, 0, 3,
+            0, 0, 0,
+        ];
+
+        let first = decode_response_frame(&buf).unwrap().unwrap();
+        assert_eq!(first.request_id, 9);
+        assert_eq!(first.body, &[0xAB, 0xCD]);
+        assert_eq!(first.frame_size, 10);
+
+        let second = decode_response_frame(&buf[first.frame_size..]).unwrap().unwrap();
+        assert_eq!(second.request_id, 3);
+        assert!(second.body.is_empty());
+
+        // Partial frames
+        assert!(decode_response_frame(&buf[..9]).unwrap().is_none());
+        assert!(decode_response_frame(&buf[18..]).unwrap().is_none());
+    }
+
+    #[test]
+    fn malformed_response_frame() {
+        assert!(decode_response_frame(&[0, 0, 0, 3, 0, 0, 0]).is_err());
+    }
+}
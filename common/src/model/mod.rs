@@ -1,4 +1,6 @@
 pub mod consts;
+pub mod frame;
+pub mod protocol;
 pub mod response;
 pub mod timezone;
 pub mod types;
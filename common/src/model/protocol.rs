@@ -0,0 +1,15 @@
+/**
+ *  Version of the request and response formats.  Requests that do not ask for a version are
+ *  served the original (version 1) formats, so that deployed clients keep working.
+ */
+pub type ProtocolVersion = u16;
+
+pub const PROTOCOL_VERSION_1: ProtocolVersion = 1;
+
+pub const DEFAULT_PROTOCOL_VERSION: ProtocolVersion = PROTOCOL_VERSION_1;
+
+/**
+ *  Header to ask for a protocol version with (if the path has no version prefix), responses
+ *  carry it with the version they were encoded in.
+ */
+pub const PROTOCOL_VERSION_HEADER: &str = "X-VC-Protocol";
@@ -6,3 +6,7 @@ pub const RESPONSE_INVALID_GLOBAL_LOCATION_ID_FLAG: u8 = 4;
 pub const RESPONSE_INVALID_LOCATION_CACHE_INDEX_FLAG: u8 = 5;
 pub const RESPONSE_INVALID_PERIOD_ID_FLAG: u8 = 6;
 pub const RESPONSE_INVALID_TIMEZONE_ID_FLAG: u8 = 7;
+pub const RESPONSE_UNSUPPORTED_PROTOCOL_VERSION_FLAG: u8 = 8;
+pub const RESPONSE_UNAUTHORIZED_FLAG: u8 = 9;
+pub const RESPONSE_RATE_LIMITED_FLAG: u8 = 10;
+pub const RESPONSE_UPDATES_BUSY_FLAG: u8 = 11;
@@ -42,3 +42,55 @@ pub const UTC_MINUS_10: usize = 35; // Hawaii/USA and 2 more	HST	Honolulu
 pub const UTC_MINUS_11: usize = 36; // American Samoa and 2 more	NUT	Alofi
 pub const UTC_MINUS_12: usize = 37; // much of US Minor Outlying Islands	AoE	Baker Island
 pub const ALL_TIME_ZONES: usize = 38; // all time zones
+
+/**
+ * UTC offsets (in minutes) of the time zones above, by time zone index.  Offsets are those of
+ * the example locations at the time of writing (hence the daylight savings abbreviations).
+ */
+pub const UTC_OFFSETS_IN_MINUTES: [i32; NUM_TIMEZONES as usize] = [
+    14 * 60, // UTC_PLUS_14
+    13 * 60, // UTC_PLUS_13
+    12 * 60 + 45, // UTC_PLUS_12_45
+    12 * 60, // UTC_PLUS_12
+    11 * 60, // UTC_PLUS_11
+    10 * 60 + 30, // UTC_PLUS_10_30
+    10 * 60, // UTC_PLUS_10
+    9 * 60 + 30, // UTC_PLUS_9_30
+    9 * 60, // UTC_PLUS_9
+    8 * 60 + 45, // UTC_PLUS_8_45
+    8 * 60, // UTC_PLUS_8
+    7 * 60, // UTC_PLUS_7
+    6 * 60 + 30, // UTC_PLUS_6_30
+    6 * 60, // UTC_PLUS_6
+    5 * 60 + 45, // UTC_PLUS_5_45
+    5 * 60 + 30, // UTC_PLUS_5_30
+    5 * 60, // UTC_PLUS_5
+    4 * 60 + 30, // UTC_PLUS_4_30
+    4 * 60, // UTC_PLUS_4
+    3 * 60 + 30, // UTC_PLUS_3_30
+    3 * 60, // UTC_PLUS_3
+    2 * 60, // UTC_PLUS_2
+    60, // UTC_PLUS_1
+    0, // UTC_PLUS_0
+    -60, // UTC_MINUS_1
+    -2 * 60, // UTC_MINUS_2
+    -(2 * 60 + 30), // UTC_MINUS_2_30
+    -3 * 60, // UTC_MINUS_3
+    -4 * 60, // UTC_MINUS_4
+    -5 * 60, // UTC_MINUS_5
+    -6 * 60, // UTC_MINUS_6
+    -7 * 60, // UTC_MINUS_7
+    -8 * 60, // UTC_MINUS_8
+    -9 * 60, // UTC_MINUS_9
+    -(9 * 60 + 30), // UTC_MINUS_9_30
+    -10 * 60, // UTC_MINUS_10
+    -11 * 60, // UTC_MINUS_11
+    -12 * 60, // UTC_MINUS_12
+];
+
+/**
+ * Global (across all time zones) periods roll over at the poll add deadline on the US West
+ * Coast (8PM PDT), see PollsByLabel.
+ */
+pub const GLOBAL_ROLLOVER_TIMEZONE: usize = UTC_MINUS_7;
+pub const GLOBAL_ROLLOVER_LOCAL_TIME_IN_MINUTES: i32 = 20 * 60;
@@ -1 +1,3 @@
-
+pub const URL_NEXT_MONTHS_LABEL_POLLS: &str = "0";
+pub const URL_NEXT_WEEKS_LABEL_POLLS: &str = "1";
+pub const URL_FUTURE_DAYS_LABEL_POLLS: &str = "2";
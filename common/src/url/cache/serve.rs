@@ -67,3 +67,67 @@ pub const URL_YESTERDAYS_LOCATION_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEXES: &str
 pub const URL_DAY_B4_YESTERDAY_LOCATION_YESTERDAY_CATEGORY_POLL_RANKINGS_BY_GLOBAL_IDS: &str = "W";
 pub const URL_DAY_B4_YESTERDAY_LOCATION_YESTERDAY_CATEGORY_POLL_RANKINGS_BY_LOCATION_CACHE_INDEX_AND_GLOBAL_CATEGORY_ID: &str = "X";
 pub const URL_DAY_B4_YESTERDAY_LOCATION_YESTERDAY_CATEGORY_POLL_RANKINGS_BY_CACHE_INDEXES: &str = "Y";
+
+pub const URL_TIME_PERIOD_IDS: &str = "Z";
+
+/*
+ * Poll ids added since a given sequence number, prefixed version of the future label poll routes
+ */
+pub const URL_NEXT_MONTHS_CATEGORY_POLLS_SINCE: &str = "+i";
+pub const URL_NEXT_WEEKS_CATEGORY_POLLS_SINCE: &str = "+j";
+pub const URL_TOMORROWS_CATEGORY_POLLS_SINCE: &str = "+k";
+pub const URL_DAY_AFTER_TOMORROWS_CATEGORY_POLLS_SINCE: &str = "+l";
+
+/*
+ * Prefix for any poll rankings route, to wait for the ranking page to change before responding.
+ * The request body is the data version (u32, Big Endian) of the page the client already has,
+ * followed by the body of the rankings request.  If the page has changed since that version,
+ * it is responded with right away (the data version moving on without changing the page
+ * doesn't count).
+ */
+pub const URL_LONG_POLL_PREFIX: &str = "~";
+
+/*
+ * Prefix for event stream routes, responded to with a text/event-stream that stays open
+ */
+pub const URL_EVENT_STREAM_PREFIX: &str = "!";
+
+/*
+ * Poll ids added to future periods, for a set of labels, locations and location labels
+ */
+pub const URL_FUTURE_POLLS_EVENTS: &str = "!p";
+
+/*
+ * Protocol version prefix, "@<version>/" in front of a route (and of its long-poll or event
+ * stream prefix) selects the request and response formats of that version
+ */
+pub const URL_PROTOCOL_VERSION_PREFIX: &str = "@";
+
+/*
+ * Protocol versions supported by the server: a count byte followed by the (u16) versions
+ */
+pub const URL_PROTOCOL_VERSIONS: &str = "@";
+
+/*
+ * Human readable (GET, query string parameters, JSON response) routes, only served when
+ * debug routes are enabled
+ */
+pub const URL_DEBUG_PREFIX: &str = "/debug/";
+
+pub const URL_DEBUG_LABEL_RANKINGS: &str = "/debug/rankings/label";
+pub const URL_DEBUG_LOCATION_RANKINGS: &str = "/debug/rankings/location";
+pub const URL_DEBUG_LOCATION_LABEL_RANKINGS: &str = "/debug/rankings/location_label";
+
+pub const URL_DEBUG_LABEL_POLLS: &str = "/debug/recent/label";
+pub const URL_DEBUG_LOCATION_POLLS: &str = "/debug/recent/location";
+pub const URL_DEBUG_LOCATION_LABEL_POLLS: &str = "/debug/recent/location_label";
+
+pub const URL_DEBUG_LABEL_POLLS_SINCE: &str = "/debug/since/label";
+
+pub const URL_DEBUG_TIME_PERIOD_IDS: &str = "/debug/period_ids";
+
+// All registered routes, with their request body schemas
+pub const URL_DEBUG_ROUTES: &str = "/debug/routes";
+
+// Scheduled jobs, with the status of their last run
+pub const URL_DEBUG_JOBS: &str = "/debug/jobs";
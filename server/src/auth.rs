@@ -0,0 +1,519 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use hex;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use super::cache::producers::PRODUCER_ID_HEADER;
+use super::cache::producers::SEQUENCE_HEADER;
+use super::request::Request;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Id of the key the request is signed with
+pub const KEY_ID_HEADER: &str = "X-VC-Key-Id";
+/// Unix time (in seconds) the request was signed at
+pub const TIMESTAMP_HEADER: &str = "X-VC-Timestamp";
+/// Unique (per key, within the allowed clock skew) value chosen by the client
+pub const NONCE_HEADER: &str = "X-VC-Nonce";
+/// Hex encoded HMAC-SHA256 of the signed message
+pub const SIGNATURE_HEADER: &str = "X-VC-Signature";
+
+/// How far the request timestamp may be from the server's clock.  Nonces are remembered for
+/// this long (on either side), so a replayed request is rejected either way.
+const MAX_CLOCK_SKEW_SECONDS: u64 = 300;
+
+const MAX_NONCE_LENGTH: usize = 64;
+
+/// How often the keys file is checked for changes
+const KEYS_CHECK_INTERVAL_SECONDS: u64 = 5;
+
+/// Why a request was not authenticated
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AuthError {
+    MissingHeaders,
+    UnknownKey,
+    StaleTimestamp,
+    ReplayedNonce,
+    BadSignature,
+}
+
+struct Keys {
+    by_id: HashMap<String, Vec<u8>>,
+    modified: Option<SystemTime>,
+    last_checked: Instant,
+}
+
+///
+/// Authenticates update requests signed with a shared key.
+///
+/// The signed message is the method, route, protocol version, timestamp, nonce, producer id
+/// and sequence (each followed by a newline) and then the request body:
+///
+/// ```text
+/// PUT\n<route>\n<protocol version>\n<timestamp>\n<nonce>\n<producer id>\n<sequence>\n<body>
+/// ```
+///
+/// The protocol version is the one asked for (by path prefix or header, the route is without
+/// the prefix), empty if the request doesn't ask for one.  The producer id and sequence (see
+/// cache::producers) are empty for requests that aren't sequenced.
+///
+/// Keys are read from a file with one "<key id> <hex encoded key>" per line (blank lines and
+/// lines starting with '#' are ignored).  The file is re-read when it changes, so keys are
+/// rotated by adding the new key, moving clients over to it and then removing the old one.
+///
+pub struct UpdateAuthenticator {
+    keys_path: PathBuf,
+    keys: Mutex<Keys>,
+    // Nonces seen (by key id and nonce), with their timestamps
+    nonces: Mutex<HashMap<(String, String), u64>>,
+}
+
+impl UpdateAuthenticator {
+
+    pub fn new(
+        keys_path: &Path
+    ) -> Result<UpdateAuthenticator, String> {
+        let modified = get_modified(keys_path);
+        let by_id = read_keys(keys_path)?;
+
+        Ok(UpdateAuthenticator {
+            keys_path: keys_path.to_path_buf(),
+            keys: Mutex::new(Keys {
+                by_id,
+                modified,
+                last_checked: Instant::now(),
+            }),
+            nonces: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn authenticate(
+        &self,
+        request: &Request,
+    ) -> Result<(), AuthError> {
+        let key_id = get_header(request, KEY_ID_HEADER)?;
+        let timestamp = get_header(request, TIMESTAMP_HEADER)?;
+        let nonce = get_header(request, NONCE_HEADER)?;
+        let signature = get_header(request, SIGNATURE_HEADER)?;
+
+        if nonce.is_empty() || nonce.len() > MAX_NONCE_LENGTH {
+            return Err(AuthError::MissingHeaders);
+        }
+        // Signed as asked for, so that it can't be changed (or added, or removed) in transit
+        let protocol_version = match request.protocol_version() {
+            Err(_) => {
+                return Err(AuthError::BadSignature);
+            }
+            Ok(None) => {
+                String::new()
+            }
+            Ok(Some(protocol_version)) => {
+                protocol_version.to_string()
+            }
+        };
+        let signed_at = timestamp.parse::<u64>().map_err(|_| AuthError::StaleTimestamp)?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        // Saturating, the timestamp is not yet authenticated
+        if signed_at.saturating_add(MAX_CLOCK_SKEW_SECONDS) < now
+            || now.saturating_add(MAX_CLOCK_SKEW_SECONDS) < signed_at {
+            return Err(AuthError::StaleTimestamp);
+        }
+        let signature = hex::decode(signature).map_err(|_| AuthError::BadSignature)?;
+
+        let mut mac = {
+            let mut keys = self.keys.lock().unwrap();
+            self.reload_if_changed(&mut keys);
+            let key = keys.by_id.get(key_id).ok_or(AuthError::UnknownKey)?;
+            HmacSha256::new_varkey(key).map_err(|_| AuthError::UnknownKey)?
+        };
+        mac.input(request.method().as_bytes());
+        mac.input(b"\n");
+        mac.input(request.route().as_bytes());
+        mac.input(b"\n");
+        mac.input(protocol_version.as_bytes());
+        mac.input(b"\n");
+        mac.input(timestamp.as_bytes());
+        mac.input(b"\n");
+        mac.input(nonce.as_bytes());
+        mac.input(b"\n");
+        // Always signed (empty if not sent), so that they can't be added to or stripped from a
+        // signed request
+        mac.input(get_header(request, PRODUCER_ID_HEADER).unwrap_or("").as_bytes());
+        mac.input(b"\n");
+        mac.input(get_header(request, SEQUENCE_HEADER).unwrap_or("").as_bytes());
+        mac.input(b"\n");
+        mac.input(request.raw_body());
+        mac.verify(&signature).map_err(|_| AuthError::BadSignature)?;
+
+        // Only signed requests get to use up nonces
+        let mut nonces = self.nonces.lock().unwrap();
+        nonces.retain(|_, seen_at| seen_at.saturating_add(MAX_CLOCK_SKEW_SECONDS) >= now);
+        let nonce_key = (key_id.to_string(), nonce.to_string());
+        if nonces.contains_key(&nonce_key) {
+            return Err(AuthError::ReplayedNonce);
+        }
+        nonces.insert(nonce_key, signed_at);
+
+        Ok(())
+    }
+
+    fn reload_if_changed(
+        &self,
+        keys: &mut Keys,
+    ) {
+        if keys.last_checked.elapsed() < Duration::from_secs(KEYS_CHECK_INTERVAL_SECONDS) {
+            return;
+        }
+        keys.last_checked = Instant::now();
+
+        let modified = get_modified(&self.keys_path);
+        if modified == keys.modified {
+            return;
+        }
+        // A file that can't be read (or is being written) keeps the current keys until the
+        // next check
+        match read_keys(&self.keys_path) {
+            Err(message) => {
+                eprintln!("Keeping current update keys: {}", message);
+            }
+            Ok(by_id) => {
+                println!("Reloaded {} update key(s) from {}", by_id.len(), self.keys_path.display());
+                keys.by_id = by_id;
+                keys.modified = modified;
+            }
+        }
+    }
+}
+
+#[inline]
+fn get_header<'a>(
+    request: &'a Request,
+    name: &str,
+) -> Result<&'a str, AuthError> {
+    request.header(name)
+        .and_then(|value| str::from_utf8(value).ok())
+        .map(|value| value.trim())
+        .ok_or(AuthError::MissingHeaders)
+}
+
+#[inline]
+fn get_modified(
+    keys_path: &Path
+) -> Option<SystemTime> {
+    fs::metadata(keys_path).and_then(|metadata| metadata.modified()).ok()
+}
+
+fn read_keys(
+    keys_path: &Path
+) -> Result<HashMap<String, Vec<u8>>, String> {
+    let contents = fs::read_to_string(keys_path)
+        .map_err(|err| format!("Could not read keys file {}: {}", keys_path.display(), err))?;
+
+    let mut keys = HashMap::new();
+    for (line_index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut parts = line.split_whitespace();
+        let key = match (parts.next(), parts.next(), parts.next()) {
+            (Some(key_id), Some(key), None) => {
+                hex::decode(key).ok().map(|key| (key_id.to_string(), key))
+            }
+            _ => {
+                None
+            }
+        };
+        match key {
+            Some((ref key_id, ref key)) if !key.is_empty() => {
+                keys.insert(key_id.clone(), key.clone());
+            }
+            _ => {
+                return Err(format!("Invalid key on line {} of {}", line_index + 1, keys_path.display()));
+            }
+        }
+    }
+    if keys.is_empty() {
+        return Err(format!("No keys in {}", keys_path.display()));
+    }
+
+    Ok(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+    use bytes::BytesMut;
+    use hex;
+    use hmac::Mac;
+
+    use super::super::request::decode;
+    use super::super::request::Request;
+    use super::AuthError;
+    use super::HmacSha256;
+    use super::UpdateAuthenticator;
+    use super::KEYS_CHECK_INTERVAL_SECONDS;
+    use super::MAX_CLOCK_SKEW_SECONDS;
+
+    const KEY: &[u8] = b"0123456789abcdef";
+    const OTHER_KEY: &[u8] = b"fedcba9876543210";
+
+    struct KeysFile {
+        path: PathBuf,
+    }
+
+    impl KeysFile {
+        fn new(
+            name: &str,
+            keys: &[(&str, &[u8])],
+        ) -> KeysFile {
+            let keys_file = KeysFile {
+                path: env::temp_dir().join(format!("vc-auth-test-{}-{}", name, ::std::process::id())),
+            };
+            keys_file.write(keys);
+
+            keys_file
+        }
+
+        fn write(
+            &self,
+            keys: &[(&str, &[u8])],
+        ) {
+            let contents: Vec<String> = keys.iter()
+                .map(|&(key_id, key)| format!("{} {}", key_id, hex::encode(key))).collect();
+            fs::write(&self.path, format!("# update keys\n\n{}\n", contents.join("\n"))).unwrap();
+        }
+    }
+
+    impl Drop for KeysFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+
+    fn now() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+    }
+
+    fn sign(
+        key: &[u8],
+        message: &[u8],
+    ) -> String {
+        let mut mac = HmacSha256::new_varkey(key).unwrap();
+        mac.input(message);
+
+        hex::encode(mac.result().code())
+    }
+
+    ///
+    /// Signed update request, producer is Some((producer id, sequence)) for sequenced requests.
+    ///
+    fn signed_request(
+        key_id: &str,
+        key: &[u8],
+        timestamp: u64,
+        nonce: &str,
+        producer: Option<(&str, &str)>,
+        body: &[u8],
+    ) -> Request {
+        let (producer_id, sequence) = producer.unwrap_or(("", ""));
+        let mut message = format!("PUT\nu\n\n{}\n{}\n{}\n{}\n", timestamp, nonce, producer_id, sequence).into_bytes();
+        message.extend_from_slice(body);
+
+        let mut headers = format!("X-VC-Key-Id: {}\r\nX-VC-Timestamp: {}\r\nX-VC-Nonce: {}\r\nX-VC-Signature: {}\r\n",
+                                  key_id, timestamp, nonce, sign(key, &message));
+        if let Some((producer_id, sequence)) = producer {
+            headers.push_str(&format!("X-VC-Producer-Id: {}\r\nX-VC-Sequence: {}\r\n", producer_id, sequence));
+        }
+
+        request(&headers, body)
+    }
+
+    fn request(
+        headers: &str,
+        body: &[u8],
+    ) -> Request {
+        request_to("u", headers, body)
+    }
+
+    fn request_to(
+        path: &str,
+        headers: &str,
+        body: &[u8],
+    ) -> Request {
+        let mut data = format!("PUT {} HTTP/1.1\r\nContent-Length: {}\r\n{}\r\n", path, body.len(), headers).into_bytes();
+        data.extend_from_slice(body);
+
+        decode(&mut BytesMut::from(data), body.len()).unwrap().unwrap()
+    }
+
+    #[test]
+    fn signed_requests() {
+        let keys_file = KeysFile::new("signed", &[("k1", KEY)]);
+        let authenticator = UpdateAuthenticator::new(&keys_file.path).unwrap();
+
+        assert_eq!(authenticator.authenticate(
+            &signed_request("k1", KEY, now(), "n1", None, b"body")), Ok(()));
+        assert_eq!(authenticator.authenticate(
+            &signed_request("k1", KEY, now(), "n2", Some(("p1", "7")), b"body")), Ok(()));
+
+        assert_eq!(authenticator.authenticate(
+            &signed_request("k2", KEY, now(), "n3", None, b"body")), Err(AuthError::UnknownKey));
+        assert_eq!(authenticator.authenticate(
+            &signed_request("k1", OTHER_KEY, now(), "n4", None, b"body")), Err(AuthError::BadSignature));
+        assert_eq!(authenticator.authenticate(
+            &request("X-VC-Key-Id: k1\r\n", b"body")), Err(AuthError::MissingHeaders));
+    }
+
+    #[test]
+    fn producer_headers_are_signed() {
+        let keys_file = KeysFile::new("producer", &[("k1", KEY)]);
+        let authenticator = UpdateAuthenticator::new(&keys_file.path).unwrap();
+        let timestamp = now();
+
+        // Producer headers added to a request signed without them
+        let signature = sign(KEY, format!("PUT\nu\n\n{}\nn1\n\n\nbody", timestamp).as_bytes());
+        let added = request(&format!(
+            "X-VC-Key-Id: k1\r\nX-VC-Timestamp: {}\r\nX-VC-Nonce: n1\r\nX-VC-Signature: {}\r\n\
+             X-VC-Producer-Id: p1\r\nX-VC-Sequence: 1\r\n", timestamp, signature), b"body");
+        assert_eq!(authenticator.authenticate(&added), Err(AuthError::BadSignature));
+
+        // Producer headers stripped from a request signed with them
+        let signature = sign(KEY, format!("PUT\nu\n\n{}\nn2\np1\n1\nbody", timestamp).as_bytes());
+        let stripped = request(&format!(
+            "X-VC-Key-Id: k1\r\nX-VC-Timestamp: {}\r\nX-VC-Nonce: n2\r\nX-VC-Signature: {}\r\n",
+            timestamp, signature), b"body");
+        assert_eq!(authenticator.authenticate(&stripped), Err(AuthError::BadSignature));
+
+        // Sequence changed
+        let signature = sign(KEY, format!("PUT\nu\n\n{}\nn3\np1\n1\nbody", timestamp).as_bytes());
+        let changed = request(&format!(
+            "X-VC-Key-Id: k1\r\nX-VC-Timestamp: {}\r\nX-VC-Nonce: n3\r\nX-VC-Signature: {}\r\n\
+             X-VC-Producer-Id: p1\r\nX-VC-Sequence: 2\r\n", timestamp, signature), b"body");
+        assert_eq!(authenticator.authenticate(&changed), Err(AuthError::BadSignature));
+    }
+
+    #[test]
+    fn protocol_version_is_signed() {
+        let keys_file = KeysFile::new("protocol", &[("k1", KEY)]);
+        let authenticator = UpdateAuthenticator::new(&keys_file.path).unwrap();
+        let timestamp = now();
+        let signed_headers = |nonce: &str, protocol_version: &str| format!(
+            "X-VC-Key-Id: k1\r\nX-VC-Timestamp: {}\r\nX-VC-Nonce: {}\r\nX-VC-Signature: {}\r\n",
+            timestamp, nonce,
+            sign(KEY, format!("PUT\nu\n{}\n{}\n{}\n\n\nbody", protocol_version, timestamp, nonce).as_bytes()));
+
+        // Asked for by path prefix or by header, either way the version is signed
+        assert_eq!(authenticator.authenticate(
+            &request_to("@2/u", &signed_headers("n1", "2"), b"body")), Ok(()));
+        assert_eq!(authenticator.authenticate(
+            &request(&format!("{}X-VC-Protocol: 2\r\n", signed_headers("n2", "2")), b"body")), Ok(()));
+
+        // Version changed
+        assert_eq!(authenticator.authenticate(
+            &request_to("@3/u", &signed_headers("n3", "2"), b"body")), Err(AuthError::BadSignature));
+        assert_eq!(authenticator.authenticate(
+            &request(&format!("{}X-VC-Protocol: 3\r\n", signed_headers("n4", "2")), b"body")),
+                   Err(AuthError::BadSignature));
+        // Version added to a request signed without one
+        assert_eq!(authenticator.authenticate(
+            &request_to("@2/u", &signed_headers("n5", ""), b"body")), Err(AuthError::BadSignature));
+        assert_eq!(authenticator.authenticate(
+            &request(&format!("{}X-VC-Protocol: 2\r\n", signed_headers("n6", "")), b"body")),
+                   Err(AuthError::BadSignature));
+    }
+
+    #[test]
+    fn timestamp_window() {
+        let keys_file = KeysFile::new("timestamp", &[("k1", KEY)]);
+        let authenticator = UpdateAuthenticator::new(&keys_file.path).unwrap();
+        // A second of slack, in case the clock ticks over during the test
+        let skew = MAX_CLOCK_SKEW_SECONDS - 1;
+
+        assert_eq!(authenticator.authenticate(
+            &signed_request("k1", KEY, now() - skew, "n1", None, b"")), Ok(()));
+        assert_eq!(authenticator.authenticate(
+            &signed_request("k1", KEY, now() + skew, "n2", None, b"")), Ok(()));
+
+        let skew = MAX_CLOCK_SKEW_SECONDS + 2;
+        assert_eq!(authenticator.authenticate(
+            &signed_request("k1", KEY, now() - skew, "n3", None, b"")), Err(AuthError::StaleTimestamp));
+        assert_eq!(authenticator.authenticate(
+            &signed_request("k1", KEY, now() + skew, "n4", None, b"")), Err(AuthError::StaleTimestamp));
+
+        // Timestamps at the end of the range don't overflow the window
+        assert_eq!(authenticator.authenticate(
+            &signed_request("k1", KEY, u64::max_value(), "n5", None, b"")), Err(AuthError::StaleTimestamp));
+        assert_eq!(authenticator.authenticate(
+            &signed_request("k1", KEY, u64::max_value() - MAX_CLOCK_SKEW_SECONDS / 2, "n6", None, b"")),
+                   Err(AuthError::StaleTimestamp));
+    }
+
+    #[test]
+    fn replayed_nonce() {
+        let keys_file = KeysFile::new("nonce", &[("k1", KEY), ("k2", OTHER_KEY)]);
+        let authenticator = UpdateAuthenticator::new(&keys_file.path).unwrap();
+        let timestamp = now();
+
+        let request = signed_request("k1", KEY, timestamp, "n1", None, b"body");
+        assert_eq!(authenticator.authenticate(&request), Ok(()));
+        assert_eq!(authenticator.authenticate(&request), Err(AuthError::ReplayedNonce));
+        assert_eq!(authenticator.authenticate(
+            &signed_request("k1", KEY, timestamp, "n1", None, b"other body")), Err(AuthError::ReplayedNonce));
+
+        // Nonces are per key
+        assert_eq!(authenticator.authenticate(
+            &signed_request("k2", OTHER_KEY, timestamp, "n1", None, b"body")), Ok(()));
+        // Badly signed requests don't use up nonces
+        assert_eq!(authenticator.authenticate(
+            &signed_request("k1", OTHER_KEY, timestamp, "n2", None, b"body")), Err(AuthError::BadSignature));
+        assert_eq!(authenticator.authenticate(
+            &signed_request("k1", KEY, timestamp, "n2", None, b"body")), Ok(()));
+    }
+
+    ///
+    /// Has the keys file checked on the next request, as if it was modified since (file
+    /// modification times can be too coarse to tell within a test).
+    ///
+    fn expire_keys_check(
+        authenticator: &UpdateAuthenticator
+    ) {
+        let mut keys = authenticator.keys.lock().unwrap();
+        keys.last_checked = Instant::now() - Duration::from_secs(KEYS_CHECK_INTERVAL_SECONDS);
+        keys.modified = None;
+    }
+
+    #[test]
+    fn key_reload() {
+        let keys_file = KeysFile::new("reload", &[("k1", KEY)]);
+        let authenticator = UpdateAuthenticator::new(&keys_file.path).unwrap();
+
+        keys_file.write(&[("k2", OTHER_KEY)]);
+        // Not checked again until the interval is up
+        assert_eq!(authenticator.authenticate(
+            &signed_request("k1", KEY, now(), "n1", None, b"")), Ok(()));
+
+        expire_keys_check(&authenticator);
+        assert_eq!(authenticator.authenticate(
+            &signed_request("k2", OTHER_KEY, now(), "n2", None, b"")), Ok(()));
+        assert_eq!(authenticator.authenticate(
+            &signed_request("k1", KEY, now(), "n3", None, b"")), Err(AuthError::UnknownKey));
+
+        // An invalid file keeps the current keys
+        fs::write(&keys_file.path, "k3 not-hex\n").unwrap();
+        expire_keys_check(&authenticator);
+        assert_eq!(authenticator.authenticate(
+            &signed_request("k2", OTHER_KEY, now(), "n4", None, b"")), Ok(()));
+    }
+}
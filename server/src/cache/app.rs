@@ -1,15 +1,89 @@
+use bytes::Bytes;
+
+use common::model::protocol::ProtocolVersion;
+use common::model::protocol::PROTOCOL_VERSION_1;
+
+use super::super::compress::{self, Encoding};
+
+use super::events::Topic;
+use super::watchers::PageKey;
+
 pub trait App {
 
+    // Response body, shared Bytes so that precomputed (or memoized) pages are written out
+    // without being copied
     fn get_response(
         &self,
+        protocol_version: ProtocolVersion,
+        path: &str,
+        request_body: &[u8],
+    ) -> Bytes;
+
+    // Response body along with its form compressed with the given encoding (None if it isn't
+    // worth compressing, see compress::compress_body).  Apps that memoize pages keep the
+    // compressed forms of them as well, instead of compressing them on every request.
+    fn get_compressed_response(
+        &self,
+        protocol_version: ProtocolVersion,
         path: &str,
         request_body: &[u8],
-    ) -> Vec<u8>;
+        encoding: Encoding,
+        min_size: usize,
+    ) -> (Bytes, Option<Bytes>) {
+        let data = self.get_response(protocol_version, path, request_body);
+        let compressed = compress::compress_body(encoding, &data, min_size);
+
+        (data, compressed)
+    }
+
+    // Page watched by a long-poll request, None if the request isn't for a watchable page
+    fn get_page_key(
+        &self,
+        _protocol_version: ProtocolVersion,
+        _path: &str,
+        _request_body: &[u8],
+    ) -> Option<PageKey> {
+        None
+    }
 
-    fn get_update_response(
+    // Protocol versions served, requests for other versions get the unsupported version
+    // response
+    fn get_protocol_versions(&self) -> &[ProtocolVersion] {
+        &[PROTOCOL_VERSION_1]
+    }
+
+    // Topics subscribed to by an event stream request, None if the request isn't a valid
+    // subscription
+    fn get_event_topics(
         &self,
         _path: &str,
         _request_body: &[u8],
-    ) -> Vec<u8>;
+    ) -> Option<Vec<Topic>> {
+        None
+    }
+
+    // JSON rendering of a debug (GET) route, None if there is no such route and an error
+    // message if the query parameters are invalid
+    fn get_debug_response(
+        &self,
+        _path: &str,
+        _query: &str,
+    ) -> Option<Result<Vec<u8>, String>> {
+        None
+    }
+
+    // Ids of the routes served, requests are recorded by route id (requests for other routes
+    // are recorded together) when metrics are enabled
+    fn get_route_ids(&self) -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    // Prometheus text rendering of the app's own metrics (sizes of its data), appended to the
+    // server's
+    fn get_metrics(
+        &self,
+        _out: &mut String,
+    ) {
+    }
 
 }
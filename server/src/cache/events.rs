@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use futures::sync::mpsc;
+
+/**
+ * Identifies a stream of events that can be subscribed to.  Computed by the App, both for
+ * incoming subscription requests and when publishing events.
+ */
+pub type Topic = u64;
+
+/**
+ * Encoded (ready to be written to the connection) event, shared by all subscribers of a topic.
+ */
+pub type Event = Arc<Vec<u8>>;
+
+/**
+ * Number of events buffered per subscriber, before the subscriber is considered too slow and
+ * is disconnected.
+ */
+const SUBSCRIBER_BUFFER_SIZE: usize = 256;
+
+struct Subscriber {
+    id: u64,
+    sender: mpsc::Sender<Event>,
+}
+
+struct Subscriptions {
+    by_topic: HashMap<Topic, Vec<Subscriber>>,
+    topics_by_subscriber: HashMap<u64, Vec<Topic>>,
+    last_subscriber_id: u64,
+}
+
+/**
+ *  Event stream subscribers, by topic.
+ *
+ *  Shared between all reactor threads (which register subscribers) and the update thread (which
+ *  publishes events).  A subscriber that falls behind by more than SUBSCRIBER_BUFFER_SIZE events
+ *  (or has disconnected) is dropped from all of its topics, which ends its stream.  Clients are
+ *  expected to re-subscribe and catch up on missed additions by sequence number.
+ */
+pub struct EventHub {
+    subscriptions: Mutex<Subscriptions>,
+}
+
+impl EventHub {
+    pub fn new() -> EventHub {
+        EventHub {
+            subscriptions: Mutex::new(Subscriptions {
+                by_topic: HashMap::new(),
+                topics_by_subscriber: HashMap::new(),
+                last_subscriber_id: 0,
+            }),
+        }
+    }
+
+    /**
+     * Registers a subscriber on a set of topics, the returned receiver yields events from all
+     * of them (in order of publishing).
+     */
+    pub fn subscribe(
+        &self,
+        topics: Vec<Topic>,
+    ) -> mpsc::Receiver<Event> {
+        let (sender, receiver) = mpsc::channel(SUBSCRIBER_BUFFER_SIZE);
+
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        subscriptions.last_subscriber_id += 1;
+        let subscriber_id = subscriptions.last_subscriber_id;
+
+        for topic in topics.iter() {
+            subscriptions.by_topic.entry(*topic).or_insert_with(Vec::new).push(Subscriber {
+                id: subscriber_id,
+                sender: sender.clone(),
+            });
+        }
+        subscriptions.topics_by_subscriber.insert(subscriber_id, topics);
+
+        receiver
+    }
+
+    /**
+     * True if anyone is subscribed to the topic, lets publishers skip encoding unwatched events.
+     */
+    pub fn has_subscribers(
+        &self,
+        topic: Topic,
+    ) -> bool {
+        self.subscriptions.lock().unwrap().by_topic.contains_key(&topic)
+    }
+
+    /**
+     * Sends an event to all subscribers of a topic.
+     */
+    pub fn publish(
+        &self,
+        topic: Topic,
+        event: Vec<u8>,
+    ) {
+        let event: Event = Arc::new(event);
+
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+
+        let mut dropped_subscriber_ids: Vec<u64> = Vec::new();
+        match subscriptions.by_topic.get_mut(&topic) {
+            None => {
+                return;
+            }
+            Some(subscribers) => {
+                for subscriber in subscribers.iter_mut() {
+                    if subscriber.sender.try_send(event.clone()).is_err() {
+                        // Buffer is full or the connection is gone
+                        dropped_subscriber_ids.push(subscriber.id);
+                    }
+                }
+            }
+        }
+
+        for subscriber_id in dropped_subscriber_ids {
+            subscriptions.drop_subscriber(subscriber_id);
+        }
+    }
+}
+
+impl Subscriptions {
+    fn drop_subscriber(
+        &mut self,
+        subscriber_id: u64,
+    ) {
+        let topics = match self.topics_by_subscriber.remove(&subscriber_id) {
+            None => {
+                return;
+            }
+            Some(topics) => {
+                topics
+            }
+        };
+
+        for topic in topics {
+            let no_subscribers_left = match self.by_topic.get_mut(&topic) {
+                None => {
+                    continue;
+                }
+                Some(subscribers) => {
+                    subscribers.retain(|subscriber| subscriber.id != subscriber_id);
+                    subscribers.is_empty()
+                }
+            };
+            if no_subscribers_left {
+                self.by_topic.remove(&topic);
+            }
+        }
+    }
+}
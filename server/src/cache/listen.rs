@@ -0,0 +1,242 @@
+#[cfg(unix)]
+use std::fs;
+use std::io;
+use std::net;
+use std::net::SocketAddr;
+#[cfg(unix)]
+use std::os::unix;
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::path::Path;
+
+use futures::{Async, Poll, Stream};
+use net2::TcpBuilder;
+#[cfg(not(windows))]
+use net2::unix::UnixTcpBuilderExt;
+
+use common::url::cache::serve::URL_EVENT_STREAM_PREFIX;
+
+use super::super::config::ListenAddress;
+use super::super::request::Request;
+
+/// A bound listener to accept connections on
+pub enum Listener {
+    Tcp(net::TcpListener),
+    #[cfg(unix)]
+    Unix(unix::net::UnixListener),
+}
+
+/// Binds the listeners of every thread up front, so that a listener that can't be bound is
+/// reported (Err, with a message) before any thread starts.  Each thread gets TCP listeners of
+/// its own (bound with SO_REUSEPORT, so that the kernel spreads connections across threads),
+/// Unix domain sockets are bound once (replacing stale socket files) and shared by all threads.
+pub fn bind_listeners(
+    addresses: &[ListenAddress],
+    num_threads: usize,
+    backlog: i32,
+) -> Result<Vec<Vec<Listener>>, String> {
+    let mut thread_listeners: Vec<Vec<Listener>> = (0..num_threads)
+        .map(|_| Vec::with_capacity(addresses.len())).collect();
+    for address in addresses.iter() {
+        match *address {
+            ListenAddress::Tcp(ref socket_addr) => {
+                for listeners in thread_listeners.iter_mut() {
+                    let listener = bind_tcp_listener(socket_addr, backlog).map_err(|err| {
+                        format!("Can't bind {}: {}", socket_addr, err)
+                    })?;
+                    listeners.push(Listener::Tcp(listener));
+                }
+            }
+            #[cfg(unix)]
+            ListenAddress::Unix(ref path) => {
+                remove_stale_socket(path)?;
+                let listener = unix::net::UnixListener::bind(path).map_err(|err| {
+                    format!("Can't bind {}: {}", path.display(), err)
+                })?;
+                for listeners in thread_listeners.iter_mut() {
+                    let listener = listener.try_clone().map_err(|err| {
+                        format!("Can't share {}: {}", path.display(), err)
+                    })?;
+                    listeners.push(Listener::Unix(listener));
+                }
+            }
+            #[cfg(not(unix))]
+            ListenAddress::Unix(ref path) => {
+                return Err(format!("Unix domain sockets are not supported on this platform: {}", path.display()));
+            }
+        }
+    }
+
+    Ok(thread_listeners)
+}
+
+/// Removes the socket file left behind by a previous run.  Anything other than a socket (or a
+/// socket that is still being listened on) is left in place, binding then fails.
+#[cfg(unix)]
+fn remove_stale_socket(path: &Path) -> Result<(), String> {
+    let metadata = match fs::symlink_metadata(path) {
+        Err(_) => {
+            // Nothing there
+            return Ok(());
+        }
+        Ok(metadata) => {
+            metadata
+        }
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(format!("Can't bind {}: not a socket", path.display()));
+    }
+    if unix::net::UnixStream::connect(path).is_ok() {
+        return Err(format!("Can't bind {}: already in use", path.display()));
+    }
+
+    fs::remove_file(path).map_err(|err| {
+        format!("Can't remove stale socket {}: {}", path.display(), err)
+    })
+}
+
+/// Binds a TCP listener, IPv6 listeners are IPv6 only so that they can be bound next to an
+/// IPv4 listener on the same port
+fn bind_tcp_listener(socket_addr: &SocketAddr, backlog: i32) -> io::Result<net::TcpListener> {
+    let builder = match *socket_addr {
+        SocketAddr::V4(_) => {
+            TcpBuilder::new_v4()?
+        }
+        SocketAddr::V6(_) => {
+            let builder = TcpBuilder::new_v6()?;
+            builder.only_v6(true)?;
+            builder
+        }
+    };
+    #[cfg(not(windows))]
+        builder.reuse_address(true)?;
+    #[cfg(not(windows))]
+        builder.reuse_port(true)?;
+    builder.bind(socket_addr)?;
+
+    builder.listen(backlog)
+}
+
+/// True if the connection is to be closed after responding to the request.  Event streams
+/// are delimited by the connection closing, so they always close it.
+#[inline]
+pub fn closes_connection(request: &Request) -> bool {
+    request.error().is_some()
+        || !request.keep_alive()
+        || request.route().starts_with(URL_EVENT_STREAM_PREFIX)
+}
+
+/// Connection header of the response, if it differs from the default of the request's
+/// HTTP version
+#[inline]
+pub fn get_connection_header(request: &Request) -> Option<&'static str> {
+    if closes_connection(request) {
+        if request.version() == 1 {
+            return Some("close");
+        }
+        return None;
+    }
+    if request.version() == 0 {
+        return Some("keep-alive");
+    }
+
+    None
+}
+
+/// Ends a stream of requests after the first one that closes the connection, so that the
+/// connection is shut down once the response to it has been written
+pub struct UntilClose<S> {
+    requests: S,
+    closed: bool,
+}
+
+impl<S> UntilClose<S> {
+    pub fn new(requests: S) -> UntilClose<S> {
+        UntilClose {
+            requests,
+            closed: false,
+        }
+    }
+}
+
+impl<S: Stream<Item=Request>> Stream for UntilClose<S> {
+    type Item = Request;
+    type Error = S::Error;
+
+    fn poll(&mut self) -> Poll<Option<Request>, S::Error> {
+        if self.closed {
+            return Ok(Async::Ready(None));
+        }
+
+        let request = match self.requests.poll()? {
+            Async::NotReady => {
+                return Ok(Async::NotReady);
+            }
+            Async::Ready(request) => {
+                request
+            }
+        };
+        if let Some(ref request) = request {
+            self.closed = closes_connection(request);
+        }
+
+        Ok(Async::Ready(request))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net;
+
+    use super::super::super::config::ListenAddress;
+    use super::bind_listeners;
+    use super::Listener;
+
+    #[test]
+    fn listeners_per_thread() {
+        let socket_addr = "127.0.0.1:0".parse().unwrap();
+        let thread_listeners = bind_listeners(&[ListenAddress::Tcp(socket_addr)], 3, 16).unwrap();
+
+        assert_eq!(thread_listeners.len(), 3);
+        for listeners in thread_listeners.iter() {
+            assert_eq!(listeners.len(), 1);
+        }
+    }
+
+    #[test]
+    fn address_in_use() {
+        // Bound without SO_REUSEPORT, the port can't be shared
+        let taken = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let socket_addr = taken.local_addr().unwrap();
+
+        match bind_listeners(&[ListenAddress::Tcp(socket_addr)], 2, 16) {
+            Err(message) => {
+                assert!(message.starts_with(&format!("Can't bind {}", socket_addr)), "{}", message);
+            }
+            Ok(_) => {
+                panic!("bound {} twice", socket_addr);
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn shared_unix_socket() {
+        let path = ::std::env::temp_dir().join(format!("vc-listen-test-{}", ::std::process::id()));
+        let thread_listeners = bind_listeners(&[ListenAddress::Unix(path.clone())], 2, 16).unwrap();
+
+        // The same socket, accepted on by every thread
+        for listeners in thread_listeners.iter() {
+            match listeners[0] {
+                Listener::Unix(ref listener) => {
+                    assert_eq!(listener.local_addr().unwrap().as_pathname(), Some(path.as_path()));
+                }
+                Listener::Tcp(_) => {
+                    panic!("not a Unix domain socket");
+                }
+            }
+        }
+        ::std::fs::remove_file(&path).unwrap();
+    }
+}
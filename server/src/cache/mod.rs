@@ -1,3 +1,10 @@
 pub mod app;
+pub mod events;
+pub mod listen;
+pub mod pages;
+pub mod producers;
+pub mod scheduler;
 pub mod server;
-pub mod updater;
\ No newline at end of file
+pub mod update_app;
+pub mod updater;
+pub mod watchers;
\ No newline at end of file
@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use bytes::Bytes;
+
+use common::model::protocol::ProtocolVersion;
+
+use super::super::codes;
+use super::super::compress::{self, Encoding};
+use super::watchers::PageKey;
+
+/**
+ * Encoded bytes of a page, as served by one of its routes (pages are usually served by a
+ * route that takes global ids and one that takes cache indexes).
+ */
+struct EncodedPage {
+    protocol_version: ProtocolVersion,
+    route: String,
+    data: Bytes,
+    // Compressed forms of the data by encoding, None if it isn't worth compressing
+    compressed: Vec<(Encoding, Option<Bytes>)>,
+}
+
+struct Pages {
+    by_key: HashMap<PageKey, Vec<EncodedPage>>,
+    // Data version the kept pages were encoded at (it is in their headers)
+    data_version: u32,
+    // Incremented by every invalidation, pages encoded across one are not kept
+    generation: u64,
+}
+
+/**
+ *  Encoded responses of pages, by page key (the same keys long-poll requests watch).  A page is
+ *  encoded once and then served as shared Bytes, until the data behind it changes.
+ *
+ *  Page headers carry the data version (and the totals) as of when the page was encoded, so
+ *  all pages are dropped once the data version moves on.  Pages of current periods are also
+ *  invalidated by the paths that change them (re-ranking), right before the long-poll requests
+ *  watching them are notified.
+ *
+ *  Compressed forms of a page are kept with it (by encoding), so that a page is also compressed
+ *  only once per encoding.
+ *
+ *  Shared between all reactor threads (which encode pages) and the update thread (which
+ *  invalidates them), lookups only take the read lock.
+ */
+pub struct PageCache {
+    pages: RwLock<Pages>,
+}
+
+impl PageCache {
+    pub fn new() -> PageCache {
+        PageCache {
+            pages: RwLock::new(Pages {
+                by_key: HashMap::new(),
+                data_version: 0,
+                generation: 0,
+            }),
+        }
+    }
+
+    /**
+     * The encoded page as of the current data version, encoding it (and keeping the result) if
+     * it isn't already kept.  Error responses are returned but not kept.
+     */
+    pub fn get_or_encode<F>(
+        &self,
+        protocol_version: ProtocolVersion,
+        route: &str,
+        page_key: PageKey,
+        data_version: u32,
+        encode: F,
+    ) -> Bytes
+        where F: FnOnce() -> Vec<u8> {
+        let generation = {
+            let pages = self.pages.read().unwrap();
+            if let Some(page) = find_page(&pages, protocol_version, route, page_key, data_version) {
+                return page.data.clone();
+            }
+            pages.generation
+        };
+
+        let data = Bytes::from(encode());
+        if codes::get_http_status(&data).is_some() {
+            return data;
+        }
+
+        let mut pages = self.pages.write().unwrap();
+        // The page may have changed while it was being encoded, it is encoded again next time
+        if pages.generation != generation {
+            return data;
+        }
+        // The first page encoded at a new data version drops the pages of the previous ones
+        if pages.data_version != data_version {
+            pages.by_key.clear();
+            pages.data_version = data_version;
+        }
+        if find_page(&pages, protocol_version, route, page_key, data_version).is_none() {
+            pages.by_key.entry(page_key).or_insert_with(Vec::new).push(EncodedPage {
+                protocol_version,
+                route: route.to_string(),
+                data: data.clone(),
+                compressed: Vec::new(),
+            });
+        }
+
+        data
+    }
+
+    /**
+     * The encoded page (as of the current data version) and its form compressed with the given
+     * encoding (None if it isn't worth compressing), encoding and compressing it (and keeping
+     * the results) as needed.
+     */
+    pub fn get_or_compress<F>(
+        &self,
+        protocol_version: ProtocolVersion,
+        route: &str,
+        page_key: PageKey,
+        data_version: u32,
+        encoding: Encoding,
+        min_size: usize,
+        encode: F,
+    ) -> (Bytes, Option<Bytes>)
+        where F: FnOnce() -> Vec<u8> {
+        {
+            let pages = self.pages.read().unwrap();
+            if let Some(page) = find_page(&pages, protocol_version, route, page_key, data_version) {
+                if let Some(compressed) = find_compressed(page, encoding) {
+                    return (page.data.clone(), compressed.clone());
+                }
+            }
+        }
+
+        let data = self.get_or_encode(protocol_version, route, page_key, data_version, encode);
+        let compressed = compress::compress_body(encoding, &data, min_size);
+
+        let mut pages = self.pages.write().unwrap();
+        if let Some(page) = find_page_mut(&mut pages, protocol_version, route, page_key, data_version) {
+            // Only kept with the page it was compressed from (which may have been invalidated,
+            // or replaced, in the meantime)
+            if page.data.as_ptr() == data.as_ptr() && page.data.len() == data.len()
+                && find_compressed(page, encoding).is_none() {
+                page.compressed.push((encoding, compressed.clone()));
+            }
+        }
+
+        (data, compressed)
+    }
+
+    /**
+     * Drops a page (as served by all of its routes), to be called when the data behind it
+     * changes.
+     */
+    pub fn invalidate(
+        &self,
+        page_key: PageKey,
+    ) {
+        let mut pages = self.pages.write().unwrap();
+        pages.generation = pages.generation.wrapping_add(1);
+        pages.by_key.remove(&page_key);
+    }
+
+    /**
+     * Drops all pages, to be called when the periods move on (and the pages of periods that are
+     * no longer served would otherwise be kept forever).
+     */
+    pub fn clear(&self) {
+        let mut pages = self.pages.write().unwrap();
+        pages.generation = pages.generation.wrapping_add(1);
+        pages.by_key.clear();
+    }
+}
+
+#[inline]
+fn find_page<'a>(
+    pages: &'a Pages,
+    protocol_version: ProtocolVersion,
+    route: &str,
+    page_key: PageKey,
+    data_version: u32,
+) -> Option<&'a EncodedPage> {
+    if pages.data_version != data_version {
+        return None;
+    }
+    pages.by_key.get(&page_key)?.iter().find(|page|
+        page.protocol_version == protocol_version && page.route == route)
+}
+
+#[inline]
+fn find_page_mut<'a>(
+    pages: &'a mut Pages,
+    protocol_version: ProtocolVersion,
+    route: &str,
+    page_key: PageKey,
+    data_version: u32,
+) -> Option<&'a mut EncodedPage> {
+    if pages.data_version != data_version {
+        return None;
+    }
+    pages.by_key.get_mut(&page_key)?.iter_mut().find(|page|
+        page.protocol_version == protocol_version && page.route == route)
+}
+
+#[inline]
+fn find_compressed(
+    page: &EncodedPage,
+    encoding: Encoding,
+) -> Option<&Option<Bytes>> {
+    page.compressed.iter()
+        .find(|&&(page_encoding, _)| page_encoding == encoding)
+        .map(|&(_, ref compressed)| compressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::super::super::compress::Encoding;
+    use super::super::watchers::PageKey;
+    use super::PageCache;
+
+    const PROTOCOL_VERSION: u16 = 1;
+    const ROUTE: &str = "r";
+    const PAGE_KEY: PageKey = PageKey {
+        kind: 0,
+        period: 6,
+        period_id: 7,
+        timezone_id: 0,
+        indexes: [0, 3],
+        block_index: 0,
+    };
+    const DATA_VERSION: u32 = 12;
+
+    #[test]
+    fn compressed_pages() {
+        let page_cache = PageCache::new();
+        let encodings = Cell::new(0);
+        let encode = || {
+            encodings.set(encodings.get() + 1);
+            vec![1u8; 4096]
+        };
+
+        let (data, compressed) = page_cache.get_or_compress(
+            PROTOCOL_VERSION, ROUTE, PAGE_KEY, DATA_VERSION, Encoding::Gzip, 1024, &encode);
+        let compressed = compressed.expect("page not compressed");
+        assert!(compressed.len() < data.len());
+        let compressed_ptr = compressed.as_ptr();
+
+        // The page and its compressed form are kept
+        let (data_again, compressed_again) = page_cache.get_or_compress(
+            PROTOCOL_VERSION, ROUTE, PAGE_KEY, DATA_VERSION, Encoding::Gzip, 1024, &encode);
+        assert_eq!(encodings.get(), 1);
+        assert_eq!(data_again.as_ptr(), data.as_ptr());
+        assert_eq!(compressed_again.unwrap().as_ptr(), compressed.as_ptr());
+
+        // Other encodings are compressed from the same page
+        let (data_again, compressed_again) = page_cache.get_or_compress(
+            PROTOCOL_VERSION, ROUTE, PAGE_KEY, DATA_VERSION, Encoding::Deflate, 1024, &encode);
+        assert_eq!(encodings.get(), 1);
+        assert_eq!(data_again.as_ptr(), data.as_ptr());
+        assert!(compressed_again.is_some());
+
+        // Pages under the minimum size are kept uncompressed
+        let (_, uncompressed) = page_cache.get_or_compress(
+            PROTOCOL_VERSION, "other", PAGE_KEY, DATA_VERSION, Encoding::Gzip, 8192, &encode);
+        assert!(uncompressed.is_none());
+        assert_eq!(encodings.get(), 2);
+
+        // Compressed forms go with the page
+        page_cache.invalidate(PAGE_KEY);
+        let (data_again, compressed_again) = page_cache.get_or_compress(
+            PROTOCOL_VERSION, ROUTE, PAGE_KEY, DATA_VERSION, Encoding::Gzip, 1024, &encode);
+        assert_eq!(encodings.get(), 3);
+        assert_ne!(data_again.as_ptr(), data.as_ptr());
+        assert_ne!(compressed_again.unwrap().as_ptr(), compressed_ptr);
+    }
+
+    #[test]
+    fn data_versions() {
+        let page_cache = PageCache::new();
+        let encodings = Cell::new(0);
+        let encode = || {
+            encodings.set(encodings.get() + 1);
+            vec![encodings.get() as u8; 16]
+        };
+
+        let data = page_cache.get_or_encode(PROTOCOL_VERSION, ROUTE, PAGE_KEY, DATA_VERSION, &encode);
+        page_cache.get_or_encode(PROTOCOL_VERSION, ROUTE, PAGE_KEY, DATA_VERSION, &encode);
+        assert_eq!(encodings.get(), 1);
+
+        // The page header is as of the data version, the page is encoded again once it moves on
+        let data_again = page_cache.get_or_encode(PROTOCOL_VERSION, ROUTE, PAGE_KEY, DATA_VERSION + 1, &encode);
+        assert_eq!(encodings.get(), 2);
+        assert_ne!(data_again, data);
+        page_cache.get_or_encode(PROTOCOL_VERSION, ROUTE, PAGE_KEY, DATA_VERSION + 1, &encode);
+        assert_eq!(encodings.get(), 2);
+    }
+}
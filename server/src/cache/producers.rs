@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::collections::hash_map;
+use std::str;
+
+use super::super::request::Request;
+
+/**
+ * Id of the producer that sent the update batch (any non-empty string, up to
+ * MAX_PRODUCER_ID_LENGTH bytes).
+ */
+pub const PRODUCER_ID_HEADER: &str = "X-VC-Producer-Id";
+/**
+ * Sequence number of the batch, increasing with every batch a producer sends (retries of a
+ * batch keep its sequence number).
+ */
+pub const SEQUENCE_HEADER: &str = "X-VC-Sequence";
+
+pub const MAX_PRODUCER_ID_LENGTH: usize = 64;
+
+/**
+ * A sequenced update request: the producer that sent it and its place in the producer's
+ * sequence of batches.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Batch {
+    pub producer_id: String,
+    pub sequence: u64,
+}
+
+/**
+ * The batch an update request is, None for unsequenced requests (neither header is set).  Err
+ * if only one of the headers is set or either one is not valid.
+ */
+pub fn get_batch(
+    request: &Request
+) -> Result<Option<Batch>, ()> {
+    let producer_id = request.header(PRODUCER_ID_HEADER);
+    let sequence = request.header(SEQUENCE_HEADER);
+
+    let (producer_id, sequence) = match (producer_id, sequence) {
+        (None, None) => {
+            return Ok(None);
+        }
+        (Some(producer_id), Some(sequence)) => {
+            (producer_id, sequence)
+        }
+        _ => {
+            return Err(());
+        }
+    };
+
+    let producer_id = str::from_utf8(producer_id).map_err(|_| ())?.trim();
+    if producer_id.is_empty() || producer_id.len() > MAX_PRODUCER_ID_LENGTH {
+        return Err(());
+    }
+    let sequence = str::from_utf8(sequence).map_err(|_| ())?.trim()
+        .parse::<u64>().map_err(|_| ())?;
+
+    Ok(Some(Batch {
+        producer_id: producer_id.to_string(),
+        sequence,
+    }))
+}
+
+/**
+ * Last applied sequence number, by producer.  Producers retry batches until they are
+ * acknowledged, batches at or below the last applied sequence are retries (or were overtaken
+ * by a later batch) and are acknowledged without being applied again.
+ *
+ * Kept with the data the batches are applied to (and persisted with it), so that the two
+ * never disagree on which batches have been applied.
+ */
+pub struct ProducerSequences {
+    last_applied: HashMap<String, u64>,
+}
+
+impl ProducerSequences {
+    pub fn new() -> ProducerSequences {
+        ProducerSequences {
+            last_applied: HashMap::new(),
+        }
+    }
+
+    /**
+     * The producer's last applied sequence if the batch has already been applied, None if it
+     * is new.
+     */
+    pub fn get_applied(
+        &self,
+        batch: &Batch,
+    ) -> Option<u64> {
+        match self.last_applied.get(&batch.producer_id) {
+            Some(&last_applied) if batch.sequence <= last_applied => {
+                Some(last_applied)
+            }
+            _ => {
+                None
+            }
+        }
+    }
+
+    /**
+     * Records a batch as applied.
+     */
+    pub fn set_applied(
+        &mut self,
+        producer_id: &str,
+        sequence: u64,
+    ) {
+        self.last_applied.insert(producer_id.to_string(), sequence);
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.last_applied.len()
+    }
+
+    #[inline]
+    pub fn iter(&self) -> hash_map::Iter<String, u64> {
+        self.last_applied.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+
+    use super::super::super::request::decode;
+    use super::get_batch;
+    use super::Batch;
+    use super::ProducerSequences;
+
+    fn batch(
+        producer_id: &str,
+        sequence: u64,
+    ) -> Batch {
+        Batch {
+            producer_id: producer_id.to_string(),
+            sequence,
+        }
+    }
+
+    fn get_request_batch(
+        headers: &str
+    ) -> Result<Option<Batch>, ()> {
+        let mut buf = BytesMut::from(format!("PUT 0 HTTP/1.1\r\n{}\r\n", headers).as_bytes());
+        let request = decode(&mut buf, 0).unwrap().unwrap();
+
+        get_batch(&request)
+    }
+
+    #[test]
+    fn batch_headers() {
+        assert_eq!(get_request_batch(""), Ok(None));
+        assert_eq!(get_request_batch("X-VC-Producer-Id: ingest-1\r\nX-VC-Sequence: 7\r\n"),
+                   Ok(Some(batch("ingest-1", 7))));
+        // Both headers are needed, and must be valid
+        assert_eq!(get_request_batch("X-VC-Producer-Id: ingest-1\r\n"), Err(()));
+        assert_eq!(get_request_batch("X-VC-Sequence: 7\r\n"), Err(()));
+        assert_eq!(get_request_batch("X-VC-Producer-Id: ingest-1\r\nX-VC-Sequence: -7\r\n"), Err(()));
+        assert_eq!(get_request_batch("X-VC-Producer-Id: \r\nX-VC-Sequence: 7\r\n"), Err(()));
+        assert_eq!(get_request_batch(&format!("X-VC-Producer-Id: {}\r\nX-VC-Sequence: 7\r\n",
+                                              "p".repeat(65))), Err(()));
+    }
+
+    #[test]
+    fn applied_sequences() {
+        let mut producer_sequences = ProducerSequences::new();
+        // A new producer has nothing applied
+        assert_eq!(producer_sequences.get_applied(&batch("ingest-1", 1)), None);
+
+        producer_sequences.set_applied("ingest-1", 5);
+        // A retry of the last batch, or a batch below it, has been applied
+        assert_eq!(producer_sequences.get_applied(&batch("ingest-1", 5)), Some(5));
+        assert_eq!(producer_sequences.get_applied(&batch("ingest-1", 3)), Some(5));
+        assert_eq!(producer_sequences.get_applied(&batch("ingest-1", 6)), None);
+        // Producers are sequenced independently
+        assert_eq!(producer_sequences.get_applied(&batch("ingest-2", 5)), None);
+        assert_eq!(producer_sequences.len(), 1);
+    }
+}
@@ -0,0 +1,494 @@
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SECONDS_IN_A_DAY: u64 = 24 * 60 * 60;
+
+/// Longest the scheduler thread sleeps at a time, so that changes to the wall clock (and jobs
+/// added with an injected clock) are picked up within a second
+const MAX_SLEEP: Duration = Duration::from_secs(1);
+
+/// Source of the wall clock time jobs are scheduled by.  Injectable, so that schedules can be
+/// driven by something other than the system clock (simulated time, replays).
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+
+    fn sleep(&self, duration: Duration);
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// When a job runs, in wall clock time (not time since the process started)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Schedule {
+    /// At every multiple of the period since the Unix epoch (every 60 seconds is at the top of
+    /// every minute), whole seconds only
+    Every(Duration),
+    /// Once a day, at a local time (minutes past midnight) in a timezone (UTC offset in minutes)
+    DailyAt {
+        utc_offset_minutes: i32,
+        local_time_minutes: i32,
+    },
+}
+
+impl Schedule {
+    /// The first time on the schedule that is after the given time
+    pub fn next_after(
+        &self,
+        time: SystemTime,
+    ) -> SystemTime {
+        let seconds = get_seconds(time);
+        let next_seconds = match *self {
+            Schedule::Every(period) => {
+                let period_seconds = period.as_secs().max(1);
+                (seconds / period_seconds + 1) * period_seconds
+            }
+            Schedule::DailyAt { utc_offset_minutes, local_time_minutes } => {
+                let offset_seconds = utc_offset_minutes as i64 * 60;
+                let local_seconds = seconds as i64 + offset_seconds;
+                let day_seconds = SECONDS_IN_A_DAY as i64;
+                let mut next_local_seconds = local_seconds - local_seconds % day_seconds
+                    + local_time_minutes as i64 * 60;
+                if next_local_seconds <= local_seconds {
+                    next_local_seconds += day_seconds;
+                }
+                (next_local_seconds - offset_seconds) as u64
+            }
+        };
+
+        UNIX_EPOCH + Duration::from_secs(next_seconds)
+    }
+}
+
+/// A job, given the time it was scheduled to run at.  Err is recorded in its status (and
+/// logged), the job stays on its schedule either way.
+pub type Job = Box<FnMut(SystemTime) -> Result<(), String> + Send>;
+
+/// Status of a registered job, as of the last time it was started or finished
+#[derive(Clone, Debug)]
+pub struct JobStatus {
+    pub name: String,
+    pub schedule: Schedule,
+    pub next_run: SystemTime,
+    pub last_started: Option<SystemTime>,
+    pub last_finished: Option<SystemTime>,
+    pub last_duration: Option<Duration>,
+    pub last_error: Option<String>,
+    pub runs: u64,
+    pub failures: u64,
+    /// Runs that were due while the previous run was still going (and were not started)
+    pub skipped: u64,
+    pub running: bool,
+}
+
+/// Statuses of all jobs of a scheduler, in the order they were added.  Shared with whoever
+/// reports on them (debug routes, metrics).
+pub struct JobStatuses {
+    statuses: Mutex<Vec<JobStatus>>,
+}
+
+impl JobStatuses {
+    fn new() -> JobStatuses {
+        JobStatuses {
+            statuses: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn get(&self) -> Vec<JobStatus> {
+        self.statuses.lock().unwrap().clone()
+    }
+}
+
+struct ScheduledJob {
+    schedule: Schedule,
+    next_run: SystemTime,
+    // Only locked by the run it is handed to, runs never overlap
+    job: Arc<Mutex<Job>>,
+}
+
+///
+/// Runs registered jobs on wall clock schedules, on a scheduler thread.
+///
+/// Every run of a job gets a thread of its own, so a slow job doesn't hold up the others.  A job
+/// never runs concurrently with itself: if a run is due while the previous one is still going,
+/// it is skipped (and counted in the job's status).  After a pause (or a jump in the clock) a
+/// job runs once (given the first time it missed) rather than once for every run it missed.
+///
+/// Jobs that change the cache don't touch it directly, they hand the change to the writer
+/// thread (see UpdateWriter) and wait for it to be applied.
+///
+pub struct Scheduler {
+    clock: Arc<Clock>,
+    jobs: Vec<ScheduledJob>,
+    job_statuses: Arc<JobStatuses>,
+}
+
+impl Scheduler {
+    pub fn new(
+        clock: Arc<Clock>
+    ) -> Scheduler {
+        Scheduler {
+            clock,
+            jobs: Vec::new(),
+            job_statuses: Arc::new(JobStatuses::new()),
+        }
+    }
+
+    /// Registers a job, its first run is the first time on its schedule after now
+    pub fn add_job<F>(
+        &mut self,
+        name: &str,
+        schedule: Schedule,
+        job: F,
+    ) where F: FnMut(SystemTime) -> Result<(), String> + Send + 'static {
+        let next_run = schedule.next_after(self.clock.now());
+        let job: Job = Box::new(job);
+        self.jobs.push(ScheduledJob {
+            schedule,
+            next_run,
+            job: Arc::new(Mutex::new(job)),
+        });
+        self.job_statuses.statuses.lock().unwrap().push(JobStatus {
+            name: name.to_string(),
+            schedule,
+            next_run,
+            last_started: None,
+            last_finished: None,
+            last_duration: None,
+            last_error: None,
+            runs: 0,
+            failures: 0,
+            skipped: 0,
+            running: false,
+        });
+    }
+
+    /// Statuses of the jobs, including ones added later
+    pub fn get_job_statuses(&self) -> Arc<JobStatuses> {
+        self.job_statuses.clone()
+    }
+
+    ///
+    /// Starts the jobs that are due (skipping the ones that are still running), returns the
+    /// time the next job is due.  Called by the scheduler thread, and directly when stepping
+    /// through time with an injected clock.
+    ///
+    pub fn run_due(&mut self) -> Option<SystemTime> {
+        let now = self.clock.now();
+        let mut next_due: Option<SystemTime> = None;
+        for job_index in 0..self.jobs.len() {
+            let scheduled_job = &mut self.jobs[job_index];
+            if scheduled_job.next_run <= now {
+                let scheduled_time = scheduled_job.next_run;
+                scheduled_job.next_run = scheduled_job.schedule.next_after(now);
+                start_job(&self.clock, &self.job_statuses, job_index, scheduled_job, scheduled_time);
+            }
+            next_due = match next_due {
+                Some(time) if time <= scheduled_job.next_run => {
+                    Some(time)
+                }
+                _ => {
+                    Some(scheduled_job.next_run)
+                }
+            };
+        }
+
+        next_due
+    }
+
+    /// Starts the scheduler thread, which runs the jobs for the life of the process
+    pub fn start(
+        mut scheduler: Scheduler
+    ) {
+        println!("Scheduler running {} jobs", scheduler.jobs.len());
+
+        thread::spawn(move || {
+            loop {
+                let next_due = scheduler.run_due();
+                let now = scheduler.clock.now();
+                let sleep = match next_due {
+                    None => {
+                        MAX_SLEEP
+                    }
+                    Some(next_due) => {
+                        next_due.duration_since(now).unwrap_or(Duration::from_secs(0)).min(MAX_SLEEP)
+                    }
+                };
+                scheduler.clock.sleep(sleep);
+            }
+        });
+    }
+}
+
+/// Starts a run of a job on its own thread, unless the previous run is still going
+fn start_job(
+    clock: &Arc<Clock>,
+    job_statuses: &Arc<JobStatuses>,
+    job_index: usize,
+    scheduled_job: &ScheduledJob,
+    scheduled_time: SystemTime,
+) {
+    {
+        let mut statuses = job_statuses.statuses.lock().unwrap();
+        let status = &mut statuses[job_index];
+        status.next_run = scheduled_job.next_run;
+        if status.running {
+            status.skipped += 1;
+            eprintln!("Job {} is still running, skipped its run at {}", status.name, get_seconds(scheduled_time));
+            return;
+        }
+        status.running = true;
+        status.last_started = Some(clock.now());
+    }
+
+    let clock = clock.clone();
+    let job_statuses = job_statuses.clone();
+    let job = scheduled_job.job.clone();
+    thread::spawn(move || {
+        // A panicking job is recorded as failed, it stays on its schedule (runs never overlap,
+        // so its mutex is only poisoned by the panic itself)
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut job = job.lock().unwrap_or_else(|error| error.into_inner());
+            (*job)(scheduled_time)
+        })).unwrap_or_else(|_| Err("panicked".to_string()));
+        let finished = clock.now();
+
+        let mut statuses = job_statuses.statuses.lock().unwrap();
+        let status = &mut statuses[job_index];
+        status.running = false;
+        status.runs += 1;
+        status.last_finished = Some(finished);
+        status.last_duration = status.last_started
+            .and_then(|started| finished.duration_since(started).ok());
+        match result {
+            Ok(()) => {
+                status.last_error = None;
+            }
+            Err(message) => {
+                eprintln!("Job {} failed: {}", status.name, message);
+                status.failures += 1;
+                status.last_error = Some(message);
+            }
+        }
+    });
+}
+
+/// Whole seconds since the Unix epoch (0 for times before it)
+pub fn get_seconds(
+    time: SystemTime
+) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use std::thread;
+    use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+    use super::Clock;
+    use super::JobStatus;
+    use super::JobStatuses;
+    use super::Schedule;
+    use super::Scheduler;
+
+    // Stepped by the tests, the scheduler thread is never started
+    struct FakeClock {
+        now: Mutex<SystemTime>,
+    }
+
+    impl FakeClock {
+        fn set(&self, seconds: u64) {
+            *self.now.lock().unwrap() = at(seconds);
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> SystemTime {
+            *self.now.lock().unwrap()
+        }
+
+        fn sleep(&self, _duration: Duration) {}
+    }
+
+    fn at(
+        seconds: u64
+    ) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(seconds)
+    }
+
+    fn new_scheduler(
+        seconds: u64
+    ) -> (Scheduler, Arc<FakeClock>) {
+        let clock = Arc::new(FakeClock {
+            now: Mutex::new(at(seconds)),
+        });
+
+        (Scheduler::new(clock.clone()), clock)
+    }
+
+    // Jobs run on threads of their own, waits for a job's status to show a number of runs
+    fn wait_for_runs(
+        job_statuses: &JobStatuses,
+        job_index: usize,
+        runs: u64,
+    ) -> JobStatus {
+        let started = Instant::now();
+        loop {
+            let status = job_statuses.get()[job_index].clone();
+            if status.runs >= runs && !status.running {
+                return status;
+            }
+            assert!(started.elapsed() < Duration::from_secs(5), "job {} did not finish", status.name);
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn next_runs() {
+        let every_minute = Schedule::Every(Duration::from_secs(60));
+        assert_eq!(every_minute.next_after(at(1000)), at(1020));
+        assert_eq!(every_minute.next_after(at(1020)), at(1080));
+
+        // Local midnight in UTC-5 is 05:00 UTC
+        let midnight = Schedule::DailyAt {
+            utc_offset_minutes: -5 * 60,
+            local_time_minutes: 0,
+        };
+        assert_eq!(midnight.next_after(at(0)), at(5 * 60 * 60));
+        assert_eq!(midnight.next_after(at(5 * 60 * 60)), at(29 * 60 * 60));
+    }
+
+    #[test]
+    fn stepped_runs() {
+        let (mut scheduler, clock) = new_scheduler(1000);
+        let (every_sender, every_runs) = mpsc::channel();
+        scheduler.add_job("every-minute", Schedule::Every(Duration::from_secs(60)), move |scheduled_time| {
+            every_sender.send(scheduled_time).unwrap();
+            Ok(())
+        });
+        let (daily_sender, daily_runs) = mpsc::channel();
+        scheduler.add_job("daily", Schedule::DailyAt {
+            utc_offset_minutes: 0,
+            local_time_minutes: 60,
+        }, move |scheduled_time| {
+            daily_sender.send(scheduled_time).unwrap();
+            Ok(())
+        });
+        let job_statuses = scheduler.get_job_statuses();
+
+        // Nothing is due yet
+        assert_eq!(scheduler.run_due(), Some(at(1020)));
+        assert_eq!(job_statuses.get()[0].runs, 0);
+
+        clock.set(1020);
+        assert_eq!(scheduler.run_due(), Some(at(1080)));
+        assert_eq!(every_runs.recv().unwrap(), at(1020));
+        wait_for_runs(&job_statuses, 0, 1);
+
+        // After a jump in the clock, jobs run once, given the first time they missed
+        clock.set(3700);
+        assert_eq!(scheduler.run_due(), Some(at(3720)));
+        assert_eq!(every_runs.recv().unwrap(), at(1080));
+        assert_eq!(daily_runs.recv().unwrap(), at(3600));
+        let status = wait_for_runs(&job_statuses, 0, 2);
+        assert_eq!(status.next_run, at(3720));
+        assert_eq!(status.last_started, Some(at(3700)));
+        let status = wait_for_runs(&job_statuses, 1, 1);
+        assert_eq!(status.next_run, at(3600 + 24 * 60 * 60));
+        assert!(every_runs.try_recv().is_err());
+        assert!(daily_runs.try_recv().is_err());
+    }
+
+    #[test]
+    fn overlapping_runs() {
+        let (mut scheduler, clock) = new_scheduler(0);
+        let (release, released) = mpsc::channel::<()>();
+        let (started_sender, started) = mpsc::channel();
+        scheduler.add_job("slow", Schedule::Every(Duration::from_secs(60)), move |scheduled_time| {
+            started_sender.send(scheduled_time).unwrap();
+            released.recv().unwrap();
+            Ok(())
+        });
+        let job_statuses = scheduler.get_job_statuses();
+
+        clock.set(60);
+        scheduler.run_due();
+        assert_eq!(started.recv().unwrap(), at(60));
+
+        // Due again while the first run is still going
+        clock.set(120);
+        scheduler.run_due();
+        let status = job_statuses.get()[0].clone();
+        assert!(status.running);
+        assert_eq!(status.skipped, 1);
+        assert_eq!(status.runs, 0);
+
+        release.send(()).unwrap();
+        wait_for_runs(&job_statuses, 0, 1);
+
+        clock.set(180);
+        scheduler.run_due();
+        assert_eq!(started.recv().unwrap(), at(180));
+        release.send(()).unwrap();
+        let status = wait_for_runs(&job_statuses, 0, 2);
+        assert_eq!(status.skipped, 1);
+        assert_eq!(status.failures, 0);
+    }
+
+    #[test]
+    fn failed_runs() {
+        let (mut scheduler, clock) = new_scheduler(0);
+        let mut run = 0;
+        scheduler.add_job("flaky", Schedule::Every(Duration::from_secs(60)), move |_| {
+            run += 1;
+            match run {
+                1 => {
+                    Err("no snapshot file".to_string())
+                }
+                2 => {
+                    panic!("job panicked")
+                }
+                _ => {
+                    Ok(())
+                }
+            }
+        });
+        let job_statuses = scheduler.get_job_statuses();
+
+        clock.set(60);
+        scheduler.run_due();
+        let status = wait_for_runs(&job_statuses, 0, 1);
+        assert_eq!(status.failures, 1);
+        assert_eq!(status.last_error, Some("no snapshot file".to_string()));
+
+        clock.set(120);
+        scheduler.run_due();
+        let status = wait_for_runs(&job_statuses, 0, 2);
+        assert_eq!(status.failures, 2);
+        assert_eq!(status.last_error, Some("panicked".to_string()));
+
+        // The job stays on its schedule after panicking
+        clock.set(180);
+        scheduler.run_due();
+        let status = wait_for_runs(&job_statuses, 0, 3);
+        assert_eq!(status.failures, 2);
+        assert_eq!(status.last_error, None);
+        assert_eq!(status.last_finished, Some(at(180)));
+    }
+}
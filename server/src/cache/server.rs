@@ -1,39 +1,203 @@
+use byteorder::{BigEndian, ByteOrder};
+use bytes::Bytes;
 use futures::future;
+use futures::stream;
 use futures::Future;
-use net2::TcpBuilder;
-#[cfg(not(windows))]
-use net2::unix::UnixTcpBuilderExt;
-use num_cpus;
 use std::io;
-use std::net::ToSocketAddrs;
+use std::net::IpAddr;
 use std::sync::Arc;
 use std::thread;
+use std::time::{Duration, Instant};
 use tokio;
-use tokio::net::{TcpStream, TcpListener};
+use tokio::net::TcpListener;
+#[cfg(unix)]
+use tokio::net::UnixListener;
 use tokio::prelude::*;
-use tokio_codec::Framed;
-
+use tokio::timer::Delay;
+use tokio::timer::Interval;
+
+use common::model::frame::FRAME_DEFAULT_PROTOCOL_VERSION;
+use common::model::protocol::ProtocolVersion;
+use common::model::protocol::DEFAULT_PROTOCOL_VERSION;
+use common::url::cache::serve::URL_DEBUG_PREFIX;
+use common::url::cache::serve::URL_EVENT_STREAM_PREFIX;
+use common::url::cache::serve::URL_LONG_POLL_PREFIX;
+use common::url::cache::serve::URL_PROTOCOL_VERSIONS;
+
+use super::super::compress::{self, Encoding};
 use super::super::http::Http;
+use super::super::http::Output;
 use super::super::request::Request;
+use super::super::request::DEFAULT_MAX_BODY_SIZE;
 use super::super::codes;
+use super::super::config::ServerConfig;
+use super::super::gather;
+use super::super::limit::ConnectionLimit;
+use super::super::limit::RateLimiter;
+use super::super::metrics::Metrics;
+use super::super::metrics::RequestKind;
+use super::super::metrics::METRICS_CONTENT_TYPE;
+use super::super::metrics::METRICS_PATH;
+use super::super::raw::Raw;
+use super::super::raw::RawRequest;
+use super::super::raw::RawResponse;
 use super::super::response::Response;
+use super::super::tls::TlsAcceptor;
 
 use super::app::App;
+use super::events::Event;
+use super::events::EventHub;
+use super::events::Topic;
+use super::listen::bind_listeners;
+use super::listen::get_connection_header;
+use super::listen::Listener;
+use super::listen::UntilClose;
+use super::watchers::PageWatchers;
+use super::watchers::PAGE_WATCHERS_PRUNE_INTERVAL_SECONDS;
+
+/**
+ * How long a long-poll request waits for its page to change, before responding with
+ * the current contents of the page.
+ */
+const LONG_POLL_TIMEOUT_SECONDS: u64 = 30;
+
+/**
+ * Size of the data version that long-poll request bodies start with.
+ */
+const DATA_VERSION_SIZE: usize = 4;
+
+/**
+ * How often an (otherwise idle) event stream gets a comment line, so that proxies don't time
+ * out the connection.
+ */
+const EVENT_STREAM_HEARTBEAT_SECONDS: u64 = 15;
+
+const EVENT_STREAM_HEARTBEAT: &[u8] = b":\n\n";
+
+/**
+ * Most requests of a raw protocol connection served at once, further requests wait (in the
+ * connection's buffers) until some of these are responded to.
+ */
+const MAX_RAW_REQUESTS_IN_FLIGHT: usize = 64;
 
 pub struct Server {
-    app: Box<App + Send + Sync>
+    app: Box<App + Send + Sync>,
+    page_watchers: Arc<PageWatchers>,
+    event_hub: Arc<EventHub>,
+    max_body_size: usize,
+    debug_routes_enabled: bool,
+    server_tls: Option<TlsAcceptor>,
+    connection_limit: Option<Arc<ConnectionLimit>>,
+    rate_limiter: Option<RateLimiter>,
+    compression_min_size: Option<usize>,
+    metrics: Option<Arc<Metrics>>,
 }
 
 impl Server {
 
     pub fn new(
         app: Box<App + Send + Sync>
+    ) -> Server {
+        Server::new_shared(app, Arc::new(PageWatchers::new()), Arc::new(EventHub::new()))
+    }
+
+    ///
+    /// Creates a server with page watchers and an event hub that are shared with the app (which
+    /// notifies the watchers when page data changes and publishes events to the hub).
+    ///
+    pub fn new_shared(
+        app: Box<App + Send + Sync>,
+        page_watchers: Arc<PageWatchers>,
+        event_hub: Arc<EventHub>,
     ) -> Server {
         Server {
-            app
+            app,
+            page_watchers,
+            event_hub,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            debug_routes_enabled: false,
+            server_tls: None,
+            connection_limit: None,
+            rate_limiter: None,
+            compression_min_size: None,
+            metrics: None,
         }
     }
 
+    ///
+    /// Sets the largest request body accepted, bigger requests are responded to with a 413.
+    ///
+    pub fn set_max_body_size(
+        &mut self,
+        max_body_size: usize,
+    ) {
+        self.max_body_size = max_body_size;
+    }
+
+    ///
+    /// Enables the human readable GET routes (under URL_DEBUG_PREFIX), off by default.
+    ///
+    pub fn set_debug_routes_enabled(
+        &mut self,
+        debug_routes_enabled: bool,
+    ) {
+        self.debug_routes_enabled = debug_routes_enabled;
+    }
+
+    ///
+    /// Serves read requests over TLS, on the TCP listeners (Unix domain sockets stay plaintext).
+    ///
+    pub fn set_server_tls(
+        &mut self,
+        server_tls: TlsAcceptor,
+    ) {
+        self.server_tls = Some(server_tls);
+    }
+
+    ///
+    /// Caps the number of concurrent connections to the read listeners, connections over the
+    /// cap are closed right away.
+    ///
+    pub fn set_max_connections(
+        &mut self,
+        max_connections: usize,
+    ) {
+        self.connection_limit = Some(Arc::new(ConnectionLimit::new(max_connections)));
+    }
+
+    ///
+    /// Limits read requests per client IP address, requests over the limit are responded to
+    /// with a 429.
+    ///
+    pub fn set_rate_limiter(
+        &mut self,
+        rate_limiter: RateLimiter,
+    ) {
+        self.rate_limiter = Some(rate_limiter);
+    }
+
+    ///
+    /// Compresses read responses of at least min_size bytes, with the encoding negotiated by
+    /// the request's Accept-Encoding header.
+    ///
+    pub fn set_compression_min_size(
+        &mut self,
+        min_size: usize,
+    ) {
+        self.compression_min_size = Some(min_size);
+    }
+
+    ///
+    /// Records read requests in the metrics and serves them (along with the app's own metrics)
+    /// on the GET metrics route.  The same metrics are to be given to the Updater.
+    ///
+    pub fn set_metrics(
+        &mut self,
+        metrics: Arc<Metrics>,
+    ) {
+        self.metrics = Some(metrics);
+    }
+
     ///
     /// Starts the app with a thread pool optimized for small requests and quick timeouts. This
     /// is done internally by spawning a separate thread for each reactor core. This is valuable
@@ -45,103 +209,154 @@ impl Server {
     ///
     /// https://users.rust-lang.org/t/getting-tokio-to-match-actix-web-performance/18659/7
     ///
+    /// All listeners are bound before the reactor threads start (see bind_listeners), every
+    /// thread accepts on TCP listeners of its own and on the shared Unix domain sockets.  Runs
+    /// for the life of the process, unless a listener can't be bound.
+    ///
     pub fn start_small_load_optimized(
         server: Server,
-        host: &str,
-        server_port: u16,
-        updater_port: u16
-    ) {
-        let updater_addr = (host, updater_port).to_socket_addrs().unwrap().next().unwrap();
+        config: &ServerConfig,
+    ) -> Result<(), String> {
         let arc_server = Arc::new(server);
+        let backlog = config.backlog;
 
-        let update_server = arc_server.clone();
-        thread::spawn(move || {
-            let mut runtime = tokio::runtime::current_thread::Runtime::new().unwrap();
-
-            let server = future::lazy(move || {
-                let listener = {
-                    let builder = TcpBuilder::new_v4().unwrap();
-                    #[cfg(not(windows))]
-                        builder.reuse_address(true).unwrap();
-                    #[cfg(not(windows))]
-                        builder.reuse_port(true).unwrap();
-                    builder.bind(updater_addr).unwrap();
-                    builder.listen(10240).unwrap()
-                };
-                let listener = TcpListener::from_std(listener, &tokio::reactor::Handle::current()).unwrap();
-
-                listener.incoming().for_each(move |socket| {
-                    process(Arc::clone(&update_server), socket);
-                    Ok(())
-                })
-                    .map_err(|err| eprintln!("accept error = {:?}", err))
-            });
-
-            runtime.spawn(server);
-            runtime.run().unwrap();
-        });
-
-        println!("Update thread running on {}", updater_addr);
-
-        let server_addr = (host, server_port).to_socket_addrs().unwrap().next().unwrap();
+        let server_listeners = bind_listeners(&config.server_addresses, config.num_threads, backlog)?;
+        let raw_listeners = bind_listeners(&config.raw_addresses, config.num_threads, backlog)?;
         let mut threads = Vec::new();
 
-        for _ in 0..num_cpus::get() {
+        let thread_listeners = server_listeners.into_iter().zip(raw_listeners.into_iter());
+        for (thread_index, (server_listeners, raw_listeners)) in thread_listeners.enumerate() {
             let arc_server = arc_server.clone();
             threads.push(thread::spawn(move || {
                 let mut runtime = tokio::runtime::current_thread::Runtime::new().unwrap();
 
-                let server = future::lazy(move || {
-                    let listener = {
-                        let builder = TcpBuilder::new_v4().unwrap();
-                        #[cfg(not(windows))]
-                            builder.reuse_address(true).unwrap();
-                        #[cfg(not(windows))]
-                            builder.reuse_port(true).unwrap();
-                        builder.bind(server_addr).unwrap();
-                        builder.listen(10240).unwrap()
-                    };
-                    let listener = TcpListener::from_std(listener, &tokio::reactor::Handle::current()).unwrap();
-
-                    listener.incoming().for_each(move |socket| {
-                        process(Arc::clone(&arc_server), socket);
-                        Ok(())
-                    })
-                        .map_err(|err| eprintln!("accept error = {:?}", err))
-                });
-
-                runtime.spawn(server);
+                // Long-poll waiters are shared by all threads, one of them prunes them
+                if thread_index == 0 {
+                    runtime.spawn(prune_page_watchers(arc_server.page_watchers.clone()));
+                }
+
+                for listener in server_listeners {
+                    let server = accept(arc_server.clone(), listener, ListenerKind::Read);
+                    runtime.spawn(server);
+                }
+                for listener in raw_listeners {
+                    let server = accept(arc_server.clone(), listener, ListenerKind::Raw);
+                    runtime.spawn(server);
+                }
                 runtime.run().unwrap();
             }));
         }
 
-        println!("Server threads running on {}", server_addr);
+        for server_address in config.server_addresses.iter() {
+            println!("{} server threads running on {}", config.num_threads, server_address);
+        }
+        for raw_address in config.raw_addresses.iter() {
+            println!("{} server threads running on {} (raw protocol)", config.num_threads, raw_address);
+        }
 
         for thread in threads {
             thread.join().unwrap();
         }
 
-        fn process(server: Arc<Server>, socket: TcpStream) {
-            let framed = Framed::new(socket, Http);
-            let (tx, rx) = framed.split();
+        return Ok(());
 
-            let task = tx.send_all(rx.and_then(move |request: Request| {
-                server.resolve(&request)
-            }))
-                .then(|_| future::ok(()));
+        fn prune_page_watchers(
+            page_watchers: Arc<PageWatchers>,
+        ) -> Box<Future<Item=(), Error=()>> {
+            let prune_interval = Duration::from_secs(PAGE_WATCHERS_PRUNE_INTERVAL_SECONDS);
 
-            // Spawn the task that handles the connection.
-            tokio::spawn(task);
+            Box::new(Interval::new(Instant::now() + prune_interval, prune_interval)
+                .for_each(move |_| {
+                    page_watchers.prune();
+                    Ok(())
+                })
+                .map_err(|err| eprintln!("page watchers prune timer error = {:?}", err)))
         }
 
-        fn process_update(server: Arc<Server>, socket: TcpStream) {
-            let framed = Framed::new(socket, Http);
-            let (tx, rx) = framed.split();
+        fn accept(
+            server: Arc<Server>,
+            listener: Listener,
+            kind: ListenerKind,
+        ) -> Box<Future<Item=(), Error=()>> {
+            match listener {
+                Listener::Tcp(listener) => {
+                    let listener = TcpListener::from_std(listener, &tokio::reactor::Handle::current()).unwrap();
 
-            let task = tx.send_all(rx.and_then(move |request: Request| {
-                server.resolve_update(&request)
-            }))
-                .then(|_| future::ok(()));
+                    let tls = server.server_tls.clone();
+
+                    Box::new(listener.incoming().for_each(move |socket| {
+                        let client = Client {
+                            address: socket.peer_addr().ok().map(|peer_addr| peer_addr.ip()),
+                        };
+                        match tls {
+                            None => {
+                                process(Arc::clone(&server), socket, kind, client);
+                            }
+                            Some(ref tls) => {
+                                process(Arc::clone(&server), tls.accept(socket), kind, client);
+                            }
+                        }
+                        Ok(())
+                    })
+                        .map_err(|err| eprintln!("accept error = {:?}", err)))
+                }
+                #[cfg(unix)]
+                Listener::Unix(listener) => {
+                    let listener = UnixListener::from_std(listener, &tokio::reactor::Handle::current()).unwrap();
+
+                    Box::new(listener.incoming().for_each(move |socket| {
+                        process(Arc::clone(&server), socket, kind, Client {
+                            address: None,
+                        });
+                        Ok(())
+                    })
+                        .map_err(|err| eprintln!("accept error = {:?}", err)))
+                }
+            }
+        }
+
+        fn process<S>(server: Arc<Server>, socket: S, kind: ListenerKind, client: Client)
+            where S: AsyncRead + AsyncWrite + Send + 'static {
+            // Dropping the socket closes connections over the cap
+            let permit = match server.connection_limit {
+                None => {
+                    None
+                }
+                Some(ref connection_limit) => {
+                    match ConnectionLimit::acquire(connection_limit) {
+                        None => {
+                            return;
+                        }
+                        Some(permit) => {
+                            Some(permit)
+                        }
+                    }
+                }
+            };
+            if kind == ListenerKind::Raw {
+                let (tx, rx) = gather::split(socket, Raw::new(server.max_body_size), Raw::new(server.max_body_size));
+
+                // Responses are sent as they complete, not in the order of the requests
+                let task = tx.send_all(rx.map(move |request: RawRequest| {
+                    Server::resolve_raw(&server, request, client.address)
+                }).buffer_unordered(MAX_RAW_REQUESTS_IN_FLIGHT))
+                    .then(move |_| {
+                        drop(permit);
+                        future::ok(())
+                    });
+
+                tokio::spawn(task);
+                return;
+            }
+            let (tx, rx) = gather::split(socket, Http::new(server.max_body_size), Http::new(server.max_body_size));
+
+            let task = tx.send_all(UntilClose::new(rx).map(move |request: Request| {
+                Server::resolve_output(&server, &request, client.address)
+            }).flatten())
+                .then(move |_| {
+                    drop(permit);
+                    future::ok(())
+                });
 
             // Spawn the task that handles the connection.
             tokio::spawn(task);
@@ -149,47 +364,205 @@ impl Server {
     }
 
     #[inline]
-    fn get_response(&self, request: &Request) -> Response {
-        let mut response = Response::new();
-
+    fn get_response(&self, request: &Request, compression: Option<(Encoding, usize)>) -> Response {
+        if let Some(error) = request.error() {
+            return codes::get_request_error_response(error);
+        }
+        if request.method() == "GET" && request.path() == METRICS_PATH {
+            if let Some(ref metrics) = self.metrics {
+                return self.get_metrics_response(metrics);
+            }
+        }
+        if self.debug_routes_enabled && request.method() == "GET"
+            && request.path().starts_with(URL_DEBUG_PREFIX) {
+            return self.get_debug_response(request);
+        }
+        if request.route() == URL_PROTOCOL_VERSIONS
+            && (request.method() == "PUT" || request.method() == "GET") {
+            return codes::get_protocol_versions_response(self.app.get_protocol_versions());
+        }
         if request.method() != "PUT" {
-            response.body_vec(codes::INVALID_DATA_FORMAT_RESPONSE.to_vec());
-            return response;
+            return codes::get_method_not_allowed_response();
         }
-
-        let path = request.path();
+        let protocol_version = match codes::get_protocol_version(
+            request, self.app.get_protocol_versions()) {
+            Err(response) => {
+                return response;
+            }
+            Ok(protocol_version) => {
+                protocol_version
+            }
+        };
+
+        let path = request.route();
         let request_body = request.raw_body();
 
-        let data = self.app.get_response(path, request_body);
+        let (data, compressed) = self.get_app_data(protocol_version, path, request_body, compression);
+
+        Server::get_app_response(protocol_version, data, compressed, compression)
+    }
+
+    /// Body of an app response and, if the client accepts a compressed response, its
+    /// compressed form (None if it isn't worth compressing)
+    fn get_app_data(&self, protocol_version: ProtocolVersion, path: &str, request_body: &[u8],
+                    compression: Option<(Encoding, usize)>) -> (Bytes, Option<Bytes>) {
+        match compression {
+            None => {
+                (self.app.get_response(protocol_version, path, request_body), None)
+            }
+            Some((encoding, min_size)) => {
+                self.app.get_compressed_response(protocol_version, path, request_body, encoding, min_size)
+            }
+        }
+    }
 
-        response.body_vec(data);
+    fn get_app_response(protocol_version: ProtocolVersion, data: Bytes, compressed: Option<Bytes>,
+                        compression: Option<(Encoding, usize)>) -> Response {
+        let mut response = codes::get_versioned_app_response(protocol_version, data);
+        if let (Some(compressed), Some((encoding, _))) = (compressed, compression) {
+            response.compressed_body(encoding.name(), compressed);
+        }
 
         response
     }
 
-    #[inline]
-    fn get_update_response(&self, request: &Request) -> Response {
-        let mut response = Response::new();
+    fn get_debug_response(&self, request: &Request) -> Response {
+        let request_path = request.path();
+        let (path, query) = match request_path.find('?') {
+            None => (request_path, ""),
+            Some(query_start) => (&request_path[..query_start], &request_path[query_start + 1..]),
+        };
 
-        if request.method() != "PUT" {
-            response.body_vec(codes::INVALID_DATA_FORMAT_RESPONSE.to_vec());
-            return response;
+        let mut response = Response::new();
+        response.content_type("application/json");
+
+        match self.app.get_debug_response(path, query) {
+            None => {
+                response
+                    .status_code(404, "Not Found")
+                    .body_vec(b"{\"error\":\"no such route\"}".to_vec());
+            }
+            Some(Err(message)) => {
+                let message = message.replace('\\', "\\\\").replace('"', "\\\"");
+                response
+                    .status_code(400, "Bad Request")
+                    .body_vec(format!("{{\"error\":\"{}\"}}", message).into_bytes());
+            }
+            Some(Ok(json)) => {
+                response.body_vec(json);
+            }
         }
 
-        let path = request.path();
-        let request_body = request.raw_body();
+        response
+    }
 
-        let data = self.app.get_update_response(path, request_body);
+    fn get_metrics_response(&self, metrics: &Metrics) -> Response {
+        let mut metrics_text = String::new();
+        metrics.render(&mut metrics_text);
+        if let Some(ref connection_limit) = self.connection_limit {
+            metrics_text.push_str("# HELP vc_connections Open connections to the read listeners\n");
+            metrics_text.push_str("# TYPE vc_connections gauge\n");
+            metrics_text.push_str(&format!("vc_connections {}\n", connection_limit.connections()));
+        }
+        self.app.get_metrics(&mut metrics_text);
 
-        response.body_vec(data);
+        let mut response = Response::new();
+        response
+            .content_type(METRICS_CONTENT_TYPE)
+            .body_vec(metrics_text.into_bytes());
 
         response
     }
 
+    /// Series a read request is recorded in (and when it came in), None if metrics are not
+    /// enabled.  Long-polls are recorded by the route they watch.
+    fn start_recording(&self, route: &str) -> Option<(Arc<Metrics>, usize, Instant)> {
+        let metrics = self.metrics.as_ref()?;
+        let route_index = if route.starts_with(URL_LONG_POLL_PREFIX) {
+            metrics.get_route_index(RequestKind::LongPoll, &route[URL_LONG_POLL_PREFIX.len()..])
+        } else {
+            metrics.get_route_index(RequestKind::Read, route)
+        };
+
+        Some((metrics.clone(), route_index, Instant::now()))
+    }
+
+    /// Resolves a request into what gets written back to the connection: a single response
+    /// or, for event stream requests, an open ended stream of events
+    fn resolve_output(server: &Arc<Server>, request: &Request, client_address: Option<IpAddr>) -> Box<Stream<Item=Output, Error=io::Error> + Send> {
+        let rate_limited_response = server.get_rate_limited_response(request, client_address);
+
+        if rate_limited_response.is_none() && request.method() == "PUT"
+            && request.route().starts_with(URL_EVENT_STREAM_PREFIX)
+            && codes::get_protocol_version(request, server.app.get_protocol_versions()).is_ok() {
+            if let Some(topics) = server.app.get_event_topics(request.route(), request.raw_body()) {
+                return Server::resolve_event_stream(server, topics);
+            }
+        }
+
+        // Event streams are open ended, they are not recorded
+        let recording = server.start_recording(request.route());
+        let connection_header = get_connection_header(request);
+        let compression = server.compression_min_size.map(|min_size| {
+            (request.header("Accept-Encoding").and_then(compress::negotiate), min_size)
+        });
+        // App responses are compressed as they are produced (so that memoized pages are
+        // compressed only once), other responses right before they are sent
+        let accepted_compression = compression.and_then(|(encoding, min_size)| {
+            encoding.map(|encoding| (encoding, min_size))
+        });
+
+        let response: Box<Future<Item=Response, Error=io::Error> + Send> = match rate_limited_response {
+            None => {
+                Server::resolve(server, request, accepted_compression)
+            }
+            Some(rate_limited_response) => {
+                Box::new(future::ok(rate_limited_response))
+            }
+        };
+
+        Box::new(response.map(move |mut response| {
+            if let Some((metrics, route_index, started)) = recording {
+                metrics.record(route_index, started.elapsed(), response.uncompressed_body());
+            }
+            if let Some((encoding, min_size)) = compression {
+                // The response depends on Accept-Encoding, whether it is compressed or not
+                response.header("Vary", "Accept-Encoding");
+                if let Some(encoding) = encoding {
+                    compress::compress_response(&mut response, encoding, min_size);
+                }
+            }
+            if let Some(connection_header) = connection_header {
+                response.header("Connection", connection_header);
+            }
+            Output::Response(response)
+        }).into_stream())
+    }
+
+    /// Takes the request's cost from the client's rate limit, the response to send instead if
+    /// the client is over it (clients on Unix domain sockets, without an address, are not limited)
+    fn get_rate_limited_response(&self, request: &Request, client_address: Option<IpAddr>) -> Option<Response> {
+        let rate_limiter = self.rate_limiter.as_ref()?;
+        let client_address = client_address?;
+
+        match rate_limiter.check(client_address, request.route()) {
+            Ok(()) => {
+                None
+            }
+            Err(wait) => {
+                let retry_after_seconds = wait.as_secs() + if wait.subsec_nanos() > 0 { 1 } else { 0 };
+                Some(codes::get_rate_limited_response(retry_after_seconds))
+            }
+        }
+    }
+
     /// Resolves a request, returning a future that is processable into a Response
+    fn resolve(server: &Arc<Server>, request: &Request, compression: Option<(Encoding, usize)>) -> Box<Future<Item=Response, Error=io::Error> + Send> {
+        if request.method() == "PUT" && request.route().starts_with(URL_LONG_POLL_PREFIX) {
+            return Server::resolve_long_poll(server, request, compression);
+        }
 
-    fn resolve(&self, request: &Request) -> impl Future<Item=Response, Error=io::Error> + Send {
-        let response = self.get_response(&request);
+        let response = server.get_response(&request, compression);
 //        request.set_params(matched_route.params);
 
 //        let context = (self.context_generator)(request);
@@ -198,12 +571,161 @@ impl Server {
 //        return_value
 //            .and_then(|context| {
 //
-        future::ok(response)
+        Box::new(future::ok(response))
 //            })
     }
 
-    fn resolve_update(&self, request: &Request) -> impl Future<Item=Response, Error=io::Error> + Send {
-        let response = self.get_update_response(&request);
-        future::ok(response)
+    /// Parks a long-poll request until the page it is watching changes (or the request times
+    /// out), then responds with the current contents of the page
+    fn resolve_long_poll(server: &Arc<Server>, request: &Request, compression: Option<(Encoding, usize)>) -> Box<Future<Item=Response, Error=io::Error> + Send> {
+        let protocol_version = match codes::get_protocol_version(
+            request, server.app.get_protocol_versions()) {
+            Err(response) => {
+                return Box::new(future::ok(response));
+            }
+            Ok(protocol_version) => {
+                protocol_version
+            }
+        };
+        let path = &request.route()[URL_LONG_POLL_PREFIX.len()..];
+
+        Box::new(Server::watch_page(server, protocol_version, path, request.raw_body(), compression)
+            .map(move |(data, compressed)| {
+                Server::get_app_response(protocol_version, data, compressed, compression)
+            }))
+    }
+
+    /// Waits for the requested page to change (or for the long-poll timeout), then gets the
+    /// current contents of the page
+    fn watch_page(server: &Arc<Server>, protocol_version: ProtocolVersion, path: &str, request_body: &[u8],
+                  compression: Option<(Encoding, usize)>) -> Box<Future<Item=(Bytes, Option<Bytes>), Error=io::Error> + Send> {
+        // The request body starts with the data version the client has the page as of
+        if request_body.len() < DATA_VERSION_SIZE {
+            return Box::new(future::ok((Bytes::from_static(&codes::INVALID_DATA_FORMAT_RESPONSE), None)));
+        }
+        let client_data_version = BigEndian::read_u32(&request_body[..DATA_VERSION_SIZE]);
+        let request_body = &request_body[DATA_VERSION_SIZE..];
+
+        let page_key = match server.app.get_page_key(protocol_version, path, request_body) {
+            None => {
+                // Not a watchable page (or not a valid request for one), respond right away
+                return Box::new(future::ok(server.get_app_data(protocol_version, path, request_body, compression)));
+            }
+            Some(page_key) => {
+                page_key
+            }
+        };
+
+        let page_changed = match server.page_watchers.watch(page_key, client_data_version) {
+            None => {
+                // The client's page is already out of date
+                return Box::new(future::ok(server.get_app_data(protocol_version, path, request_body, compression)));
+            }
+            Some(page_changed) => {
+                page_changed.map_err(|_| ())
+            }
+        };
+        let timed_out = Delay::new(Instant::now() + Duration::from_secs(LONG_POLL_TIMEOUT_SECONDS))
+            .map_err(|_| ());
+
+        let server = server.clone();
+        let path = path.to_string();
+        let request_body = request_body.to_vec();
+
+        Box::new(page_changed.select(timed_out).then(move |_| {
+            Ok(server.get_app_data(protocol_version, &path, &request_body, compression))
+        }))
+    }
+
+    /// Resolves a raw protocol request into its response frame, long-poll requests complete
+    /// when their page changes (other requests right away)
+    fn resolve_raw(server: &Arc<Server>, request: RawRequest, client_address: Option<IpAddr>) -> Box<Future<Item=RawResponse, Error=io::Error> + Send> {
+        let id = request.id;
+        let recording = server.start_recording(&request.route);
+
+        Box::new(Server::get_raw_data(server, request, client_address)
+            .map(move |body| {
+                if let Some((metrics, route_index, started)) = recording {
+                    metrics.record(route_index, started.elapsed(), &body);
+                }
+                RawResponse {
+                    id,
+                    body,
+                }
+            }))
+    }
+
+    fn get_raw_data(server: &Arc<Server>, request: RawRequest, client_address: Option<IpAddr>) -> Box<Future<Item=Bytes, Error=io::Error> + Send> {
+        if let (Some(rate_limiter), Some(client_address)) = (server.rate_limiter.as_ref(), client_address) {
+            if rate_limiter.check(client_address, &request.route).is_err() {
+                return Box::new(future::ok(Bytes::from_static(&codes::RATE_LIMITED_RESPONSE)));
+            }
+        }
+        let protocol_versions = server.app.get_protocol_versions();
+        if request.route == URL_PROTOCOL_VERSIONS {
+            return Box::new(future::ok(Bytes::from(codes::get_protocol_versions_data(protocol_versions))));
+        }
+        let protocol_version = if request.protocol_version == FRAME_DEFAULT_PROTOCOL_VERSION {
+            DEFAULT_PROTOCOL_VERSION
+        } else {
+            request.protocol_version
+        };
+        let protocol_version = match codes::check_protocol_version(
+            Some(protocol_version), protocol_versions) {
+            Err(data) => {
+                return Box::new(future::ok(Bytes::from(data)));
+            }
+            Ok(protocol_version) => {
+                protocol_version
+            }
+        };
+
+        if request.route.starts_with(URL_LONG_POLL_PREFIX) {
+            // Raw protocol responses are never compressed
+            return Box::new(Server::watch_page(server, protocol_version,
+                                               &request.route[URL_LONG_POLL_PREFIX.len()..], &request.body, None)
+                .map(|(data, _)| data));
+        }
+        // Event streams are only served over HTTP
+        if request.route.starts_with(URL_EVENT_STREAM_PREFIX) {
+            return Box::new(future::ok(Bytes::from_static(&codes::INVALID_DATA_FORMAT_RESPONSE)));
+        }
+
+        Box::new(future::ok(server.app.get_response(protocol_version, &request.route, &request.body)))
+    }
+
+    /// Subscribes to the requested topics and streams events as they are published, until either
+    /// the client disconnects or the subscription is dropped (for falling behind)
+    fn resolve_event_stream(server: &Arc<Server>, topics: Vec<Topic>) -> Box<Stream<Item=Output, Error=io::Error> + Send> {
+        // None marks the end of the subscription
+        let events = server.event_hub.subscribe(topics)
+            .map(|event: Event| Some(Output::Event(event)))
+            .chain(stream::once(Ok(None)))
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "event subscription failed"));
+        let heartbeat: Event = Arc::new(EVENT_STREAM_HEARTBEAT.to_vec());
+        let heartbeats = Interval::new(
+            Instant::now() + Duration::from_secs(EVENT_STREAM_HEARTBEAT_SECONDS),
+            Duration::from_secs(EVENT_STREAM_HEARTBEAT_SECONDS))
+            .map(move |_| Some(Output::Event(heartbeat.clone())))
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err));
+
+        Box::new(stream::once(Ok(Output::EventStreamHead))
+            .chain(events.select(heartbeats)
+                .take_while(|output| Ok(output.is_some()))
+                .map(|output| output.unwrap())))
     }
-}
\ No newline at end of file
+}
+
+/// The client end of a connection: its IP address (none for Unix domain sockets)
+#[derive(Clone, Copy)]
+struct Client {
+    address: Option<IpAddr>,
+}
+
+/// What a listener serves: read requests over HTTP or over the raw protocol (updates are
+/// served by the Updater, on its own listeners)
+#[derive(Clone, Copy, PartialEq)]
+enum ListenerKind {
+    Read,
+    Raw,
+}
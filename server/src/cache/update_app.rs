@@ -0,0 +1,174 @@
+use byteorder::{BigEndian, WriteBytesExt};
+
+use common::model::protocol::ProtocolVersion;
+use common::model::protocol::PROTOCOL_VERSION_1;
+
+use super::super::route::RouteKind;
+use super::super::route::RouteRegistry;
+use super::producers::ProducerSequences;
+
+pub const UPDATE_RESULT_VERSION_1: u8 = 1;
+
+/**
+ * Why an item of an update request was not applied.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RejectionReason {
+    // The item's period is already current (or over), it can no longer be changed
+    TooLate,
+    // The item's period does not accept changes yet
+    TooEarly,
+    // The item does not make sense (unknown ids, empty lists)
+    Invalid,
+}
+
+impl RejectionReason {
+    pub fn code(&self) -> u8 {
+        match *self {
+            RejectionReason::TooLate => 1,
+            RejectionReason::TooEarly => 2,
+            RejectionReason::Invalid => 3,
+        }
+    }
+}
+
+/**
+ * A rejected item, by its position in the update request.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rejection {
+    pub index: u32,
+    pub reason: RejectionReason,
+}
+
+/**
+ * Outcome of an update request: how many of its items were applied, which ones were not (and
+ * why), and the data version of the cache once the update is done.  For sequenced requests
+ * (see producers::Batch) also the producer's applied sequence, and whether the request was a
+ * duplicate that was acknowledged without being applied again.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct UpdateResult {
+    pub accepted: u32,
+    pub rejected: Vec<Rejection>,
+    pub data_version: u32,
+    pub applied_sequence: u64,
+    pub duplicate: bool,
+}
+
+impl UpdateResult {
+    pub fn new(
+        data_version: u32
+    ) -> UpdateResult {
+        UpdateResult {
+            accepted: 0,
+            rejected: Vec::new(),
+            data_version,
+            applied_sequence: 0,
+            duplicate: false,
+        }
+    }
+
+    /**
+     * Acknowledgement of a batch that has already been applied.
+     */
+    pub fn duplicate(
+        data_version: u32,
+        applied_sequence: u64,
+    ) -> UpdateResult {
+        UpdateResult {
+            accepted: 0,
+            rejected: Vec::new(),
+            data_version,
+            applied_sequence,
+            duplicate: true,
+        }
+    }
+
+    pub fn reject(
+        &mut self,
+        index: u32,
+        reason: RejectionReason,
+    ) {
+        self.rejected.push(Rejection {
+            index,
+            reason,
+        });
+    }
+
+    /**
+     * Response body of an update request (all numbers are Big Endian):
+     *
+     *  1 byte  - result version (UPDATE_RESULT_VERSION_*)
+     *  1 byte  - 1 if the request was a duplicate (and was not applied again), 0 otherwise
+     *  4 bytes - number of accepted items
+     *  4 bytes - data version after the update
+     *  8 bytes - applied sequence of the producer (0 for unsequenced requests)
+     *  4 bytes - number of rejected items, followed by that many:
+     *      4 bytes - index of the item in the request
+     *      1 byte  - reason code (RejectionReason::code)
+     *
+     * The leading version byte never has RESPONSE_INVALID_FLAG set, so error responses (an
+     * unknown route or a malformed request) are told apart by their first byte.
+     */
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(22 + self.rejected.len() * 5);
+        data.push(UPDATE_RESULT_VERSION_1);
+        data.push(if self.duplicate { 1 } else { 0 });
+        data.write_u32::<BigEndian>(self.accepted).unwrap();
+        data.write_u32::<BigEndian>(self.data_version).unwrap();
+        data.write_u64::<BigEndian>(self.applied_sequence).unwrap();
+        data.write_u32::<BigEndian>(self.rejected.len() as u32).unwrap();
+        for rejection in self.rejected.iter() {
+            data.write_u32::<BigEndian>(rejection.index).unwrap();
+            data.push(rejection.reason.code());
+        }
+
+        data
+    }
+}
+
+/**
+ * Mutations of an App's data, served by the Updater on its own listeners.  Requests are decoded
+ * on the updater thread and applied, one at a time, on the writer thread (the only one that
+ * mutates the data), which owns the UpdateApp.
+ */
+pub trait UpdateApp {
+
+    // A decoded update request, handed from the updater thread to the writer thread
+    type Update: Send + 'static;
+
+    // Context the App's read routes run against, they are registered along with the update
+    // routes
+    type Context: ?Sized;
+
+    // Routes of the App, update requests are decoded (on the updater thread) by its update
+    // routes
+    fn get_routes() -> RouteRegistry<Self::Context, Self::Update>;
+
+    // Applies a decoded update (on the writer thread)
+    fn apply_update(
+        &mut self,
+        update: Self::Update,
+    ) -> UpdateResult;
+
+    // Current version of the data, returned with acknowledgements of duplicate requests
+    fn get_data_version(&self) -> u32;
+
+    // Last applied sequence by producer, kept (and persisted) with the data updates are
+    // applied to
+    fn get_producer_sequences(&mut self) -> &mut ProducerSequences;
+
+    // Protocol versions served, requests for other versions get the unsupported version
+    // response
+    fn get_protocol_versions() -> &'static [ProtocolVersion] {
+        &[PROTOCOL_VERSION_1]
+    }
+
+    // Ids of the update routes, requests are recorded by route id (requests for other routes
+    // are recorded together) when metrics are enabled
+    fn get_route_ids() -> Vec<&'static str> {
+        Self::get_routes().route_ids(RouteKind::Update)
+    }
+
+}
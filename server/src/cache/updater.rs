@@ -1,129 +1,616 @@
+use bytes::Bytes;
 use futures::future;
+use futures::sync::oneshot;
 use futures::Future;
-use net2::TcpBuilder;
-#[cfg(not(windows))]
-use net2::unix::UnixTcpBuilderExt;
 use std::io;
-use std::net::ToSocketAddrs;
+use std::panic;
+use std::panic::AssertUnwindSafe;
 use std::sync::Arc;
+use std::sync::mpsc;
 use std::thread;
+use std::time::Instant;
 use tokio;
-use tokio::net::{TcpStream, TcpListener};
+use tokio::net::TcpListener;
+#[cfg(unix)]
+use tokio::net::UnixListener;
 use tokio::prelude::*;
-use tokio_codec::Framed;
 
+use common::model::protocol::ProtocolVersion;
+
+use super::super::auth::UpdateAuthenticator;
 use super::super::http::Http;
+use super::super::http::Output;
 use super::super::request::Request;
+use super::super::request::DEFAULT_MAX_BODY_SIZE;
 use super::super::codes;
+use super::super::config::ServerConfig;
+use super::super::gather;
+use super::super::metrics::Metrics;
+use super::super::metrics::RequestKind;
 use super::super::response::Response;
+use super::super::route::RouteRegistry;
+use super::super::tls::TlsAcceptor;
+
+use super::listen::bind_listeners;
+use super::listen::get_connection_header;
+use super::listen::Listener;
+use super::listen::UntilClose;
+use super::producers;
+use super::producers::Batch;
+use super::update_app::UpdateApp;
+use super::update_app::UpdateResult;
+
+/// Default number of decoded updates waiting to be applied, past which update requests get
+/// the busy response
+pub const DEFAULT_QUEUE_SIZE: usize = 1024;
+
+/// Retry-After of the busy response
+const BUSY_RETRY_AFTER_SECONDS: u64 = 1;
 
-use super::app::App;
+///
+/// Serves update requests (PUTs) on the updater listeners.  Requests are authenticated and
+/// decoded on the updater thread, then queued for the writer thread, which owns the UpdateApp
+/// and applies the updates one at a time.  Responses (acknowledgements) are sent once the
+/// update is applied.
+///
+/// The queue is bounded, requests that find it full get a 503 right away, so that slow applies
+/// (rehashing a map, saving a snapshot) never hold up accepting and reading requests.
+///
+/// Other changes to the app's data (scheduled jobs) are run on the writer thread too, through
+/// the UpdateWriter returned by start, in between updates.
+///
+pub struct Updater<A: UpdateApp> {
+    app: A,
+    max_body_size: usize,
+    update_authenticator: Option<UpdateAuthenticator>,
+    unsigned_updates_allowed: bool,
+    tls: Option<TlsAcceptor>,
+    queue_size: usize,
+    metrics: Option<Arc<Metrics>>,
+}
+
+/// A decoded update (or a job), waiting in the queue to be applied
+enum Command<A: UpdateApp> {
+    Update {
+        update: A::Update,
+        batch: Option<Batch>,
+        ack: oneshot::Sender<UpdateResult>,
+    },
+    Job {
+        job: Box<FnOnce(&mut A) -> Result<(), String> + Send>,
+        done: mpsc::Sender<Result<(), String>>,
+    },
+}
 
-pub struct Updater {
-    app: Box<App + Send + Sync>
+/// Runs jobs on the writer thread (with the app), for changes to its data other than update
+/// requests
+pub struct UpdateWriter<A: UpdateApp> {
+    commands: mpsc::SyncSender<Command<A>>,
+    metrics: Option<Arc<Metrics>>,
 }
 
-impl Updater {
+/// The updater thread's half of the Updater: authenticates and decodes requests, and queues
+/// them for the writer thread
+struct Intake<A: UpdateApp> {
+    // Update requests are decoded by the App's update routes
+    routes: RouteRegistry<A::Context, A::Update>,
+    max_body_size: usize,
+    update_authenticator: Option<UpdateAuthenticator>,
+    unsigned_updates_allowed: bool,
+    tls: Option<TlsAcceptor>,
+    commands: mpsc::SyncSender<Command<A>>,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl<A: UpdateApp + Send + 'static> Updater<A> {
     pub fn new(
-        app: Box<App + Send + Sync>
-    ) -> Updater {
+        app: A
+    ) -> Updater<A> {
         Updater {
-            app
+            app,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            update_authenticator: None,
+            unsigned_updates_allowed: false,
+            tls: None,
+            queue_size: DEFAULT_QUEUE_SIZE,
+            metrics: None,
         }
     }
 
+    pub fn set_max_body_size(
+        &mut self,
+        max_body_size: usize,
+    ) {
+        self.max_body_size = max_body_size;
+    }
+
+    ///
+    /// Requires update requests to be signed with one of the authenticator's keys, requests
+    /// that aren't are responded to with a 401.
+    ///
+    pub fn set_update_authenticator(
+        &mut self,
+        update_authenticator: UpdateAuthenticator,
+    ) {
+        self.update_authenticator = Some(update_authenticator);
+    }
+
+    ///
+    /// Accepts unsigned update requests when there is no authenticator (for local development),
+    /// off by default.
+    ///
+    pub fn set_unsigned_updates_allowed(
+        &mut self,
+        unsigned_updates_allowed: bool,
+    ) {
+        self.unsigned_updates_allowed = unsigned_updates_allowed;
+    }
+
+    ///
+    /// Serves update requests over TLS, on the TCP listeners (Unix domain sockets stay
+    /// plaintext).  If the acceptor verifies client certificates, requests on these listeners
+    /// don't need to be signed.
+    ///
+    pub fn set_tls(
+        &mut self,
+        tls: TlsAcceptor,
+    ) {
+        self.tls = Some(tls);
+    }
+
+    ///
+    /// Most decoded updates waiting to be applied, further update requests get a 503 (with
+    /// Retry-After) until there is room.
+    ///
+    pub fn set_queue_size(
+        &mut self,
+        queue_size: usize,
+    ) {
+        self.queue_size = queue_size;
+    }
+
     ///
-    /// Starts the app with a thread pool optimized for small requests and quick timeouts. This
-    /// is done internally by spawning a separate thread for each reactor core. This is valuable
-    /// if all app endpoints are similar in their load, as work is divided evenly among threads.
-    /// As seanmonstar points out though, this is a very specific use case and might not be useful
-    /// for everyday work loads.alloc
+    /// Records update requests (by route id) and the depth of the queue in the metrics, the
+    /// same ones that are served by the Server.
     ///
-    /// See the discussion here for more information:
+    pub fn set_metrics(
+        &mut self,
+        metrics: Arc<Metrics>,
+    ) {
+        self.metrics = Some(metrics);
+    }
+
     ///
-    /// https://users.rust-lang.org/t/getting-tokio-to-match-actix-web-performance/18659/7
+    /// Starts the writer thread and the update thread, accepting on all of the updater
+    /// listeners.  Returns (the writer for jobs) once the listeners are bound, both threads run
+    /// for the life of the process.  Err if a listener can't be bound (neither thread is
+    /// started then).
     ///
-    pub fn start_small_load_optimized(server: Updater, host: &str, port: u16) {
-        let addr = (host, port).to_socket_addrs().unwrap().next().unwrap();
+    pub fn start(
+        updater: Updater<A>,
+        config: &ServerConfig,
+    ) -> Result<UpdateWriter<A>, String> {
+        // All on the one update thread
+        let updater_listeners = bind_listeners(&config.updater_addresses, 1, config.backlog)?
+            .remove(0);
+        let (commands, queue) = mpsc::sync_channel(updater.queue_size);
+        let update_writer = UpdateWriter {
+            commands: commands.clone(),
+            metrics: updater.metrics.clone(),
+        };
 
-        let arc_server = Arc::new(server);
+        let app = updater.app;
+        let metrics = updater.metrics.clone();
+        thread::spawn(move || {
+            write(app, queue, metrics);
+        });
+
+        let intake: Arc<Intake<A>> = Arc::new(Intake {
+            routes: A::get_routes(),
+            max_body_size: updater.max_body_size,
+            update_authenticator: updater.update_authenticator,
+            unsigned_updates_allowed: updater.unsigned_updates_allowed,
+            tls: updater.tls,
+            commands,
+            metrics: updater.metrics,
+        });
 
         thread::spawn(move || {
             let mut runtime = tokio::runtime::current_thread::Runtime::new().unwrap();
 
-            let server = future::lazy(move || {
-                let listener = {
-                    let builder = TcpBuilder::new_v4().unwrap();
-                    #[cfg(not(windows))]
-                        builder.reuse_address(true).unwrap();
-                    #[cfg(not(windows))]
-                        builder.reuse_port(true).unwrap();
-                    builder.bind(addr).unwrap();
-                    builder.listen(10240).unwrap()
-                };
-                let listener = TcpListener::from_std(listener, &tokio::reactor::Handle::current()).unwrap();
-
-                listener.incoming().for_each(move |socket| {
-                    process(Arc::clone(&arc_server), socket);
-                    Ok(())
-                })
-                    .map_err(|err| eprintln!("accept error = {:?}", err))
-            });
-
-            runtime.spawn(server);
+            for listener in updater_listeners {
+                let updater = accept(intake.clone(), listener);
+                runtime.spawn(updater);
+            }
             runtime.run().unwrap();
         });
 
-        println!("Updater running on {}", addr);
+        for updater_address in config.updater_addresses.iter() {
+            println!("Update thread running on {}", updater_address);
+        }
+
+        return Ok(update_writer);
 
+        fn accept<A: UpdateApp + 'static>(
+            intake: Arc<Intake<A>>,
+            listener: Listener,
+        ) -> Box<Future<Item=(), Error=()>> {
+            match listener {
+                Listener::Tcp(listener) => {
+                    let listener = TcpListener::from_std(listener, &tokio::reactor::Handle::current()).unwrap();
 
+                    let tls = intake.tls.clone();
+
+                    Box::new(listener.incoming().for_each(move |socket| {
+                        match tls {
+                            None => {
+                                process(Arc::clone(&intake), socket, false);
+                            }
+                            Some(ref tls) => {
+                                process(Arc::clone(&intake), tls.accept(socket), tls.verifies_clients());
+                            }
+                        }
+                        Ok(())
+                    })
+                        .map_err(|err| eprintln!("accept error = {:?}", err)))
+                }
+                #[cfg(unix)]
+                Listener::Unix(listener) => {
+                    let listener = UnixListener::from_std(listener, &tokio::reactor::Handle::current()).unwrap();
+
+                    Box::new(listener.incoming().for_each(move |socket| {
+                        process(Arc::clone(&intake), socket, false);
+                        Ok(())
+                    })
+                        .map_err(|err| eprintln!("accept error = {:?}", err)))
+                }
+            }
+        }
 
-        fn process(server: Arc<Updater>, socket: TcpStream) {
-            let framed = Framed::new(socket, Http);
-            let (tx, rx) = framed.split();
+        fn process<A, S>(intake: Arc<Intake<A>>, socket: S, client_verified: bool)
+            where A: UpdateApp + 'static, S: AsyncRead + AsyncWrite + Send + 'static {
+            let (tx, rx) = gather::split(socket, Http::new(intake.max_body_size), Http::new(intake.max_body_size));
 
-            let task = tx.send_all(rx.and_then(move |request: Request| {
-                server.resolve(&request)
-            }))
+            let task = tx.send_all(UntilClose::new(rx).and_then(move |request: Request| {
+                intake.resolve(&request, client_verified)
+            }).map(Output::Response))
                 .then(|_| future::ok(()));
 
             // Spawn the task that handles the connection.
             tokio::spawn(task);
         }
     }
+}
+
+impl<A: UpdateApp> UpdateWriter<A> {
+    ///
+    /// Runs a job on the writer thread, after the updates queued before it, and waits for it
+    /// to finish.  Unlike update requests, jobs wait for room in the queue.
+    ///
+    pub fn run<F>(
+        &self,
+        job: F,
+    ) -> Result<(), String> where F: FnOnce(&mut A) -> Result<(), String> + Send + 'static {
+        let (done, result) = mpsc::channel();
+        if let Some(ref metrics) = self.metrics {
+            metrics.update_queued();
+        }
+        self.commands.send(Command::Job {
+            job: Box::new(job),
+            done,
+        }).map_err(|_| {
+            if let Some(ref metrics) = self.metrics {
+                metrics.update_dequeued();
+            }
+            "Update writer thread stopped".to_string()
+        })?;
+
+        result.recv().unwrap_or_else(|_| Err("Update writer thread stopped".to_string()))
+    }
+}
 
-    #[inline]
-    fn get_response(&self, request: &Request) -> Response {
-        let mut response = Response::new();
+impl<A: UpdateApp> Clone for UpdateWriter<A> {
+    fn clone(&self) -> UpdateWriter<A> {
+        UpdateWriter {
+            commands: self.commands.clone(),
+            metrics: self.metrics.clone(),
+        }
+    }
+}
 
+impl<A: UpdateApp> Intake<A> {
+    /// Queues the request's update, returning the protocol version to respond in and the
+    /// acknowledgement to come.  Err is the response to send right away.
+    fn queue(&self, request: &Request, client_verified: bool) -> Result<(ProtocolVersion, oneshot::Receiver<UpdateResult>), Response> {
+        if let Some(error) = request.error() {
+            return Err(codes::get_request_error_response(error));
+        }
         if request.method() != "PUT" {
-            response.body_vec(codes::INVALID_DATA_FORMAT_RESPONSE.to_vec());
-            return response;
+            return Err(codes::get_method_not_allowed_response());
         }
+        // Clients with a verified certificate don't sign their requests
+        match self.update_authenticator {
+            _ if client_verified => {}
+            None => {
+                if !self.unsigned_updates_allowed {
+                    return Err(codes::get_unauthorized_response());
+                }
+            }
+            Some(ref update_authenticator) => {
+                if let Err(error) = update_authenticator.authenticate(request) {
+                    eprintln!("Rejected update request {}: {:?}", request.route(), error);
+                    return Err(codes::get_unauthorized_response());
+                }
+            }
+        }
+
+        let protocol_version = codes::get_protocol_version(request, A::get_protocol_versions())?;
+        let invalid_data_format_response = || codes::get_versioned_app_response(
+            protocol_version, Bytes::from_static(&codes::INVALID_DATA_FORMAT_RESPONSE));
+
+        let batch = producers::get_batch(request)
+            .map_err(|_| invalid_data_format_response())?;
+        let update = self.routes.decode_update(protocol_version, request.route(), request.raw_body())
+            .ok_or_else(invalid_data_format_response)?;
+
+        let (ack, acknowledgement) = oneshot::channel();
+        // Counted before it is sent, the writer thread may take it off the queue right away
+        if let Some(ref metrics) = self.metrics {
+            metrics.update_queued();
+        }
+        let sent = self.commands.try_send(Command::Update {
+            update,
+            batch,
+            ack,
+        });
+        if let (&Err(_), Some(metrics)) = (&sent, self.metrics.as_ref()) {
+            metrics.update_dequeued();
+        }
+        match sent {
+            Ok(()) => {
+                Ok((protocol_version, acknowledgement))
+            }
+            Err(mpsc::TrySendError::Full(_)) => {
+                Err(codes::get_updates_busy_response(BUSY_RETRY_AFTER_SECONDS))
+            }
+            Err(mpsc::TrySendError::Disconnected(_)) => {
+                eprintln!("Update writer thread stopped, update request {} not applied", request.route());
+                Err(codes::get_update_failed_response())
+            }
+        }
+    }
+
+    /// Resolves a request, returning a future that is processable into a Response (once the
+    /// update is applied)
+    fn resolve(&self, request: &Request, client_verified: bool) -> Box<Future<Item=Response, Error=io::Error> + Send> {
+        let connection_header = get_connection_header(request);
+        let recording = self.metrics.as_ref().map(|metrics| {
+            (metrics.clone(), metrics.get_route_index(RequestKind::Update, request.route()), Instant::now())
+        });
+
+        let response: Box<Future<Item=Response, Error=io::Error> + Send> = match self.queue(request, client_verified) {
+            Err(response) => {
+                Box::new(future::ok(response))
+            }
+            Ok((protocol_version, acknowledgement)) => {
+                // The ack is dropped (without a result) if applying the update panicked
+                Box::new(acknowledgement
+                    .then(move |update_result| {
+                        match update_result {
+                            Ok(update_result) => {
+                                Ok(codes::get_versioned_app_response(protocol_version, Bytes::from(update_result.encode())))
+                            }
+                            Err(_) => {
+                                Ok(codes::get_update_failed_response())
+                            }
+                        }
+                    }))
+            }
+        };
+
+        Box::new(response.map(move |mut response| {
+            if let Some((metrics, route_index, started)) = recording {
+                metrics.record(route_index, started.elapsed(), &response.response);
+            }
+            if let Some(connection_header) = connection_header {
+                response.header("Connection", connection_header);
+            }
+            response
+        }))
+    }
+}
+
+/// The writer thread: applies queued updates (and runs queued jobs) in order
+fn write<A: UpdateApp>(mut app: A, queue: mpsc::Receiver<Command<A>>, metrics: Option<Arc<Metrics>>) {
+    for command in queue.iter() {
+        if let Some(ref metrics) = metrics {
+            metrics.update_dequeued();
+        }
+        match command {
+            // A panic fails only the command that caused it, the writer thread keeps applying
+            // the commands after it
+            Command::Update { update, batch, ack } => {
+                match panic::catch_unwind(AssertUnwindSafe(|| apply(&mut app, update, batch))) {
+                    Ok(update_result) => {
+                        // The client may have gone away in the meantime
+                        let _ = ack.send(update_result);
+                    }
+                    Err(_) => {
+                        eprintln!("Applying an update panicked, update not applied");
+                    }
+                }
+            }
+            Command::Job { job, done } => {
+                let job_result = panic::catch_unwind(AssertUnwindSafe(|| job(&mut app)))
+                    .unwrap_or_else(|_| Err("Job panicked".to_string()));
+                let _ = done.send(job_result);
+            }
+        }
+    }
+}
+
+/// Applies an update, unless it is a retry of a batch that has already been applied
+fn apply<A: UpdateApp>(app: &mut A, update: A::Update, batch: Option<Batch>) -> UpdateResult {
+    let batch = match batch {
+        None => {
+            return app.apply_update(update);
+        }
+        Some(batch) => {
+            batch
+        }
+    };
+    if let Some(applied_sequence) = app.get_producer_sequences().get_applied(&batch) {
+        return UpdateResult::duplicate(app.get_data_version(), applied_sequence);
+    }
+
+    let mut update_result = app.apply_update(update);
+    // A batch with rejected items is applied all the same, retrying it would not change the
+    // outcome
+    app.get_producer_sequences().set_applied(&batch.producer_id, batch.sequence);
+    update_result.applied_sequence = batch.sequence;
 
-        let path = request.path();
-        let request_body = request.raw_body();
+    update_result
+}
 
-        let data = self.app.get_response(path, request_body);
+#[cfg(test)]
+mod tests {
+    use futures::sync::oneshot;
+    use futures::Future;
+    use std::sync::mpsc;
+    use std::thread;
 
-        response.body_vec(data);
+    use super::super::super::route::Route;
+    use super::super::super::route::RouteRegistry;
+    use super::super::producers::Batch;
+    use super::super::producers::ProducerSequences;
+    use super::super::update_app::UpdateApp;
+    use super::super::update_app::UpdateResult;
+    use super::apply;
+    use super::write;
+    use super::Command;
 
-        response
+    // Counts the updates applied to it, every update bumps the data version
+    struct CountingApp {
+        applied: u32,
+        producer_sequences: ProducerSequences,
     }
 
-    /// Resolves a request, returning a future that is processable into a Response
+    impl UpdateApp for CountingApp {
+        type Update = ();
+        type Context = ();
+
+        fn get_routes() -> RouteRegistry<(), ()> {
+            let mut routes = RouteRegistry::new();
+            routes.register(Route::update("u", "count", &[], |_, _| Some(())));
+
+            routes
+        }
 
-    fn resolve(&self, request: &Request) -> impl Future<Item=Response, Error=io::Error> + Send {
-        let response = self.get_response(&request);
-//        request.set_params(matched_route.params);
+        fn apply_update(
+            &mut self,
+            _update: (),
+        ) -> UpdateResult {
+            self.applied += 1;
+            let mut update_result = UpdateResult::new(self.applied);
+            update_result.accepted = 1;
 
-//        let context = (self.context_generator)(request);
-//        let return_value = Box::new(future::ok(context));
+            update_result
+        }
+
+        fn get_data_version(&self) -> u32 {
+            self.applied
+        }
 
-//        return_value
-//            .and_then(|context| {
-//
-        future::ok(response)
-//            })
+        fn get_producer_sequences(&mut self) -> &mut ProducerSequences {
+            &mut self.producer_sequences
+        }
+    }
+
+    fn batch(
+        producer_id: &str,
+        sequence: u64,
+    ) -> Option<Batch> {
+        Some(Batch {
+            producer_id: producer_id.to_string(),
+            sequence,
+        })
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn sequenced_updates() {
+        let mut app = CountingApp {
+            applied: 0,
+            producer_sequences: ProducerSequences::new(),
+        };
+
+        let update_result = apply(&mut app, (), batch("ingest-1", 5));
+        assert_eq!(update_result.accepted, 1);
+        assert_eq!(update_result.applied_sequence, 5);
+        assert!(!update_result.duplicate);
+
+        // A retry of the last batch is acknowledged without being applied again
+        let update_result = apply(&mut app, (), batch("ingest-1", 5));
+        assert_eq!(update_result, UpdateResult::duplicate(1, 5));
+        // And so is a batch below it
+        let update_result = apply(&mut app, (), batch("ingest-1", 2));
+        assert_eq!(update_result, UpdateResult::duplicate(1, 5));
+        assert_eq!(app.applied, 1);
+
+        // A new producer starts its own sequence
+        let update_result = apply(&mut app, (), batch("ingest-2", 1));
+        assert_eq!(update_result.applied_sequence, 1);
+        assert!(!update_result.duplicate);
+        assert_eq!(app.applied, 2);
+
+        // The next batch is applied (sequences may skip)
+        let update_result = apply(&mut app, (), batch("ingest-1", 9));
+        assert_eq!(update_result.applied_sequence, 9);
+        assert_eq!(app.applied, 3);
+    }
+
+    #[test]
+    fn unsequenced_updates() {
+        let mut app = CountingApp {
+            applied: 0,
+            producer_sequences: ProducerSequences::new(),
+        };
+
+        // Always applied, there is nothing to tell a retry apart by
+        apply(&mut app, (), None);
+        let update_result = apply(&mut app, (), None);
+        assert_eq!(update_result.applied_sequence, 0);
+        assert!(!update_result.duplicate);
+        assert_eq!(app.applied, 2);
+        assert_eq!(app.producer_sequences.len(), 0);
+    }
+
+    #[test]
+    fn panicking_commands() {
+        let app = CountingApp {
+            applied: 0,
+            producer_sequences: ProducerSequences::new(),
+        };
+        let (commands, queue) = mpsc::sync_channel(4);
+        let writer = thread::spawn(move || write(app, queue, None));
+
+        // A job that panics fails on its own
+        let (done, job_result) = mpsc::channel();
+        commands.send(Command::Job {
+            job: Box::new(|_app: &mut CountingApp| panic!("job failed")),
+            done,
+        }).unwrap();
+        assert_eq!(job_result.recv().unwrap(), Err("Job panicked".to_string()));
+
+        // And the writer thread keeps applying updates after it
+        let (ack, acknowledgement) = oneshot::channel();
+        commands.send(Command::Update {
+            update: (),
+            batch: None,
+            ack,
+        }).unwrap();
+        assert_eq!(acknowledgement.wait().unwrap().accepted, 1);
+
+        drop(commands);
+        writer.join().unwrap();
+    }
+}
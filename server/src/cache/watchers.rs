@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::mem;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use futures::sync::oneshot;
+
+/**
+ * Identifies a page of data that can be watched by long-poll requests (and is memoized).
+ * Computed by the App, both for incoming requests and when the data behind a page changes.
+ *
+ * Keys are compared field by field, two different pages never share a key: the kind of page,
+ * its period (and the id of that period), its timezone, the (cache) indexes of what the page
+ * is for and its block.  Fields that do not apply to a kind of page are 0.
+ */
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct PageKey {
+    pub kind: u8,
+    pub period: u8,
+    pub period_id: u32,
+    pub timezone_id: u32,
+    pub indexes: [u32; 2],
+    pub block_index: u32,
+}
+
+/**
+ * How often waiters whose requests have timed out or disconnected are dropped, across all
+ * pages.  Pages that are neither watched again nor notified would otherwise keep them.
+ */
+pub const PAGE_WATCHERS_PRUNE_INTERVAL_SECONDS: u64 = 30;
+
+struct Waiters {
+    by_page_key: HashMap<PageKey, Vec<oneshot::Sender<()>>>,
+    last_pruned: Instant,
+    // Data version each page last changed at, pages not in it last changed with all of them
+    changed_at: HashMap<PageKey, u32>,
+    all_changed_at: u32,
+}
+
+/**
+ *  Long-poll requests waiting for pages to change, by page key.  Also keeps the data version
+ *  each page last changed at, long-poll requests for a page that changed since the version the
+ *  client has it as of are responded to right away (and the others wait, however many times
+ *  the data version moves on without changing their page).
+ *
+ *  Shared between all reactor threads (which register waiters) and the update thread (which
+ *  notifies them), hence the Mutex.  Registrations and notifications are both short and
+ *  infrequent compared to regular requests.
+ */
+pub struct PageWatchers {
+    waiters: Mutex<Waiters>,
+}
+
+impl PageWatchers {
+    pub fn new() -> PageWatchers {
+        PageWatchers {
+            waiters: Mutex::new(Waiters {
+                by_page_key: HashMap::new(),
+                last_pruned: Instant::now(),
+                changed_at: HashMap::new(),
+                all_changed_at: 0,
+            }),
+        }
+    }
+
+    /**
+     * Registers a waiter on a page the client has as of a data version, the returned receiver
+     * completes when the page changes.  None if the page has already changed since that version.
+     */
+    pub fn watch(
+        &self,
+        page_key: PageKey,
+        data_version: u32,
+    ) -> Option<oneshot::Receiver<()>> {
+        let mut waiters = self.waiters.lock().unwrap();
+        let changed_at = waiters.changed_at.get(&page_key).cloned().unwrap_or(waiters.all_changed_at);
+        if is_after(changed_at, data_version) {
+            return None;
+        }
+
+        let (sender, receiver) = oneshot::channel();
+        let page_waiters = waiters.by_page_key.entry(page_key).or_insert_with(Vec::new);
+        // Drop waiters whose requests have already timed out or disconnected
+        page_waiters.retain(|waiter| !waiter.is_canceled());
+        page_waiters.push(sender);
+
+        Some(receiver)
+    }
+
+    /**
+     * Wakes up all waiters on a page, which changed as of the given data version.
+     */
+    pub fn notify(
+        &self,
+        page_key: PageKey,
+        data_version: u32,
+    ) {
+        let page_waiters = {
+            let mut waiters = self.waiters.lock().unwrap();
+            waiters.changed_at.insert(page_key, data_version);
+            prune_if_due(&mut waiters);
+            match waiters.by_page_key.remove(&page_key) {
+                None => {
+                    return;
+                }
+                Some(page_waiters) => {
+                    page_waiters
+                }
+            }
+        };
+
+        for waiter in page_waiters {
+            // Request may have already timed out, nothing to do then
+            let _ = waiter.send(());
+        }
+    }
+
+    /**
+     * Wakes up all waiters, on all pages, which all changed as of the given data version (when
+     * the periods move on).
+     */
+    pub fn notify_all(
+        &self,
+        data_version: u32,
+    ) {
+        let by_page_key = {
+            let mut waiters = self.waiters.lock().unwrap();
+            waiters.changed_at.clear();
+            waiters.all_changed_at = data_version;
+            mem::replace(&mut waiters.by_page_key, HashMap::new())
+        };
+
+        for (_, page_waiters) in by_page_key {
+            for waiter in page_waiters {
+                // Request may have already timed out, nothing to do then
+                let _ = waiter.send(());
+            }
+        }
+    }
+
+    /**
+     * Drops the waiters whose requests have timed out or disconnected, across all pages (run
+     * on a timer, see PAGE_WATCHERS_PRUNE_INTERVAL_SECONDS).
+     */
+    pub fn prune(&self) {
+        prune_waiters(&mut self.waiters.lock().unwrap());
+    }
+}
+
+/**
+ * Data versions wrap around, a version is after another if it is less than half the range
+ * ahead of it.
+ */
+#[inline]
+fn is_after(
+    data_version: u32,
+    other_data_version: u32,
+) -> bool {
+    (data_version.wrapping_sub(other_data_version) as i32) > 0
+}
+
+/**
+ * Notifications also prune (at most every PAGE_WATCHERS_PRUNE_INTERVAL_SECONDS), in case the
+ * timer falls behind.
+ */
+fn prune_if_due(
+    waiters: &mut Waiters,
+) {
+    if waiters.last_pruned.elapsed() >= Duration::from_secs(PAGE_WATCHERS_PRUNE_INTERVAL_SECONDS) {
+        prune_waiters(waiters);
+    }
+}
+
+fn prune_waiters(
+    waiters: &mut Waiters,
+) {
+    waiters.last_pruned = Instant::now();
+    waiters.by_page_key.retain(|_, page_waiters| {
+        page_waiters.retain(|waiter| !waiter.is_canceled());
+        !page_waiters.is_empty()
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PageKey;
+    use super::PageWatchers;
+
+    const PAGE_KEY: PageKey = PageKey {
+        kind: 0,
+        period: 6,
+        period_id: 7,
+        timezone_id: 0,
+        indexes: [0, 3],
+        block_index: 0,
+    };
+    const OTHER_PAGE_KEY: PageKey = PageKey {
+        kind: 0,
+        period: 6,
+        period_id: 7,
+        timezone_id: 0,
+        indexes: [0, 3],
+        block_index: 1,
+    };
+
+    #[test]
+    fn page_versions() {
+        let page_watchers = PageWatchers::new();
+        let mut page_changed = page_watchers.watch(PAGE_KEY, 5).unwrap();
+
+        page_watchers.notify(PAGE_KEY, 6);
+        assert_eq!(page_changed.try_recv(), Ok(Some(())));
+        // Clients with the page as of before the change get it right away
+        assert!(page_watchers.watch(PAGE_KEY, 5).is_none());
+        let mut page_changed = page_watchers.watch(PAGE_KEY, 6).unwrap();
+
+        // Other pages changing moves the data version on, but this page is still as of it
+        page_watchers.notify(OTHER_PAGE_KEY, 7);
+        assert_eq!(page_changed.try_recv(), Ok(None));
+        assert!(page_watchers.watch(PAGE_KEY, 6).is_some());
+        assert!(page_watchers.watch(PAGE_KEY, 7).is_some());
+
+        // All pages change when the periods move on
+        page_watchers.notify_all(8);
+        assert_eq!(page_changed.try_recv(), Ok(Some(())));
+        assert!(page_watchers.watch(PAGE_KEY, 7).is_none());
+        assert!(page_watchers.watch(OTHER_PAGE_KEY, 7).is_none());
+        assert!(page_watchers.watch(PAGE_KEY, 8).is_some());
+    }
+
+    #[test]
+    fn wrapped_versions() {
+        let page_watchers = PageWatchers::new();
+        page_watchers.notify(PAGE_KEY, 0);
+
+        assert!(page_watchers.watch(PAGE_KEY, u32::max_value()).is_none());
+        assert!(page_watchers.watch(PAGE_KEY, 0).is_some());
+        assert!(page_watchers.watch(PAGE_KEY, 1).is_some());
+    }
+}
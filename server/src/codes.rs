@@ -1,3 +1,10 @@
+use byteorder::{BigEndian, WriteBytesExt};
+use bytes::Bytes;
+
+use super::request::Request;
+use super::request::RequestError;
+use super::response::Response;
+
 use common::model::response::RESPONSE_INVALID_DATA_FORMAT_FLAG;
 use common::model::response::RESPONSE_INVALID_FLAG;
 use common::model::response::RESPONSE_INVALID_GLOBAL_CATEGORY_ID_FLAG;
@@ -6,6 +13,13 @@ use common::model::response::RESPONSE_INVALID_GLOBAL_LOCATION_ID_FLAG;
 use common::model::response::RESPONSE_INVALID_LOCATION_CACHE_INDEX_FLAG;
 use common::model::response::RESPONSE_INVALID_PERIOD_ID_FLAG;
 use common::model::response::RESPONSE_INVALID_TIMEZONE_ID_FLAG;
+use common::model::response::RESPONSE_UNSUPPORTED_PROTOCOL_VERSION_FLAG;
+use common::model::response::RESPONSE_RATE_LIMITED_FLAG;
+use common::model::response::RESPONSE_UNAUTHORIZED_FLAG;
+use common::model::response::RESPONSE_UPDATES_BUSY_FLAG;
+use common::model::protocol::ProtocolVersion;
+use common::model::protocol::DEFAULT_PROTOCOL_VERSION;
+use common::model::protocol::PROTOCOL_VERSION_HEADER;
 
 pub const INVALID_DATA_FORMAT_RESPONSE: [u8; 1] = [RESPONSE_INVALID_FLAG + RESPONSE_INVALID_DATA_FORMAT_FLAG];
 pub const INVALID_GLOBAL_CATEGORY_ID_RESPONSE: [u8; 1] = [RESPONSE_INVALID_FLAG + RESPONSE_INVALID_GLOBAL_CATEGORY_ID_FLAG];
@@ -14,4 +28,245 @@ pub const INVALID_GLOBAL_LOCATION_ID_RESPONSE: [u8; 1] = [RESPONSE_INVALID_FLAG
 pub const INVALID_LOCATION_CACHE_INDEX_RESPONSE: [u8; 1] = [RESPONSE_INVALID_FLAG + RESPONSE_INVALID_LOCATION_CACHE_INDEX_FLAG];
 pub const INVALID_PERIOD_ID_RESPONSE: [u8; 1] = [RESPONSE_INVALID_FLAG + RESPONSE_INVALID_PERIOD_ID_FLAG];
 pub const INVALID_TIMEZONE_ID_RESPONSE: [u8; 1] = [RESPONSE_INVALID_FLAG + RESPONSE_INVALID_TIMEZONE_ID_FLAG];
+pub const UNSUPPORTED_PROTOCOL_VERSION_RESPONSE: [u8; 1] = [RESPONSE_INVALID_FLAG + RESPONSE_UNSUPPORTED_PROTOCOL_VERSION_FLAG];
+pub const UNAUTHORIZED_RESPONSE: [u8; 1] = [RESPONSE_INVALID_FLAG + RESPONSE_UNAUTHORIZED_FLAG];
+pub const RATE_LIMITED_RESPONSE: [u8; 1] = [RESPONSE_INVALID_FLAG + RESPONSE_RATE_LIMITED_FLAG];
+pub const UPDATES_BUSY_RESPONSE: [u8; 1] = [RESPONSE_INVALID_FLAG + RESPONSE_UPDATES_BUSY_FLAG];
+
+/**
+ * Period id mismatch response, carries the server's current period id for the requested
+ * period slot (Big Endian), so that a client with a stale period id can retry right away.
+ */
+pub fn get_invalid_period_id_response(
+    current_period_id: u32
+) -> Vec<u8> {
+    let mut response = Vec::with_capacity(5);
+    response.extend_from_slice(&INVALID_PERIOD_ID_RESPONSE);
+    response.write_u32::<BigEndian>(current_period_id).unwrap();
+
+    response
+}
+
+/**
+ * HTTP status for an App response body, None for a successful (200 OK) response.
+ *
+ * Error responses start with a byte that has RESPONSE_INVALID_FLAG set, no valid response
+ * does (they all start with a small count, version or width byte).  The body is sent as is,
+ * so clients that only read the flags keep working.
+ */
+pub fn get_http_status(
+    response_body: &[u8]
+) -> Option<(u32, &'static str)> {
+    if response_body.is_empty() || response_body[0] & RESPONSE_INVALID_FLAG == 0 {
+        return None;
+    }
+
+    match response_body[0] - RESPONSE_INVALID_FLAG {
+        RESPONSE_INVALID_DATA_FORMAT_FLAG => Some((400, "Bad Request")),
+        RESPONSE_INVALID_GLOBAL_CATEGORY_ID_FLAG => Some((404, "Not Found")),
+        RESPONSE_INVALID_CATEGORY_CACHE_INDEX_FLAG => Some((404, "Not Found")),
+        RESPONSE_INVALID_GLOBAL_LOCATION_ID_FLAG => Some((404, "Not Found")),
+        RESPONSE_INVALID_LOCATION_CACHE_INDEX_FLAG => Some((404, "Not Found")),
+        // Client's period id is stale, the body carries the current one
+        RESPONSE_INVALID_PERIOD_ID_FLAG => Some((409, "Conflict")),
+        RESPONSE_INVALID_TIMEZONE_ID_FLAG => Some((400, "Bad Request")),
+        // The body carries the supported versions
+        RESPONSE_UNSUPPORTED_PROTOCOL_VERSION_FLAG => Some((400, "Bad Request")),
+        RESPONSE_UNAUTHORIZED_FLAG => Some((401, "Unauthorized")),
+        RESPONSE_RATE_LIMITED_FLAG => Some((429, "Too Many Requests")),
+        // Too many updates waiting to be applied, the client is to retry later
+        RESPONSE_UPDATES_BUSY_FLAG => Some((503, "Service Unavailable")),
+        _ => Some((500, "Internal Server Error")),
+    }
+}
+
+/**
+ * Wraps an App response body into an HTTP response with the matching status.
+ */
+pub fn get_app_response(
+    response_body: Bytes
+) -> Response {
+    let mut response = Response::new();
+    if let Some((code, message)) = get_http_status(&response_body) {
+        response.status_code(code, message);
+    }
+    response.body_bytes(response_body);
+
+    response
+}
+
+/**
+ * Response to any method other than PUT.
+ */
+pub fn get_method_not_allowed_response() -> Response {
+    let mut response = Response::new();
+    response
+        .status_code(405, "Method Not Allowed")
+        .header("Allow", "PUT")
+        .body_bytes(Bytes::from_static(&INVALID_DATA_FORMAT_RESPONSE));
+
+    response
+}
+
+/**
+ * Response to a request that could not be decoded, the connection is closed after it.
+ */
+pub fn get_request_error_response(
+    error: RequestError
+) -> Response {
+    let mut response = Response::new();
+    match error {
+        RequestError::Malformed => {
+            response.status_code(400, "Bad Request");
+        }
+        RequestError::BodyTooLarge => {
+            response.status_code(413, "Payload Too Large");
+        }
+    }
+    response.body_bytes(Bytes::from_static(&INVALID_DATA_FORMAT_RESPONSE));
+
+    response
+}
+
+/**
+ * Supported protocol versions: a count byte followed by the versions (Big Endian).
+ */
+pub fn get_protocol_versions_response(
+    protocol_versions: &[ProtocolVersion]
+) -> Response {
+    let mut response = Response::new();
+    response.body_vec(get_protocol_versions_data(protocol_versions));
+
+    response
+}
+
+/**
+ * Protocol version of a request, or the response to send if the version is not valid or not
+ * in the supported versions.  The unsupported version response carries the supported versions
+ * (in the same format as the versions route), so that a client can downgrade right away.
+ */
+pub fn get_protocol_version(
+    request: &Request,
+    protocol_versions: &[ProtocolVersion],
+) -> Result<ProtocolVersion, Response> {
+    let protocol_version = match request.protocol_version() {
+        Err(_) => {
+            None
+        }
+        Ok(None) => {
+            Some(DEFAULT_PROTOCOL_VERSION)
+        }
+        Ok(Some(protocol_version)) => {
+            Some(protocol_version)
+        }
+    };
+
+    check_protocol_version(protocol_version, protocol_versions)
+        .map_err(|response_body| get_app_response(Bytes::from(response_body)))
+}
+
+/**
+ * The requested protocol version (None if it could not be parsed) if it is supported, the
+ * unsupported version response body otherwise.
+ */
+pub fn check_protocol_version(
+    protocol_version: Option<ProtocolVersion>,
+    protocol_versions: &[ProtocolVersion],
+) -> Result<ProtocolVersion, Vec<u8>> {
+    if let Some(protocol_version) = protocol_version {
+        if protocol_versions.contains(&protocol_version) {
+            return Ok(protocol_version);
+        }
+    }
+
+    let mut response_body = UNSUPPORTED_PROTOCOL_VERSION_RESPONSE.to_vec();
+    response_body.extend_from_slice(&get_protocol_versions_data(protocol_versions));
+
+    Err(response_body)
+}
+
+/**
+ * Wraps an App response body (encoded in the given protocol version) into an HTTP response.
+ */
+pub fn get_versioned_app_response(
+    protocol_version: ProtocolVersion,
+    response_body: Bytes,
+) -> Response {
+    let mut response = get_app_response(response_body);
+    response.header(PROTOCOL_VERSION_HEADER, &protocol_version.to_string());
+
+    response
+}
+
+/**
+ * Body of the versions route response.
+ */
+#[inline]
+pub fn get_protocol_versions_data(
+    protocol_versions: &[ProtocolVersion]
+) -> Vec<u8> {
+    let mut data = Vec::with_capacity(1 + protocol_versions.len() * 2);
+    data.push(protocol_versions.len() as u8);
+    for protocol_version in protocol_versions {
+        data.write_u16::<BigEndian>(*protocol_version).unwrap();
+    }
+
+    data
+}
+
+/**
+ * Response to an update request that is not signed (or is signed with an unknown key, a stale
+ * timestamp or a nonce that was already used).
+ */
+pub fn get_unauthorized_response() -> Response {
+    let mut response = Response::new();
+    response
+        .status_code(401, "Unauthorized")
+        .body_bytes(Bytes::from_static(&UNAUTHORIZED_RESPONSE));
+
+    response
+}
+
+/**
+ * Response to a request over the client's rate limit, Retry-After is when (in whole seconds)
+ * the request would be accepted.
+ */
+pub fn get_rate_limited_response(
+    retry_after_seconds: u64
+) -> Response {
+    let mut response = Response::new();
+    response
+        .status_code(429, "Too Many Requests")
+        .header("Retry-After", &retry_after_seconds.to_string())
+        .body_bytes(Bytes::from_static(&RATE_LIMITED_RESPONSE));
+
+    response
+}
+
+/**
+ * Response to an update request that could not be queued, because the queue of updates waiting
+ * to be applied is full.  Retry-After is a guess (in whole seconds) at when there will be room.
+ */
+pub fn get_updates_busy_response(
+    retry_after_seconds: u64
+) -> Response {
+    let mut response = Response::new();
+    response
+        .status_code(503, "Service Unavailable")
+        .header("Retry-After", &retry_after_seconds.to_string())
+        .body_bytes(Bytes::from_static(&UPDATES_BUSY_RESPONSE));
+
+    response
+}
+
+/**
+ * Response to an update request that was not applied because the update writer failed (it
+ * panicked applying the update, or it has stopped).
+ */
+pub fn get_update_failed_response() -> Response {
+    let mut response = Response::new();
+    response
+        .status_code(500, "Internal Server Error");
 
+    response
+}
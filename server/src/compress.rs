@@ -0,0 +1,170 @@
+use std::io::Write;
+use std::str;
+
+use bytes::Bytes;
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression;
+#[cfg(feature = "zstd")]
+use zstd;
+
+use super::response::Response;
+
+/// Level used for zstd, a fast level (similar in speed to the default deflate level)
+#[cfg(feature = "zstd")]
+const ZSTD_LEVEL: i32 = 3;
+
+/**
+ * Content encodings responses can be compressed with, in order of preference.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    #[cfg(feature = "zstd")]
+    Zstd,
+    Gzip,
+    Deflate,
+}
+
+#[cfg(feature = "zstd")]
+const SUPPORTED_ENCODINGS: [Encoding; 3] = [Encoding::Zstd, Encoding::Gzip, Encoding::Deflate];
+#[cfg(not(feature = "zstd"))]
+const SUPPORTED_ENCODINGS: [Encoding; 2] = [Encoding::Gzip, Encoding::Deflate];
+
+impl Encoding {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            #[cfg(feature = "zstd")]
+            Encoding::Zstd => "zstd",
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+}
+
+/**
+ * The encoding to compress a response with, given the request's Accept-Encoding header: the
+ * supported encoding with the highest quality value (ties go to the preferred encoding).  None
+ * if the client does not accept any of them.
+ */
+pub fn negotiate(
+    accept_encoding: &[u8]
+) -> Option<Encoding> {
+    let accept_encoding = match str::from_utf8(accept_encoding) {
+        Err(_) => {
+            return None;
+        }
+        Ok(accept_encoding) => {
+            accept_encoding
+        }
+    };
+
+    let mut best: Option<(Encoding, f32)> = None;
+    for encoding in SUPPORTED_ENCODINGS.iter() {
+        let quality = get_quality(accept_encoding, encoding.name());
+        if quality <= 0f32 {
+            continue;
+        }
+        match best {
+            Some((_, best_quality)) if best_quality >= quality => {}
+            _ => {
+                best = Some((*encoding, quality));
+            }
+        }
+    }
+
+    best.map(|(encoding, _)| encoding)
+}
+
+/**
+ * Compresses the response body (and sets Content-Encoding), if it is at least min_size bytes
+ * and compressing makes it smaller.  Responses that are already compressed are left as they are.
+ */
+pub fn compress_response(
+    response: &mut Response,
+    encoding: Encoding,
+    min_size: usize,
+) {
+    if response.uncompressed.is_some() {
+        return;
+    }
+
+    if let Some(compressed) = compress_body(encoding, &response.response, min_size) {
+        response.compressed_body(encoding.name(), compressed);
+    }
+}
+
+/**
+ * Compressed form of a body, None if it is under min_size bytes or compressing does not make it
+ * smaller (it is then served as is).
+ */
+pub fn compress_body(
+    encoding: Encoding,
+    data: &[u8],
+    min_size: usize,
+) -> Option<Bytes> {
+    if data.len() < min_size {
+        return None;
+    }
+
+    match compress(encoding, data) {
+        Some(compressed) => {
+            if compressed.len() < data.len() {
+                Some(Bytes::from(compressed))
+            } else {
+                None
+            }
+        }
+        None => {
+            None
+        }
+    }
+}
+
+fn compress(
+    encoding: Encoding,
+    data: &[u8],
+) -> Option<Vec<u8>> {
+    match encoding {
+        #[cfg(feature = "zstd")]
+        Encoding::Zstd => {
+            zstd::stream::encode_all(data, ZSTD_LEVEL).ok()
+        }
+        Encoding::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::with_capacity(data.len() / 2), Compression::default());
+            encoder.write_all(data).ok()?;
+            encoder.finish().ok()
+        }
+        Encoding::Deflate => {
+            // HTTP "deflate" is the zlib format
+            let mut encoder = ZlibEncoder::new(Vec::with_capacity(data.len() / 2), Compression::default());
+            encoder.write_all(data).ok()?;
+            encoder.finish().ok()
+        }
+    }
+}
+
+/// Quality value of an encoding in an Accept-Encoding header, 0 if it is not listed (a "*"
+/// entry applies to encodings that are not listed)
+fn get_quality(
+    accept_encoding: &str,
+    name: &str,
+) -> f32 {
+    let mut wildcard_quality = 0f32;
+    for entry in accept_encoding.split(',') {
+        let mut parts = entry.split(';');
+        let coding = parts.next().unwrap_or("").trim();
+        let quality = parts
+            .map(|parameter| parameter.trim())
+            .find(|parameter| parameter.starts_with("q=") || parameter.starts_with("Q="))
+            .and_then(|parameter| parameter[2..].trim().parse::<f32>().ok())
+            .unwrap_or(1f32);
+
+        if coding.eq_ignore_ascii_case(name) {
+            return quality;
+        }
+        if coding == "*" {
+            wildcard_quality = quality;
+        }
+    }
+
+    wildcard_quality
+}
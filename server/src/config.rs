@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::net::SocketAddr;
+use std::net::ToSocketAddrs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use num_cpus;
+
+use super::cache::updater::DEFAULT_QUEUE_SIZE;
+use super::request::DEFAULT_MAX_BODY_SIZE;
+
+/// Prefix of Unix domain socket listen addresses
+pub const UNIX_ADDRESS_PREFIX: &str = "unix:";
+
+/// Where a listener accepts connections: a TCP (IPv4 or IPv6) address or, on Unix, the path of
+/// a Unix domain socket ("unix:/run/vc/cache.sock")
+#[derive(Clone, Debug, PartialEq)]
+pub enum ListenAddress {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl FromStr for ListenAddress {
+    type Err = String;
+
+    fn from_str(address: &str) -> Result<ListenAddress, String> {
+        if address.starts_with(UNIX_ADDRESS_PREFIX) {
+            let path = &address[UNIX_ADDRESS_PREFIX.len()..];
+            if path.is_empty() {
+                return Err(format!("Missing socket path in {}", address));
+            }
+            return Ok(ListenAddress::Unix(PathBuf::from(path)));
+        }
+
+        // IPv6 addresses are given in brackets, "[::]:4321"
+        match address.to_socket_addrs() {
+            Err(err) => {
+                Err(format!("Invalid listen address {}: {}", address, err))
+            }
+            Ok(mut socket_addrs) => {
+                socket_addrs.next()
+                    .map(ListenAddress::Tcp)
+                    .ok_or_else(|| format!("Listen address {} did not resolve", address))
+            }
+        }
+    }
+}
+
+impl fmt::Display for ListenAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ListenAddress::Tcp(ref socket_addr) => write!(f, "{}", socket_addr),
+            ListenAddress::Unix(ref path) => write!(f, "{}{}", UNIX_ADDRESS_PREFIX, path.display()),
+        }
+    }
+}
+
+/// Certificate and key (PEM files) of a TLS listener, re-read when they change
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+    pub cert_file: PathBuf,
+    pub key_file: PathBuf,
+    /// Require client certificates signed by this CA (PEM file)
+    pub client_ca_file: Option<PathBuf>,
+}
+
+/// Per client (IP address) token bucket limit of read requests
+#[derive(Clone, Debug)]
+pub struct RateLimitConfig {
+    /// Tokens added to a client's bucket per second
+    pub requests_per_second: f64,
+    /// Size of the bucket, the most requests a client can make at once
+    pub burst: f64,
+    /// Tokens taken by requests to a route (by route id, including any long-poll or event
+    /// stream prefix), 1 for routes that are not listed
+    pub route_costs: HashMap<String, u32>,
+}
+
+/// Runtime settings of the server: listeners, threads and request limits
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    /// Listeners for read requests, served by all reactor threads
+    pub server_addresses: Vec<ListenAddress>,
+    /// Listeners for read requests over the raw (length prefixed, non-HTTP) protocol, served by
+    /// all reactor threads
+    pub raw_addresses: Vec<ListenAddress>,
+    /// Listeners for update requests, served by the update thread
+    pub updater_addresses: Vec<ListenAddress>,
+    /// TLS for the TCP read listeners, plaintext if not set
+    pub server_tls: Option<TlsConfig>,
+    /// TLS for the TCP update listeners, plaintext if not set.  With a client CA, requests from
+    /// verified clients don't need to be signed.
+    pub updater_tls: Option<TlsConfig>,
+    /// Most concurrent connections to the read listeners, further connections are closed right
+    /// away (0 for no limit)
+    pub max_connections: usize,
+    /// Limit of read requests per client, not limited if not set (Unix domain socket clients are
+    /// never limited)
+    pub rate_limit: Option<RateLimitConfig>,
+    /// Number of reactor threads serving read requests
+    pub num_threads: usize,
+    /// Listen backlog of TCP listeners
+    pub backlog: i32,
+    /// Largest request body accepted
+    pub max_body_size: usize,
+    /// Serve the human readable GET routes
+    pub debug_routes: bool,
+    /// Record requests (and the update queue) and serve them, with the app's own metrics, on
+    /// the GET metrics route (Prometheus text format)
+    pub metrics: bool,
+    /// Compress read responses for clients that accept it (Accept-Encoding)
+    pub compression: bool,
+    /// Smallest response body that gets compressed
+    pub compression_min_size: usize,
+    /// File with the keys update requests are signed with (see auth::UpdateAuthenticator)
+    pub update_keys_file: Option<PathBuf>,
+    /// Accept unsigned update requests when there is no keys file
+    pub allow_unsigned_updates: bool,
+    /// Most decoded updates waiting to be applied by the writer thread, further update requests
+    /// get a 503
+    pub update_queue_size: usize,
+    /// File the updated data (and the last applied sequence of each producer) is saved to and
+    /// loaded from on start, not saved if not set
+    pub snapshot_file: Option<PathBuf>,
+    /// How often a snapshot is saved, if there have been updates since the last one
+    pub snapshot_interval_seconds: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> ServerConfig {
+        ServerConfig {
+            server_addresses: vec!["0.0.0.0:4321".parse().unwrap()],
+            raw_addresses: Vec::new(),
+            updater_addresses: vec!["0.0.0.0:5432".parse().unwrap()],
+            server_tls: None,
+            updater_tls: None,
+            max_connections: 0,
+            rate_limit: None,
+            num_threads: num_cpus::get(),
+            backlog: 10240,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            debug_routes: false,
+            metrics: false,
+            compression: true,
+            compression_min_size: 1024,
+            update_keys_file: None,
+            allow_unsigned_updates: false,
+            update_queue_size: DEFAULT_QUEUE_SIZE,
+            snapshot_file: None,
+            snapshot_interval_seconds: 60,
+        }
+    }
+}
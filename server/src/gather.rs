@@ -0,0 +1,222 @@
+use std::collections::VecDeque;
+use std::io;
+
+use bytes::{Buf, Bytes, BytesMut};
+use futures::{Async, AsyncSink, Poll, Sink, StartSend};
+use iovec::IoVec;
+use tokio_codec::FramedRead;
+use tokio_io::codec::Decoder;
+use tokio_io::io::{ReadHalf, WriteHalf};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Bodies smaller than this are copied next to their head, instead of being written as a
+/// separate chunk
+const MIN_SHARED_CHUNK_SIZE: usize = 256;
+
+/// Output queued on a connection past which no more items are taken (until some is written)
+const BACKPRESSURE_BOUNDARY: usize = 64 * 1024;
+
+/**
+ * Output queued on a connection, as a list of chunks that are written with vectored writes
+ * (where the connection supports them).  Small pieces (response heads, frame headers) are
+ * copied into a buffer at the end of the output, shared bodies are queued as they are.
+ */
+pub struct Chunks {
+    chunks: VecDeque<Bytes>,
+    chunks_len: usize,
+    buf: BytesMut,
+}
+
+impl Chunks {
+    pub fn new() -> Chunks {
+        Chunks {
+            chunks: VecDeque::new(),
+            chunks_len: 0,
+            buf: BytesMut::new(),
+        }
+    }
+
+    /**
+     * The buffer at the end of the output, for small pieces that are copied as is.
+     */
+    #[inline]
+    pub fn buffer(&mut self) -> &mut BytesMut {
+        &mut self.buf
+    }
+
+    #[inline]
+    pub fn extend_from_slice(
+        &mut self,
+        data: &[u8],
+    ) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /**
+     * Queues a shared body, without copying it (unless it is small).
+     */
+    pub fn push(
+        &mut self,
+        data: Bytes,
+    ) {
+        if data.len() < MIN_SHARED_CHUNK_SIZE {
+            self.buf.extend_from_slice(&data);
+            return;
+        }
+
+        // What is already buffered goes before the body
+        if !self.buf.is_empty() {
+            let buf = self.buf.take().freeze();
+            self.chunks_len += buf.len();
+            self.chunks.push_back(buf);
+        }
+        self.chunks_len += data.len();
+        self.chunks.push_back(data);
+    }
+}
+
+impl Buf for Chunks {
+    fn remaining(&self) -> usize {
+        self.chunks_len + self.buf.len()
+    }
+
+    fn bytes(&self) -> &[u8] {
+        match self.chunks.front() {
+            None => {
+                &self.buf
+            }
+            Some(chunk) => {
+                chunk
+            }
+        }
+    }
+
+    fn advance(&mut self, mut cnt: usize) {
+        while cnt > 0 {
+            let chunk_len = match self.chunks.front_mut() {
+                None => {
+                    self.buf.advance(cnt);
+                    return;
+                }
+                Some(chunk) => {
+                    if cnt < chunk.len() {
+                        chunk.advance(cnt);
+                        self.chunks_len -= cnt;
+                        return;
+                    }
+                    chunk.len()
+                }
+            };
+            self.chunks.pop_front();
+            self.chunks_len -= chunk_len;
+            cnt -= chunk_len;
+        }
+    }
+
+    fn bytes_vec<'a>(&'a self, dst: &mut [&'a IoVec]) -> usize {
+        let mut filled = 0;
+        let buf: &[u8] = &self.buf;
+        for chunk in self.chunks.iter().map(|chunk| chunk.as_ref()).chain(Some(buf)) {
+            if filled == dst.len() {
+                break;
+            }
+            // IoVecs can't be empty
+            if chunk.is_empty() {
+                continue;
+            }
+            dst[filled] = chunk.into();
+            filled += 1;
+        }
+
+        filled
+    }
+}
+
+/**
+ * Encodes items into the output of a connection.  The counterpart of tokio's Encoder, for
+ * items with shared bodies that should not be copied into the output buffer.
+ */
+pub trait GatherEncoder {
+    type Item;
+
+    fn encode(&mut self, item: Self::Item, chunks: &mut Chunks) -> io::Result<()>;
+}
+
+/**
+ * Sink that encodes items into a connection's output and writes it with vectored writes.
+ * Takes the place of the write half of a Framed.
+ */
+pub struct GatherWrite<W, E> {
+    io: W,
+    encoder: E,
+    chunks: Chunks,
+}
+
+impl<W, E> GatherWrite<W, E> {
+    pub fn new(
+        io: W,
+        encoder: E,
+    ) -> GatherWrite<W, E> {
+        GatherWrite {
+            io,
+            encoder,
+            chunks: Chunks::new(),
+        }
+    }
+}
+
+impl<W: AsyncWrite, E: GatherEncoder> Sink for GatherWrite<W, E> {
+    type SinkItem = E::Item;
+    type SinkError = io::Error;
+
+    fn start_send(&mut self, item: E::Item) -> StartSend<E::Item, io::Error> {
+        if self.chunks.remaining() >= BACKPRESSURE_BOUNDARY {
+            self.poll_complete()?;
+            if self.chunks.remaining() >= BACKPRESSURE_BOUNDARY {
+                return Ok(AsyncSink::NotReady(item));
+            }
+        }
+        self.encoder.encode(item, &mut self.chunks)?;
+
+        Ok(AsyncSink::Ready)
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), io::Error> {
+        while self.chunks.has_remaining() {
+            match self.io.write_buf(&mut self.chunks)? {
+                Async::NotReady => {
+                    return Ok(Async::NotReady);
+                }
+                Async::Ready(0) => {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero, "failed to write output"));
+                }
+                Async::Ready(_) => {}
+            }
+        }
+
+        self.io.poll_flush()
+    }
+
+    fn close(&mut self) -> Poll<(), io::Error> {
+        if self.poll_complete()?.is_not_ready() {
+            return Ok(Async::NotReady);
+        }
+
+        self.io.shutdown()
+    }
+}
+
+/**
+ * Splits a connection into a stream of decoded requests and a sink of encoded responses,
+ * like Framed::split but with vectored writes.
+ */
+pub fn split<S, D, E>(
+    socket: S,
+    decoder: D,
+    encoder: E,
+) -> (GatherWrite<WriteHalf<S>, E>, FramedRead<ReadHalf<S>, D>)
+    where S: AsyncRead + AsyncWrite, D: Decoder, E: GatherEncoder {
+    let (read_half, write_half) = socket.split();
+
+    (GatherWrite::new(write_half, encoder), FramedRead::new(read_half, decoder))
+}
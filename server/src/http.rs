@@ -1,11 +1,41 @@
 use bytes::BytesMut;
-use tokio_io::codec::{Encoder, Decoder};
+use tokio_io::codec::Decoder;
 
+use super::cache::events::Event;
+use super::gather::{Chunks, GatherEncoder};
 use super::response::{self, Response};
 use super::request::{self, Request};
 use std::io;
 
-pub struct Http;
+/**
+ * HTTP/1.1 framing of a single connection.  Once a request fails to decode, the rest of the
+ * connection's input is discarded (the error is responded to and the connection closed).
+ */
+pub struct Http {
+    max_body_size: usize,
+    failed: bool,
+}
+
+impl Http {
+    pub fn new(
+        max_body_size: usize
+    ) -> Http {
+        Http {
+            max_body_size,
+            failed: false,
+        }
+    }
+}
+
+/**
+ * What gets written to a connection: either a complete response, or the head of an event
+ * stream followed by any number of events (the stream then owns the connection).
+ */
+pub enum Output {
+    Response(Response),
+    EventStreamHead,
+    Event(Event),
+}
 
 impl Decoder for Http {
     type Item = Request;
@@ -13,19 +43,38 @@ impl Decoder for Http {
 
 
     fn decode(&mut self, buf: &mut BytesMut) -> io::Result<Option<Request>> {
-        let res = request::decode(buf);
+        if self.failed {
+            buf.clear();
+            return Ok(None);
+        }
 
-        res
+        match request::decode(buf, self.max_body_size) {
+            Ok(request) => Ok(request),
+            Err(error) => {
+                self.failed = true;
+                buf.clear();
+                Ok(Some(Request::with_error(error)))
+            }
+        }
     }
 }
 
-impl Encoder for Http {
-    type Item = Response;
-    type Error = io::Error;
+impl GatherEncoder for Http {
+    type Item = Output;
 
 
-    fn encode(&mut self, msg: Response, buf: &mut BytesMut) -> io::Result<()> {
-        response::encode(msg, buf);
+    fn encode(&mut self, msg: Output, chunks: &mut Chunks) -> io::Result<()> {
+        match msg {
+            Output::Response(response) => {
+                response::encode(response, chunks);
+            }
+            Output::EventStreamHead => {
+                response::encode_event_stream_head(chunks);
+            }
+            Output::Event(event) => {
+                chunks.extend_from_slice(&event);
+            }
+        }
 
         Ok(())
     }
@@ -2,23 +2,43 @@
 
 extern crate byteorder;
 extern crate bytes;
+extern crate flate2;
 extern crate futures;
+extern crate hex;
+extern crate hmac;
 extern crate httparse;
 extern crate http as httplib;
+extern crate iovec;
 extern crate lazy_static;
 extern crate net2;
 extern crate num_cpus;
+#[cfg(test)]
+extern crate rcgen;
+extern crate rustls;
+extern crate sha2;
 extern crate smallvec;
 extern crate tokio;
 extern crate tokio_codec;
 extern crate tokio_core;
 extern crate tokio_io;
+extern crate webpki;
+#[cfg(feature = "zstd")]
+extern crate zstd;
 
 extern crate common;
 
+pub mod auth;
 pub mod cache;
 pub mod codes;
+pub mod compress;
+pub mod config;
+pub mod gather;
 pub mod http;
+pub mod limit;
+pub mod metrics;
+pub mod raw;
 pub mod read;
 pub mod request;
 pub mod response;
+pub mod route;
+pub mod tls;
@@ -0,0 +1,358 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::config::RateLimitConfig;
+
+/// Buckets are split across shards (by client address), so that reactor threads rarely wait on
+/// each other
+const NUM_SHARDS: usize = 16;
+
+/// How often full buckets (of clients that have been idle long enough) are dropped
+const PRUNE_INTERVAL_SECONDS: u64 = 60;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+struct Shard {
+    buckets: HashMap<IpAddr, Bucket>,
+    last_pruned: Instant,
+}
+
+///
+/// Per client (IP address) token buckets.  Every request takes its route's cost in tokens from
+/// the client's bucket, which refills at a steady rate up to the burst size.
+///
+pub struct RateLimiter {
+    requests_per_second: f64,
+    burst: f64,
+    route_costs: HashMap<String, u32>,
+    shards: Vec<Mutex<Shard>>,
+}
+
+impl RateLimiter {
+
+    ///
+    /// The config is expected to have passed check_rate_limit_config.
+    ///
+    pub fn new(
+        config: &RateLimitConfig
+    ) -> RateLimiter {
+        let now = Instant::now();
+        let mut shards = Vec::with_capacity(NUM_SHARDS);
+        for _ in 0..NUM_SHARDS {
+            shards.push(Mutex::new(Shard {
+                buckets: HashMap::new(),
+                last_pruned: now,
+            }));
+        }
+
+        RateLimiter {
+            requests_per_second: config.requests_per_second,
+            burst: config.burst,
+            route_costs: config.route_costs.clone(),
+            shards,
+        }
+    }
+
+    ///
+    /// Takes the route's cost from the client's bucket, or returns how long until the bucket
+    /// has enough tokens for it.
+    ///
+    pub fn check(
+        &self,
+        client: IpAddr,
+        route: &str,
+    ) -> Result<(), Duration> {
+        self.check_at(client, route, Instant::now())
+    }
+
+    fn check_at(
+        &self,
+        client: IpAddr,
+        route: &str,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        let cost = match self.route_costs.get(route) {
+            None => 1f64,
+            Some(cost) => *cost as f64,
+        };
+
+        let mut shard = self.shards[get_shard_index(&client)].lock().unwrap();
+        if now.duration_since(shard.last_pruned) >= Duration::from_secs(PRUNE_INTERVAL_SECONDS) {
+            self.prune(&mut shard, now);
+        }
+
+        let burst = self.burst;
+        let bucket = shard.buckets.entry(client).or_insert_with(|| Bucket {
+            tokens: burst,
+            updated: now,
+        });
+        bucket.tokens = self.get_tokens(bucket, now);
+        bucket.updated = now;
+
+        if bucket.tokens >= cost {
+            bucket.tokens -= cost;
+            return Ok(());
+        }
+
+        let wait_millis = (cost - bucket.tokens) / self.requests_per_second * 1000f64;
+        Err(Duration::from_millis(wait_millis.ceil() as u64))
+    }
+
+    #[inline]
+    fn get_tokens(
+        &self,
+        bucket: &Bucket,
+        now: Instant,
+    ) -> f64 {
+        let elapsed = now.duration_since(bucket.updated);
+        let elapsed_seconds = elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1_000_000_000f64;
+
+        (bucket.tokens + elapsed_seconds * self.requests_per_second).min(self.burst)
+    }
+
+    fn prune(
+        &self,
+        shard: &mut Shard,
+        now: Instant,
+    ) {
+        shard.last_pruned = now;
+        // A full bucket is the same as no bucket
+        let burst = self.burst;
+        shard.buckets.retain(|_, bucket| self.get_tokens(bucket, now) < burst);
+    }
+}
+
+///
+/// Rejects (at startup) rate limits that would stall or never let requests through: a rate of
+/// 0 (or less), a burst of less than 1 request and routes that cost more than the burst.
+///
+pub fn check_rate_limit_config(
+    config: &RateLimitConfig
+) -> Result<(), String> {
+    if config.requests_per_second.is_nan() || config.requests_per_second <= 0f64 {
+        return Err("The rate limit must be more than 0 requests per second".to_string());
+    }
+    if config.burst.is_nan() || config.burst < 1f64 {
+        return Err("The rate limit burst must be at least 1 request".to_string());
+    }
+    // A request that costs more than the burst would never be allowed
+    for (route, cost) in config.route_costs.iter() {
+        if *cost as f64 > config.burst {
+            return Err(format!("Cost {} of route {} is more than the rate limit burst", cost, route));
+        }
+    }
+
+    Ok(())
+}
+
+#[inline]
+fn get_shard_index(
+    client: &IpAddr
+) -> usize {
+    let mut hasher = DefaultHasher::new();
+    client.hash(&mut hasher);
+
+    hasher.finish() as usize % NUM_SHARDS
+}
+
+///
+/// Caps the number of concurrent connections, shared by all reactor threads.
+///
+pub struct ConnectionLimit {
+    max_connections: usize,
+    connections: AtomicUsize,
+}
+
+impl ConnectionLimit {
+
+    pub fn new(
+        max_connections: usize
+    ) -> ConnectionLimit {
+        ConnectionLimit {
+            max_connections,
+            connections: AtomicUsize::new(0),
+        }
+    }
+
+    ///
+    /// A permit for a new connection (held for as long as the connection is open), None if
+    /// there are already max_connections.
+    ///
+    pub fn acquire(
+        connection_limit: &Arc<ConnectionLimit>
+    ) -> Option<ConnectionPermit> {
+        let connections = connection_limit.connections.fetch_add(1, Ordering::SeqCst);
+        if connections >= connection_limit.max_connections {
+            connection_limit.connections.fetch_sub(1, Ordering::SeqCst);
+            return None;
+        }
+
+        Some(ConnectionPermit {
+            connection_limit: connection_limit.clone(),
+        })
+    }
+
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+}
+
+pub struct ConnectionPermit {
+    connection_limit: Arc<ConnectionLimit>,
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        self.connection_limit.connections.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use super::super::config::RateLimitConfig;
+    use super::check_rate_limit_config;
+    use super::get_shard_index;
+    use super::ConnectionLimit;
+    use super::RateLimiter;
+    use super::PRUNE_INTERVAL_SECONDS;
+
+    fn rate_limit_config(
+        requests_per_second: f64,
+        burst: f64,
+        route_costs: &[(&str, u32)],
+    ) -> RateLimitConfig {
+        RateLimitConfig {
+            requests_per_second,
+            burst,
+            route_costs: route_costs.iter().map(|&(route, cost)| (route.to_string(), cost)).collect(),
+        }
+    }
+
+    fn client(
+        last_byte: u8
+    ) -> IpAddr {
+        IpAddr::from([10, 0, 0, last_byte])
+    }
+
+    #[test]
+    fn config_check() {
+        assert!(check_rate_limit_config(&rate_limit_config(10f64, 20f64, &[("r", 20)])).is_ok());
+        assert!(check_rate_limit_config(&rate_limit_config(0.5f64, 1f64, &[])).is_ok());
+
+        assert!(check_rate_limit_config(&rate_limit_config(0f64, 20f64, &[])).is_err());
+        assert!(check_rate_limit_config(&rate_limit_config(-1f64, 20f64, &[])).is_err());
+        assert!(check_rate_limit_config(&rate_limit_config(f64::NAN, 20f64, &[])).is_err());
+        assert!(check_rate_limit_config(&rate_limit_config(10f64, 0f64, &[])).is_err());
+        assert!(check_rate_limit_config(&rate_limit_config(10f64, -5f64, &[])).is_err());
+        assert!(check_rate_limit_config(&rate_limit_config(10f64, 20f64, &[("r", 21)])).is_err());
+    }
+
+    #[test]
+    fn bucket_refill() {
+        let rate_limiter = RateLimiter::new(&rate_limit_config(10f64, 3f64, &[]));
+        let start = Instant::now();
+
+        // The burst is available right away
+        for _ in 0..3 {
+            assert_eq!(rate_limiter.check_at(client(1), "r", start), Ok(()));
+        }
+        assert_eq!(rate_limiter.check_at(client(1), "r", start), Err(Duration::from_millis(100)));
+        // Other clients have their own buckets
+        assert_eq!(rate_limiter.check_at(client(2), "r", start), Ok(()));
+
+        // A token every 100ms
+        assert_eq!(rate_limiter.check_at(client(1), "r", start + Duration::from_millis(50)),
+                   Err(Duration::from_millis(50)));
+        assert_eq!(rate_limiter.check_at(client(1), "r", start + Duration::from_millis(100)), Ok(()));
+        assert!(rate_limiter.check_at(client(1), "r", start + Duration::from_millis(100)).is_err());
+
+        // Refills up to the burst only
+        let later = start + Duration::from_secs(10);
+        for _ in 0..3 {
+            assert_eq!(rate_limiter.check_at(client(1), "r", later), Ok(()));
+        }
+        assert!(rate_limiter.check_at(client(1), "r", later).is_err());
+    }
+
+    #[test]
+    fn route_costs() {
+        let rate_limiter = RateLimiter::new(&rate_limit_config(1f64, 5f64, &[("expensive", 4)]));
+        let start = Instant::now();
+
+        assert_eq!(rate_limiter.check_at(client(1), "expensive", start), Ok(()));
+        // 1 token left, enough for a route that isn't listed (and so costs 1)
+        assert_eq!(rate_limiter.check_at(client(1), "expensive", start), Err(Duration::from_secs(3)));
+        assert_eq!(rate_limiter.check_at(client(1), "cheap", start), Ok(()));
+        assert_eq!(rate_limiter.check_at(client(1), "cheap", start), Err(Duration::from_secs(1)));
+
+        // A rejected request takes no tokens
+        assert_eq!(rate_limiter.check_at(client(1), "expensive", start + Duration::from_secs(4)), Ok(()));
+    }
+
+    #[test]
+    fn pruning() {
+        let rate_limiter = RateLimiter::new(&rate_limit_config(1f64, 2f64, &[]));
+        let start = Instant::now();
+        // Clients in the same shard, pruning is per shard
+        let idle_client = client(1);
+        let shard_index = get_shard_index(&idle_client);
+        let busy_client = (2..255).map(client)
+            .find(|client| get_shard_index(client) == shard_index).unwrap();
+        let num_buckets = || rate_limiter.shards[shard_index].lock().unwrap().buckets.len();
+
+        assert_eq!(rate_limiter.check_at(idle_client, "r", start), Ok(()));
+        assert_eq!(rate_limiter.check_at(busy_client, "r", start), Ok(()));
+        assert_eq!(num_buckets(), 2);
+
+        // The idle client's bucket is full again by the next prune, the busy one's is not
+        let prune_time = start + Duration::from_secs(PRUNE_INTERVAL_SECONDS);
+        for offset in 0..PRUNE_INTERVAL_SECONDS {
+            let _ = rate_limiter.check_at(busy_client, "r", start + Duration::from_secs(offset));
+            let _ = rate_limiter.check_at(busy_client, "r", start + Duration::from_secs(offset));
+        }
+        assert_eq!(num_buckets(), 2);
+        assert!(rate_limiter.check_at(busy_client, "r", prune_time).is_ok());
+        assert_eq!(num_buckets(), 1);
+
+        // A pruned client starts over with a full bucket
+        assert_eq!(rate_limiter.check_at(idle_client, "r", prune_time), Ok(()));
+        assert_eq!(rate_limiter.check_at(idle_client, "r", prune_time), Ok(()));
+        assert!(rate_limiter.check_at(idle_client, "r", prune_time).is_err());
+    }
+
+    #[test]
+    fn connection_limit() {
+        let connection_limit = Arc::new(ConnectionLimit::new(2));
+
+        let first = ConnectionLimit::acquire(&connection_limit);
+        let second = ConnectionLimit::acquire(&connection_limit);
+        assert!(first.is_some());
+        assert!(second.is_some());
+        assert!(ConnectionLimit::acquire(&connection_limit).is_none());
+        // A refused connection doesn't count
+        assert_eq!(connection_limit.connections(), 2);
+
+        drop(first);
+        assert_eq!(connection_limit.connections(), 1);
+        let third = ConnectionLimit::acquire(&connection_limit);
+        assert!(third.is_some());
+        assert!(ConnectionLimit::acquire(&connection_limit).is_none());
+
+        drop(second);
+        drop(third);
+        assert_eq!(connection_limit.connections(), 0);
+    }
+}
@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use common::model::response::RESPONSE_INVALID_CATEGORY_CACHE_INDEX_FLAG;
+use common::model::response::RESPONSE_INVALID_DATA_FORMAT_FLAG;
+use common::model::response::RESPONSE_INVALID_FLAG;
+use common::model::response::RESPONSE_INVALID_GLOBAL_CATEGORY_ID_FLAG;
+use common::model::response::RESPONSE_INVALID_GLOBAL_LOCATION_ID_FLAG;
+use common::model::response::RESPONSE_INVALID_LOCATION_CACHE_INDEX_FLAG;
+use common::model::response::RESPONSE_INVALID_PERIOD_ID_FLAG;
+use common::model::response::RESPONSE_INVALID_TIMEZONE_ID_FLAG;
+use common::model::response::RESPONSE_RATE_LIMITED_FLAG;
+use common::model::response::RESPONSE_UNAUTHORIZED_FLAG;
+use common::model::response::RESPONSE_UNSUPPORTED_PROTOCOL_VERSION_FLAG;
+use common::model::response::RESPONSE_UPDATES_BUSY_FLAG;
+use common::url::cache::serve::URL_PROTOCOL_VERSIONS;
+
+/// Path of the metrics (GET) route, served on the read listeners when metrics are enabled
+pub const METRICS_PATH: &str = "/metrics";
+
+/// Content type of the Prometheus text exposition format
+pub const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// What a request was served as, the kind label of its series
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RequestKind {
+    /// Read requests (other than long-polls), over HTTP or the raw protocol
+    Read,
+    /// Long-poll requests, by the route they watch.  Their latency includes the wait.
+    LongPoll,
+    /// Update requests, on the updater listeners.  Their latency includes the wait in the queue.
+    Update,
+}
+
+impl RequestKind {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            RequestKind::Read => "read",
+            RequestKind::LongPoll => "long_poll",
+            RequestKind::Update => "update",
+        }
+    }
+
+    fn index(&self) -> usize {
+        match *self {
+            RequestKind::Read => 0,
+            RequestKind::LongPoll => 1,
+            RequestKind::Update => 2,
+        }
+    }
+}
+
+/// Route label of requests for routes the metrics were not created with, so that clients
+/// can't add series
+pub const OTHER_ROUTE: &str = "other";
+
+/// Upper bounds of the latency histogram buckets, in microseconds (long-polls wait up to 30
+/// seconds)
+const LATENCY_BUCKETS_MICROS: [usize; 17] = [
+    100, 250, 500,
+    1000, 2500, 5000,
+    10000, 25000, 50000,
+    100000, 250000, 500000,
+    1000000, 2500000, 5000000,
+    10000000, 30000000,
+];
+
+/// Upper bounds of the response size histogram buckets, in bytes
+const SIZE_BUCKETS_BYTES: [usize; 9] = [16, 64, 256, 1024, 4096, 16384, 65536, 262144, 1048576];
+
+/// Names (the error label) of the RESPONSE_INVALID_* flags, by flag
+const ERROR_NAMES: [(u8, &str); 11] = [
+    (RESPONSE_INVALID_DATA_FORMAT_FLAG, "invalid_data_format"),
+    (RESPONSE_INVALID_GLOBAL_CATEGORY_ID_FLAG, "invalid_global_category_id"),
+    (RESPONSE_INVALID_CATEGORY_CACHE_INDEX_FLAG, "invalid_category_cache_index"),
+    (RESPONSE_INVALID_GLOBAL_LOCATION_ID_FLAG, "invalid_global_location_id"),
+    (RESPONSE_INVALID_LOCATION_CACHE_INDEX_FLAG, "invalid_location_cache_index"),
+    (RESPONSE_INVALID_PERIOD_ID_FLAG, "invalid_period_id"),
+    (RESPONSE_INVALID_TIMEZONE_ID_FLAG, "invalid_timezone_id"),
+    (RESPONSE_UNSUPPORTED_PROTOCOL_VERSION_FLAG, "unsupported_protocol_version"),
+    (RESPONSE_UNAUTHORIZED_FLAG, "unauthorized"),
+    (RESPONSE_RATE_LIMITED_FLAG, "rate_limited"),
+    (RESPONSE_UPDATES_BUSY_FLAG, "updates_busy"),
+];
+
+struct Histogram {
+    // Not cumulative, the last bucket is +Inf
+    buckets: Vec<AtomicUsize>,
+    sum: AtomicUsize,
+}
+
+impl Histogram {
+    fn new(
+        num_bounds: usize
+    ) -> Histogram {
+        Histogram {
+            buckets: (0..num_bounds + 1).map(|_| AtomicUsize::new(0)).collect(),
+            sum: AtomicUsize::new(0),
+        }
+    }
+
+    #[inline]
+    fn observe(
+        &self,
+        bounds: &[usize],
+        value: usize,
+    ) {
+        let bucket_index = bounds.iter().position(|&bound| value <= bound).unwrap_or(bounds.len());
+        self.buckets[bucket_index].fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(value, Ordering::Relaxed);
+    }
+
+    /// Appends the histogram's series, bounds and sum are divided by the unit (to get seconds
+    /// out of microseconds)
+    fn render(
+        &self,
+        out: &mut String,
+        name: &str,
+        labels: &str,
+        bounds: &[usize],
+        unit: f64,
+    ) {
+        let mut count = 0;
+        for (bucket_index, bucket) in self.buckets.iter().enumerate() {
+            count += bucket.load(Ordering::Relaxed);
+            if bucket_index < bounds.len() {
+                writeln!(out, "{}_bucket{{{},le=\"{}\"}} {}",
+                         name, labels, bounds[bucket_index] as f64 / unit, count).unwrap();
+            } else {
+                writeln!(out, "{}_bucket{{{},le=\"+Inf\"}} {}", name, labels, count).unwrap();
+            }
+        }
+        writeln!(out, "{}_sum{{{}}} {}", name, labels, self.sum.load(Ordering::Relaxed) as f64 / unit).unwrap();
+        writeln!(out, "{}_count{{{}}} {}", name, labels, count).unwrap();
+    }
+}
+
+struct RouteMetrics {
+    kind: RequestKind,
+    route_id: String,
+    requests: AtomicUsize,
+    latency: Histogram,
+    response_size: Histogram,
+}
+
+impl RouteMetrics {
+    fn new(
+        kind: RequestKind,
+        route_id: &str,
+    ) -> RouteMetrics {
+        RouteMetrics {
+            kind,
+            route_id: route_id.to_string(),
+            requests: AtomicUsize::new(0),
+            latency: Histogram::new(LATENCY_BUCKETS_MICROS.len()),
+            response_size: Histogram::new(SIZE_BUCKETS_BYTES.len()),
+        }
+    }
+
+    fn get_labels(&self) -> String {
+        // Route ids are short printable strings, escaped all the same
+        format!("kind=\"{}\",route=\"{}\"", self.kind.name(),
+                self.route_id.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+///
+/// Request and updater metrics, shared by all reactor threads, the update thread and the
+/// writer thread.  Recording is lock free (relaxed atomic counters), the set of routes is
+/// fixed at creation.
+///
+/// Rendered in the Prometheus text format, along with the app's own metrics (see
+/// App::get_metrics).
+///
+pub struct Metrics {
+    // In the order they are rendered in: by kind, then by route id
+    routes: Vec<RouteMetrics>,
+    // Index in routes, by RequestKind index and route id
+    route_indexes: [HashMap<String, usize>; 3],
+    // By RESPONSE_INVALID_* flag
+    errors: Vec<AtomicUsize>,
+    update_queue_depth: AtomicUsize,
+}
+
+impl Metrics {
+    ///
+    /// Metrics for the given read and update route ids (the protocol versions route, served by
+    /// the server itself, is added to the read routes).  Requests for any other route are
+    /// recorded as OTHER_ROUTE.
+    ///
+    pub fn new(
+        read_route_ids: &[&str],
+        update_route_ids: &[&str],
+    ) -> Metrics {
+        let mut read_route_ids: Vec<&str> = read_route_ids.to_vec();
+        read_route_ids.push(URL_PROTOCOL_VERSIONS);
+        let update_route_ids: Vec<&str> = update_route_ids.to_vec();
+
+        let mut metrics = Metrics {
+            routes: Vec::new(),
+            route_indexes: [HashMap::new(), HashMap::new(), HashMap::new()],
+            errors: (0..RESPONSE_INVALID_FLAG).map(|_| AtomicUsize::new(0)).collect(),
+            update_queue_depth: AtomicUsize::new(0),
+        };
+        metrics.add_routes(RequestKind::Read, read_route_ids.clone());
+        metrics.add_routes(RequestKind::LongPoll, read_route_ids);
+        metrics.add_routes(RequestKind::Update, update_route_ids);
+
+        metrics
+    }
+
+    fn add_routes(
+        &mut self,
+        kind: RequestKind,
+        mut route_ids: Vec<&str>,
+    ) {
+        route_ids.push(OTHER_ROUTE);
+        route_ids.sort();
+        route_ids.dedup();
+        for route_id in route_ids {
+            self.route_indexes[kind.index()].insert(route_id.to_string(), self.routes.len());
+            self.routes.push(RouteMetrics::new(kind, route_id));
+        }
+    }
+
+    ///
+    /// Index of the series a request is recorded in (OTHER_ROUTE's for routes the metrics were
+    /// not created with).  Looked up when the request comes in, recorded once it is responded to.
+    ///
+    pub fn get_route_index(
+        &self,
+        kind: RequestKind,
+        route_id: &str,
+    ) -> usize {
+        let route_indexes = &self.route_indexes[kind.index()];
+        match route_indexes.get(route_id) {
+            None => {
+                route_indexes[OTHER_ROUTE]
+            }
+            Some(&route_index) => {
+                route_index
+            }
+        }
+    }
+
+    ///
+    /// Records a request that has been responded to: its latency, the size of the response
+    /// body and the error (if the response is one).
+    ///
+    pub fn record(
+        &self,
+        route_index: usize,
+        latency: Duration,
+        response_body: &[u8],
+    ) {
+        let route_metrics = &self.routes[route_index];
+        let latency_micros = latency.as_secs() as usize * 1000000 + latency.subsec_micros() as usize;
+
+        route_metrics.requests.fetch_add(1, Ordering::Relaxed);
+        route_metrics.latency.observe(&LATENCY_BUCKETS_MICROS, latency_micros);
+        route_metrics.response_size.observe(&SIZE_BUCKETS_BYTES, response_body.len());
+
+        if !response_body.is_empty() && response_body[0] & RESPONSE_INVALID_FLAG != 0 {
+            self.errors[(response_body[0] - RESPONSE_INVALID_FLAG) as usize].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// An update (or a job) is about to be queued for the writer thread
+    pub fn update_queued(&self) {
+        self.update_queue_depth.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The writer thread took an update (or a job) off the queue, or it could not be queued
+    pub fn update_dequeued(&self) {
+        self.update_queue_depth.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn render(
+        &self,
+        out: &mut String,
+    ) {
+        let route_labels: Vec<String> = self.routes.iter()
+            .map(|route_metrics| route_metrics.get_labels()).collect();
+
+        out.push_str("# HELP vc_requests_total Requests responded to, by kind and route id\n");
+        out.push_str("# TYPE vc_requests_total counter\n");
+        for (route_metrics, labels) in self.routes.iter().zip(route_labels.iter()) {
+            writeln!(out, "vc_requests_total{{{}}} {}",
+                     labels, route_metrics.requests.load(Ordering::Relaxed)).unwrap();
+        }
+
+        out.push_str("# HELP vc_request_duration_seconds Time from reading a request to its response being ready\n");
+        out.push_str("# TYPE vc_request_duration_seconds histogram\n");
+        for (route_metrics, labels) in self.routes.iter().zip(route_labels.iter()) {
+            route_metrics.latency.render(out, "vc_request_duration_seconds",
+                                         labels, &LATENCY_BUCKETS_MICROS, 1000000.0);
+        }
+
+        out.push_str("# HELP vc_response_size_bytes Size of response bodies, before compression\n");
+        out.push_str("# TYPE vc_response_size_bytes histogram\n");
+        for (route_metrics, labels) in self.routes.iter().zip(route_labels.iter()) {
+            route_metrics.response_size.render(out, "vc_response_size_bytes",
+                                               labels, &SIZE_BUCKETS_BYTES, 1.0);
+        }
+
+        out.push_str("# HELP vc_errors_total Error responses, by RESPONSE_INVALID_* flag\n");
+        out.push_str("# TYPE vc_errors_total counter\n");
+        for (flag, errors) in self.errors.iter().enumerate() {
+            let errors = errors.load(Ordering::Relaxed);
+            match ERROR_NAMES.iter().find(|&&(error_flag, _)| error_flag as usize == flag) {
+                Some(&(_, name)) => {
+                    writeln!(out, "vc_errors_total{{flag=\"{}\"}} {}", name, errors).unwrap();
+                }
+                // Flags without a name only show up once they are sent
+                None if errors != 0 => {
+                    writeln!(out, "vc_errors_total{{flag=\"{}\"}} {}", flag, errors).unwrap();
+                }
+                None => {}
+            }
+        }
+
+        out.push_str("# HELP vc_update_queue_depth Updates (and jobs) waiting to be applied by the writer thread\n");
+        out.push_str("# TYPE vc_update_queue_depth gauge\n");
+        writeln!(out, "vc_update_queue_depth {}", self.update_queue_depth.load(Ordering::Relaxed)).unwrap();
+    }
+}
@@ -0,0 +1,120 @@
+use std::io;
+use std::str;
+
+use byteorder::{BigEndian, ByteOrder};
+use bytes::{BufMut, Bytes, BytesMut};
+use tokio_io::codec::Decoder;
+
+use common::model::frame::FRAME_LENGTH_SIZE;
+use common::model::frame::MAX_FRAME_ROUTE_LENGTH;
+use common::model::frame::REQUEST_FRAME_HEADER_SIZE;
+use common::model::frame::RESPONSE_FRAME_HEADER_SIZE;
+use common::model::protocol::ProtocolVersion;
+
+use super::gather::{Chunks, GatherEncoder};
+
+/**
+ * A request frame of the raw protocol (see common::model::frame).
+ */
+pub struct RawRequest {
+    pub id: u32,
+    pub protocol_version: ProtocolVersion,
+    pub route: String,
+    pub body: Vec<u8>,
+}
+
+/**
+ * A response frame of the raw protocol, for the request with the same id.
+ */
+pub struct RawResponse {
+    pub id: u32,
+    pub body: Bytes,
+}
+
+/**
+ * Length prefixed framing of a single raw protocol connection.  Frames that can't be decoded
+ * are errors, which close the connection.
+ */
+pub struct Raw {
+    max_body_size: usize,
+}
+
+impl Raw {
+    pub fn new(
+        max_body_size: usize
+    ) -> Raw {
+        Raw {
+            max_body_size,
+        }
+    }
+}
+
+impl Decoder for Raw {
+    type Item = RawRequest;
+    type Error = io::Error;
+
+
+    fn decode(&mut self, buf: &mut BytesMut) -> io::Result<Option<RawRequest>> {
+        if buf.len() < FRAME_LENGTH_SIZE {
+            return Ok(None);
+        }
+        let length = BigEndian::read_u32(&buf[..FRAME_LENGTH_SIZE]) as usize;
+        if length < REQUEST_FRAME_HEADER_SIZE
+            || length > REQUEST_FRAME_HEADER_SIZE + MAX_FRAME_ROUTE_LENGTH + self.max_body_size {
+            return Err(invalid_frame("frame length out of range"));
+        }
+        if buf.len() < FRAME_LENGTH_SIZE + length {
+            buf.reserve(FRAME_LENGTH_SIZE + length);
+            return Ok(None);
+        }
+
+        let frame = buf.split_to(FRAME_LENGTH_SIZE + length);
+        let frame = &frame[FRAME_LENGTH_SIZE..];
+        let id = BigEndian::read_u32(&frame[0..4]);
+        let protocol_version = BigEndian::read_u16(&frame[4..6]);
+        let route_end = REQUEST_FRAME_HEADER_SIZE + frame[6] as usize;
+        if route_end > frame.len() {
+            return Err(invalid_frame("route longer than the frame"));
+        }
+        let route = match str::from_utf8(&frame[REQUEST_FRAME_HEADER_SIZE..route_end]) {
+            Err(_) => {
+                return Err(invalid_frame("route is not UTF-8"));
+            }
+            Ok(route) => {
+                route.to_string()
+            }
+        };
+
+        Ok(Some(RawRequest {
+            id,
+            protocol_version,
+            route,
+            body: frame[route_end..].to_vec(),
+        }))
+    }
+}
+
+impl GatherEncoder for Raw {
+    type Item = RawResponse;
+
+
+    fn encode(&mut self, msg: RawResponse, chunks: &mut Chunks) -> io::Result<()> {
+        let length = RESPONSE_FRAME_HEADER_SIZE + msg.body.len();
+        {
+            let buf = chunks.buffer();
+            buf.reserve(FRAME_LENGTH_SIZE + RESPONSE_FRAME_HEADER_SIZE);
+            buf.put_u32_be(length as u32);
+            buf.put_u32_be(msg.id);
+        }
+        chunks.push(msg.body);
+
+        Ok(())
+    }
+}
+
+#[inline]
+fn invalid_frame(
+    message: &str
+) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
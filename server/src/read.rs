@@ -26,6 +26,17 @@ pub fn wrong_request_length_28(request_body: &[u8]) -> bool {
     request_body.len() != 28
 }
 
+#[inline]
+pub fn read_int_and_two_longs(request_body: &[u8]) -> (u32, u64, u64) {
+    let mut request_data_reader = Cursor::new(request_body);
+
+    (
+        request_data_reader.read_u32::<BigEndian>().unwrap(),
+        request_data_reader.read_u64::<BigEndian>().unwrap(),
+        request_data_reader.read_u64::<BigEndian>().unwrap()
+    )
+}
+
 #[inline]
 pub fn read_two_ints_and_long(request_body: &[u8]) -> (u32, u32, u64) {
     let mut request_data_reader = Cursor::new(request_body);
@@ -1,4 +1,4 @@
-use std::{io, str, fmt};
+use std::{str, fmt};
 use std::collections::HashMap;
 //use serde;
 use bytes::BytesMut;
@@ -9,6 +9,28 @@ use smallvec::SmallVec;
 use httparse;
 use httplib;
 
+use common::model::protocol::ProtocolVersion;
+use common::model::protocol::PROTOCOL_VERSION_HEADER;
+use common::url::cache::serve::URL_PROTOCOL_VERSION_PREFIX;
+
+/// Largest request body accepted, unless configured otherwise
+pub const DEFAULT_MAX_BODY_SIZE: usize = 64 * 1024;
+
+/// Largest request line + headers (or chunked body trailer) accepted
+const MAX_HEAD_SIZE: usize = 16 * 1024;
+
+/// Number of headers parsed at first, doubled (up to MAX_HEADERS) for requests with more
+const INITIAL_NUM_HEADERS: usize = 32;
+const MAX_HEADERS: usize = 256;
+
+/// Why a request could not be decoded.  The connection is responded to and closed, since
+/// there is no telling where the next request would start.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RequestError {
+    Malformed,
+    BodyTooLarge,
+}
+
 pub struct Request {
     body: Slice,
     method: Slice,
@@ -17,11 +39,26 @@ pub struct Request {
     // TODO: use a small vec to avoid this unconditional allocation
     pub headers: SmallVec<[(Slice, Slice); 8]>,
     data: BytesMut,
+    error: Option<RequestError>,
 //    pub params: HashMap<String, String>
 }
 
 type Slice = (usize, usize);
 
+enum BodyFraming {
+    ContentLength(usize),
+    Chunked,
+}
+
+struct Head {
+    method: Slice,
+    path: Slice,
+    version: u8,
+    headers: SmallVec<[(Slice, Slice); 8]>,
+    length: usize,
+    body_framing: BodyFraming,
+}
+
 impl Request {
     pub fn new() -> Self {
       Request  {
@@ -31,33 +68,104 @@ impl Request {
         version: 0,
         headers: SmallVec::new(),
         data: BytesMut::new(),
+        error: None,
 //        params: HashMap::new(),
       }
     }
 
+    /// Placeholder for a request that could not be decoded, responded to with an error
+    pub fn with_error(error: RequestError) -> Self {
+        let mut request = Request::new();
+        request.version = 1;
+        request.error = Some(error);
+
+        request
+    }
+
+    pub fn error(&self) -> Option<RequestError> {
+        self.error
+    }
+
     pub fn raw_body(&self) -> &[u8] {
         self.slice(&self.body)
     }
 
     pub fn method(&self) -> &str {
-        str::from_utf8(self.slice(&self.method)).unwrap()
+        str::from_utf8(self.slice(&self.method)).unwrap_or("")
     }
 
     pub fn path(&self) -> &str {
-        str::from_utf8(self.slice(&self.path)).unwrap()
+        str::from_utf8(self.slice(&self.path)).unwrap_or("")
     }
 
     pub fn version(&self) -> u8 {
         self.version
     }
 
+    /// Path without the protocol version prefix (if any)
+    pub fn route(&self) -> &str {
+        let path = self.path();
+        match self.protocol_version_prefix_end() {
+            None => path,
+            Some(prefix_end) => &path[prefix_end + 1..],
+        }
+    }
+
+    /// Protocol version asked for, by path prefix ("@<version>/<route>") or else by header.
+    /// None if the request does not ask for one, Err if the version isn't a number.
+    pub fn protocol_version(&self) -> Result<Option<ProtocolVersion>, ()> {
+        if let Some(prefix_end) = self.protocol_version_prefix_end() {
+            return self.path()[URL_PROTOCOL_VERSION_PREFIX.len()..prefix_end]
+                .parse::<ProtocolVersion>().map(Some).map_err(|_| ());
+        }
+
+        match self.header(PROTOCOL_VERSION_HEADER) {
+            None => Ok(None),
+            Some(value) => {
+                str::from_utf8(value).ok()
+                    .and_then(|value| value.trim().parse::<ProtocolVersion>().ok())
+                    .map(Some).ok_or(())
+            }
+        }
+    }
+
+    /// Value of the first header with the given (case insensitive) name
+    pub fn header(&self, name: &str) -> Option<&[u8]> {
+        for slice_pair in self.headers.iter() {
+            if self.slice(&slice_pair.0).eq_ignore_ascii_case(name.as_bytes()) {
+                return Some(self.slice(&slice_pair.1));
+            }
+        }
+
+        None
+    }
+
+    /// True if the connection should stay open after responding to this request: the
+    /// default for HTTP/1.1 (unless "Connection: close" is sent), opt-in for HTTP/1.0
+    pub fn keep_alive(&self) -> bool {
+        for slice_pair in self.headers.iter() {
+            if !self.slice(&slice_pair.0).eq_ignore_ascii_case(b"connection") {
+                continue;
+            }
+            let value = self.slice(&slice_pair.1);
+            if value.eq_ignore_ascii_case(b"close") {
+                return false;
+            }
+            if value.eq_ignore_ascii_case(b"keep-alive") {
+                return true;
+            }
+        }
+
+        self.version == 1
+    }
+
     pub fn headers(&self) -> HashMap<String, String> {
         let mut header_map = HashMap::new();
 
         for slice_pair in self.headers.iter() {
             header_map.insert(
-                str::from_utf8(self.slice(&slice_pair.0)).unwrap().to_owned(),
-                str::from_utf8(self.slice(&slice_pair.1)).unwrap().to_owned()
+                String::from_utf8_lossy(self.slice(&slice_pair.0)).into_owned(),
+                String::from_utf8_lossy(self.slice(&slice_pair.1)).into_owned()
             );
         }
 
@@ -70,6 +178,15 @@ impl Request {
 //        serde_json::from_str(body)
 //    }
 
+   fn protocol_version_prefix_end(&self) -> Option<usize> {
+       let path = self.path();
+       if !path.starts_with(URL_PROTOCOL_VERSION_PREFIX) {
+           return None;
+       }
+
+       path.find('/')
+   }
+
    fn slice(&self, slice: &Slice) -> &[u8] {
        &self.data[slice.0..slice.1]
    }
@@ -90,54 +207,334 @@ impl fmt::Debug for Request {
 }
 
 
-pub fn decode(buf: &mut BytesMut) -> io::Result<Option<Request>> {
-    // TODO: we should grow this headers array if parsing fails and asks
-    //       for more headers
-    let (method, path, version, headers, amt, body_len) = {
-        let mut headers = [httparse::EMPTY_HEADER; 32];
+/// Decodes the next request in the buffer, leaving any pipelined requests after it in place.
+/// Ok(None) until the whole request (including the body) has arrived.
+pub fn decode(buf: &mut BytesMut, max_body_size: usize) -> Result<Option<Request>, RequestError> {
+    let head = match decode_head(buf)? {
+        None => {
+            return Ok(None);
+        }
+        Some(head) => {
+            head
+        }
+    };
+
+    match head.body_framing {
+        BodyFraming::ContentLength(body_len) => {
+            if body_len > max_body_size {
+                return Err(RequestError::BodyTooLarge);
+            }
+            if buf.len() < head.length + body_len {
+                return Ok(None);
+            }
+
+            Ok(Some(Request {
+                method: head.method,
+                path: head.path,
+                version: head.version,
+                headers: head.headers,
+                data: buf.split_to(head.length + body_len),
+                body: (head.length, head.length + body_len),
+                error: None,
+//                params: HashMap::new()
+            }))
+        }
+        BodyFraming::Chunked => {
+            let (body, body_length) = match decode_chunked_body(&buf[head.length..], max_body_size)? {
+                None => {
+                    return Ok(None);
+                }
+                Some(body_and_length) => {
+                    body_and_length
+                }
+            };
+
+            // Keep the head (headers point into it) and replace the chunks with the joined body
+            let mut data = buf.split_to(head.length + body_length);
+            data.truncate(head.length);
+            data.extend_from_slice(&body);
+
+            Ok(Some(Request {
+                method: head.method,
+                path: head.path,
+                version: head.version,
+                headers: head.headers,
+                data,
+                body: (head.length, head.length + body.len()),
+                error: None,
+//                params: HashMap::new()
+            }))
+        }
+    }
+}
+
+fn decode_head(buf: &[u8]) -> Result<Option<Head>, RequestError> {
+    let mut num_headers = INITIAL_NUM_HEADERS;
+
+    loop {
+        let mut headers = vec![httparse::EMPTY_HEADER; num_headers];
         let mut r = httparse::Request::new(&mut headers);
-        let mut body_len: usize = 0;
-        let mut header_vec = SmallVec::new();
-        let status = try!(r.parse(buf).map_err(|e| {
-            let msg = format!("failed to parse http request: {:?}", e);
-            eprintln!("msg: {}", msg);
-            io::Error::new(io::ErrorKind::Other, msg)
-        }));
-        let amt = match status {
-            httparse::Status::Complete(amt) => amt,
-            httparse::Status::Partial => {
-                return Ok(None)
-            },
-        };
-        let toslice = |a: &[u8]| {
-            let start = a.as_ptr() as usize - buf.as_ptr() as usize;
-            assert!(start < buf.len());
-            (start, start + a.len())
-        };
-        for header in r.headers.iter() {
-            if header.name == httplib::header::CONTENT_LENGTH {
-                let value = str::from_utf8(header.value).unwrap_or("0");
-                body_len = value.parse::<usize>().unwrap_or(0);
+
+        match r.parse(buf) {
+            Ok(httparse::Status::Complete(amt)) => {
+                return get_head(buf, &r, amt).map(Some);
             }
+            Ok(httparse::Status::Partial) => {
+                if buf.len() > MAX_HEAD_SIZE {
+                    return Err(RequestError::Malformed);
+                }
+                return Ok(None);
+            }
+            Err(httparse::Error::TooManyHeaders) if num_headers < MAX_HEADERS => {
+                num_headers *= 2;
+            }
+            Err(e) => {
+                eprintln!("msg: failed to parse http request: {:?}", e);
+                return Err(RequestError::Malformed);
+            }
+        }
+    }
+}
+
+fn get_head(buf: &[u8], r: &httparse::Request, amt: usize) -> Result<Head, RequestError> {
+    let toslice = |a: &[u8]| {
+        let start = a.as_ptr() as usize - buf.as_ptr() as usize;
+        (start, start + a.len())
+    };
+
+    let mut content_length: Option<usize> = None;
+    let mut chunked = false;
+    let mut header_vec = SmallVec::new();
 
-            header_vec.push((toslice(header.name.as_bytes()), toslice(header.value)));
+    for header in r.headers.iter() {
+        if header.name.eq_ignore_ascii_case(httplib::header::CONTENT_LENGTH.as_str()) {
+            let body_len = str::from_utf8(header.value).ok()
+                .and_then(|value| value.trim().parse::<usize>().ok())
+                .ok_or(RequestError::Malformed)?;
+            if content_length.is_some() && content_length != Some(body_len) {
+                return Err(RequestError::Malformed);
+            }
+            content_length = Some(body_len);
+        } else if header.name.eq_ignore_ascii_case(httplib::header::TRANSFER_ENCODING.as_str()) {
+            // Chunked is the only transfer coding supported
+            if !header.value.eq_ignore_ascii_case(b"chunked") {
+                return Err(RequestError::Malformed);
+            }
+            chunked = true;
         }
 
-        (toslice(r.method.unwrap().as_bytes()),
-         toslice(r.path.unwrap().as_bytes()),
-         r.version.unwrap(),
-         header_vec,
-         amt,
-         body_len)
+        header_vec.push((toslice(header.name.as_bytes()), toslice(header.value)));
+    }
+
+    let body_framing = match (content_length, chunked) {
+        // Ambiguous framing, could be used to smuggle a request past a proxy
+        (Some(_), true) => {
+            return Err(RequestError::Malformed);
+        }
+        (_, true) => BodyFraming::Chunked,
+        (Some(body_len), false) => BodyFraming::ContentLength(body_len),
+        (None, false) => BodyFraming::ContentLength(0),
     };
 
-    Ok(Request {
-        method: method,
-        path: path,
-        version: version,
-        headers: headers,
-        data: buf.split_to(amt + body_len),
-        body: (amt, amt + body_len),
-//        params: HashMap::new()
-    }.into())
+    let method = r.method.ok_or(RequestError::Malformed)?;
+    let path = r.path.ok_or(RequestError::Malformed)?;
+    let version = r.version.ok_or(RequestError::Malformed)?;
+
+    Ok(Head {
+        method: toslice(method.as_bytes()),
+        path: toslice(path.as_bytes()),
+        version,
+        headers: header_vec,
+        length: amt,
+        body_framing,
+    })
+}
+
+/// Joined body of a chunked request and the number of bytes it took in the buffer (including
+/// the trailer), None if the body hasn't fully arrived yet.
+fn decode_chunked_body(buf: &[u8], max_body_size: usize) -> Result<Option<(Vec<u8>, usize)>, RequestError> {
+    let mut body: Vec<u8> = Vec::new();
+    let mut position = 0;
+
+    loop {
+        let (size_length, chunk_size) = match httparse::parse_chunk_size(&buf[position..]) {
+            Ok(httparse::Status::Complete(size_length_and_chunk_size)) => size_length_and_chunk_size,
+            Ok(httparse::Status::Partial) => {
+                return Ok(None);
+            }
+            Err(_) => {
+                return Err(RequestError::Malformed);
+            }
+        };
+        position += size_length;
+
+        if chunk_size == 0 {
+            break;
+        }
+        if chunk_size > (max_body_size - body.len()) as u64 {
+            return Err(RequestError::BodyTooLarge);
+        }
+
+        let chunk_end = position + chunk_size as usize;
+        if buf.len() < chunk_end + 2 {
+            return Ok(None);
+        }
+        if &buf[chunk_end..chunk_end + 2] != b"\r\n" {
+            return Err(RequestError::Malformed);
+        }
+        body.extend_from_slice(&buf[position..chunk_end]);
+        position = chunk_end + 2;
+    }
+
+    // Trailer fields (ignored), up to an empty line
+    let trailer_start = position;
+    loop {
+        let line_length = match buf[position..].windows(2).position(|crlf| crlf == b"\r\n") {
+            None => {
+                if buf.len() - trailer_start > MAX_HEAD_SIZE {
+                    return Err(RequestError::Malformed);
+                }
+                return Ok(None);
+            }
+            Some(line_length) => {
+                line_length
+            }
+        };
+        position += line_length + 2;
+
+        if line_length == 0 {
+            return Ok(Some((body, position)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+
+    use super::decode;
+    use super::RequestError;
+
+    const MAX_BODY_SIZE: usize = 16;
+
+    fn decode_all(
+        input: &[u8]
+    ) -> Result<Option<super::Request>, RequestError> {
+        let mut buf = BytesMut::from(input);
+        decode(&mut buf, MAX_BODY_SIZE)
+    }
+
+    #[test]
+    fn content_length_body() {
+        let mut buf = BytesMut::from(&b"PUT r HTTP/1.1\r\nContent-Length: 3\r\n\r\nabcPUT s HTTP/1.1\r\n\r\n"[..]);
+
+        let request = decode(&mut buf, MAX_BODY_SIZE).unwrap().unwrap();
+        assert_eq!(request.method(), "PUT");
+        assert_eq!(request.path(), "r");
+        assert_eq!(request.raw_body(), b"abc");
+        // The pipelined request is left in place
+        assert_eq!(&buf[..], &b"PUT s HTTP/1.1\r\n\r\n"[..]);
+    }
+
+    #[test]
+    fn chunked_body() {
+        let request = decode_all(
+            b"PUT r HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n")
+            .unwrap().unwrap();
+        assert_eq!(request.raw_body(), b"abcde");
+        assert_eq!(request.path(), "r");
+    }
+
+    #[test]
+    fn chunked_body_with_trailer_and_pipelined_request() {
+        let mut buf = BytesMut::from(
+            &b"PUT r HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n1\r\na\r\n0\r\nX-Trailer: 1\r\n\r\nPUT s"[..]);
+
+        let request = decode(&mut buf, MAX_BODY_SIZE).unwrap().unwrap();
+        assert_eq!(request.raw_body(), b"a");
+        assert_eq!(&buf[..], &b"PUT s"[..]);
+    }
+
+    #[test]
+    fn split_buffer() {
+        let input: &[u8] = b"PUT r HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n";
+        let mut buf = BytesMut::new();
+        for (index, byte) in input.iter().enumerate() {
+            buf.extend_from_slice(&[*byte]);
+            let decoded = decode(&mut buf, MAX_BODY_SIZE).unwrap();
+            if index + 1 < input.len() {
+                assert!(decoded.is_none(), "decoded early, at byte {}", index);
+            } else {
+                assert_eq!(decoded.unwrap().raw_body(), b"abcde");
+            }
+        }
+
+        let input: &[u8] = b"PUT r HTTP/1.1\r\nContent-Length: 4\r\n\r\nabcd";
+        let mut buf = BytesMut::from(&input[..input.len() - 1]);
+        assert!(decode(&mut buf, MAX_BODY_SIZE).unwrap().is_none());
+        buf.extend_from_slice(&input[input.len() - 1..]);
+        assert_eq!(decode(&mut buf, MAX_BODY_SIZE).unwrap().unwrap().raw_body(), b"abcd");
+    }
+
+    #[test]
+    fn content_length_and_transfer_encoding() {
+        assert_eq!(decode_all(
+            b"PUT r HTTP/1.1\r\nContent-Length: 3\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n")
+                       .err(), Some(RequestError::Malformed));
+    }
+
+    #[test]
+    fn unsupported_transfer_encoding() {
+        assert_eq!(decode_all(b"PUT r HTTP/1.1\r\nTransfer-Encoding: gzip\r\n\r\n").err(),
+                   Some(RequestError::Malformed));
+    }
+
+    #[test]
+    fn duplicate_content_length() {
+        assert_eq!(decode_all(b"PUT r HTTP/1.1\r\nContent-Length: 3\r\nContent-Length: 4\r\n\r\nabcd").err(),
+                   Some(RequestError::Malformed));
+        // The same length twice is unambiguous
+        assert_eq!(decode_all(b"PUT r HTTP/1.1\r\nContent-Length: 3\r\nContent-Length: 3\r\n\r\nabc")
+                       .unwrap().unwrap().raw_body(), b"abc");
+        assert_eq!(decode_all(b"PUT r HTTP/1.1\r\nContent-Length: x\r\n\r\n").err(),
+                   Some(RequestError::Malformed));
+    }
+
+    #[test]
+    fn body_size_limit() {
+        let at_limit = format!("PUT r HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+                               MAX_BODY_SIZE, "a".repeat(MAX_BODY_SIZE));
+        assert_eq!(decode_all(at_limit.as_bytes()).unwrap().unwrap().raw_body().len(), MAX_BODY_SIZE);
+
+        // Rejected as soon as the head arrives, without waiting for the body
+        let oversized = format!("PUT r HTTP/1.1\r\nContent-Length: {}\r\n\r\n", MAX_BODY_SIZE + 1);
+        assert_eq!(decode_all(oversized.as_bytes()).err(), Some(RequestError::BodyTooLarge));
+    }
+
+    #[test]
+    fn oversized_chunked_body() {
+        // A single chunk over the limit
+        assert_eq!(decode_all(b"PUT r HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n11\r\n").err(),
+                   Some(RequestError::BodyTooLarge));
+        // Chunks that add up to over the limit
+        assert_eq!(decode_all(
+            b"PUT r HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n8\r\naaaaaaaa\r\n8\r\naaaaaaaa\r\n1\r\n").err(),
+                   Some(RequestError::BodyTooLarge));
+    }
+
+    #[test]
+    fn oversized_head() {
+        let head = format!("PUT r HTTP/1.1\r\nX-Padding: {}", "a".repeat(super::MAX_HEAD_SIZE));
+        assert_eq!(decode_all(head.as_bytes()).err(), Some(RequestError::Malformed));
+    }
+
+    #[test]
+    fn malformed_chunks() {
+        // Chunk size that isn't hex
+        assert_eq!(decode_all(b"PUT r HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\nabc\r\n0\r\n\r\n").err(),
+                   Some(RequestError::Malformed));
+        // Chunk longer than its size
+        assert_eq!(decode_all(b"PUT r HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nabc\r\n0\r\n\r\n").err(),
+                   Some(RequestError::Malformed));
+    }
 }
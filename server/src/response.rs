@@ -1,11 +1,17 @@
 use std::fmt::{self, Write};
+use std::mem;
 
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
+
+use super::gather::Chunks;
 
 pub struct Response {
-    pub response: Vec<u8>,
+    pub response: Bytes,
     pub status_message: StatusMessage,
-//    pub header_raw: BytesMut
+    pub content_type: &'static str,
+    pub header_raw: BytesMut,
+    /// Body before it was compressed (shared, not a copy), None if it isn't compressed
+    pub uncompressed: Option<Bytes>
 }
 
 pub enum StatusMessage {
@@ -16,9 +22,11 @@ pub enum StatusMessage {
 impl Response {
     pub fn new() -> Response {
         Response {
-            response: Vec::new(),
+            response: Bytes::new(),
             status_message: StatusMessage::Ok,
-//            header_raw: BytesMut::new()
+            content_type: "application/octet-stream",
+            header_raw: BytesMut::new(),
+            uncompressed: None
         }
     }
 
@@ -27,46 +35,84 @@ impl Response {
         self
     }
 
-//    pub fn header(&mut self, name: &str, val: &str) -> &mut Response {
-//        let header_string = templatify! { "" ; name ; ": " ; val ; "\r\n" };
-//        self.header_raw.extend_from_slice(header_string.as_bytes());
-//
-//        self
-//    }
+    pub fn content_type(&mut self, content_type: &'static str) -> &mut Response {
+        self.content_type = content_type;
+        self
+    }
 
-//    pub fn header_raw(&mut self, buf: &BytesMut) -> &mut Response {
-//        self.header_raw.extend_from_slice(buf);
-//
-//        self
-//    }
+    /// Adds a header, in addition to the Content-Length and Content-Type that every response
+    /// gets.  Name and value are written as is and must not contain line breaks.
+    pub fn header(&mut self, name: &str, val: &str) -> &mut Response {
+        write!(FastWrite(&mut self.header_raw), "{}: {}\r\n", name, val).unwrap();
+
+        self
+    }
+
+    /// Adds already encoded header lines (each terminated by "\r\n").
+    pub fn header_raw(&mut self, buf: &BytesMut) -> &mut Response {
+        self.header_raw.extend_from_slice(buf);
+
+        self
+    }
 
 //    pub fn body(&mut self, s: &str) -> &mut Response {
 //        self.response = s.as_bytes().to_vec();
 //        self
 //    }
 
-//    pub fn body_bytes(&mut self, b: &[u8]) -> &mut Response {
-//        self.response = b.to_vec();
-//        self
-//    }
+    /// Shared body, written to the connection without being copied (static and precomputed
+    /// bodies are sent without any allocation).
+    pub fn body_bytes(&mut self, data: Bytes) -> &mut Response {
+        self.response = data;
+        self
+    }
 
     pub fn body_vec(&mut self, data: Vec<u8>) -> &mut Response {
-        self.response = data;
+        self.response = Bytes::from(data);
         self
     }
+
+    /// Replaces the body with its compressed form (and sets Content-Encoding), the body it
+    /// replaces is kept as the uncompressed one.
+    pub fn compressed_body(&mut self, encoding_name: &str, data: Bytes) -> &mut Response {
+        let uncompressed = mem::replace(&mut self.response, data);
+        self.uncompressed = Some(uncompressed);
+        self.header("Content-Encoding", encoding_name)
+    }
+
+    /// Body as produced (before any compression)
+    pub fn uncompressed_body(&self) -> &Bytes {
+        self.uncompressed.as_ref().unwrap_or(&self.response)
+    }
 }
 
-pub fn encode(msg: Response, buf: &mut BytesMut) {
+/// Writes the head of the response into the output buffer, the body is queued after it as is.
+pub fn encode(msg: Response, chunks: &mut Chunks) {
     let length = msg.response.len();
 
-    write!(FastWrite(buf), "\
+    write!(FastWrite(chunks.buffer()), "\
         HTTP/1.1 {}\r\n\
         Content-Length: {}\r\n\
-    ", msg.status_message, length).unwrap();
+        Content-Type: {}\r\n\
+    ", msg.status_message, length, msg.content_type).unwrap();
+
+    chunks.extend_from_slice(&msg.header_raw);
+    chunks.extend_from_slice("\r\n".as_bytes());
+    chunks.push(msg.response);
+}
 
-//    buf.extend_from_slice(&msg.header_raw);
-    buf.extend_from_slice("\r\n".as_bytes());
-    buf.extend_from_slice(msg.response.as_slice());
+/**
+ * Head of a text/event-stream response.  There is no Content-Length, the stream lasts until
+ * either side closes the connection.
+ */
+pub fn encode_event_stream_head(chunks: &mut Chunks) {
+    chunks.extend_from_slice("\
+        HTTP/1.1 200 OK\r\n\
+        Content-Type: text/event-stream\r\n\
+        Cache-Control: no-cache\r\n\
+        Connection: close\r\n\
+        \r\n\
+    ".as_bytes());
 }
 
 // TODO: impl fmt::Write for Vec<u8>
@@ -0,0 +1,518 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::io::Cursor;
+
+use byteorder::{BigEndian, ReadBytesExt};
+use smallvec::SmallVec;
+
+use common::model::protocol::ProtocolVersion;
+use common::model::protocol::PROTOCOL_VERSION_1;
+
+use super::cache::watchers::PageKey;
+use super::codes;
+
+/**
+ * Type of a request body field, all fields are Big Endian.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FieldType {
+    Int,
+    Long,
+}
+
+impl FieldType {
+    pub fn size(&self) -> usize {
+        match *self {
+            FieldType::Int => 4,
+            FieldType::Long => 8,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            FieldType::Int => "u32",
+            FieldType::Long => "u64",
+        }
+    }
+}
+
+/**
+ * A named field of a route's request body.
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct Field {
+    pub name: &'static str,
+    pub field_type: FieldType,
+}
+
+/**
+ * Read routes are served by the reactor threads, update routes by the Updater (and applied on
+ * the writer thread).
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RouteKind {
+    Read,
+    Update,
+}
+
+impl RouteKind {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            RouteKind::Read => "read",
+            RouteKind::Update => "update",
+        }
+    }
+}
+
+/**
+ * Decoded request body, in schema order.
+ */
+pub struct Args {
+    values: SmallVec<[u64; 8]>,
+}
+
+impl Args {
+    #[inline]
+    pub fn int(
+        &self,
+        index: usize,
+    ) -> u32 {
+        self.values[index] as u32
+    }
+
+    #[inline]
+    pub fn long(
+        &self,
+        index: usize,
+    ) -> u64 {
+        self.values[index]
+    }
+}
+
+pub type Handler<C> = fn(&Args, &C) -> Vec<u8>;
+
+/**
+ * Decodes an update request into the update applied on the writer thread.  Gets the schema
+ * fields and the rest of the body (lists of items, which a schema can't describe), None if the
+ * rest of the body is not valid.
+ */
+pub type UpdateDecoder<U> = fn(&Args, &[u8]) -> Option<U>;
+
+/**
+ * What a route does with a request: responds to it (read) or decodes it into an update U.
+ */
+pub enum RouteHandler<C: ?Sized, U> {
+    Read(Handler<C>),
+    Update(UpdateDecoder<U>),
+}
+
+pub type PageKeyResolver<C> = fn(&Args, &C) -> Option<PageKey>;
+
+/**
+ * A single route: id (path), request body schema and handler.  C is the context the read
+ * handlers run against (the cache, for the cache app), it may be a trait.  U is the update
+ * the update routes decode requests into.
+ *
+ * Routes serve all protocol versions by default.  A route whose request or response format
+ * changes is registered once per format, each registration with its range of versions.
+ */
+pub struct Route<C: ?Sized, U> {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub schema: &'static [Field],
+    pub handler: RouteHandler<C, U>,
+    pub min_version: ProtocolVersion,
+    pub max_version: ProtocolVersion,
+    // Page watched by long-poll requests to this route, if it can be long-polled
+    pub page_key: Option<PageKeyResolver<C>>,
+}
+
+impl<C: ?Sized, U> Route<C, U> {
+    pub fn read(
+        id: &'static str,
+        name: &'static str,
+        schema: &'static [Field],
+        handler: Handler<C>,
+    ) -> Route<C, U> {
+        Route {
+            id,
+            name,
+            schema,
+            handler: RouteHandler::Read(handler),
+            min_version: PROTOCOL_VERSION_1,
+            max_version: ProtocolVersion::max_value(),
+            page_key: None,
+        }
+    }
+
+    /**
+     * An update route, its schema covers the fields before the items of the request body.
+     */
+    pub fn update(
+        id: &'static str,
+        name: &'static str,
+        schema: &'static [Field],
+        decoder: UpdateDecoder<U>,
+    ) -> Route<C, U> {
+        Route {
+            id,
+            name,
+            schema,
+            handler: RouteHandler::Update(decoder),
+            min_version: PROTOCOL_VERSION_1,
+            max_version: ProtocolVersion::max_value(),
+            page_key: None,
+        }
+    }
+
+    pub fn kind(&self) -> RouteKind {
+        match self.handler {
+            RouteHandler::Read(_) => RouteKind::Read,
+            RouteHandler::Update(_) => RouteKind::Update,
+        }
+    }
+
+    /**
+     * Restricts the route to a (inclusive) range of protocol versions.
+     */
+    pub fn with_versions(
+        mut self,
+        min_version: ProtocolVersion,
+        max_version: ProtocolVersion,
+    ) -> Route<C, U> {
+        self.min_version = min_version;
+        self.max_version = max_version;
+
+        self
+    }
+
+    #[inline]
+    pub fn serves(
+        &self,
+        protocol_version: ProtocolVersion,
+    ) -> bool {
+        self.min_version <= protocol_version && protocol_version <= self.max_version
+    }
+
+    pub fn with_page_key(
+        mut self,
+        page_key: PageKeyResolver<C>,
+    ) -> Route<C, U> {
+        self.page_key = Some(page_key);
+
+        self
+    }
+
+    pub fn body_size(&self) -> usize {
+        self.schema.iter().map(|field| field.field_type.size()).sum()
+    }
+
+    /**
+     * Validates the request body against the schema and decodes it, None if the body
+     * does not match.  The body of an update route goes on past the schema fields, the rest
+     * of it is returned along with them (and is empty for read routes).
+     */
+    pub fn decode<'a>(
+        &self,
+        request_body: &'a [u8],
+    ) -> Option<(Args, &'a [u8])> {
+        let body_size = self.body_size();
+        let matches = match self.handler {
+            RouteHandler::Read(_) => request_body.len() == body_size,
+            RouteHandler::Update(_) => request_body.len() >= body_size,
+        };
+        if !matches {
+            return None;
+        }
+
+        let mut request_data_reader = Cursor::new(request_body);
+        let mut values = SmallVec::new();
+        for field in self.schema {
+            let value = match field.field_type {
+                FieldType::Int => request_data_reader.read_u32::<BigEndian>().unwrap() as u64,
+                FieldType::Long => request_data_reader.read_u64::<BigEndian>().unwrap(),
+            };
+            values.push(value);
+        }
+
+        Some((Args {
+            values
+        }, &request_body[body_size..]))
+    }
+}
+
+/**
+ *  All routes of an App, by kind and id (and protocol versions, for ids with more than one
+ *  registration).  Read routes are served by the App, update routes by the Updater (with
+ *  an UpdateApp applying the decoded updates).
+ */
+pub struct RouteRegistry<C: ?Sized, U> {
+    routes: Vec<Route<C, U>>,
+    read_routes: HashMap<&'static str, Vec<usize>>,
+    update_routes: HashMap<&'static str, Vec<usize>>,
+}
+
+impl<C: ?Sized, U> RouteRegistry<C, U> {
+    pub fn new() -> RouteRegistry<C, U> {
+        RouteRegistry {
+            routes: Vec::new(),
+            read_routes: HashMap::new(),
+            update_routes: HashMap::new(),
+        }
+    }
+
+    /**
+     * Adds a route, panics if a route of the same kind and id is already registered for any
+     * of its protocol versions.
+     */
+    pub fn register(
+        &mut self,
+        route: Route<C, U>,
+    ) {
+        let index = self.routes.len();
+        {
+            let routes = &self.routes;
+            let indexes = match route.kind() {
+                RouteKind::Read => self.read_routes.entry(route.id).or_insert_with(Vec::new),
+                RouteKind::Update => self.update_routes.entry(route.id).or_insert_with(Vec::new),
+            };
+            for existing_index in indexes.iter() {
+                let existing = &routes[*existing_index];
+                if existing.min_version <= route.max_version && route.min_version <= existing.max_version {
+                    panic!("Duplicate {:?} route {} for protocol versions {}-{}",
+                           route.kind(), route.id, route.min_version, route.max_version);
+                }
+            }
+            indexes.push(index);
+        }
+        self.routes.push(route);
+    }
+
+    pub fn get(
+        &self,
+        kind: RouteKind,
+        protocol_version: ProtocolVersion,
+        id: &str,
+    ) -> Option<&Route<C, U>> {
+        let indexes = match kind {
+            RouteKind::Read => self.read_routes.get(id),
+            RouteKind::Update => self.update_routes.get(id),
+        };
+
+        indexes?.iter()
+            .map(|index| &self.routes[*index])
+            .find(|route| route.serves(protocol_version))
+    }
+
+    /**
+     * All routes, in order of registration.
+     */
+    pub fn routes(&self) -> &[Route<C, U>] {
+        &self.routes
+    }
+
+    /**
+     * Ids of the routes of a kind, sorted (routes registered for several protocol versions
+     * share an id).
+     */
+    pub fn route_ids(
+        &self,
+        kind: RouteKind,
+    ) -> Vec<&'static str> {
+        let mut route_ids: Vec<&'static str> = self.routes.iter()
+            .filter(|route| route.kind() == kind)
+            .map(|route| route.id)
+            .collect();
+        route_ids.sort();
+        route_ids.dedup();
+
+        route_ids
+    }
+
+    /**
+     * Decodes the request and runs the route's handler, unknown routes and request bodies
+     * that do not match the schema get the invalid data format response.
+     */
+    pub fn get_response(
+        &self,
+        protocol_version: ProtocolVersion,
+        id: &str,
+        request_body: &[u8],
+        context: &C,
+    ) -> Vec<u8> {
+        let route = match self.get(RouteKind::Read, protocol_version, id) {
+            None => {
+                return codes::INVALID_DATA_FORMAT_RESPONSE.to_vec();
+            }
+            Some(route) => {
+                route
+            }
+        };
+
+        match (route.decode(request_body), &route.handler) {
+            (Some((args, _)), &RouteHandler::Read(handler)) => {
+                handler(&args, context)
+            }
+            _ => {
+                codes::INVALID_DATA_FORMAT_RESPONSE.to_vec()
+            }
+        }
+    }
+
+    /**
+     * Decodes an update request, None for unknown update routes and request bodies that
+     * are not valid for the route.
+     */
+    pub fn decode_update(
+        &self,
+        protocol_version: ProtocolVersion,
+        id: &str,
+        request_body: &[u8],
+    ) -> Option<U> {
+        let route = self.get(RouteKind::Update, protocol_version, id)?;
+        let (args, items) = route.decode(request_body)?;
+
+        match route.handler {
+            RouteHandler::Update(decoder) => {
+                decoder(&args, items)
+            }
+            RouteHandler::Read(_) => {
+                None
+            }
+        }
+    }
+
+    pub fn get_page_key(
+        &self,
+        protocol_version: ProtocolVersion,
+        id: &str,
+        request_body: &[u8],
+        context: &C,
+    ) -> Option<PageKey> {
+        let route = self.get(RouteKind::Read, protocol_version, id)?;
+        let page_key = route.page_key?;
+        let (args, _) = route.decode(request_body)?;
+
+        page_key(&args, context)
+    }
+
+    /**
+     * JSON description of all routes (id, name, kind, protocol versions, long-poll support and
+     * body schema).
+     */
+    pub fn describe(&self) -> String {
+        let mut json = String::from("[");
+        for (route_index, route) in self.routes.iter().enumerate() {
+            if route_index != 0 {
+                json.push(',');
+            }
+            write!(json, "{{\"id\":\"{}\",\"name\":\"{}\",\"kind\":\"{}\",\"minVersion\":{},\
+                          \"maxVersion\":{},\"longPoll\":{},\"schema\":[",
+                   route.id, route.name, route.kind().name(),
+                   route.min_version, route.max_version,
+                   route.page_key.is_some()).unwrap();
+            for (field_index, field) in route.schema.iter().enumerate() {
+                if field_index != 0 {
+                    json.push(',');
+                }
+                write!(json, "{{\"name\":\"{}\",\"type\":\"{}\"}}",
+                       field.name, field.field_type.name()).unwrap();
+            }
+            json.push_str("]}");
+        }
+        json.push(']');
+
+        json
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use byteorder::{BigEndian, WriteBytesExt};
+
+    use common::model::protocol::PROTOCOL_VERSION_1;
+
+    use super::super::codes;
+    use super::Field;
+    use super::FieldType;
+    use super::Route;
+    use super::RouteKind;
+    use super::RouteRegistry;
+
+    const COUNT: Field = Field { name: "count", field_type: FieldType::Int };
+
+    // Reads respond with the count and the context, updates decode to the count and the items
+    fn get_routes() -> RouteRegistry<u8, (u32, Vec<u8>)> {
+        let mut routes = RouteRegistry::new();
+        routes.register(Route::read(
+            "a", "read_a", &[COUNT],
+            |args, context| vec![args.int(0) as u8, *context]));
+        routes.register(Route::update(
+            "a", "update_a", &[COUNT],
+            |args, items| Some((args.int(0), items.to_vec()))));
+        routes.register(Route::update(
+            "b", "update_b", &[],
+            |_, items| if items.is_empty() { None } else { Some((0, items.to_vec())) }));
+
+        routes
+    }
+
+    fn get_body(
+        count: u32,
+        items: &[u8],
+    ) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.write_u32::<BigEndian>(count).unwrap();
+        body.extend_from_slice(items);
+
+        body
+    }
+
+    #[test]
+    fn route_kinds() {
+        let routes = get_routes();
+
+        assert_eq!(routes.get(RouteKind::Read, PROTOCOL_VERSION_1, "a").unwrap().name, "read_a");
+        assert_eq!(routes.get(RouteKind::Update, PROTOCOL_VERSION_1, "a").unwrap().name, "update_a");
+        assert!(routes.get(RouteKind::Read, PROTOCOL_VERSION_1, "b").is_none());
+
+        assert_eq!(routes.route_ids(RouteKind::Read), vec!["a"]);
+        assert_eq!(routes.route_ids(RouteKind::Update), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn read_responses() {
+        let routes = get_routes();
+
+        assert_eq!(routes.get_response(PROTOCOL_VERSION_1, "a", &get_body(3, &[]), &7), vec![3, 7]);
+        // Read bodies must match the schema exactly
+        assert_eq!(routes.get_response(PROTOCOL_VERSION_1, "a", &get_body(3, &[1]), &7),
+                   codes::INVALID_DATA_FORMAT_RESPONSE.to_vec());
+        // Update routes are not served as reads
+        assert_eq!(routes.get_response(PROTOCOL_VERSION_1, "b", &[1], &7),
+                   codes::INVALID_DATA_FORMAT_RESPONSE.to_vec());
+    }
+
+    #[test]
+    fn decoded_updates() {
+        let routes = get_routes();
+
+        assert_eq!(routes.decode_update(PROTOCOL_VERSION_1, "a", &get_body(3, &[1, 2])),
+                   Some((3, vec![1, 2])));
+        assert_eq!(routes.decode_update(PROTOCOL_VERSION_1, "a", &get_body(3, &[])),
+                   Some((3, vec![])));
+        // Cut short before the end of the schema fields
+        assert_eq!(routes.decode_update(PROTOCOL_VERSION_1, "a", &[0, 0, 3]), None);
+        // Items the decoder rejects
+        assert_eq!(routes.decode_update(PROTOCOL_VERSION_1, "b", &[]), None);
+        // Unknown route
+        assert_eq!(routes.decode_update(PROTOCOL_VERSION_1, "c", &[1]), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Duplicate Update route a")]
+    fn duplicate_routes() {
+        let mut routes = get_routes();
+        routes.register(Route::update("a", "update_a_again", &[], |_, _| None));
+    }
+}
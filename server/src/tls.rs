@@ -0,0 +1,629 @@
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use futures::{Async, Poll};
+use rustls;
+use rustls::internal::pemfile;
+use rustls::sign;
+use rustls::sign::CertifiedKey;
+use rustls::AllowAnyAuthenticatedClient;
+use rustls::NoClientAuth;
+use rustls::ResolvesServerCert;
+use rustls::RootCertStore;
+use rustls::ServerSession;
+use rustls::Session;
+use rustls::SignatureScheme;
+use tokio_io::{AsyncRead, AsyncWrite};
+use webpki;
+
+use super::config::TlsConfig;
+
+/// How often the certificate and key files are checked for changes
+const CERTIFICATE_CHECK_INTERVAL_SECONDS: u64 = 5;
+
+/// Signature schemes of the supported key types, used to check that a key matches its certificate
+static KEY_CHECK_SCHEMES: [(SignatureScheme, &webpki::SignatureAlgorithm); 3] = [
+    (SignatureScheme::RSA_PKCS1_SHA256, &webpki::RSA_PKCS1_2048_8192_SHA256),
+    (SignatureScheme::ECDSA_NISTP256_SHA256, &webpki::ECDSA_P256_SHA256),
+    (SignatureScheme::ECDSA_NISTP384_SHA384, &webpki::ECDSA_P384_SHA384),
+];
+
+///
+/// Accepts TLS connections on a listener.  Cheap to clone, all clones share the rustls config
+/// (and with it the reloading certificate).
+///
+#[derive(Clone)]
+pub struct TlsAcceptor {
+    config: Arc<rustls::ServerConfig>,
+    verifies_clients: bool,
+}
+
+impl TlsAcceptor {
+
+    pub fn new(
+        tls_config: &TlsConfig
+    ) -> Result<TlsAcceptor, String> {
+        let verifies_clients = tls_config.client_ca_file.is_some();
+        let client_cert_verifier = match tls_config.client_ca_file {
+            None => {
+                NoClientAuth::new()
+            }
+            Some(ref client_ca_file) => {
+                let mut roots = RootCertStore::empty();
+                for certificate in read_certificates(client_ca_file)? {
+                    roots.add(&certificate).map_err(|err| format!(
+                        "Invalid client CA certificate in {}: {:?}", client_ca_file.display(), err))?;
+                }
+                AllowAnyAuthenticatedClient::new(roots)
+            }
+        };
+
+        let mut config = rustls::ServerConfig::new(client_cert_verifier);
+        config.cert_resolver = Arc::new(ReloadingCertResolver::new(
+            &tls_config.cert_file, &tls_config.key_file)?);
+
+        Ok(TlsAcceptor {
+            config: Arc::new(config),
+            verifies_clients,
+        })
+    }
+
+    ///
+    /// True if only clients with a certificate signed by the client CA can connect.
+    ///
+    #[inline]
+    pub fn verifies_clients(&self) -> bool {
+        self.verifies_clients
+    }
+
+    ///
+    /// Wraps an accepted connection, the handshake is done as the stream is first read from.
+    ///
+    pub fn accept<S>(
+        &self,
+        socket: S,
+    ) -> TlsStream<S> {
+        TlsStream {
+            io: socket,
+            session: ServerSession::new(&self.config),
+            eof: false,
+        }
+    }
+}
+
+///
+/// A TLS server connection over a non-blocking stream.  Reads and writes return WouldBlock (and
+/// so register the current task) when the underlying stream does, including while handshaking.
+///
+pub struct TlsStream<S> {
+    io: S,
+    session: ServerSession,
+    eof: bool,
+}
+
+impl<S: Read + Write> TlsStream<S> {
+
+    fn write_tls(&mut self) -> io::Result<()> {
+        while self.session.wants_write() {
+            // A stream that takes nothing would have this spin forever
+            if self.session.write_tls(&mut self.io)? == 0 {
+                return Err(io::Error::new(io::ErrorKind::WriteZero, "TLS stream accepted no data"));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_tls(&mut self) -> io::Result<()> {
+        if self.session.read_tls(&mut self.io)? == 0 {
+            self.eof = true;
+            return Ok(());
+        }
+        if let Err(err) = self.session.process_new_packets() {
+            // Let the client know why, if it is still listening
+            let _ = self.write_tls();
+            return Err(io::Error::new(io::ErrorKind::InvalidData, err));
+        }
+
+        Ok(())
+    }
+}
+
+impl<S: Read + Write> Read for TlsStream<S> {
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            // Handshake messages and alerts
+            match self.write_tls() {
+                Ok(()) => {}
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock && !self.session.is_handshaking() => {}
+                Err(err) => {
+                    return Err(err);
+                }
+            }
+
+            match self.session.read(buf) {
+                Ok(0) => {}
+                Ok(length) => {
+                    return Ok(length);
+                }
+                // close_notify
+                Err(ref err) if err.kind() == io::ErrorKind::ConnectionAborted => {
+                    return Ok(0);
+                }
+                Err(err) => {
+                    return Err(err);
+                }
+            }
+            if self.eof {
+                return Ok(0);
+            }
+
+            self.read_tls()?;
+        }
+    }
+}
+
+impl<S: Read + Write> Write for TlsStream<S> {
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let length = self.session.write(buf)?;
+        // Whatever is not written now stays buffered in the session, until the next flush
+        match self.write_tls() {
+            Ok(()) => {}
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {}
+            Err(err) => {
+                return Err(err);
+            }
+        }
+
+        Ok(length)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.session.flush()?;
+        self.write_tls()?;
+
+        self.io.flush()
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite> AsyncRead for TlsStream<S> {}
+
+impl<S: AsyncRead + AsyncWrite> AsyncWrite for TlsStream<S> {
+
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.session.send_close_notify();
+        match self.write_tls() {
+            Ok(()) => {}
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                return Ok(Async::NotReady);
+            }
+            Err(err) => {
+                return Err(err);
+            }
+        }
+
+        self.io.shutdown()
+    }
+}
+
+struct CurrentCertificate {
+    certified_key: CertifiedKey,
+    modified: (Option<SystemTime>, Option<SystemTime>),
+    last_checked: Instant,
+}
+
+///
+/// Serves the certificate from the configured files, re-reading them when they change so that
+/// certificates are renewed without a restart.  Files that can't be read (or don't match) keep
+/// the current certificate.
+///
+struct ReloadingCertResolver {
+    cert_file: PathBuf,
+    key_file: PathBuf,
+    current: Mutex<CurrentCertificate>,
+}
+
+impl ReloadingCertResolver {
+
+    fn new(
+        cert_file: &Path,
+        key_file: &Path,
+    ) -> Result<ReloadingCertResolver, String> {
+        let modified = (get_modified(cert_file), get_modified(key_file));
+        let certified_key = read_certified_key(cert_file, key_file)?;
+
+        Ok(ReloadingCertResolver {
+            cert_file: cert_file.to_path_buf(),
+            key_file: key_file.to_path_buf(),
+            current: Mutex::new(CurrentCertificate {
+                certified_key,
+                modified,
+                last_checked: Instant::now(),
+            }),
+        })
+    }
+}
+
+impl ResolvesServerCert for ReloadingCertResolver {
+
+    fn resolve(
+        &self,
+        _server_name: Option<webpki::DNSNameRef>,
+        _sigschemes: &[SignatureScheme],
+    ) -> Option<CertifiedKey> {
+        let mut current = self.current.lock().unwrap();
+        if current.last_checked.elapsed() >= Duration::from_secs(CERTIFICATE_CHECK_INTERVAL_SECONDS) {
+            current.last_checked = Instant::now();
+
+            let modified = (get_modified(&self.cert_file), get_modified(&self.key_file));
+            if modified != current.modified {
+                match read_certified_key(&self.cert_file, &self.key_file) {
+                    Err(message) => {
+                        eprintln!("Keeping current certificate: {}", message);
+                    }
+                    Ok(certified_key) => {
+                        println!("Reloaded certificate from {}", self.cert_file.display());
+                        current.certified_key = certified_key;
+                        current.modified = modified;
+                    }
+                }
+            }
+        }
+
+        Some(current.certified_key.clone())
+    }
+}
+
+#[inline]
+fn get_modified(
+    path: &Path
+) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+fn read_certificates(
+    path: &Path
+) -> Result<Vec<rustls::Certificate>, String> {
+    let file = File::open(path)
+        .map_err(|err| format!("Could not read certificate file {}: {}", path.display(), err))?;
+    let certificates = pemfile::certs(&mut BufReader::new(file))
+        .map_err(|_| format!("Invalid certificate file {}", path.display()))?;
+    if certificates.is_empty() {
+        return Err(format!("No certificates in {}", path.display()));
+    }
+
+    Ok(certificates)
+}
+
+fn read_certified_key(
+    cert_file: &Path,
+    key_file: &Path,
+) -> Result<CertifiedKey, String> {
+    let certificates = read_certificates(cert_file)?;
+
+    // PKCS#8 ("BEGIN PRIVATE KEY") or PKCS#1 ("BEGIN RSA PRIVATE KEY")
+    let mut keys = {
+        let file = File::open(key_file)
+            .map_err(|err| format!("Could not read key file {}: {}", key_file.display(), err))?;
+        pemfile::pkcs8_private_keys(&mut BufReader::new(file))
+            .map_err(|_| format!("Invalid key file {}", key_file.display()))?
+    };
+    if keys.is_empty() {
+        let file = File::open(key_file)
+            .map_err(|err| format!("Could not read key file {}: {}", key_file.display(), err))?;
+        keys = pemfile::rsa_private_keys(&mut BufReader::new(file))
+            .map_err(|_| format!("Invalid key file {}", key_file.display()))?;
+    }
+    let key = match keys.into_iter().next() {
+        None => {
+            return Err(format!("No private key in {}", key_file.display()));
+        }
+        Some(key) => {
+            key
+        }
+    };
+    let signing_key = sign::any_supported_type(&key)
+        .map_err(|_| format!("Unsupported private key in {}", key_file.display()))?;
+
+    // Certificates are usually renewed by replacing both files, a pair that is read in between
+    // is rejected here (and the current certificate kept)
+    let mismatch = || format!("Certificate {} does not match key {}", cert_file.display(), key_file.display());
+    let end_entity_cert = webpki::EndEntityCert::from(certificates[0].as_ref())
+        .map_err(|_| format!("Invalid certificate in {}", cert_file.display()))?;
+    let schemes: Vec<SignatureScheme> = KEY_CHECK_SCHEMES.iter().map(|&(scheme, _)| scheme).collect();
+    let signer = signing_key.choose_scheme(&schemes).ok_or_else(&mismatch)?;
+    let algorithm = KEY_CHECK_SCHEMES.iter()
+        .find(|&&(scheme, _)| scheme == signer.get_scheme())
+        .map(|&(_, algorithm)| algorithm)
+        .ok_or_else(&mismatch)?;
+    let message = b"vc-cache key check";
+    let signature = signer.sign(message).map_err(|_| mismatch())?;
+    end_entity_cert.verify_signature(algorithm, message, &signature).map_err(|_| mismatch())?;
+
+    Ok(CertifiedKey::new(certificates, Arc::new(signing_key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::io;
+    use std::io::Read;
+    use std::io::Write;
+    use std::net::{TcpListener, TcpStream};
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use rcgen;
+    use rustls;
+    use rustls::ResolvesServerCert;
+    use rustls::Session;
+    use webpki;
+
+    use super::super::config::TlsConfig;
+    use super::read_certificates;
+    use super::ReloadingCertResolver;
+    use super::TlsAcceptor;
+    use super::CERTIFICATE_CHECK_INTERVAL_SECONDS;
+
+    ///
+    /// A CA, server and client certificates (signed by it) and their files, removed on drop.
+    ///
+    struct Certificates {
+        directory: PathBuf,
+        ca: rcgen::Certificate,
+    }
+
+    impl Certificates {
+        fn new(
+            name: &str,
+        ) -> Certificates {
+            let directory = env::temp_dir().join(format!("vc-tls-test-{}-{}", name, ::std::process::id()));
+            fs::create_dir_all(&directory).unwrap();
+
+            let mut ca_params = rcgen::CertificateParams::new(Vec::new());
+            ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+            let ca = rcgen::Certificate::from_params(ca_params).unwrap();
+            fs::write(directory.join("ca.pem"), ca.serialize_pem().unwrap()).unwrap();
+
+            Certificates {
+                directory,
+                ca,
+            }
+        }
+
+        fn path(
+            &self,
+            file_name: &str,
+        ) -> PathBuf {
+            self.directory.join(file_name)
+        }
+
+        ///
+        /// Writes a certificate (signed by the CA, or self-signed) for localhost and its key to
+        /// <name>.pem and <name>.key, returns the certificate (DER) and key (PKCS#8 DER).
+        ///
+        fn write(
+            &self,
+            name: &str,
+            signed_by_ca: bool,
+        ) -> (rustls::Certificate, rustls::PrivateKey) {
+            let certificate = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+            let certificate_pem = if signed_by_ca {
+                certificate.serialize_pem_with_signer(&self.ca).unwrap()
+            } else {
+                certificate.serialize_pem().unwrap()
+            };
+            let certificate_file = self.path(&format!("{}.pem", name));
+            fs::write(&certificate_file, certificate_pem).unwrap();
+            fs::write(self.path(&format!("{}.key", name)), certificate.serialize_private_key_pem()).unwrap();
+
+            // Read back, every serialization is signed anew (and ECDSA signatures differ)
+            (read_certificates(&certificate_file).unwrap().remove(0),
+             rustls::PrivateKey(certificate.serialize_private_key_der()))
+        }
+
+        fn tls_config(
+            &self,
+            name: &str,
+            verifies_clients: bool,
+        ) -> TlsConfig {
+            TlsConfig {
+                cert_file: self.path(&format!("{}.pem", name)),
+                key_file: self.path(&format!("{}.key", name)),
+                client_ca_file: if verifies_clients {
+                    Some(self.path("ca.pem"))
+                } else {
+                    None
+                },
+            }
+        }
+
+        fn client_config(
+            &self,
+            client_certificate: Option<(rustls::Certificate, rustls::PrivateKey)>,
+        ) -> Arc<rustls::ClientConfig> {
+            let mut config = rustls::ClientConfig::new();
+            config.root_store.add(&rustls::Certificate(self.ca.serialize_der().unwrap())).unwrap();
+            if let Some((certificate, key)) = client_certificate {
+                config.set_single_client_cert(vec![certificate], key);
+            }
+
+            Arc::new(config)
+        }
+    }
+
+    impl Drop for Certificates {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.directory);
+        }
+    }
+
+    ///
+    /// Sends "ping" over a TLS connection to a (single connection) server that responds with
+    /// "pong", returns what the client read back and whether the server read the ping.
+    ///
+    fn ping(
+        acceptor: TlsAcceptor,
+        client_config: Arc<rustls::ClientConfig>,
+    ) -> (io::Result<Vec<u8>>, bool) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (socket, _) = listener.accept().unwrap();
+            let mut stream = acceptor.accept(socket);
+            let mut request = [0u8; 4];
+            match stream.read_exact(&mut request) {
+                Err(_) => {
+                    false
+                }
+                Ok(()) => {
+                    stream.write_all(b"pong").unwrap();
+                    stream.flush().unwrap();
+                    &request == b"ping"
+                }
+            }
+        });
+
+        let mut socket = TcpStream::connect(address).unwrap();
+        socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let mut session = rustls::ClientSession::new(
+            &client_config, webpki::DNSNameRef::try_from_ascii_str("localhost").unwrap());
+        let response = {
+            let mut stream = rustls::Stream::new(&mut session, &mut socket);
+            stream.write_all(b"ping").and_then(|_| {
+                let mut response = [0u8; 4];
+                stream.read_exact(&mut response).map(|_| response.to_vec())
+            })
+        };
+        drop(socket);
+
+        (response, server.join().unwrap())
+    }
+
+    #[test]
+    fn handshake() {
+        let certificates = Certificates::new("handshake");
+        certificates.write("server", true);
+        let acceptor = TlsAcceptor::new(&certificates.tls_config("server", false)).unwrap();
+        assert!(!acceptor.verifies_clients());
+
+        let (response, server_read_ping) = ping(acceptor, certificates.client_config(None));
+        assert_eq!(response.unwrap(), b"pong".to_vec());
+        assert!(server_read_ping);
+    }
+
+    #[test]
+    fn client_certificates() {
+        let certificates = Certificates::new("clients");
+        certificates.write("server", true);
+        let client_certificate = certificates.write("client", true);
+        let untrusted_client_certificate = certificates.write("untrusted", false);
+        let acceptor = TlsAcceptor::new(&certificates.tls_config("server", true)).unwrap();
+        assert!(acceptor.verifies_clients());
+
+        let (response, server_read_ping) = ping(
+            acceptor.clone(), certificates.client_config(Some(client_certificate)));
+        assert_eq!(response.unwrap(), b"pong".to_vec());
+        assert!(server_read_ping);
+
+        let (response, server_read_ping) = ping(acceptor.clone(), certificates.client_config(None));
+        assert!(response.is_err());
+        assert!(!server_read_ping);
+
+        let (response, server_read_ping) = ping(
+            acceptor, certificates.client_config(Some(untrusted_client_certificate)));
+        assert!(response.is_err());
+        assert!(!server_read_ping);
+    }
+
+    #[test]
+    fn invalid_files() {
+        let certificates = Certificates::new("invalid");
+        certificates.write("server", true);
+        certificates.write("other", true);
+
+        // Key of a different certificate
+        let mut tls_config = certificates.tls_config("server", false);
+        tls_config.key_file = certificates.path("other.key");
+        assert!(TlsAcceptor::new(&tls_config).is_err());
+
+        let mut tls_config = certificates.tls_config("server", false);
+        tls_config.cert_file = certificates.path("missing.pem");
+        assert!(TlsAcceptor::new(&tls_config).is_err());
+    }
+
+    #[test]
+    fn certificate_reload() {
+        let certificates = Certificates::new("reload");
+        let (first_certificate, _) = certificates.write("server", true);
+        let resolver = ReloadingCertResolver::new(
+            &certificates.path("server.pem"), &certificates.path("server.key")).unwrap();
+        let resolve = || resolver.resolve(None, &[]).unwrap().cert[0].clone();
+        let expire_check = || {
+            let mut current = resolver.current.lock().unwrap();
+            current.last_checked = Instant::now() - Duration::from_secs(CERTIFICATE_CHECK_INTERVAL_SECONDS);
+            // File modification times can be too coarse to tell within a test
+            current.modified = (None, None);
+        };
+        assert_eq!(resolve(), first_certificate);
+
+        let (second_certificate, _) = certificates.write("server", true);
+        // Not checked again until the interval is up
+        assert_eq!(resolve(), first_certificate);
+        expire_check();
+        assert_eq!(resolve(), second_certificate);
+
+        // A certificate that doesn't match the key (half way through a renewal) is not loaded
+        certificates.write("other", true);
+        fs::copy(certificates.path("other.pem"), certificates.path("server.pem")).unwrap();
+        expire_check();
+        assert_eq!(resolve(), second_certificate);
+    }
+
+    ///
+    /// A stream that never takes any data.
+    ///
+    struct Full;
+
+    impl Read for Full {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Ok(0)
+        }
+    }
+
+    impl Write for Full {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Ok(0)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_zero() {
+        let certificates = Certificates::new("write-zero");
+        certificates.write("server", true);
+        let acceptor = TlsAcceptor::new(&certificates.tls_config("server", false)).unwrap();
+
+        let mut stream = acceptor.accept(Full);
+        stream.session.send_close_notify();
+        assert!(stream.session.wants_write());
+        assert_eq!(stream.write_tls().unwrap_err().kind(), io::ErrorKind::WriteZero);
+    }
+}